@@ -1,30 +1,107 @@
 //! Object marker components.
 
-use crate::{utils, Game};
+use crate::{
+    bot::{Bot, BotNavigationStatus},
+    event::GameEvent,
+    net::InstanceDescriptor,
+    utils, Game,
+};
 use fyrox::{
     core::{
-        algebra::Vector3, math::Vector3Ext, pool::Handle, pool::MultiBorrowContext,
-        reflect::prelude::*, variable::InheritableVariable, visitor::prelude::*,
+        algebra::{Point3, UnitQuaternion, Vector3},
+        arrayvec::ArrayVec,
+        color::Color,
+        log::Log,
+        math::Vector3Ext,
+        pool::Handle,
+        pool::MultiBorrowContext,
+        reflect::prelude::*,
+        sstorage::ImmutableString,
+        variable::InheritableVariable,
+        visitor::prelude::*,
     },
     graph::SceneGraph,
+    material::PropertyValue,
     rand::{prelude::SliceRandom, thread_rng},
     resource::model::{ModelResource, ModelResourceExtension},
     scene::{
         animation::{absm::prelude::*, AnimationPlayer},
-        collider::Collider,
-        graph::Graph,
+        base::{Base, SceneNodeId},
+        collider::{BitMask, CapsuleShape, Collider, ColliderShape, InteractionGroups},
+        graph::{physics::RayCastOptions, Graph},
+        mesh::Mesh,
         node::{container::NodeContainer, Node},
         ragdoll::Ragdoll,
-        rigidbody::RigidBody,
+        rigidbody::{RigidBody, RigidBodyType},
         sound::Sound,
+        Scene,
     },
     script::{ScriptContext, ScriptMessageContext, ScriptMessagePayload},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use strum_macros::AsRefStr;
+
+/// Collision group of actors with active spawn protection. Actors in this group do not collide with
+/// each other, but still collide with everything else (ground, walls, etc.).
+const SPAWN_PROTECTION_GROUP: BitMask = BitMask(1 << 31);
+
+/// Linear velocity (m/s) below which a grounded actor is considered settled after spawn.
+const SETTLED_VELOCITY_THRESHOLD: f32 = 0.1;
+
+/// Minimal amount of time (in seconds) of spawn protection, even if the actor has settled. Actors
+/// are not moving right after spawn, so it gives physics a few steps to settle them down.
+const MIN_SPAWN_PROTECTION_TIME: f32 = 0.25;
+
+/// Lifetime (in seconds) of cosmetic effects (dust, sparks, etc.).
+const EFFECT_LIFETIME: f32 = 2.0;
+
+/// Minimal amount of time (in seconds) in air, after which ground contact is considered a landing.
+const LANDING_AIR_TIME: f32 = 0.3;
+
+/// Amount of time (in seconds) after a hard landing, during which contacts are not considered
+/// serious impacts, so the same fall is not counted twice.
+const HARD_LANDING_IMPACT_GRACE: f32 = 0.25;
+
+/// Max distance (in meters) from the origin along any axis, beyond which the position of an actor
+/// is considered broken by the physics.
+const MAX_WORLD_EXTENT: f32 = 100_000.0;
+
+fn is_finite(v: &Vector3<f32>) -> bool {
+    v.iter().all(|c| c.is_finite())
+}
 
 #[derive(Debug)]
 pub enum ActorMessage {
     RespawnAt(Vector3<f32>),
+    /// Knocks the actor out for the given amount of time (in seconds) and then respawns it at
+    /// the given position. Ignored if the actor is already knocked out.
+    KnockOut {
+        respawn_position: Vector3<f32>,
+        duration: f32,
+    },
+    /// Eliminates the actor from the round instead of respawn, see [`Actor::eliminate`].
+    Eliminate,
+    /// Applies an impulse to a single limb of the actor, see [`Actor::apply_limb_impulse`].
+    /// Ignored on clients, the resulting motion is replicated from the server.
+    LimbImpulse {
+        limb: LimbSelector,
+        impulse: Vector3<f32>,
+    },
+    /// Pushes the actor with the given impulse and stuns it, as a serious impact does. Ignored on
+    /// clients, the resulting motion is replicated from the server.
+    Knockback {
+        impulse: Vector3<f32>,
+    },
+}
+
+/// Selects a limb of the ragdoll of an actor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LimbSelector {
+    /// Name of the physical bone (rigid body) of the limb.
+    Name(String),
+    /// Index of the limb in depth-first order of the limb tree, the root limb has zero index.
+    Index(usize),
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Visit, Serialize, Deserialize)]
@@ -34,6 +111,140 @@ pub enum ActorKind {
     RemotePlayer,
 }
 
+/// A temporary effect (usually granted by a power-up) that could be active on an actor.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, AsRefStr)]
+pub enum StatusEffect {
+    SpeedBoost,
+    Shield,
+    LowGravity,
+}
+
+impl StatusEffect {
+    pub const ALL: [StatusEffect; 3] = [
+        StatusEffect::SpeedBoost,
+        StatusEffect::Shield,
+        StatusEffect::LowGravity,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A set of active status effects packed in a single byte, so it is cheap to send it over the
+/// network every tick.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct StatusEffects(u8);
+
+impl StatusEffects {
+    pub fn contains(&self, effect: StatusEffect) -> bool {
+        self.0 & effect.bit() != 0
+    }
+
+    pub fn insert(&mut self, effect: StatusEffect) {
+        self.0 |= effect.bit();
+    }
+
+    pub fn remove(&mut self, effect: StatusEffect) {
+        self.0 &= !effect.bit();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = StatusEffect> + '_ {
+        StatusEffect::ALL
+            .into_iter()
+            .filter(|effect| self.contains(*effect))
+    }
+}
+
+/// A lightweight read-only view of an actor, that could be used by various observers (HUD,
+/// spectator camera, scoreboard, etc.) to avoid querying actor scripts manually.
+#[derive(Clone, Copy, Debug)]
+pub struct ActorView<'a> {
+    pub handle: Handle<Node>,
+    pub actor: &'a Actor,
+}
+
+impl<'a> ActorView<'a> {
+    pub fn kind(&self) -> ActorKind {
+        self.actor.kind
+    }
+
+    pub fn name(&self) -> &'a str {
+        &self.actor.name
+    }
+
+    /// Returns `true` if the actor is controlled by this instance of the game.
+    pub fn is_local_player(&self) -> bool {
+        self.actor.kind == ActorKind::Player
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.actor.kind == ActorKind::Bot
+    }
+
+    pub fn position(&self, graph: &Graph) -> Option<Vector3<f32>> {
+        graph
+            .try_get(self.actor.rigid_body)
+            .map(|rigid_body| rigid_body.global_position())
+    }
+
+    /// Returns the speed (m/s) of the actor's body.
+    pub fn speed(&self, graph: &Graph) -> f32 {
+        graph
+            .try_get_of_type::<RigidBody>(self.actor.rigid_body)
+            .map_or(0.0, |rigid_body| rigid_body.lin_vel().norm())
+    }
+
+    pub fn is_grounded(&self, graph: &Graph) -> bool {
+        self.actor.has_ground_contact(graph)
+    }
+
+    pub fn in_air_time(&self) -> f32 {
+        self.actor.in_air_time
+    }
+
+    pub fn is_ragdoll_enabled(&self, graph: &Graph) -> bool {
+        self.actor.is_ragdoll_enabled(graph)
+    }
+
+    pub fn stand_up_timer(&self) -> f32 {
+        self.actor.stand_up_timer
+    }
+
+    /// Returns the navigation state of the actor, if it is a bot.
+    pub fn bot_navigation(&self, graph: &Graph) -> Option<BotNavigationStatus> {
+        graph
+            .try_get_script_of::<Bot>(self.handle)
+            .map(|bot| bot.navigation_status())
+    }
+}
+
+/// Categories of bodies that are allowed to knock an actor down on a serious impact.
+#[derive(Clone, Debug, Visit, Reflect)]
+#[visit(optional)]
+pub struct ImpactSources {
+    #[reflect(description = "Static bodies, such as ground and walls.")]
+    pub static_bodies: bool,
+    #[reflect(description = "Other actors (players or bots).")]
+    pub actors: bool,
+    #[reflect(description = "Dynamic and kinematic bodies, such as hazards and projectiles.")]
+    pub hazards: bool,
+}
+
+impl Default for ImpactSources {
+    fn default() -> Self {
+        Self {
+            static_bodies: true,
+            actors: true,
+            hazards: true,
+        }
+    }
+}
+
 /// A marker that indicates that an object is an actor (player or bot).
 #[derive(Clone, Debug, Visit, Reflect)]
 #[visit(optional)]
@@ -41,6 +252,13 @@ pub struct Actor {
     pub name: String,
     #[reflect(hidden)]
     pub kind: ActorKind,
+    /// `true` if the actor is simulated by a remote host and this instance of the game should
+    /// only apply replicated state to it.
+    #[reflect(hidden)]
+    pub is_remote: bool,
+    /// Team of the actor, actors without a team are on their own (free-for-all).
+    #[reflect(hidden)]
+    pub team: Option<u32>,
     #[reflect(hidden)]
     pub in_air_time: f32,
     #[reflect(
@@ -59,8 +277,67 @@ pub struct Actor {
     pub collider: Handle<Node>,
     #[reflect(description = "Handle to actor's rigid body.")]
     pub rigid_body: Handle<Node>,
+    #[reflect(
+        description = "Radius of the capsule collider of the actor. The radius of the prefab is \
+        used if not set.",
+        min_value = 0.01
+    )]
+    pub capsule_radius: InheritableVariable<Option<f32>>,
+    #[reflect(
+        description = "Full height (including caps) of the capsule collider of the actor. The \
+        height of the prefab is used if not set.",
+        min_value = 0.01
+    )]
+    pub capsule_height: InheritableVariable<Option<f32>>,
     #[reflect(description = "Speed of the actor.")]
     pub speed: f32,
+    #[reflect(description = "Speed multiplier that is applied when the actor is sprinting.")]
+    pub sprint_multiplier: f32,
+    #[reflect(
+        description = "Full height of the capsule collider of the actor while crouching.",
+        min_value = 0.01
+    )]
+    pub crouch_height: InheritableVariable<f32>,
+    #[reflect(
+        description = "Speed multiplier that is applied when the actor is crouching.",
+        min_value = 0.0
+    )]
+    pub crouch_speed_multiplier: InheritableVariable<f32>,
+    #[reflect(
+        description = "Duration (in seconds) of a slide, that begins when the actor crouches \
+        while sprinting. The actor keeps its momentum during the slide. Zero disables sliding.",
+        min_value = 0.0
+    )]
+    pub slide_duration: InheritableVariable<f32>,
+    #[reflect(
+        description = "Max distance (in meters) to another actor, that could be grabbed.",
+        min_value = 0.0
+    )]
+    pub grab_distance: InheritableVariable<f32>,
+    #[reflect(
+        description = "Rate at which the actor turns toward its movement direction, the remaining \
+        angle decays as exp(-turn_rate * t). Zero makes the actor face the direction instantly.",
+        min_value = 0.0
+    )]
+    pub turn_rate: InheritableVariable<f32>,
+    /// `true` while the grab button is held. Grabs are resolved by the level, see
+    /// [`crate::grab::Grabs`].
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub wants_grab: bool,
+    /// Instance id of the actor, that is held by this actor. It is replicated to clients.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub grabbing: Option<SceneNodeId>,
+    /// Instance id of the cannon, that is operated by this actor (see
+    /// [`crate::cannon::Cannon::is_operable`]). It is replicated to clients.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub operated_cannon: Option<SceneNodeId>,
+    /// Orientation of the aim of the actor, the operated cannon is turned to it.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub aim: UnitQuaternion<f32>,
     #[reflect(description = "Jump speed of the actor.")]
     pub jump_vel: f32,
     #[reflect(hidden)]
@@ -72,8 +349,192 @@ pub struct Actor {
     #[reflect(hidden)]
     pub jump_interval: f32,
     pub footsteps: InheritableVariable<Vec<Handle<Node>>>,
+    #[reflect(
+        description = "Relative velocity (m/s) between the actor and a colliding body, that is \
+        considered a serious impact. Serious impact knocks the actor down immediately, skipping \
+        `max_in_air_time`."
+    )]
+    pub impact_velocity_threshold: f32,
+    #[reflect(
+        description = "Contact impulse that is considered a serious impact at the prefab mass. \
+        The threshold grows with `mass`, so heavier actors resist shoves. Serious impact knocks \
+        the actor down immediately, skipping `max_in_air_time`."
+    )]
+    pub impact_impulse_threshold: f32,
+    #[reflect(
+        description = "Impulse, that pushes the actor away from the body of a serious impact. \
+        Zero disables knockback.",
+        min_value = 0.0
+    )]
+    pub knockback_impulse: InheritableVariable<f32>,
+    #[reflect(
+        description = "Duration (in seconds) of the stun after a serious impact, the actor \
+        ignores movement input during it.",
+        min_value = 0.0
+    )]
+    pub impact_stun_duration: InheritableVariable<f32>,
+    #[reflect(
+        description = "Mass (in kg) of the main rigid body of the actor. Masses of the ragdoll \
+        limbs are scaled by the same factor. The mass of the prefab is used if not set.",
+        min_value = 0.1
+    )]
+    pub mass: InheritableVariable<Option<f32>>,
+    /// Mass of the main rigid body in the prefab, it is captured on the first sync.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    prefab_mass: Option<f32>,
+    #[reflect(description = "Bodies that are able to knock the actor down on a serious impact.")]
+    pub impact_sources: ImpactSources,
+    #[reflect(
+        description = "Vertical landing speed (m/s), above which the actor is briefly stunned and \
+        can't move. Fall consequences are disabled if not set.",
+        min_value = 0.0
+    )]
+    pub fall_stun_speed: InheritableVariable<Option<f32>>,
+    #[reflect(
+        description = "Duration (in seconds) of the stun after a hard landing.",
+        min_value = 0.0
+    )]
+    pub fall_stun_duration: InheritableVariable<f32>,
+    #[reflect(
+        description = "Vertical landing speed (m/s), above which the actor is knocked down with \
+        the ragdoll. Extreme falls are not punished if not set.",
+        min_value = 0.0
+    )]
+    pub fall_knockdown_speed: InheritableVariable<Option<f32>>,
+    #[reflect(
+        description = "Max speed (m/s) of the bodies of the actor. Faster bodies are slowed down, \
+        so stacked impulses can't fling the actor out of the world. Not limited if not set.",
+        min_value = 0.0
+    )]
+    pub max_velocity: InheritableVariable<Option<f32>>,
+    #[reflect(
+        description = "Respawn the actor (with effects and spawn protection) when its physics \
+        state breaks (NaN, absurd position), instead of silently moving it back to its last \
+        valid position."
+    )]
+    pub respawn_on_physics_error: InheritableVariable<bool>,
+    #[reflect(description = "Meshes, that will be tinted with the color of the actor.")]
+    pub colored_meshes: InheritableVariable<Vec<Handle<Node>>>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub color: Color,
+    #[reflect(description = "A node that will be shown while speed boost is active.")]
+    pub speed_boost_visual: Handle<Node>,
+    #[reflect(description = "A node that will be shown while shield is active.")]
+    pub shield_visual: Handle<Node>,
+    #[reflect(description = "A node that will be shown while low gravity is active.")]
+    pub low_gravity_visual: Handle<Node>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub status_effects: StatusEffects,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    status_effect_timers: [f32; StatusEffect::ALL.len()],
+    #[reflect(
+        description = "Amount of time (in seconds) after spawn, during which the actor does not \
+        collide with other actors. Protection ends earlier, if the actor has settled on the ground.",
+        min_value = 0.0
+    )]
+    pub spawn_protection_time: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    spawn_protection_timer: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    spawn_collision_groups: Option<InteractionGroups>,
+    /// `true` if the actor is waiting for respawn. On clients this flag is replicated from the
+    /// server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub knocked_out: bool,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    knocked_out_timer: f32,
+    /// Position of the last respawn, that was not handled yet, see
+    /// [`Self::take_respawn_position`].
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pending_respawn: Option<Vector3<f32>>,
+    /// `true` if the actor can't move after a hard landing or a serious impact. On clients this
+    /// flag is replicated from the server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub stunned: bool,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    stun_timer: f32,
+    /// Max downward speed of the actor during the current fall.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    fall_speed: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    hard_landing_timer: f32,
+    /// `true` if the actor waits for the respawn button after a knock out, instead of respawning
+    /// automatically. It is set by the server from the input of the player.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub manual_respawn: bool,
+    /// `true` while the respawn button is held.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub wants_respawn: bool,
+    /// `true` if the knock out is over and the actor waits for the respawn button. On clients
+    /// this flag is replicated from the server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub awaiting_respawn: bool,
+    /// `true` if the actor is out of the round. On clients this flag is replicated from the
+    /// server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub eliminated: bool,
+    /// `true` if the actor stands on something. It is computed on the server and replicated to
+    /// clients, so they could select correct animations of remote actors.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub grounded: bool,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    was_grounded: bool,
+    /// Position of the actor on the previous frame, it is used by clients to estimate velocity.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    last_position: Option<Vector3<f32>>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    respawn_position: Vector3<f32>,
+    /// Position of the actor on the last frame, when its physics state was valid.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    last_valid_position: Option<Vector3<f32>>,
+    /// `true` if the actor is crouching. On clients this flag is replicated from the server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub crouched: bool,
+    /// `true` if the actor is sliding. On clients this flag is replicated from the server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub sliding: bool,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    slide_timer: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    slide_velocity: Vector3<f32>,
+    /// Capsule of the standing actor, it is set only while the collider is crouched.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    standing_capsule: Option<CapsuleShape>,
     pub disappear_effect: InheritableVariable<Option<ModelResource>>,
     pub appear_effect: InheritableVariable<Option<ModelResource>>,
+    #[reflect(description = "Cosmetic effect, that is spawned on every footstep.")]
+    pub footstep_effect: InheritableVariable<Option<ModelResource>>,
+    #[reflect(description = "Cosmetic effect, that is spawned when the actor lands after a fall.")]
+    pub landing_effect: InheritableVariable<Option<ModelResource>>,
+    #[reflect(description = "Cosmetic effect, that is spawned on every serious impact.")]
+    pub impact_effect: InheritableVariable<Option<ModelResource>>,
 }
 
 impl Default for Actor {
@@ -81,6 +542,8 @@ impl Default for Actor {
         Self {
             name: "Player".to_string(),
             kind: ActorKind::Player,
+            is_remote: false,
+            team: None,
             in_air_time: 0.0,
             max_in_air_time: 1.1,
             stand_up_timer: 0.0,
@@ -89,15 +552,73 @@ impl Default for Actor {
             jump: false,
             collider: Default::default(),
             rigid_body: Default::default(),
+            capsule_radius: Default::default(),
+            capsule_height: Default::default(),
             speed: 4.0,
+            sprint_multiplier: 1.5,
+            crouch_height: 1.0.into(),
+            crouch_speed_multiplier: 0.5.into(),
+            slide_duration: 0.75.into(),
+            grab_distance: 1.2.into(),
+            turn_rate: 0.0.into(),
+            wants_grab: false,
+            grabbing: None,
+            operated_cannon: None,
+            aim: Default::default(),
             jump_vel: 6.5,
             target_desired_velocity: Default::default(),
             desired_velocity: Default::default(),
             absm: Default::default(),
             jump_interval: 0.0,
             footsteps: Default::default(),
+            impact_velocity_threshold: 10.0,
+            impact_impulse_threshold: 2.0,
+            knockback_impulse: 3.0.into(),
+            impact_stun_duration: 0.5.into(),
+            mass: Default::default(),
+            prefab_mass: None,
+            impact_sources: Default::default(),
+            fall_stun_speed: None.into(),
+            fall_stun_duration: 0.75.into(),
+            fall_knockdown_speed: None.into(),
+            max_velocity: Some(100.0).into(),
+            respawn_on_physics_error: false.into(),
+            colored_meshes: Default::default(),
+            color: Color::WHITE,
+            speed_boost_visual: Default::default(),
+            shield_visual: Default::default(),
+            low_gravity_visual: Default::default(),
+            status_effects: Default::default(),
+            status_effect_timers: Default::default(),
+            spawn_protection_time: 1.0,
+            spawn_protection_timer: 0.0,
+            spawn_collision_groups: None,
+            knocked_out: false,
+            knocked_out_timer: 0.0,
+            pending_respawn: None,
+            stunned: false,
+            stun_timer: 0.0,
+            fall_speed: 0.0,
+            hard_landing_timer: 0.0,
+            manual_respawn: false,
+            wants_respawn: false,
+            awaiting_respawn: false,
+            eliminated: false,
+            grounded: false,
+            was_grounded: false,
+            last_position: None,
+            crouched: false,
+            sliding: false,
+            slide_timer: 0.0,
+            slide_velocity: Default::default(),
+            standing_capsule: None,
+            respawn_position: Default::default(),
+            last_valid_position: None,
             disappear_effect: Default::default(),
             appear_effect: Default::default(),
+            footstep_effect: Default::default(),
+            landing_effect: Default::default(),
+            impact_effect: Default::default(),
         }
     }
 }
@@ -124,6 +645,66 @@ impl Actor {
         utils::has_ground_contact(self.collider, graph) || self.is_ragdoll_has_ground_contact(graph)
     }
 
+    /// Returns `true` if the actor is allowed to fall into ragdoll. It is decided by the debug
+    /// settings and the level config (per actor kind).
+    pub fn is_ragdoll_allowed(&self, game: &Game, graph: &Graph) -> bool {
+        !game.debug_settings.disable_ragdoll
+            && game
+                .level
+                .config(graph)
+                .map_or(true, |config| config.is_ragdoll_enabled(self.kind))
+    }
+
+    /// Returns the rigid body of the given limb of the ragdoll, or `None` if there's no such limb.
+    pub fn limb_rigid_body(&self, limb: &LimbSelector, graph: &Graph) -> Option<Handle<Node>> {
+        let ragdoll = graph.try_get_of_type::<Ragdoll>(self.ragdoll)?;
+        let mut index = 0;
+        let mut result = None;
+        ragdoll.root_limb.iterate_recursive(&mut |current| {
+            if result.is_none() {
+                let is_match = match limb {
+                    LimbSelector::Index(limb_index) => *limb_index == index,
+                    LimbSelector::Name(name) => graph
+                        .try_get(current.physical_bone)
+                        .map_or(false, |bone| bone.name() == name),
+                };
+                if is_match {
+                    result = Some(current.physical_bone);
+                }
+            }
+            index += 1;
+        });
+        result
+    }
+
+    /// Applies an impulse to a single limb, so the actor could be tripped by a leg sweep or spun
+    /// by a hit to the shoulder. The ragdoll is activated, since limbs follow the animation
+    /// otherwise, and the actor stands up as usual once it is on the ground. If ragdoll is not
+    /// allowed, the whole body is pushed instead. Returns `false` if there's no such limb.
+    pub fn apply_limb_impulse(
+        &mut self,
+        limb: &LimbSelector,
+        impulse: Vector3<f32>,
+        ragdoll_allowed: bool,
+        graph: &mut Graph,
+    ) -> bool {
+        let Some(limb_body) = self.limb_rigid_body(limb, graph) else {
+            return false;
+        };
+
+        let body = if ragdoll_allowed {
+            self.set_ragdoll_enabled(graph, true);
+            self.stand_up_timer = 0.0;
+            limb_body
+        } else {
+            self.rigid_body
+        };
+        if let Some(rigid_body) = graph.try_get_mut_of_type::<RigidBody>(body) {
+            rigid_body.apply_impulse(impulse);
+        }
+        true
+    }
+
     pub fn set_ragdoll_enabled(&mut self, graph: &mut Graph, enabled: bool) {
         if let Some(ragdoll) = graph.try_get_mut_of_type::<Ragdoll>(self.ragdoll) {
             ragdoll.is_active.set_value_and_mark_modified(enabled);
@@ -138,6 +719,136 @@ impl Actor {
         }
     }
 
+    /// Returns the center of mass of the ragdoll limbs, if the ragdoll is enabled. Unlike the
+    /// position of a single limb, it is stable enough to be followed by the camera.
+    pub fn ragdoll_center_of_mass(&self, graph: &Graph) -> Option<Vector3<f32>> {
+        let ragdoll = graph.try_get_of_type::<Ragdoll>(self.ragdoll)?;
+        if !*ragdoll.is_active {
+            return None;
+        }
+        let mut weighted_sum = Vector3::default();
+        let mut total_mass = 0.0;
+        ragdoll.root_limb.iterate_recursive(&mut |limb| {
+            if let Some(rigid_body) = graph.try_get_of_type::<RigidBody>(limb.physical_bone) {
+                let mass = rigid_body.mass().max(f32::EPSILON);
+                weighted_sum += rigid_body.global_position().scale(mass);
+                total_mass += mass;
+            }
+        });
+        (total_mass > 0.0).then(|| weighted_sum.scale(1.0 / total_mass))
+    }
+
+    /// Disables collisions with other actors for [`Self::spawn_protection_time`] seconds, so
+    /// actors spawned at overlapping positions won't launch each other on the first physics step.
+    pub fn begin_spawn_protection(&mut self, graph: &mut Graph) {
+        if self.spawn_protection_time <= 0.0 {
+            return;
+        }
+
+        if let Some(collider) = graph.try_get_mut_of_type::<Collider>(self.collider) {
+            let groups = collider.collision_groups();
+            // Keep original groups if the protection is already active.
+            let original = *self.spawn_collision_groups.get_or_insert(groups);
+            collider.set_collision_groups(InteractionGroups::new(
+                SPAWN_PROTECTION_GROUP,
+                BitMask(original.filter.0 & !SPAWN_PROTECTION_GROUP.0),
+            ));
+            self.spawn_protection_timer = self.spawn_protection_time;
+        }
+    }
+
+    fn end_spawn_protection(&mut self, graph: &mut Graph) {
+        self.spawn_protection_timer = 0.0;
+        if let Some(groups) = self.spawn_collision_groups.take() {
+            if let Some(collider) = graph.try_get_mut_of_type::<Collider>(self.collider) {
+                collider.set_collision_groups(groups);
+            }
+        }
+    }
+
+    pub fn has_spawn_protection(&self) -> bool {
+        self.spawn_collision_groups.is_some()
+    }
+
+    fn update_spawn_protection(&mut self, dt: f32, has_ground_contact: bool, graph: &mut Graph) {
+        if !self.has_spawn_protection() {
+            return;
+        }
+
+        self.spawn_protection_timer -= dt;
+
+        let elapsed = self.spawn_protection_time - self.spawn_protection_timer;
+        let is_settled = elapsed >= MIN_SPAWN_PROTECTION_TIME
+            && has_ground_contact
+            && graph
+                .try_get_of_type::<RigidBody>(self.rigid_body)
+                .map_or(false, |rb| rb.lin_vel().norm() < SETTLED_VELOCITY_THRESHOLD);
+
+        if self.spawn_protection_timer <= 0.0 || is_settled {
+            self.end_spawn_protection(graph);
+        }
+    }
+
+    /// Re-fits the capsule collider to the configured dimensions. The center and the axis of the
+    /// capsule are preserved, so the actor stays at the same place. Ground contacts are taken from
+    /// contact manifolds of the collider, so they follow the new shape automatically. Ragdoll
+    /// limbs have their own colliders and are not affected.
+    fn apply_capsule_dimensions(&self, graph: &mut Graph) {
+        if self.capsule_radius.is_none() && self.capsule_height.is_none() {
+            return;
+        }
+
+        let Some(collider) = graph.try_get_mut_of_type::<Collider>(self.collider) else {
+            return;
+        };
+
+        let ColliderShape::Capsule(capsule) = collider.shape().clone() else {
+            Log::warn(format!(
+                "Unable to change dimensions of actor {}: its collider is not a capsule.",
+                self.name
+            ));
+            return;
+        };
+
+        let axis = capsule.end - capsule.begin;
+        let center = (capsule.begin + capsule.end).scale(0.5);
+        let direction = axis.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y);
+        let radius = self.capsule_radius.unwrap_or(capsule.radius).max(0.01);
+        let height = self
+            .capsule_height
+            .unwrap_or(axis.norm() + 2.0 * capsule.radius);
+        // The height can't be less than the diameter, the capsule becomes a sphere in this case.
+        let half_segment = (height * 0.5 - radius).max(0.0);
+
+        collider.set_shape(ColliderShape::Capsule(CapsuleShape {
+            begin: center - direction.scale(half_segment),
+            end: center + direction.scale(half_segment),
+            radius,
+        }));
+    }
+
+    /// Returns the radius of the collider of the actor, if it is a capsule or a ball.
+    pub fn collider_radius(&self, graph: &Graph) -> Option<f32> {
+        match graph.try_get_of_type::<Collider>(self.collider)?.shape() {
+            ColliderShape::Capsule(capsule) => Some(capsule.radius),
+            ColliderShape::Ball(ball) => Some(ball.radius),
+            _ => None,
+        }
+    }
+
+    pub fn on_start(&mut self, ctx: &mut ScriptContext) {
+        self.apply_capsule_dimensions(&mut ctx.scene.graph);
+        self.sync_mass(&mut ctx.scene.graph);
+        self.begin_spawn_protection(&mut ctx.scene.graph);
+
+        let game = ctx.plugins.get::<Game>();
+        if let Some(config) = game.level.config(&ctx.scene.graph).cloned() {
+            self.for_each_rigid_body(&mut ctx.scene.graph, |rigid_body| {
+                config.apply_drag(rigid_body)
+            });
+        }
+    }
+
     pub fn on_message(
         &mut self,
         message: &mut dyn ScriptMessagePayload,
@@ -149,25 +860,236 @@ impl Actor {
 
         match message {
             ActorMessage::RespawnAt(position) => {
-                if let Some(disappear_effect) = self.disappear_effect.as_ref() {
-                    let current_position = ctx.scene.graph[self.rigid_body].global_position();
-                    disappear_effect.instantiate_at(
-                        ctx.scene,
-                        current_position,
-                        Default::default(),
-                    );
+                let game = ctx.plugins.get::<Game>();
+                if self.manual_respawn && manual_respawn_wait(game).is_some() {
+                    // An instant respawn turns into a prompt in manual mode.
+                    self.knock_out(*position, 0.0, game, &mut ctx.scene.graph);
+                } else {
+                    self.respawn_at(*position, ctx.scene);
                 }
+            }
+            ActorMessage::KnockOut {
+                respawn_position,
+                duration,
+            } => {
+                let game = ctx.plugins.get::<Game>();
+                self.knock_out(*respawn_position, *duration, game, &mut ctx.scene.graph);
+            }
+            ActorMessage::Eliminate => {
+                let game = ctx.plugins.get_mut::<Game>();
+                self.eliminate(ctx.handle, game, ctx.scene);
+            }
+            ActorMessage::LimbImpulse { limb, impulse } => {
+                let game = ctx.plugins.get::<Game>();
+                if !game.is_client() {
+                    let ragdoll_allowed = self.is_ragdoll_allowed(game, &ctx.scene.graph);
+                    self.apply_limb_impulse(limb, *impulse, ragdoll_allowed, &mut ctx.scene.graph);
+                }
+            }
+            ActorMessage::Knockback { impulse } => {
+                let game = ctx.plugins.get::<Game>();
+                if !game.is_client() && !self.is_knocked_out() {
+                    self.apply_knockback(*impulse, &mut ctx.scene.graph);
+                }
+            }
+        }
+    }
 
-                self.set_ragdoll_enabled(&mut ctx.scene.graph, false);
+    fn knock_out(
+        &mut self,
+        respawn_position: Vector3<f32>,
+        duration: f32,
+        game: &Game,
+        graph: &mut Graph,
+    ) {
+        if !self.knocked_out {
+            self.knocked_out = true;
+            self.knocked_out_timer = duration;
+            self.respawn_position = respawn_position;
+            if self.is_ragdoll_allowed(game, graph) {
+                self.set_ragdoll_enabled(graph, true);
+            }
+        }
+    }
+
+    fn respawn_at(&mut self, position: Vector3<f32>, scene: &mut Scene) {
+        if let Some(disappear_effect) = self.disappear_effect.as_ref() {
+            let current_position = scene.graph[self.rigid_body].global_position();
+            disappear_effect.instantiate_at(scene, current_position, Default::default());
+        }
 
-                self.for_each_rigid_body(&mut ctx.scene.graph, |rb| {
-                    rb.local_transform_mut().set_position(*position);
-                });
+        self.knocked_out = false;
+        self.knocked_out_timer = 0.0;
+        self.awaiting_respawn = false;
+        self.crouched = false;
+        self.sliding = false;
+        self.stunned = false;
+        self.stun_timer = 0.0;
+        self.operated_cannon = None;
+        self.sync_crouch_capsule(&mut scene.graph);
+
+        self.set_ragdoll_enabled(&mut scene.graph, false);
+        self.begin_spawn_protection(&mut scene.graph);
+
+        // Zero the velocities, so the body does not keep flying after a fall or a hit.
+        self.pending_respawn = Some(position);
+        self.for_each_rigid_body(&mut scene.graph, |rb| {
+            rb.local_transform_mut().set_position(position);
+            rb.set_lin_vel(Vector3::default());
+            rb.set_ang_vel(Vector3::default());
+        });
 
-                if let Some(appear_effect) = self.appear_effect.as_ref() {
-                    appear_effect.instantiate_at(ctx.scene, *position, Default::default());
+        if let Some(appear_effect) = self.appear_effect.as_ref() {
+            appear_effect.instantiate_at(scene, position, Default::default());
+        }
+    }
+
+    /// Limits velocities of the bodies of the actor and detects broken physics state (non-finite
+    /// or absurd values, that could appear after stacked impulses). The broken actor is moved back
+    /// to its last valid position or respawned there (see [`Self::respawn_on_physics_error`]).
+    fn recover_from_physics_error(&mut self, scene: &mut Scene) {
+        let max_velocity = *self.max_velocity;
+        let mut is_broken = false;
+        self.for_each_rigid_body(&mut scene.graph, |rigid_body| {
+            let transform = rigid_body.local_transform();
+            let position = **transform.position();
+            let velocity = rigid_body.lin_vel();
+            if !is_finite(&position)
+                || position.amax() > MAX_WORLD_EXTENT
+                || !transform.rotation().coords.iter().all(|c| c.is_finite())
+                || !is_finite(&velocity)
+                || !is_finite(&rigid_body.ang_vel())
+            {
+                is_broken = true;
+            } else if let Some(max_velocity) = max_velocity {
+                let speed = velocity.norm();
+                if speed > max_velocity {
+                    rigid_body.set_lin_vel(velocity.scale(max_velocity / speed));
                 }
             }
+        });
+
+        if !is_broken {
+            self.last_valid_position = scene
+                .graph
+                .try_get(self.rigid_body)
+                .map(|rigid_body| **rigid_body.local_transform().position());
+            return;
+        }
+
+        let position = self.last_valid_position.unwrap_or(self.respawn_position);
+        Log::warn(format!(
+            "Physics state of {} is broken, it is moved back to {position:?}.",
+            self.name
+        ));
+        self.for_each_rigid_body(&mut scene.graph, |rigid_body| {
+            let transform = rigid_body.local_transform_mut();
+            transform.set_position(position);
+            if !transform.rotation().coords.iter().all(|c| c.is_finite()) {
+                transform.set_rotation(UnitQuaternion::identity());
+            }
+            rigid_body.set_lin_vel(Vector3::zeros());
+            rigid_body.set_ang_vel(Vector3::zeros());
+        });
+        if *self.respawn_on_physics_error {
+            self.respawn_at(position, scene);
+        }
+    }
+
+    /// Returns `true` if the actor is waiting for respawn. Knocked out actors ignore any input.
+    pub fn is_knocked_out(&self) -> bool {
+        self.knocked_out
+    }
+
+    /// Returns `true` if the actor operates a cannon and aims with it.
+    pub fn is_aiming(&self) -> bool {
+        self.operated_cannon.is_some()
+    }
+
+    /// Returns `true` if the actor can't move after a hard landing.
+    pub fn is_stunned(&self) -> bool {
+        self.stunned
+    }
+
+    /// Returns `true` if the actor is out of the round. Eliminated actors are hidden and do not
+    /// interact with anything until the next level.
+    pub fn is_eliminated(&self) -> bool {
+        self.eliminated
+    }
+
+    /// Removes the actor from the round instead of respawning it. The elimination is recorded in
+    /// the leaderboard, so the round could end once nobody is left racing. Players spectate other
+    /// actors after elimination. Does nothing if the actor is already eliminated.
+    pub fn eliminate(&mut self, self_handle: Handle<Node>, game: &mut Game, scene: &mut Scene) {
+        if self.eliminated {
+            return;
+        }
+
+        if let Some(disappear_effect) = self.disappear_effect.as_ref() {
+            let current_position = scene.graph[self.rigid_body].global_position();
+            disappear_effect.instantiate_at(scene, current_position, Default::default());
+        }
+
+        self.eliminated = true;
+        self.knocked_out = false;
+        self.knocked_out_timer = 0.0;
+        self.awaiting_respawn = false;
+        self.target_desired_velocity = Default::default();
+        self.set_ragdoll_enabled(&mut scene.graph, false);
+        self.end_spawn_protection(&mut scene.graph);
+        apply_elimination(
+            self_handle,
+            self.collider,
+            self.rigid_body,
+            &mut scene.graph,
+        );
+
+        if game.level.leaderboard.eliminate(self_handle) {
+            game.emit(GameEvent::ActorEliminated { actor: self_handle });
+        }
+    }
+
+    /// Returns the position of the last respawn once. Respawns happen on messages as well as at the
+    /// end of a knock out, so this is the only reliable way to know about them.
+    pub fn take_respawn_position(&mut self) -> Option<Vector3<f32>> {
+        self.pending_respawn.take()
+    }
+
+    /// Counts down the knock out and respawns the actor. In manual mode the actor then waits for
+    /// the respawn button, but no longer than `max_wait` seconds. Manual mode is ignored if
+    /// `max_wait` is `None`.
+    fn update_knock_out(
+        &mut self,
+        dt: f32,
+        ragdoll_allowed: bool,
+        max_wait: Option<f32>,
+        scene: &mut Scene,
+    ) {
+        if !self.knocked_out {
+            return;
+        }
+
+        // Keep the actor lying on the ground (or standing still) until respawn.
+        self.target_desired_velocity.x = 0.0;
+        self.target_desired_velocity.z = 0.0;
+        if ragdoll_allowed {
+            self.set_ragdoll_enabled(&mut scene.graph, true);
+        }
+
+        self.knocked_out_timer -= dt;
+        if self.knocked_out_timer > 0.0 {
+            return;
+        }
+
+        // The timer keeps going below zero while waiting, so it measures the wait time.
+        self.awaiting_respawn = match max_wait {
+            Some(max_wait) if self.manual_respawn => {
+                !self.wants_respawn && -self.knocked_out_timer < max_wait
+            }
+            _ => false,
+        };
+        if !self.awaiting_respawn {
+            self.respawn_at(self.respawn_position, scene);
         }
     }
 
@@ -191,7 +1113,21 @@ impl Actor {
         };
     }
 
+    /// Rotates the given body of the actor toward the target facing with [`Self::turn_rate`]
+    /// instead of snapping to it.
+    pub fn turn_towards(&self, body: &mut Base, target: UnitQuaternion<f32>, dt: f32) {
+        let current = **body.local_transform().rotation();
+        let t = turn_fraction(*self.turn_rate, dt);
+        body.local_transform_mut()
+            .set_rotation(current.slerp(&target, t));
+    }
+
+    /// Sets horizontal velocity of the actor and adds the vertical one. Does nothing while the
+    /// actor is stunned, so knockback is not cancelled by the input.
     pub fn set_velocity(&mut self, velocity: Vector3<f32>, graph: &mut Graph) {
+        if self.stunned {
+            return;
+        }
         self.for_each_rigid_body(graph, &mut |rigid_body: &mut RigidBody| {
             let y_vel = rigid_body.lin_vel().y + velocity.y;
             rigid_body.set_lin_vel(Vector3::new(velocity.x, y_vel, velocity.z));
@@ -199,6 +1135,9 @@ impl Actor {
     }
 
     pub fn add_force(&mut self, force: Vector3<f32>, max_speed: f32, graph: &mut Graph) {
+        if self.stunned {
+            return;
+        }
         self.for_each_rigid_body(graph, &mut |rigid_body: &mut RigidBody| {
             if rigid_body.lin_vel().xz().norm() < max_speed {
                 rigid_body.apply_force(force);
@@ -212,6 +1151,232 @@ impl Actor {
         }
     }
 
+    /// Changes the mass of the actor, `None` restores the mass of the prefab. It is applied to the
+    /// live bodies on the next update, so power-ups could change it temporarily.
+    pub fn set_mass(&mut self, mass: Option<f32>) {
+        self.mass.set_value_and_mark_modified(mass);
+    }
+
+    /// Returns the ratio between the current mass and the mass of the prefab.
+    pub fn mass_scale(&self, graph: &Graph) -> f32 {
+        match (
+            self.prefab_mass,
+            graph.try_get_of_type::<RigidBody>(self.rigid_body),
+        ) {
+            (Some(prefab_mass), Some(rigid_body)) if prefab_mass > 0.0 => {
+                rigid_body.mass() / prefab_mass
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Applies [`Self::mass`] to the main body. Every ragdoll limb is scaled by the same factor, so
+    /// the ragdoll keeps its proportions and does not behave differently from the main body.
+    ///
+    /// Movement sets velocities directly and does not depend on mass. Contacts, wind (a force) and
+    /// limb impulses do: a twice as heavy actor gains half the velocity from the same impulse. The
+    /// impulse, that knocks the actor down, is scaled by mass as well, while the velocity
+    /// threshold stays the same, since a fast hit is serious regardless of weight.
+    fn sync_mass(&mut self, graph: &mut Graph) {
+        let Some(current_mass) = graph
+            .try_get_of_type::<RigidBody>(self.rigid_body)
+            .map(|rigid_body| rigid_body.mass())
+        else {
+            return;
+        };
+        let prefab_mass = *self.prefab_mass.get_or_insert(current_mass);
+        let target_mass = self.mass.map_or(prefab_mass, |mass| mass.max(0.1));
+        if current_mass <= 0.0 || (target_mass - current_mass).abs() <= f32::EPSILON {
+            return;
+        }
+        let scale = target_mass / current_mass;
+        self.for_each_rigid_body(graph, |rigid_body| {
+            let mass = rigid_body.mass();
+            rigid_body.set_mass(mass * scale);
+        });
+    }
+
+    pub fn movement_speed(&self, sprint: bool) -> f32 {
+        if self.crouched {
+            self.speed * self.crouch_speed_multiplier.max(0.0)
+        } else if sprint {
+            self.speed * self.sprint_multiplier
+        } else {
+            self.speed
+        }
+    }
+
+    /// Returns `true` if there's enough free space above the crouching actor to stand up.
+    fn can_stand_up(&self, graph: &Graph) -> bool {
+        let (Some(collider), Some(standing)) = (
+            graph.try_get_of_type::<Collider>(self.collider),
+            self.standing_capsule.as_ref(),
+        ) else {
+            return true;
+        };
+        let ColliderShape::Capsule(crouched) = collider.shape() else {
+            return true;
+        };
+
+        let transform = collider.global_transform();
+        let top = |capsule: &CapsuleShape| {
+            let direction = (capsule.end - capsule.begin)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y);
+            transform
+                .transform_point(&Point3::from(capsule.end + direction.scale(capsule.radius)))
+                .coords
+        };
+        let origin = top(crouched);
+        let delta = top(standing) - origin;
+        let distance = delta.norm();
+        let Some(direction) = delta.try_normalize(f32::EPSILON) else {
+            return true;
+        };
+
+        let mut buffer = ArrayVec::<_, 64>::new();
+        graph.physics.cast_ray(
+            RayCastOptions {
+                ray_origin: Point3::from(origin),
+                ray_direction: direction,
+                max_len: distance,
+                groups: Default::default(),
+                sort_results: false,
+            },
+            &mut buffer,
+        );
+
+        !buffer.iter().any(|intersection| {
+            intersection.collider != self.collider
+                && graph
+                    .try_get_of_type::<Collider>(intersection.collider)
+                    .map_or(true, |collider| {
+                        // Ragdoll colliders are parts of the actor.
+                        collider.collision_groups().memberships.0 & 0b0000_0010 == 0
+                    })
+        })
+    }
+
+    /// Shrinks (or restores) the capsule collider according to [`Self::crouched`]. The bottom of
+    /// the capsule stays at the same place, so the actor does not fall when crouching.
+    fn sync_crouch_capsule(&mut self, graph: &mut Graph) {
+        if self.crouched == self.standing_capsule.is_some() {
+            return;
+        }
+
+        let Some(collider) = graph.try_get_mut_of_type::<Collider>(self.collider) else {
+            return;
+        };
+
+        if let Some(standing) = self.standing_capsule.take() {
+            collider.set_shape(ColliderShape::Capsule(standing));
+        } else if let ColliderShape::Capsule(capsule) = collider.shape().clone() {
+            let direction = (capsule.end - capsule.begin)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y);
+            let bottom = capsule.begin - direction.scale(capsule.radius);
+            let height = self.crouch_height.max(2.0 * capsule.radius);
+            let half_segment = height * 0.5 - capsule.radius;
+            let center = bottom + direction.scale(height * 0.5);
+            collider.set_shape(ColliderShape::Capsule(CapsuleShape {
+                begin: center - direction.scale(half_segment),
+                end: center + direction.scale(half_segment),
+                radius: capsule.radius,
+            }));
+            self.standing_capsule = Some(capsule);
+        }
+    }
+
+    /// Makes the actor crouch or stand up. Crouching while sprinting begins a slide, that keeps
+    /// the current momentum of the actor. Standing up is postponed while there's something
+    /// overhead.
+    pub fn set_crouch(&mut self, crouch: bool, sprint: bool, graph: &mut Graph) {
+        if crouch && !self.crouched {
+            self.crouched = true;
+            let velocity = Vector3::new(self.desired_velocity.x, 0.0, self.desired_velocity.z);
+            if sprint && *self.slide_duration > 0.0 && velocity.norm() > self.speed {
+                self.sliding = true;
+                self.slide_timer = *self.slide_duration;
+                self.slide_velocity = velocity;
+            }
+        } else if !crouch && self.crouched && self.can_stand_up(graph) {
+            self.crouched = false;
+            self.sliding = false;
+        }
+
+        self.sync_crouch_capsule(graph);
+    }
+
+    /// Returns horizontal velocity of the slide, if the actor is sliding. The velocity decays from
+    /// the initial one to the crouching speed by the end of the slide.
+    pub fn slide_velocity(&self) -> Option<Vector3<f32>> {
+        if !self.sliding {
+            return None;
+        }
+
+        let crouch_speed = self.movement_speed(false);
+        let t = (self.slide_timer / self.slide_duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+        let speed = crouch_speed + (self.slide_velocity.norm() - crouch_speed).max(0.0) * t;
+        self.slide_velocity
+            .try_normalize(f32::EPSILON)
+            .map(|direction| direction.scale(speed))
+    }
+
+    /// Punishes a hard landing with the given vertical speed: a stun, or a full knock down with the
+    /// ragdoll on extreme falls (if the ragdoll is allowed). Returns `true` if the landing was
+    /// hard.
+    fn apply_fall_consequences(
+        &mut self,
+        landing_speed: f32,
+        ragdoll_allowed: bool,
+        graph: &mut Graph,
+    ) -> bool {
+        let exceeds = |threshold: Option<f32>| threshold.map_or(false, |t| landing_speed >= t);
+        if ragdoll_allowed && exceeds(*self.fall_knockdown_speed) {
+            self.set_ragdoll_enabled(graph, true);
+            self.stand_up_timer = 0.0;
+        } else if exceeds(*self.fall_stun_speed) {
+            self.stunned = true;
+            self.stun_timer = *self.fall_stun_duration;
+        } else {
+            return false;
+        }
+        self.hard_landing_timer = HARD_LANDING_IMPACT_GRACE;
+        true
+    }
+
+    /// Pushes the actor with the given impulse and stuns it for a short time, so the push isn't
+    /// cancelled by the movement input.
+    fn apply_knockback(&mut self, impulse: Vector3<f32>, graph: &mut Graph) {
+        if let Some(rigid_body) = graph.try_get_mut_of_type::<RigidBody>(self.rigid_body) {
+            rigid_body.apply_impulse(impulse);
+        }
+        // A longer stun of a hard landing is kept.
+        if *self.impact_stun_duration > 0.0 {
+            self.stunned = true;
+            self.stun_timer = self.stun_timer.max(*self.impact_stun_duration);
+        }
+    }
+
+    fn update_stun(&mut self, dt: f32) {
+        self.hard_landing_timer -= dt;
+        if self.stunned {
+            self.stun_timer -= dt;
+            if self.stun_timer <= 0.0 {
+                self.stunned = false;
+            }
+        }
+    }
+
+    fn update_slide(&mut self, dt: f32) {
+        if self.sliding {
+            self.slide_timer -= dt;
+            if self.slide_timer <= 0.0 || !self.crouched {
+                self.sliding = false;
+            }
+        }
+    }
+
     pub fn jump(&mut self) {
         if self.jump_interval <= 0.0 {
             self.jump_interval = 0.35;
@@ -219,30 +1384,143 @@ impl Actor {
         }
     }
 
-    fn has_serious_impact(&mut self, ctx: &mut ScriptContext) -> bool {
-        if let Some(collider) = ctx.scene.graph.try_get_of_type::<Collider>(self.collider) {
-            for contact in collider.contacts(&ctx.scene.graph.physics) {
-                if contact.has_any_active_contact {
-                    for manifold in contact.manifolds.iter() {
-                        if let (Some(rb1), Some(rb2)) = (
-                            ctx.scene
-                                .graph
-                                .try_get_of_type::<RigidBody>(manifold.rigid_body1),
-                            ctx.scene
-                                .graph
-                                .try_get_of_type::<RigidBody>(manifold.rigid_body2),
-                        ) {
-                            if (rb1.lin_vel() - rb2.lin_vel()).norm() > 10.0
-                                || manifold.points.iter().any(|p| p.impulse > 2.0)
-                            {
-                                return true;
-                            }
-                        }
-                    }
-                }
+    /// Activates the given status effect for the given amount of time (in seconds). Status
+    /// effects are server-authoritative, clients receive them with each tick.
+    pub fn apply_status_effect(&mut self, effect: StatusEffect, duration: f32) {
+        self.status_effects.insert(effect);
+        let timer = &mut self.status_effect_timers[effect as usize];
+        *timer = timer.max(duration);
+    }
+
+    fn update_status_effects(&mut self, dt: f32) {
+        for effect in StatusEffect::ALL {
+            let timer = &mut self.status_effect_timers[effect as usize];
+            *timer -= dt;
+            if *timer <= 0.0 {
+                *timer = 0.0;
+                self.status_effects.remove(effect);
             }
         }
-        false
+    }
+
+    /// Returns visual nodes of every status effect paired with the desired visibility.
+    pub fn status_effect_visuals(&self) -> [(Handle<Node>, bool); StatusEffect::ALL.len()] {
+        StatusEffect::ALL.map(|effect| {
+            let visual = match effect {
+                StatusEffect::SpeedBoost => self.speed_boost_visual,
+                StatusEffect::Shield => self.shield_visual,
+                StatusEffect::LowGravity => self.low_gravity_visual,
+            };
+            (visual, self.status_effects.contains(effect))
+        })
+    }
+
+    pub fn is_teammate_of(&self, other: &Actor) -> bool {
+        self.team.is_some() && self.team == other.team
+    }
+
+    fn is_impact_source_allowed(
+        &self,
+        body_handle: Handle<Node>,
+        body: &RigidBody,
+        actors: &HashSet<Handle<Node>>,
+        friendly_fire: bool,
+        graph: &Graph,
+    ) -> bool {
+        let other_actor = actors.iter().find_map(|actor| {
+            graph
+                .try_get_script_component_of::<Actor>(*actor)
+                .filter(|actor| actor.rigid_body == body_handle)
+        });
+
+        if let Some(other_actor) = other_actor {
+            self.impact_sources.actors && (friendly_fire || !self.is_teammate_of(other_actor))
+        } else if body.body_type() == RigidBodyType::Static {
+            self.impact_sources.static_bodies
+        } else {
+            self.impact_sources.hazards
+        }
+    }
+
+    fn set_animation_parameters(&self, run: bool, jump: bool, graph: &mut Graph) {
+        if let Some(absm) = graph.try_get_mut_of_type::<AnimationBlendingStateMachine>(self.absm) {
+            absm.machine_mut()
+                .get_value_mut_silent()
+                .set_parameter("Run", Parameter::Rule(run))
+                .set_parameter("Jump", Parameter::Rule(jump))
+                .set_parameter("Crouch", Parameter::Rule(self.crouched))
+                .set_parameter("Slide", Parameter::Rule(self.sliding))
+                .set_parameter("Grab", Parameter::Rule(self.grabbing.is_some()));
+        }
+    }
+
+    /// Drives animations of the actor on clients, where the actor is not simulated. Velocity is
+    /// estimated from the (interpolated) movement of the actor and the grounded flag comes from
+    /// the server, so airborne actors are never shown running.
+    pub fn update_client_animation(&mut self, dt: f32, graph: &mut Graph) {
+        self.sync_crouch_capsule(graph);
+
+        let Some(position) = graph.try_get(self.rigid_body).map(|n| n.global_position()) else {
+            return;
+        };
+
+        let speed = self
+            .last_position
+            .filter(|_| dt > 0.0)
+            .map_or(0.0, |last| (position - last).xz().norm() / dt);
+        self.last_position = Some(position);
+
+        let run = self.grounded && speed >= 0.75 * self.speed;
+        let jump = self.was_grounded && !self.grounded;
+        self.was_grounded = self.grounded;
+
+        self.set_animation_parameters(run, jump, graph);
+    }
+
+    /// Returns a handle of the body, that has seriously hit the actor, if any, the relative
+    /// velocity of the impact and the direction of the knockback. The knockback pushes the actor
+    /// away from the contact and along the movement of the body.
+    fn find_serious_impact(
+        &mut self,
+        ctx: &mut ScriptContext,
+    ) -> Option<(Handle<Node>, f32, Vector3<f32>)> {
+        let game = ctx.plugins.get::<Game>();
+        let graph = &ctx.scene.graph;
+        // Impacts are resolved on the server only, so the setting of the server is used.
+        let friendly_fire = game
+            .server
+            .as_ref()
+            .map_or(true, |server| server.friendly_fire);
+        let mass_scale = self.mass_scale(graph);
+        utils::actor_contacts(self.collider, graph)
+            .find(|contact| {
+                let Some(other_body) = contact.other_body else {
+                    return false;
+                };
+
+                self.is_impact_source_allowed(
+                    contact.other_body_handle,
+                    other_body,
+                    &game.level.actors,
+                    friendly_fire,
+                    graph,
+                ) && (contact.relative_velocity() > self.impact_velocity_threshold
+                    || contact.max_impulse > self.impact_impulse_threshold * mass_scale)
+            })
+            .map(|contact| {
+                let push = contact
+                    .relative_velocity_vector()
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_default();
+                let direction = (contact.normal + push)
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or(contact.normal);
+                (
+                    contact.other_body_handle,
+                    contact.relative_velocity(),
+                    direction,
+                )
+            })
     }
 
     pub fn is_in_jump_state(&self, graph: &Graph) -> bool {
@@ -283,12 +1561,17 @@ impl Actor {
         sound.play();
     }
 
-    fn process_animation_events(&mut self, ctx: &mut ScriptContext, has_ground_contact: bool) {
+    /// Plays footstep sounds and returns `true` if there was at least one footstep.
+    fn process_animation_events(
+        &mut self,
+        ctx: &mut ScriptContext,
+        has_ground_contact: bool,
+    ) -> bool {
         let mbc = ctx.scene.graph.begin_multi_borrow();
 
         let Ok(absm) = mbc.try_get_component_of_type::<AnimationBlendingStateMachine>(self.absm)
         else {
-            return;
+            return false;
         };
 
         let machine = absm.machine();
@@ -296,11 +1579,11 @@ impl Actor {
         let Ok(mut animation_player) =
             mbc.try_get_component_of_type_mut::<AnimationPlayer>(absm.animation_player())
         else {
-            return;
+            return false;
         };
 
         let Some(first) = machine.layers().first() else {
-            return;
+            return false;
         };
 
         let events_collection = first.collect_active_animations_events(
@@ -309,9 +1592,11 @@ impl Actor {
             AnimationEventCollectionStrategy::All,
         );
 
+        let mut has_footstep = false;
         for (_, event) in events_collection.events {
             if event.name == "Footstep" && has_ground_contact {
                 self.play_random_footstep_sound(&mbc);
+                has_footstep = true;
             }
         }
 
@@ -319,13 +1604,47 @@ impl Actor {
             .animations_mut()
             .get_value_mut_silent()
             .clear_animation_events();
+
+        has_footstep
+    }
+
+    /// Asks every client to show the given cosmetic effect at the given position. Effects are
+    /// never instantiated by the simulation itself, so they can't affect it.
+    fn spawn_effect(effect: Option<&ModelResource>, position: Vector3<f32>, game: &mut Game) {
+        let Some(effect) = effect else {
+            return;
+        };
+
+        let Some(path) = effect.kind().path().map(|path| path.to_path_buf()) else {
+            return;
+        };
+
+        game.instantiate(vec![InstanceDescriptor {
+            path,
+            position,
+            ids: effect.generate_ids(),
+            lifetime: Some(EFFECT_LIFETIME),
+            ..Default::default()
+        }]);
     }
 
     pub fn on_update(&mut self, ctx: &mut ScriptContext) {
+        if self.eliminated {
+            return;
+        }
+
+        self.sync_mass(&mut ctx.scene.graph);
+        self.recover_from_physics_error(ctx.scene);
+
         let game = ctx.plugins.get::<Game>();
+        let ragdoll_allowed = self.is_ragdoll_allowed(game, &ctx.scene.graph);
         let has_ground_contact = self.has_ground_contact(&ctx.scene.graph);
+        self.grounded = has_ground_contact;
+        let has_landed = has_ground_contact && self.in_air_time >= LANDING_AIR_TIME;
+        let landing_speed = self.fall_speed;
         if has_ground_contact {
             self.in_air_time = 0.0;
+            self.fall_speed = 0.0;
             self.stand_up_timer += ctx.dt;
             if self.stand_up_timer >= self.stand_up_interval {
                 self.set_ragdoll_enabled(&mut ctx.scene.graph, false);
@@ -333,19 +1652,53 @@ impl Actor {
         } else {
             self.in_air_time += ctx.dt;
             self.stand_up_timer = 0.0;
-            if !game.debug_settings.disable_ragdoll && self.in_air_time >= self.max_in_air_time {
+            if let Some(rigid_body) = ctx
+                .scene
+                .graph
+                .try_get_of_type::<RigidBody>(self.rigid_body)
+            {
+                self.fall_speed = self.fall_speed.max(-rigid_body.lin_vel().y);
+            }
+            if ragdoll_allowed && self.in_air_time >= self.max_in_air_time {
                 self.set_ragdoll_enabled(&mut ctx.scene.graph, true);
             }
         }
+        self.update_knock_out(
+            ctx.dt,
+            ragdoll_allowed,
+            manual_respawn_wait(game),
+            ctx.scene,
+        );
+
+        // Falls, that are long enough to enable the ragdoll in the air, are already punished.
+        if has_landed && !self.is_ragdoll_enabled(&ctx.scene.graph) {
+            self.apply_fall_consequences(landing_speed, ragdoll_allowed, &mut ctx.scene.graph);
+        }
+
         let finished = game.level.leaderboard.is_finished(ctx.handle);
-        if finished {
+        if self.stunned {
+            self.target_desired_velocity = Vector3::default();
+        } else if finished {
             // Stand still.
             self.target_desired_velocity.x = 0.0;
             self.target_desired_velocity.z = 0.0;
         }
 
-        if self.has_serious_impact(ctx) {
+        // Contacts could last for a few frames, so only the impact, that knocks the actor down,
+        // is considered for effects. Contacts of a hard landing are not impacts, the fall is
+        // punished already.
+        let serious_impact =
+            if self.in_air_time < self.max_in_air_time && self.hard_landing_timer <= 0.0 {
+                self.find_serious_impact(ctx)
+            } else {
+                None
+            };
+        if let Some((_, _, direction)) = serious_impact {
             self.in_air_time = 999.0;
+            self.apply_knockback(
+                direction.scale(*self.knockback_impulse),
+                &mut ctx.scene.graph,
+            );
         }
 
         let y_vel = self.target_desired_velocity.y;
@@ -357,24 +1710,328 @@ impl Actor {
 
         self.do_move(self.desired_velocity, &mut ctx.scene.graph);
 
-        if let Some(absm) = ctx
-            .scene
-            .graph
-            .try_get_mut_of_type::<AnimationBlendingStateMachine>(self.absm)
-        {
-            absm.machine_mut()
-                .get_value_mut_silent()
-                .set_parameter(
-                    "Run",
-                    Parameter::Rule(self.desired_velocity.xz().norm() >= 0.75 * self.speed),
-                )
-                .set_parameter("Jump", Parameter::Rule(self.jump));
+        self.set_animation_parameters(
+            self.desired_velocity.xz().norm() >= 0.75 * self.speed,
+            self.jump,
+            &mut ctx.scene.graph,
+        );
+
+        let has_footstep = self.process_animation_events(ctx, has_ground_contact);
+
+        let position = ctx.scene.graph[self.rigid_body].global_position();
+        let game = ctx.plugins.get_mut::<Game>();
+        if has_footstep {
+            Self::spawn_effect(self.footstep_effect.as_ref(), position, game);
+        }
+        if has_landed {
+            Self::spawn_effect(self.landing_effect.as_ref(), position, game);
+        }
+        if let Some((hazard, velocity, _)) = serious_impact {
+            Self::spawn_effect(self.impact_effect.as_ref(), position, game);
+            game.emit(GameEvent::HazardHit {
+                actor: ctx.handle,
+                hazard,
+            });
+
+            let elimination_velocity = game
+                .level
+                .config(&ctx.scene.graph)
+                .and_then(|config| config.elimination_impact_velocity());
+            if elimination_velocity.map_or(false, |threshold| velocity >= threshold) {
+                self.eliminate(ctx.handle, game, ctx.scene);
+                return;
+            }
         }
 
-        self.process_animation_events(ctx, has_ground_contact);
+        self.update_spawn_protection(ctx.dt, has_ground_contact, &mut ctx.scene.graph);
+
+        self.update_status_effects(ctx.dt);
+        self.update_slide(ctx.dt);
+        self.update_stun(ctx.dt);
+        apply_status_effect_visuals(self.status_effect_visuals(), &mut ctx.scene.graph);
 
         self.jump_interval -= ctx.dt;
 
         self.jump = false;
     }
 }
+
+/// Gives mutable access to the main rigid bodies of two distinct actors at once, which is useful
+/// for interactions between actors (grabs, pushes, etc.). Returns `None` if any of the handles
+/// does not point to an actor, if both handles point to the same actor or if both actors share
+/// the same rigid body (aliasing).
+///
+/// The helper holds exactly two borrows of the multi-borrow context at a time, so it could be
+/// safely called from code, that does not hold any other graph borrows.
+pub fn with_actor_pair<F, R>(
+    graph: &mut Graph,
+    first: Handle<Node>,
+    second: Handle<Node>,
+    func: F,
+) -> Option<R>
+where
+    F: FnOnce(&mut RigidBody, &mut RigidBody) -> R,
+{
+    if first == second {
+        return None;
+    }
+
+    let first_body = graph
+        .try_get_script_component_of::<Actor>(first)?
+        .rigid_body;
+    let second_body = graph
+        .try_get_script_component_of::<Actor>(second)?
+        .rigid_body;
+
+    if first_body == second_body {
+        return None;
+    }
+
+    let mbc = graph.begin_multi_borrow();
+    let mut first_body = mbc
+        .try_get_component_of_type_mut::<RigidBody>(first_body)
+        .ok()?;
+    let mut second_body = mbc
+        .try_get_component_of_type_mut::<RigidBody>(second_body)
+        .ok()?;
+
+    Some(func(&mut first_body, &mut second_body))
+}
+
+/// Returns the fraction of the remaining angle, that an actor with the given turn rate turns by in
+/// `dt` seconds. It does not depend on the frame rate: two steps of `dt / 2` turn the actor as
+/// much as one step of `dt`. Non-positive rates snap the actor to the target facing.
+fn turn_fraction(turn_rate: f32, dt: f32) -> f32 {
+    if turn_rate <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-turn_rate * dt).exp()
+    }
+}
+
+/// Returns max amount of time (in seconds) an actor could wait for manual respawn, or `None` if
+/// manual respawn is not allowed by the server.
+fn manual_respawn_wait(game: &Game) -> Option<f32> {
+    game.server
+        .as_ref()
+        .and_then(|server| server.manual_respawn_wait())
+}
+
+/// Tints the given meshes with the given color. Every mesh gets its own copy of materials, so
+/// other instances of the same prefab are not affected.
+pub fn apply_actor_color(meshes: &[Handle<Node>], color: Color, graph: &mut Graph) {
+    for mesh in meshes {
+        let Some(mesh) = graph.try_get_mut_of_type::<Mesh>(*mesh) else {
+            continue;
+        };
+
+        for surface in mesh.surfaces_mut() {
+            let material = surface.material().deep_copy();
+            Log::verify(material.data_ref().set_property(
+                &ImmutableString::new("diffuseColor"),
+                PropertyValue::Color(color),
+            ));
+            surface.set_material(material);
+        }
+    }
+}
+
+/// Hides an eliminated actor and removes it from the physical world. It is a free function, so it
+/// could be used on clients, when the elimination is replicated from the server.
+pub fn apply_elimination(
+    actor: Handle<Node>,
+    collider: Handle<Node>,
+    rigid_body: Handle<Node>,
+    graph: &mut Graph,
+) {
+    if let Some(actor) = graph.try_get_mut(actor) {
+        actor.set_visibility(false);
+    }
+    if let Some(collider) = graph.try_get_mut_of_type::<Collider>(collider) {
+        collider.set_collision_groups(InteractionGroups::new(BitMask(0), BitMask(0)));
+    }
+    if let Some(rigid_body) = graph.try_get_mut_of_type::<RigidBody>(rigid_body) {
+        rigid_body.set_body_type(RigidBodyType::KinematicPositionBased);
+        rigid_body.set_lin_vel(Default::default());
+    }
+}
+
+pub fn apply_status_effect_visuals(
+    visuals: [(Handle<Node>, bool); StatusEffect::ALL.len()],
+    graph: &mut Graph,
+) {
+    for (visual, visible) in visuals {
+        if let Some(visual) = graph.try_get_mut(visual) {
+            if visual.visibility() != visible {
+                visual.set_visibility(visible);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fyrox::{
+        core::algebra::Vector2,
+        scene::{
+            base::BaseBuilder,
+            graph::GraphUpdateSwitches,
+            pivot::PivotBuilder,
+            ragdoll::{Limb, RagdollBuilder},
+            rigidbody::RigidBodyBuilder,
+            transform::TransformBuilder,
+        },
+        script::Script,
+    };
+
+    /// Adds a node with an actor (of a bot), that uses the given rigid body.
+    fn add_actor(rigid_body: Handle<Node>, graph: &mut Graph) -> Handle<Node> {
+        let bot = Bot {
+            actor: Actor {
+                rigid_body,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        PivotBuilder::new(BaseBuilder::new().with_script(Script::new(bot))).build(graph)
+    }
+
+    #[test]
+    fn nan_velocity_is_recovered() {
+        let mut scene = Scene::new();
+        let valid_position = Vector3::new(1.0, 2.0, 3.0);
+        let rigid_body = RigidBodyBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(valid_position)
+                    .build(),
+            ),
+        )
+        .build(&mut scene.graph);
+        let mut actor = Actor {
+            rigid_body,
+            ..Default::default()
+        };
+        // Remember the last valid state.
+        actor.recover_from_physics_error(&mut scene);
+
+        let body = scene.graph[rigid_body].cast_mut::<RigidBody>().unwrap();
+        body.set_lin_vel(Vector3::repeat(f32::NAN));
+        body.local_transform_mut()
+            .set_position(Vector3::repeat(f32::NAN));
+        actor.recover_from_physics_error(&mut scene);
+
+        let body = scene.graph[rigid_body].cast::<RigidBody>().unwrap();
+        assert!(is_finite(&body.lin_vel()));
+        assert_eq!(**body.local_transform().position(), valid_position);
+    }
+
+    #[test]
+    fn turn_fraction_does_not_depend_on_frame_rate() {
+        let once = turn_fraction(8.0, 0.1);
+        let half = turn_fraction(8.0, 0.05);
+        let twice = 1.0 - (1.0 - half) * (1.0 - half);
+        assert!((once - twice).abs() < 1e-6);
+        assert!(once > 0.0 && once < 1.0);
+    }
+
+    #[test]
+    fn zero_turn_rate_snaps() {
+        assert_eq!(turn_fraction(0.0, 1.0 / 60.0), 1.0);
+    }
+
+    #[test]
+    fn actor_pair_is_borrowed_without_aliasing() {
+        let mut graph = Graph::new();
+        let first_body = RigidBodyBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let second_body = RigidBodyBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let first = add_actor(first_body, &mut graph);
+        let second = add_actor(second_body, &mut graph);
+        let twin = add_actor(first_body, &mut graph);
+        let not_an_actor = PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+
+        // Two distinct actors give access to both of their bodies.
+        let result = with_actor_pair(&mut graph, first, second, |a, b| {
+            a.set_lin_vel(Vector3::x());
+            b.set_lin_vel(Vector3::y());
+        });
+        assert!(result.is_some());
+        assert_eq!(
+            graph[first_body].cast::<RigidBody>().unwrap().lin_vel(),
+            Vector3::x()
+        );
+        assert_eq!(
+            graph[second_body].cast::<RigidBody>().unwrap().lin_vel(),
+            Vector3::y()
+        );
+
+        // The same actor twice, or two actors sharing the same body, must not alias.
+        assert!(with_actor_pair(&mut graph, first, first, |_, _| ()).is_none());
+        assert!(with_actor_pair(&mut graph, first, twin, |_, _| ()).is_none());
+        assert!(with_actor_pair(&mut graph, first, not_an_actor, |_, _| ()).is_none());
+        assert!(with_actor_pair(&mut graph, first, Handle::NONE, |_, _| ()).is_none());
+    }
+
+    fn add_limb(name: &str, children: Vec<Limb>, graph: &mut Graph) -> Limb {
+        Limb {
+            bone: PivotBuilder::new(BaseBuilder::new()).build(graph),
+            physical_bone: RigidBodyBuilder::new(BaseBuilder::new().with_name(name))
+                .with_gravity_scale(0.0)
+                .build(graph),
+            children,
+        }
+    }
+
+    fn step(graph: &mut Graph) {
+        graph.update(
+            Vector2::new(100.0, 100.0),
+            1.0 / 60.0,
+            GraphUpdateSwitches::default(),
+        );
+    }
+
+    #[test]
+    fn limb_impulse_moves_only_the_selected_limb() {
+        let mut scene = Scene::new();
+        let graph = &mut scene.graph;
+        let left_leg = add_limb("LeftLeg", vec![], graph);
+        let right_leg = add_limb("RightLeg", vec![], graph);
+        let bodies = [left_leg.physical_bone, right_leg.physical_bone];
+        let hips = add_limb("Hips", vec![left_leg, right_leg], graph);
+        let bodies = [hips.physical_bone, bodies[0], bodies[1]];
+        let ragdoll = RagdollBuilder::new(BaseBuilder::new())
+            .with_root_limb(hips)
+            .build(graph);
+        let mut actor = Actor {
+            ragdoll,
+            ..Default::default()
+        };
+        actor.set_ragdoll_enabled(graph, true);
+        step(graph);
+
+        assert!(!actor.apply_limb_impulse(
+            &LimbSelector::Name("Head".to_string()),
+            Vector3::x(),
+            true,
+            graph
+        ));
+        assert!(actor.apply_limb_impulse(
+            &LimbSelector::Name("LeftLeg".to_string()),
+            Vector3::new(5.0, 0.0, 0.0),
+            true,
+            graph
+        ));
+        step(graph);
+
+        let speeds = bodies.map(|body| graph[body].cast::<RigidBody>().unwrap().lin_vel().norm());
+        assert!(speeds[1] > 1.0, "{speeds:?}");
+        assert!(speeds[0] < 1.0e-3 && speeds[2] < 1.0e-3, "{speeds:?}");
+
+        // Limbs are also selected by their depth-first index.
+        assert_eq!(
+            actor.limb_rigid_body(&LimbSelector::Index(2), graph),
+            Some(bodies[2])
+        );
+    }
+}
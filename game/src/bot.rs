@@ -1,8 +1,21 @@
 //! A simple bot that tries to react Target points on a level.
+//!
+//! Decisions of bots are deterministic, so a match could be reproduced from its seed. To keep it
+//! this way, bot logic must depend only on:
+//!
+//! - the match seed (see [`crate::level::Level::seed`]), the handle and the spawn position of the
+//! bot, that are used to seed the random number generator of the bot;
+//! - the fixed simulation step ([`SIMULATION_STEP`]), all timers of bots are advanced by it instead
+//! of the frame time;
+//! - the state of the scene (positions, contacts, the navigational mesh), which is the same for
+//! the same inputs of players.
+//!
+//! Never use thread-local random number generators or wall-clock time in bot logic.
 
 use crate::actor::ActorKind;
 use crate::{
     actor::{Actor, ActorMessage},
+    level::Level,
     respawn::Respawner,
     utils, Game,
 };
@@ -20,6 +33,7 @@ use fyrox::{
         visitor::prelude::*,
     },
     graph::{BaseSceneGraph, SceneGraph},
+    rand::{rngs::StdRng, Rng},
     scene::{
         collider::{Collider, ColliderShape},
         debug::Line,
@@ -33,7 +47,78 @@ use fyrox::{
     },
     utils::navmesh::{Navmesh, NavmeshAgent, NavmeshAgentBuilder},
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use strum_macros::{AsRefStr, EnumString, VariantNames};
+
+/// Difficulty of a bot, it is chosen by the host and replicated to clients with the spawn
+/// descriptor of the bot.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Visit,
+    Serialize,
+    Deserialize,
+    AsRefStr,
+    EnumString,
+    VariantNames,
+)]
+pub enum BotDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl BotDifficulty {
+    pub fn speed_multiplier(self) -> f32 {
+        match self {
+            BotDifficulty::Easy => 0.8,
+            BotDifficulty::Normal => 1.0,
+            BotDifficulty::Hard => 1.1,
+        }
+    }
+
+    pub fn can_sprint(self) -> bool {
+        self == BotDifficulty::Hard
+    }
+
+    /// Max fraction of speed, that a bot randomly loses on every decision. Worse bots are less
+    /// consistent.
+    pub fn speed_jitter(self) -> f32 {
+        match self {
+            BotDifficulty::Easy => 0.2,
+            BotDifficulty::Normal => 0.1,
+            BotDifficulty::Hard => 0.03,
+        }
+    }
+}
+
+/// Time step (in seconds) of the simulation. The engine updates scripts with this fixed rate, but
+/// bots use the constant directly, so their decisions do not depend on the frame time.
+pub const SIMULATION_STEP: f32 = 1.0 / 60.0;
+
+/// Amount of time (in seconds) between "random" decisions of a bot.
+const DECISION_INTERVAL: f32 = 0.5;
+
+/// Amount of time (in seconds) after which a bot, that does not move, is considered stuck.
+const STUCK_TIME: f32 = 1.0;
+
+/// Distance (in meters) ahead of a bot, at which the ground is probed for drop-offs.
+const EDGE_PROBE_DISTANCE: f32 = 1.0;
+
+/// Max depth (in meters) of the ground probes for drop-offs. The ground is unsafe if it is farther.
+const EDGE_PROBE_MAX_HEIGHT: f32 = 20.0;
+
+/// Amount of time (in seconds) after which a bot picks the nearest target again.
+const TARGET_REEVALUATION_INTERVAL: f32 = 1.0;
+
+/// Amount of consecutive updates without access to the navmesh, after which a warning is logged.
+const NAVMESH_LOCK_WARN_THRESHOLD: u32 = 60;
 
 #[derive(Clone, Debug, Default)]
 struct SensorBox {
@@ -79,6 +164,34 @@ pub struct Bot {
     probe_end: Handle<Node>,
     #[reflect(description = "Handle of an obstacle sensor collider")]
     obstacle_sensor_collider: Handle<Node>,
+    #[reflect(description = "Whether the bot should use sprint boost or not.")]
+    sprint: bool,
+    #[reflect(
+        description = "Radius of the navmesh agent of the bot. The radius of the collider of the \
+        actor is used if not set.",
+        min_value = 0.01
+    )]
+    agent_radius: Option<f32>,
+    #[reflect(
+        description = "Allows the radius of the navmesh agent to be smaller than the radius of \
+        the collider. Paths of such bot could go too close to walls, so it could clip corners or \
+        get stuck."
+    )]
+    allow_small_agent_radius: bool,
+    #[reflect(
+        description = "How strongly the bot steers away from drop-offs next to its path. Zero \
+        disables edge avoidance.",
+        min_value = 0.0
+    )]
+    edge_avoidance_strength: f32,
+    #[reflect(
+        description = "Distance (in meters) to the side of the bot, at which the ground is probed \
+        for drop-offs.",
+        min_value = 0.0
+    )]
+    edge_probe_fan_width: f32,
+    #[reflect(hidden)]
+    difficulty: BotDifficulty,
     #[component(include)]
     pub actor: Actor,
     #[reflect(hidden)]
@@ -91,10 +204,29 @@ pub struct Bot {
     debug_data: DebugDataWrapper,
     #[reflect(hidden)]
     backwards_movement_timer: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    rng: Option<StdRng>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    decision_timer: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    speed_factor: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    stuck_timer: f32,
+    /// Amount of consecutive updates, in which the navmesh was locked by someone else.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    navmesh_lock_failures: u32,
+    /// The target, that the bot runs to, see [`Bot::update_target`].
+    #[visit(skip)]
     #[reflect(hidden)]
-    target_orientation: UnitQuaternion<f32>,
+    target: Handle<Node>,
+    #[visit(skip)]
     #[reflect(hidden)]
-    orientation: UnitQuaternion<f32>,
+    target_timer: f32,
 }
 
 impl Default for Bot {
@@ -102,8 +234,15 @@ impl Default for Bot {
         Self {
             actor: Actor {
                 kind: ActorKind::Bot,
+                turn_rate: 8.0.into(),
                 ..Default::default()
             },
+            sprint: false,
+            agent_radius: None,
+            allow_small_agent_radius: false,
+            edge_avoidance_strength: 1.0,
+            edge_probe_fan_width: 0.75,
+            difficulty: Default::default(),
             probe_begin: Default::default(),
             probe_end: Default::default(),
             agent: NavmeshAgentBuilder::new()
@@ -113,12 +252,41 @@ impl Default for Bot {
             debug_data: Default::default(),
             obstacle_sensor_collider: Default::default(),
             backwards_movement_timer: 0.0,
-            target_orientation: Default::default(),
-            orientation: Default::default(),
+            rng: None,
+            decision_timer: 0.0,
+            speed_factor: 1.0,
+            stuck_timer: 0.0,
+            navmesh_lock_failures: 0,
+            target: Default::default(),
+            target_timer: 0.0,
         }
     }
 }
 
+fn distance_to_segment(point: Vector3<f32>, begin: Vector3<f32>, end: Vector3<f32>) -> f32 {
+    let segment = end - begin;
+    let t = (point - begin).dot(&segment) / segment.norm_squared().max(f32::EPSILON);
+    point.metric_distance(&(begin + segment.scale(t.clamp(0.0, 1.0))))
+}
+
+/// Returns the next point of the path to steer to: the first point after the nearest segment of
+/// the path, that is far enough from the position. Points behind the position are skipped, so the
+/// bot never walks back to the start of the path.
+fn next_path_point(path: &[Vector3<f32>], position: Vector3<f32>) -> Option<Vector3<f32>> {
+    let next = path
+        .windows(2)
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            distance_to_segment(position, a[0], a[1])
+                .total_cmp(&distance_to_segment(position, b[0], b[1]))
+        })
+        .map_or(0, |(index, _)| index + 1);
+    path[next..]
+        .iter()
+        .find(|point| point.metric_distance(&position) > 1.0)
+        .cloned()
+}
+
 fn probe_ground(begin: Vector3<f32>, max_height: f32, graph: &Graph) -> Option<Vector3<f32>> {
     let mut buffer = ArrayVec::<_, 64>::new();
 
@@ -191,10 +359,130 @@ enum GapTestResult {
     JumpOver,
 }
 
+/// Navigation state of a bot, it is used for debugging.
+#[derive(Clone, Debug)]
+pub struct BotNavigationStatus {
+    /// Position, that the bot is trying to reach.
+    pub target: Vector3<f32>,
+    /// Amount of points in the current path of the bot.
+    pub path_points: usize,
+    /// Amount of consecutive updates, in which the navmesh could not be locked. The bot follows
+    /// the last known path meanwhile.
+    pub navmesh_lock_failures: u32,
+    /// Amount of time (in seconds) the bot wants to move, but does not.
+    pub stuck_time: f32,
+    /// Radius of the navmesh agent, see [`Bot::sync_agent_radius`].
+    pub agent_radius: f32,
+}
+
 impl Bot {
+    /// Returns `true` if the bot must navigate and make decisions on this host. Remote bots (see
+    /// [`Actor::is_remote`]) are driven by the server, so their input must never be changed
+    /// locally.
+    fn runs_ai(&self, is_paused: bool) -> bool {
+        !self.actor.is_remote && !is_paused && !self.actor.is_eliminated()
+    }
+
+    pub fn difficulty(&self) -> BotDifficulty {
+        self.difficulty
+    }
+
+    pub fn navigation_status(&self) -> BotNavigationStatus {
+        BotNavigationStatus {
+            target: self.agent.target(),
+            path_points: self.agent.path().len(),
+            navmesh_lock_failures: self.navmesh_lock_failures,
+            stuck_time: self.stuck_timer,
+            agent_radius: self.agent.radius(),
+        }
+    }
+
+    /// Makes the navmesh agent as wide as the body of the bot, so paths keep enough distance from
+    /// walls and corners. An explicit agent radius, that is smaller than the radius of the
+    /// collider, is ignored unless it is allowed.
+    fn sync_agent_radius(&mut self, graph: &Graph) {
+        let collider_radius = self.actor.collider_radius(graph);
+        let radius = match (self.agent_radius, collider_radius) {
+            (Some(radius), Some(collider_radius))
+                if radius < collider_radius && !self.allow_small_agent_radius =>
+            {
+                Log::warn(format!(
+                    "Agent radius {radius} of bot {} is smaller than the radius \
+                    {collider_radius} of its collider, the radius of the collider is used.",
+                    self.actor.name
+                ));
+                collider_radius
+            }
+            (Some(radius), _) => radius,
+            (None, Some(collider_radius)) => collider_radius,
+            (None, None) => return,
+        };
+        self.agent.set_radius(radius.max(0.01));
+    }
+
+    /// Sets new difficulty of the bot. Must be called only once, right after the bot is spawned,
+    /// because it scales the speed of the bot.
+    pub fn set_difficulty(&mut self, difficulty: BotDifficulty) {
+        self.difficulty = difficulty;
+        self.actor.speed *= difficulty.speed_multiplier();
+        self.sprint |= difficulty.can_sprint();
+    }
+
+    /// Makes "random" decisions of the bot with a fixed interval: picks a speed jitter and tries
+    /// to get out if the bot is stuck.
+    fn update_decisions(&mut self, is_moving: bool, wants_to_move: bool) {
+        let Some(rng) = self.rng.as_mut() else {
+            return;
+        };
+
+        self.decision_timer -= SIMULATION_STEP;
+        if self.decision_timer <= 0.0 {
+            self.decision_timer = DECISION_INTERVAL;
+            let jitter = self.difficulty.speed_jitter();
+            self.speed_factor = 1.0 - rng.gen_range(0.0..=jitter);
+        }
+
+        if wants_to_move && !is_moving {
+            self.stuck_timer += SIMULATION_STEP;
+            if self.stuck_timer >= STUCK_TIME {
+                self.stuck_timer = 0.0;
+                // Step back for a random amount of time, so stuck bots won't repeat each other.
+                self.backwards_movement_timer = rng.gen_range(0.2..=0.6);
+            }
+        } else {
+            self.stuck_timer = 0.0;
+        }
+    }
+
+    /// Returns the position of the target nearest to the bot. The choice is cached and made again
+    /// periodically or when the target is removed from the level.
+    fn update_target(&mut self, level: &Level, graph: &Graph) -> Option<Vector3<f32>> {
+        self.target_timer -= SIMULATION_STEP;
+        if self.target_timer <= 0.0 || !level.targets.contains(&self.target) {
+            self.target_timer = TARGET_REEVALUATION_INTERVAL;
+            self.target = graph
+                .try_get(self.actor.rigid_body)
+                .and_then(|body| level.nearest_target(body.global_position(), graph))
+                .map(|(target, _)| target)
+                .unwrap_or_default();
+        }
+        graph
+            .try_get(self.target)
+            .map(|target| target.global_position())
+    }
+
     fn debug_draw(&self, ctx: &mut ScriptContext) {
-        let game = ctx.plugins.get::<Game>();
-        if game.debug_settings.show_paths {
+        let debug_settings = &ctx.plugins.get::<Game>().debug_settings;
+        let show_paths = debug_settings.show_paths;
+        let draw_probes = ctx
+            .scene
+            .graph
+            .try_get(self.actor.rigid_body)
+            .map_or(false, |body| {
+                debug_settings.should_draw_probes(body.global_position(), &ctx.scene.graph)
+            });
+
+        if show_paths {
             for pts in self.agent.path().windows(2) {
                 let a = pts[0];
                 let b = pts[1];
@@ -217,8 +505,7 @@ impl Bot {
                 Color::GREEN,
             );
 
-            if let Some(navmesh) = self.navmesh.as_ref() {
-                let navmesh = navmesh.read();
+            if let Some(navmesh) = self.navmesh.as_ref().and_then(|n| n.try_read()) {
                 if let Some(closest) =
                     navmesh.query_closest(ctx.scene.graph[self.actor.rigid_body].global_position())
                 {
@@ -227,8 +514,12 @@ impl Bot {
                         .draw_sphere(closest.0, 16, 16, 0.25, Color::BLUE);
                 }
             }
+        }
+
+        let debug_data = self.debug_data.0.lock();
 
-            let debug_data = self.debug_data.0.lock();
+        // Rays of the ground probes (see `probe_ground`) and their hit points.
+        if show_paths || draw_probes {
             for line in debug_data.lines.iter() {
                 ctx.scene.drawing_context.add_line(line.clone());
                 ctx.scene
@@ -239,7 +530,9 @@ impl Bot {
                     .drawing_context
                     .draw_sphere(line.end, 16, 16, 0.25, line.color);
             }
+        }
 
+        if show_paths {
             for oobb in debug_data.oobbs.iter() {
                 ctx.scene
                     .drawing_context
@@ -288,6 +581,42 @@ impl Bot {
         }
     }
 
+    /// Stops the bot in place, see [`crate::DebugSettings::freeze_ai`]. The actor is not updated
+    /// meanwhile, so the bot does not accumulate in-air time and won't ragdoll when unfrozen.
+    fn hold_position(&mut self, graph: &mut Graph) {
+        self.actor.target_desired_velocity = Vector3::default();
+        self.actor.in_air_time = 0.0;
+        self.stuck_timer = 0.0;
+        if let Some(rigid_body) = graph.try_get_mut_of_type::<RigidBody>(self.actor.rigid_body) {
+            let velocity = rigid_body.lin_vel();
+            rigid_body.set_lin_vel(Vector3::new(0.0, velocity.y, 0.0));
+        }
+    }
+
+    /// Probes the ground ahead of the bot on both sides of the given direction, and returns a
+    /// horizontal direction away from the drop-offs (zero if there are none, or if there are
+    /// drop-offs on both sides). Only two rays are cast per call.
+    fn edge_avoidance(
+        &self,
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        graph: &Graph,
+    ) -> Vector3<f32> {
+        let side = Vector3::y().cross(&direction);
+        let mut avoidance = Vector3::default();
+        for sign in [-1.0, 1.0] {
+            let probe = position
+                + direction.scale(EDGE_PROBE_DISTANCE)
+                + side.scale(sign * self.edge_probe_fan_width);
+            if !is_safe_height_difference(probe, EDGE_PROBE_MAX_HEIGHT, graph, |p| {
+                self.debug_data.add_line(probe, p, Color::RED);
+            }) {
+                avoidance -= side.scale(sign);
+            }
+        }
+        avoidance
+    }
+
     fn is_any_obstacle_in_front(&self, ctx: &ScriptContext) -> bool {
         let game = ctx.plugins.get::<Game>();
         let graph = &ctx.scene.graph;
@@ -348,6 +677,10 @@ impl ScriptTrait for Bot {
         ctx.message_dispatcher
             .subscribe_to::<ActorMessage>(ctx.handle);
 
+        self.actor.on_start(ctx);
+        // The capsule is re-fitted by the actor, so the radius is taken after that.
+        self.sync_agent_radius(&ctx.scene.graph);
+
         self.navmesh = ctx
             .scene
             .graph
@@ -355,8 +688,11 @@ impl ScriptTrait for Bot {
             .and_then(|(_, n)| n.cast::<NavigationalMesh>())
             .map(|n| n.navmesh());
 
-        self.agent
-            .set_position(ctx.scene.graph[ctx.handle].global_position());
+        let spawn_position = ctx.scene.graph[ctx.handle].global_position();
+        self.agent.set_position(spawn_position);
+
+        let seed = ctx.plugins.get::<Game>().level.seed;
+        self.rng = Some(utils::seeded_rng(seed, ctx.handle, spawn_position));
     }
 
     fn on_deinit(&mut self, ctx: &mut ScriptDeinitContext) {
@@ -371,21 +707,38 @@ impl ScriptTrait for Bot {
     fn on_update(&mut self, ctx: &mut ScriptContext) {
         self.debug_data.clear();
 
+        // Bots are simulated only on the host, clients receive their state from the server.
         let game = ctx.plugins.get::<Game>();
         if game.is_client() {
+            self.actor
+                .update_client_animation(ctx.dt, &mut ctx.scene.graph);
+            return;
+        }
+        if !self.runs_ai(game.is_paused()) {
+            return;
+        }
+
+        // The navmesh agent must follow the actor after a respawn, otherwise it keeps planning
+        // paths from the place where the bot fell.
+        if let Some(position) = self.actor.take_respawn_position() {
+            self.agent.set_position(position);
+        }
+
+        if game.debug_settings.freeze_ai {
+            self.hold_position(&mut ctx.scene.graph);
+            return;
+        }
+
+        if self.actor.is_knocked_out() {
+            self.actor.target_desired_velocity = Vector3::default();
+            self.actor.on_update(ctx);
             return;
         }
 
         let is_in_jump_state = self.actor.is_in_jump_state(&ctx.scene.graph);
 
-        // Dead-simple AI - run straight to target.
-        let target_pos = game
-            .level
-            .targets
-            .iter()
-            .next()
-            .cloned()
-            .map(|t| ctx.scene.graph[t].global_position());
+        // Dead-simple AI - run straight to the nearest target.
+        let target_pos = self.update_target(&game.level, &ctx.scene.graph);
 
         let gap_test_result = self.gap_test(ctx);
         let is_any_obstacle_in_front = self.is_any_obstacle_in_front(ctx);
@@ -397,27 +750,37 @@ impl ScriptTrait for Bot {
             self.backwards_movement_timer = 0.1;
         }
 
+        let max_speed = self.actor.movement_speed(self.sprint) * self.speed_factor;
         let speed = if gap_test_result == GapTestResult::Stop {
             0.0
         } else if self.backwards_movement_timer > 0.0 {
-            -self.actor.speed
+            -max_speed
         } else {
-            self.actor.speed
+            max_speed
         };
 
-        self.backwards_movement_timer -= ctx.dt;
+        self.backwards_movement_timer -= SIMULATION_STEP;
 
         if let Some(target_pos) = target_pos {
             if let Some(rigid_body) = ctx.scene.graph[self.actor.rigid_body].cast_mut::<RigidBody>()
             {
                 let self_position = rigid_body.global_position();
 
-                if let Some(navmesh) = self.navmesh.as_ref() {
-                    let navmesh = navmesh.read();
+                let mut steering_point = None;
+                if let Some(navmesh) = self.navmesh.as_ref().and_then(|n| {
+                    // Never block the update loop on the navmesh, it could be locked for writing
+                    // (rebuilt) for a while.
+                    let navmesh = n.try_read();
+                    if navmesh.is_none() {
+                        self.navmesh_lock_failures += 1;
+                    }
+                    navmesh
+                }) {
+                    self.navmesh_lock_failures = 0;
                     let agent_speed = if self.backwards_movement_timer > 0.0 {
-                        -self.actor.speed
+                        -max_speed
                     } else {
-                        self.actor.speed
+                        max_speed
                     };
                     self.agent.set_speed(agent_speed);
                     self.agent.set_target(target_pos);
@@ -428,18 +791,40 @@ impl ScriptTrait for Bot {
                     if self.agent.position().metric_distance(&new_position) > 1.99 {
                         self.agent.set_position(new_position);
                     }
-                    let _ = self.agent.update(ctx.dt, &navmesh);
+                    let _ = self.agent.update(SIMULATION_STEP, &navmesh);
+                } else if self.navmesh_lock_failures > 0 {
+                    if self.navmesh_lock_failures == NAVMESH_LOCK_WARN_THRESHOLD {
+                        Log::warn(format!(
+                            "Bot {:?} could not lock the navmesh for {} updates in a row!",
+                            ctx.handle, NAVMESH_LOCK_WARN_THRESHOLD
+                        ));
+                    }
+                    // Follow the last known path until the navmesh is available again.
+                    steering_point = next_path_point(self.agent.path(), self_position);
                 }
+                let steering_point = steering_point.unwrap_or_else(|| self.agent.position());
 
                 let has_reached_destination =
                     self.agent.target().metric_distance(&self_position) <= 1.0;
                 let horizontal_velocity = if has_reached_destination {
                     Vector3::new(0.0, 0.0, 0.0)
                 } else {
-                    let mut vel = (self.agent.position() - self_position)
+                    let mut direction = (steering_point - self_position)
                         .try_normalize(f32::EPSILON)
-                        .unwrap_or_default()
-                        .scale(speed);
+                        .unwrap_or_default();
+                    // Edges are crossed only intentionally, when there's a platform to jump on.
+                    if self.edge_avoidance_strength > 0.0
+                        && speed > 0.0
+                        && has_ground_contact
+                        && gap_test_result != GapTestResult::JumpOver
+                    {
+                        let avoidance =
+                            self.edge_avoidance(self_position, direction, &ctx.scene.graph);
+                        direction = (direction + avoidance.scale(self.edge_avoidance_strength))
+                            .try_normalize(f32::EPSILON)
+                            .unwrap_or(direction);
+                    }
+                    let mut vel = direction.scale(speed);
                     vel.y = 0.0;
                     vel
                 };
@@ -464,16 +849,16 @@ impl ScriptTrait for Bot {
 
                 let mut look_dir = self.agent.steering_target().unwrap_or_default() - self_position;
                 look_dir.y = 0.0;
-                self.target_orientation =
-                    UnitQuaternion::face_towards(&look_dir, &Vector3::y_axis());
-
-                self.orientation = self
-                    .orientation
-                    .slerp(&self.target_orientation, 8.0 * ctx.dt);
+                if let Some(look_dir) = look_dir.try_normalize(f32::EPSILON) {
+                    self.actor.turn_towards(
+                        rigid_body,
+                        UnitQuaternion::face_towards(&look_dir, &Vector3::y_axis()),
+                        SIMULATION_STEP,
+                    );
+                }
 
-                rigid_body
-                    .local_transform_mut()
-                    .set_rotation(self.orientation);
+                let is_moving = rigid_body.lin_vel().xz().norm() > 0.5;
+                self.update_decisions(is_moving, speed > 0.0 && !has_reached_destination);
             }
         }
 
@@ -488,15 +873,98 @@ impl ScriptTrait for Bot {
         ctx: &mut ScriptMessageContext,
     ) {
         self.actor.on_message(message, ctx);
+    }
+}
 
-        let Some(message) = message.downcast_ref::<ActorMessage>() else {
-            return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fyrox::scene::{base::BaseBuilder, pivot::PivotBuilder, transform::TransformBuilder};
+
+    /// Runs decisions of a bot with the given seed for a few seconds and returns their results.
+    fn decisions(seed: u64) -> Vec<(f32, f32)> {
+        let mut bot = Bot {
+            rng: Some(utils::seeded_rng(
+                seed,
+                Handle::new(1, 1),
+                Vector3::new(1.0, 0.0, 2.0),
+            )),
+            ..Default::default()
         };
+        bot.set_difficulty(BotDifficulty::Easy);
+        (0..600)
+            .map(|step| {
+                // Alternate between moving and being stuck, so both kinds of decisions are made.
+                bot.update_decisions(step % 200 < 100, true);
+                (bot.speed_factor, bot.backwards_movement_timer)
+            })
+            .collect()
+    }
 
-        match message {
-            ActorMessage::RespawnAt(position) => {
-                self.agent.set_position(*position);
-            }
-        }
+    #[test]
+    fn bot_runs_to_nearest_target() {
+        let mut graph = Graph::new();
+        let mut add_node = |position| {
+            PivotBuilder::new(
+                BaseBuilder::new().with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(position)
+                        .build(),
+                ),
+            )
+            .build(&mut graph)
+        };
+        let body = add_node(Vector3::default());
+        let far = add_node(Vector3::new(10.0, 0.0, 0.0));
+        let near = add_node(Vector3::new(-3.0, 0.0, 0.0));
+        graph.update_hierarchical_data();
+
+        let level = Level {
+            targets: [far, near].into_iter().collect(),
+            ..Default::default()
+        };
+        let mut bot = Bot::default();
+        bot.actor.rigid_body = body;
+        assert_eq!(
+            bot.update_target(&level, &graph),
+            Some(Vector3::new(-3.0, 0.0, 0.0))
+        );
+        assert_eq!(bot.target, near);
+    }
+
+    #[test]
+    fn next_path_point_skips_passed_points() {
+        let path = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+        ];
+        // The start of the path is far behind, but the bot must keep going forward.
+        assert_eq!(
+            next_path_point(&path, Vector3::new(5.5, 0.0, 0.0)),
+            Some(path[2])
+        );
+        assert_eq!(
+            next_path_point(&path, Vector3::new(2.0, 0.0, 0.0)),
+            Some(path[1])
+        );
+        assert_eq!(next_path_point(&path, Vector3::new(9.5, 0.0, 0.0)), None);
+        assert_eq!(next_path_point(&[], Vector3::default()), None);
+    }
+
+    #[test]
+    fn decisions_are_identical_for_identical_seeds() {
+        assert_eq!(decisions(123), decisions(123));
+        assert_ne!(decisions(123), decisions(321));
+    }
+
+    #[test]
+    fn remote_bot_does_not_run_ai() {
+        let mut bot = Bot::default();
+        assert!(bot.runs_ai(false));
+        assert!(!bot.runs_ai(true));
+
+        bot.actor.is_remote = true;
+        assert!(!bot.runs_ai(false));
     }
 }
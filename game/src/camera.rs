@@ -13,9 +13,123 @@ use fyrox::{
         uuid::{uuid, Uuid},
         visitor::prelude::*,
     },
-    scene::{collider::Collider, graph::physics::RayCastOptions, node::Node},
+    scene::{
+        camera::{Camera, PerspectiveProjection, Projection},
+        collider::Collider,
+        graph::{physics::RayCastOptions, Graph},
+        node::Node,
+    },
     script::{ScriptContext, ScriptTrait},
 };
+use serde::{Deserialize, Serialize};
+
+/// Behavior of the camera, while the followed actor is ragdolled.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Visit, Reflect, Serialize, Deserialize)]
+pub enum RagdollCameraMode {
+    /// Smoothly follows the center of mass of the ragdoll.
+    #[default]
+    FollowCenterOfMass,
+    /// Holds the position, at which the actor went ragdoll, and only rotates.
+    HoldPosition,
+}
+
+impl RagdollCameraMode {
+    pub const ALL: [Self; 2] = [Self::FollowCenterOfMass, Self::HoldPosition];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FollowCenterOfMass => "Follow",
+            Self::HoldPosition => "Hold Position",
+        }
+    }
+}
+
+/// Transition of the spectator camera, when it switches to another followed actor.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Visit, Reflect, Serialize, Deserialize)]
+pub enum SpectatorTransition {
+    /// Jumps to the new actor immediately.
+    Cut,
+    /// Flies to the new actor.
+    #[default]
+    Smooth,
+    /// Rises over the level to show an overview, and then closes in on the new actor.
+    Cinematic,
+}
+
+impl SpectatorTransition {
+    pub const ALL: [Self; 3] = [Self::Cut, Self::Smooth, Self::Cinematic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cut => "Instant Cut",
+            Self::Smooth => "Smooth Fly",
+            Self::Cinematic => "Cinematic",
+        }
+    }
+}
+
+/// Duration (in seconds) of the smooth fly of the spectator camera at the normal speed.
+const SMOOTH_TRANSITION_TIME: f32 = 0.75;
+
+/// Duration (in seconds) of the cinematic transition of the spectator camera at the normal speed.
+/// The first half of it is the overview, the second one is the close-in.
+const CINEMATIC_TRANSITION_TIME: f32 = 2.5;
+
+/// Height (in meters) of the overview point of the cinematic transition above the highest of the
+/// start and the end of the transition.
+const CINEMATIC_OVERVIEW_HEIGHT: f32 = 12.0;
+
+/// State of an active transition of the spectator camera.
+#[derive(Clone, Debug, Default, Visit, Reflect)]
+struct Transition {
+    mode: SpectatorTransition,
+    /// Position of the camera at the beginning of the transition, it is captured on the first
+    /// update of the transition.
+    from: Option<Vector3<f32>>,
+    /// Duration (in seconds) of the transition.
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Transition {
+    /// Returns the position of the camera on the way to the given target. The target is the
+    /// current position of the followed actor, so the camera arrives at the actor even if it
+    /// moves during the transition.
+    fn position(&self, target: Vector3<f32>) -> Vector3<f32> {
+        let from = self.from.unwrap_or(target);
+        let t = (self.elapsed / self.duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+        match self.mode {
+            SpectatorTransition::Cut => target,
+            SpectatorTransition::Smooth => from.lerp(&target, smooth_step(t)),
+            SpectatorTransition::Cinematic => {
+                let mut overview = (from + target).scale(0.5);
+                overview.y = from.y.max(target.y) + CINEMATIC_OVERVIEW_HEIGHT;
+                if t < 0.5 {
+                    from.lerp(&overview, smooth_step(t * 2.0))
+                } else {
+                    overview.lerp(&target, smooth_step(t * 2.0 - 1.0))
+                }
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Smooth step, so the camera does not jerk at the beginning and the end of a transition.
+fn smooth_step(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Rate at which the camera catches up with the center of mass of a ragdoll. It is lower than the
+/// regular follow rate, so violent tumbling is not transferred to the camera.
+const RAGDOLL_FOLLOW_RATE: f32 = 3.0;
+
+/// Amount of time (in seconds) over which the camera returns to the regular follow after the
+/// followed actor stands up.
+const RAGDOLL_RECOVERY_TIME: f32 = 0.6;
 
 #[derive(Clone, Visit, Debug, Reflect, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "0c45d21f-878e-4aa5-b4e1-097aaa44f314")]
@@ -31,8 +145,61 @@ pub struct CameraController {
     probe_radius: f32,
     #[reflect(description = "A collider that should be ignored by ray casting.")]
     pub collider_to_ignore: Handle<Node>,
+    #[reflect(
+        description = "Vertical field of view (in degrees). It is overridden by the game settings.",
+        min_value = 1.0,
+        max_value = 179.0
+    )]
+    pub fov: f32,
+    #[reflect(
+        description = "Min pitch (in degrees) of the camera. It is overridden by the game settings.",
+        min_value = -89.0,
+        max_value = 89.0
+    )]
+    pub min_pitch: f32,
+    #[reflect(
+        description = "Max pitch (in degrees) of the camera. It is overridden by the game settings.",
+        min_value = -89.0,
+        max_value = 89.0
+    )]
+    pub max_pitch: f32,
+    #[reflect(
+        description = "Pitch (in degrees), that the camera returns to, when auto-centering is \
+        enabled in the settings.",
+        min_value = -89.0,
+        max_value = 89.0
+    )]
+    pub neutral_pitch: f32,
+    /// Zoom factor, that is applied on top of the field of view. Values greater than one narrow
+    /// the view.
+    #[reflect(hidden)]
+    pub zoom: f32,
     #[reflect(hidden)]
     pub target_position: Vector3<f32>,
+    /// Center of mass of the followed actor, while it is ragdolled. It is used instead of
+    /// [`Self::target_position`] according to [`Self::ragdoll_mode`].
+    #[reflect(hidden)]
+    pub ragdoll_position: Option<Vector3<f32>>,
+    /// Behavior of the camera while the followed actor is ragdolled. It is set from the input of
+    /// the player, so the server uses the mode of the player, not its own settings.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub ragdoll_mode: RagdollCameraMode,
+    /// Position, that the camera tracks while the followed actor is ragdolled, and blends away
+    /// from after it stands up.
+    #[reflect(hidden)]
+    ragdoll_anchor: Option<Vector3<f32>>,
+    #[reflect(hidden)]
+    ragdoll_blend: f32,
+    /// A node, at which the camera is placed while the followed actor aims with a cannon. The
+    /// camera looks from the hinge without the follow distance (over-the-cannon view), and
+    /// returns to the regular follow when the handle is reset.
+    #[reflect(hidden)]
+    pub aim_anchor: Handle<Node>,
+    /// Active transition of the spectator camera, see [`Self::begin_transition`].
+    #[visit(skip)]
+    #[reflect(hidden)]
+    transition: Option<Transition>,
     #[reflect(hidden)]
     pub pitch: f32,
     #[reflect(hidden)]
@@ -49,12 +216,121 @@ impl Default for CameraController {
             probe_radius: 0.2,
             yaw: 0.0,
             collider_to_ignore: Default::default(),
+            fov: 75.0,
+            min_pitch: -70.0,
+            max_pitch: 80.0,
+            neutral_pitch: 10.0,
+            zoom: 1.0,
             target_position: Default::default(),
+            ragdoll_position: None,
+            ragdoll_mode: Default::default(),
+            ragdoll_anchor: None,
+            ragdoll_blend: 0.0,
+            aim_anchor: Default::default(),
+            transition: None,
         }
     }
 }
 
 impl CameraController {
+    /// Returns resulting field of view (in degrees), that takes zoom into account.
+    pub fn effective_fov(&self) -> f32 {
+        (self.fov / self.zoom.max(0.01)).clamp(1.0, 179.0)
+    }
+
+    /// Clamps the given pitch (in radians) to the pitch limits of the camera. Every source of
+    /// pitch (player input, spectator camera, etc.) passes through it, so the camera never flips
+    /// over the top or goes under the floor.
+    pub fn clamp_pitch(&self, pitch: f32) -> f32 {
+        let min = self.min_pitch.min(self.max_pitch).clamp(-89.0, 89.0);
+        let max = self.max_pitch.max(self.min_pitch).clamp(-89.0, 89.0);
+        pitch.clamp(min.to_radians(), max.to_radians())
+    }
+
+    /// Starts a transition of the camera from its current position to [`Self::target_position`].
+    /// It is used when the spectator camera switches to another actor, a new transition replaces
+    /// the active one and starts from the current position of the camera. Higher speed makes the
+    /// transition shorter.
+    pub fn begin_transition(&mut self, mode: SpectatorTransition, speed: f32) {
+        let duration = match mode {
+            SpectatorTransition::Cut => 0.0,
+            SpectatorTransition::Smooth => SMOOTH_TRANSITION_TIME,
+            SpectatorTransition::Cinematic => CINEMATIC_TRANSITION_TIME,
+        } / speed.max(f32::EPSILON);
+        self.transition = Some(Transition {
+            mode,
+            from: None,
+            duration,
+            elapsed: 0.0,
+        });
+    }
+
+    /// Advances the active transition and returns the position of the camera on the way to the
+    /// given target, or `None` if there's no transition.
+    fn update_transition(
+        &mut self,
+        current_position: Vector3<f32>,
+        target_position: Vector3<f32>,
+        dt: f32,
+    ) -> Option<Vector3<f32>> {
+        let transition = self.transition.as_mut()?;
+        transition.from.get_or_insert(current_position);
+        transition.elapsed += dt;
+        let position = transition.position(target_position);
+        if transition.is_finished() {
+            self.transition = None;
+        }
+        Some(position)
+    }
+
+    /// Returns the position, that the camera should follow. While the followed actor is ragdolled,
+    /// it is either the smoothed center of mass of the ragdoll or the position at which the actor
+    /// fell, after the actor stands up the camera blends back to the regular target.
+    fn follow_position(
+        &mut self,
+        mode: RagdollCameraMode,
+        current_position: Vector3<f32>,
+        dt: f32,
+    ) -> Vector3<f32> {
+        if let Some(ragdoll_position) = self.ragdoll_position {
+            let anchor = self.ragdoll_anchor.get_or_insert(current_position);
+            if mode == RagdollCameraMode::FollowCenterOfMass {
+                let t = 1.0 - (-RAGDOLL_FOLLOW_RATE * dt).exp();
+                *anchor += (ragdoll_position - *anchor).scale(t);
+            }
+            self.ragdoll_blend = 1.0;
+            return *anchor;
+        }
+
+        let Some(anchor) = self.ragdoll_anchor else {
+            return self.target_position;
+        };
+
+        self.ragdoll_blend -= dt / RAGDOLL_RECOVERY_TIME;
+        if self.ragdoll_blend <= 0.0 {
+            self.ragdoll_anchor = None;
+            self.ragdoll_blend = 0.0;
+            return self.target_position;
+        }
+
+        self.target_position
+            .lerp(&anchor, smooth_step(self.ragdoll_blend))
+    }
+
+    fn apply_fov(&self, graph: &mut Graph) {
+        let fov = self.effective_fov().to_radians();
+        if let Some(camera) = graph.try_get_mut_of_type::<Camera>(self.camera) {
+            if let Projection::Perspective(perspective) = camera.projection() {
+                if perspective.fov != fov {
+                    camera.set_projection(Projection::Perspective(PerspectiveProjection {
+                        fov,
+                        ..perspective.clone()
+                    }));
+                }
+            }
+        }
+    }
+
     fn check_for_obstacles(
         &self,
         begin: Vector3<f32>,
@@ -111,15 +387,35 @@ impl CameraController {
 
 impl ScriptTrait for CameraController {
     fn on_update(&mut self, ctx: &mut ScriptContext) {
-        if ctx.plugins.get::<Game>().is_client() {
+        let game = ctx.plugins.get::<Game>();
+        {
+            let settings = game.settings.read();
+            self.fov = settings.field_of_view();
+            (self.min_pitch, self.max_pitch) = settings.camera_pitch_limits();
+        }
+        self.apply_fov(&mut ctx.scene.graph);
+
+        if game.is_client() {
             return;
         }
 
+        let current_position = **ctx.scene.graph[ctx.handle].local_transform().position();
+        let aim_position = ctx
+            .scene
+            .graph
+            .try_get(self.aim_anchor)
+            .map(|anchor| anchor.global_position());
+        let target_position = self.follow_position(self.ragdoll_mode, current_position, ctx.dt);
+        let transition_position = self.update_transition(current_position, target_position, ctx.dt);
+
         let controller = &mut ctx.scene.graph[ctx.handle];
 
         let local_transform = controller.local_transform_mut();
-        let new_position = **local_transform.position()
-            + (self.target_position - **local_transform.position()) * 0.1;
+        // Aiming must be precise, so the camera sticks to the anchor. After leaving the anchor the
+        // camera smoothly returns to the followed actor.
+        let new_position = aim_position.or(transition_position).unwrap_or_else(|| {
+            **local_transform.position() + (target_position - **local_transform.position()) * 0.1
+        });
         local_transform.set_rotation(UnitQuaternion::from_axis_angle(
             &Vector3::y_axis(),
             self.yaw,
@@ -131,11 +427,17 @@ impl ScriptTrait for CameraController {
                 .local_transform_mut()
                 .set_rotation(UnitQuaternion::from_axis_angle(
                     &Vector3::x_axis(),
-                    self.pitch,
+                    self.clamp_pitch(self.pitch),
                 ));
 
             let hinge_position = hinge.global_position();
-            if let Some(camera) = ctx.scene.graph.try_get(self.camera) {
+            if aim_position.is_some() {
+                if let Some(camera) = ctx.scene.graph.try_get_mut(self.camera) {
+                    camera
+                        .local_transform_mut()
+                        .set_position(Vector3::default());
+                }
+            } else if let Some(camera) = ctx.scene.graph.try_get(self.camera) {
                 self.check_for_obstacles(
                     hinge_position,
                     camera.global_position(),
@@ -1,17 +1,37 @@
 //! Cannon shoots large balls that push players (or bots) off the platforms.
+//!
+//! Spread of shots is picked by a random number generator of the cannon, that is seeded from the
+//! match seed, the handle and the position of the cannon (see [`utils::seeded_rng`]), so every
+//! run with the same seed has the same shots.
+//!
+//! An operable cannon could be taken over by a player with the grab button. The operator aims the
+//! cannon with the camera (see [`crate::camera::CameraController::aim_anchor`]), while the cannon
+//! keeps firing with its own rate. The orientation of the cannon is replicated to clients as any
+//! other transform, so everyone sees where it's pointing.
+//!
+//! Shots are fired with a fixed interval of the level time, or on "Shoot" events of the animation
+//! of the cannon, if the interval is not set. Balls are spawned by the server and replicated as any
+//! other instance, a ball with [`crate::projectile::Projectile`] script pushes actors it hits.
+//!
+//! A cannon with an activation radius is paused while no actor is near. Clients make the same
+//! decision from replicated positions of actors and the replicated level time, so the animation of
+//! the cannon stops and resumes in sync with the server.
 
-use crate::{
-    net::{InstanceDescriptor, ServerMessage},
-    Game,
-};
+use crate::{actor::Actor, level::Level, net::InstanceDescriptor, utils, Game};
 use fyrox::core::math::vector_to_quat;
 use fyrox::{
     core::{
-        pool::Handle, reflect::prelude::*, type_traits::prelude::*, variable::InheritableVariable,
+        algebra::{UnitQuaternion, UnitVector3, Vector3},
+        pool::Handle,
+        reflect::prelude::*,
+        type_traits::prelude::*,
+        variable::InheritableVariable,
         visitor::prelude::*,
     },
+    graph::BaseSceneGraph,
+    rand::{rngs::StdRng, Rng},
     resource::model::{ModelResource, ModelResourceExtension},
-    scene::{animation::AnimationPlayer, node::Node, sound::Sound},
+    scene::{animation::AnimationPlayer, graph::Graph, node::Node, sound::Sound},
     script::{ScriptContext, ScriptTrait},
 };
 
@@ -19,11 +39,78 @@ use fyrox::{
 #[type_uuid(id = "becf5c5f-c745-40ee-85c9-491656fd222e")]
 #[visit(optional)]
 pub struct Cannon {
+    #[reflect(
+        description = "A prefab of a ball. Its root rigid body must have the Projectile script, \
+        otherwise balls do not push actors."
+    )]
     ball_prefab: InheritableVariable<Option<ModelResource>>,
     shot_effect: InheritableVariable<Option<ModelResource>>,
+    #[reflect(description = "Initial speed (m/s) of a ball.", min_value = 0.0)]
     shooting_force: InheritableVariable<f32>,
+    #[reflect(
+        description = "A node at the end of the barrel, balls are spawned at it. The cannon itself \
+        is used if not set."
+    )]
+    muzzle: InheritableVariable<Handle<Node>>,
+    #[reflect(
+        description = "Interval (in seconds) between two shots. Shots are fired on \"Shoot\" events \
+        of the animation if not set.",
+        min_value = 0.01
+    )]
+    fire_interval: InheritableVariable<Option<f32>>,
+    #[reflect(description = "Lifetime (in seconds) of a ball, after which it will be removed.")]
+    ball_lifetime: InheritableVariable<f32>,
+    #[reflect(
+        description = "Max angle (in degrees) by which a shot randomly deviates from the aim \
+        direction, both horizontally and vertically.",
+        min_value = 0.0
+    )]
+    aim_spread: InheritableVariable<f32>,
+    #[reflect(
+        description = "Max fraction of the shooting force, that a shot randomly loses.",
+        min_value = 0.0,
+        max_value = 1.0
+    )]
+    force_jitter: InheritableVariable<f32>,
     shot_sound: InheritableVariable<Handle<Node>>,
     animation_player: InheritableVariable<Handle<Node>>,
+    #[reflect(
+        description = "Players could take control of the cannon with the grab button and aim it. \
+        The cannon keeps firing with its own rate."
+    )]
+    operable: InheritableVariable<bool>,
+    #[reflect(
+        description = "Max distance (in meters) to a player, that could take control of the \
+        cannon.",
+        min_value = 0.0
+    )]
+    operate_distance: InheritableVariable<f32>,
+    #[reflect(
+        description = "A node, at which the aiming camera of the operator is placed. The cannon \
+        itself is used if not set."
+    )]
+    camera_anchor: InheritableVariable<Handle<Node>>,
+    #[reflect(
+        description = "Max distance (in meters) to the nearest actor, beyond which the cannon \
+        stops firing to save simulation cost. The cannon is always active if not set.",
+        min_value = 0.0
+    )]
+    activation_radius: InheritableVariable<Option<f32>>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    is_active: bool,
+    /// Index of the last shot interval, see [`Self::fire_interval`].
+    #[visit(skip)]
+    #[reflect(hidden)]
+    last_shot: Option<u64>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    rng: Option<StdRng>,
+    /// Orientation of the cannon before it was taken over, it is restored when the operator
+    /// leaves.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    rest_rotation: Option<UnitQuaternion<f32>>,
 }
 
 impl Default for Cannon {
@@ -32,72 +119,337 @@ impl Default for Cannon {
             ball_prefab: None.into(),
             shot_effect: Default::default(),
             shooting_force: 100.0.into(),
+            muzzle: Default::default(),
+            fire_interval: Some(3.0).into(),
+            ball_lifetime: 10.0.into(),
+            aim_spread: 0.0.into(),
+            force_jitter: 0.0.into(),
             shot_sound: Default::default(),
             animation_player: Default::default(),
+            operable: false.into(),
+            operate_distance: 2.0.into(),
+            camera_anchor: Default::default(),
+            activation_radius: None.into(),
+            is_active: true,
+            last_shot: None,
+            rng: None,
+            rest_rotation: None,
         }
     }
 }
 
-impl ScriptTrait for Cannon {
-    fn on_update(&mut self, ctx: &mut ScriptContext) {
-        let game = ctx.plugins.get_mut::<Game>();
-        if game.is_client() {
-            return;
+impl Cannon {
+    pub fn is_operable(&self) -> bool {
+        *self.operable
+    }
+
+    pub fn operate_distance(&self) -> f32 {
+        *self.operate_distance
+    }
+
+    /// Returns a node, at which the aiming camera of the operator is placed.
+    pub fn camera_anchor(&self, self_handle: Handle<Node>) -> Handle<Node> {
+        if self.camera_anchor.is_some() {
+            *self.camera_anchor
+        } else {
+            self_handle
         }
+    }
+
+    /// Turns the cannon to the aim of its operator, or back to its initial orientation, when the
+    /// operator leaves.
+    fn update_aim(&mut self, self_handle: Handle<Node>, level: &Level, graph: &mut Graph) {
+        let instance_id = graph[self_handle].instance_id();
+        let aim = level.actors.iter().find_map(|actor| {
+            graph
+                .try_get_script_component_of::<Actor>(*actor)
+                .filter(|actor| actor.operated_cannon == Some(instance_id))
+                .map(|actor| actor.aim)
+        });
 
-        let Some(server) = game.server.as_mut() else {
+        let transform = graph[self_handle].local_transform_mut();
+        if let Some(aim) = aim {
+            self.rest_rotation.get_or_insert(**transform.rotation());
+            transform.set_rotation(aim);
+        } else if let Some(rest_rotation) = self.rest_rotation.take() {
+            transform.set_rotation(rest_rotation);
+        }
+    }
+
+    /// Starts or stops the animation of the cannon, that drives its shots. A resumed animation
+    /// continues from the phase it would have, if it had been playing all the time, so the cannon
+    /// does not jump out of its rhythm.
+    fn set_active(&mut self, active: bool, level_time: f32, graph: &mut Graph) {
+        self.is_active = active;
+        // Missed intervals are skipped instead of firing on resume.
+        self.last_shot = None;
+
+        let Some(node) = graph.try_get_mut(*self.animation_player) else {
             return;
         };
+        node.set_enabled(active);
 
-        let mbc = ctx.scene.graph.begin_multi_borrow();
+        if active {
+            if let Some(animation_player) = node.cast_mut::<AnimationPlayer>() {
+                let animations = animation_player.animations_mut().get_value_mut_silent();
+                for animation in animations.iter_mut() {
+                    let time_slice = animation.time_slice();
+                    let phase = animation_phase(
+                        level_time,
+                        animation.speed(),
+                        time_slice.end - time_slice.start,
+                    );
+                    animation.set_time_position(time_slice.start + phase);
+                }
+            }
+        }
+    }
 
-        let self_node = mbc.get(ctx.handle);
-        let self_position = self_node.global_position();
-        let shooting_dir = self_node
-            .look_vector()
-            .try_normalize(f32::EPSILON)
-            .unwrap_or_default();
+    /// Returns the amount of shots, that must be fired on this frame.
+    fn pending_shots(&mut self, level_time: f32, graph: &mut Graph) -> usize {
+        if let Some(interval) = self.fire_interval.filter(|interval| *interval > 0.0) {
+            // Shots are bound to the level time, so they happen at the same moments on every run.
+            let index = (level_time / interval) as u64;
+            let shots = self.last_shot.map_or(0, |last| index.saturating_sub(last));
+            self.last_shot = Some(index);
+            return shots as usize;
+        }
 
-        if let Ok(mut animation_player) =
-            mbc.try_get_component_of_type_mut::<AnimationPlayer>(*self.animation_player)
-        {
-            let animations = animation_player.animations_mut().get_value_mut_silent();
-            if let Some(shot_animation) = animations.iter_mut().next() {
+        graph
+            .try_get_mut_of_type::<AnimationPlayer>(*self.animation_player)
+            .and_then(|animation_player| {
+                let animations = animation_player.animations_mut().get_value_mut_silent();
+                let shot_animation = animations.iter_mut().next()?;
+                let mut shots = 0;
                 while let Some(event) = shot_animation.pop_event() {
                     if event.name == "Shoot" {
-                        if let Some(ball_prefab) = self.ball_prefab.as_ref() {
-                            server.broadcast_message_to_clients(ServerMessage::Instantiate(vec![
-                                InstanceDescriptor {
-                                    path: ball_prefab.kind().path().unwrap().to_path_buf(),
-                                    position: self_position,
-                                    rotation: Default::default(),
-                                    velocity: shooting_dir.scale(*self.shooting_force),
-                                    ids: ball_prefab.generate_ids(),
-                                },
-                            ]));
-                        }
-
-                        if let Some(shot_effect) = self.shot_effect.as_ref() {
-                            server.broadcast_message_to_clients(ServerMessage::Instantiate(vec![
-                                InstanceDescriptor {
-                                    path: shot_effect.kind().path().unwrap().to_path_buf(),
-                                    position: self_position,
-                                    rotation: vector_to_quat(shooting_dir),
-                                    ids: shot_effect.generate_ids(),
-                                    ..Default::default()
-                                },
-                            ]));
-                        }
-
-                        if let Ok(mut sound) =
-                            mbc.try_get_component_of_type_mut::<Sound>(*self.shot_sound)
-                        {
-                            sound.set_playback_time(0.0);
-                            sound.play();
-                        }
+                        shots += 1;
                     }
                 }
-            }
+                Some(shots)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the position and the aim direction of the muzzle.
+    fn muzzle(&self, self_handle: Handle<Node>, graph: &Graph) -> (Vector3<f32>, Vector3<f32>) {
+        let muzzle = graph
+            .try_get(*self.muzzle)
+            .unwrap_or_else(|| &graph[self_handle]);
+        let direction = graph[self_handle]
+            .look_vector()
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_default();
+        (muzzle.global_position(), direction)
+    }
+
+    fn shoot(&mut self, position: Vector3<f32>, aim_direction: Vector3<f32>, game: &mut Game) {
+        let (shot_dir, force) = self.next_shot(aim_direction);
+
+        if let Some(ball_prefab) = self.ball_prefab.as_ref() {
+            game.instantiate(vec![InstanceDescriptor {
+                path: ball_prefab.kind().path().unwrap().to_path_buf(),
+                position,
+                rotation: Default::default(),
+                velocity: shot_dir.scale(force),
+                ids: ball_prefab.generate_ids(),
+                lifetime: Some(*self.ball_lifetime),
+            }]);
+        }
+
+        if let Some(shot_effect) = self.shot_effect.as_ref() {
+            game.instantiate(vec![InstanceDescriptor {
+                path: shot_effect.kind().path().unwrap().to_path_buf(),
+                position,
+                rotation: vector_to_quat(shot_dir),
+                ids: shot_effect.generate_ids(),
+                ..Default::default()
+            }]);
+        }
+    }
+
+    /// Returns the direction and the force of the next shot. Random values are picked even if
+    /// there's no spread, so changing the spread does not shift the sequence of other values.
+    fn next_shot(&mut self, aim_direction: Vector3<f32>) -> (Vector3<f32>, f32) {
+        let Some(rng) = self.rng.as_mut() else {
+            return (aim_direction, *self.shooting_force);
         };
+
+        let spread = self.aim_spread.max(0.0).to_radians();
+        let yaw = rng.gen_range(-spread..=spread);
+        let pitch = rng.gen_range(-spread..=spread);
+        let force_loss = rng.gen_range(0.0..=self.force_jitter.clamp(0.0, 1.0));
+
+        let side = aim_direction
+            .cross(&Vector3::y())
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::x);
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw)
+            * UnitQuaternion::from_axis_angle(&UnitVector3::new_unchecked(side), pitch);
+
+        (
+            rotation * aim_direction,
+            *self.shooting_force * (1.0 - force_loss),
+        )
+    }
+}
+
+/// Returns the time position (relative to the start) of a looped animation with the given speed and
+/// length, that has been playing since the start of the level.
+fn animation_phase(level_time: f32, speed: f32, length: f32) -> f32 {
+    if length > f32::EPSILON {
+        (level_time * speed).rem_euclid(length)
+    } else {
+        0.0
+    }
+}
+
+impl ScriptTrait for Cannon {
+    fn on_start(&mut self, ctx: &mut ScriptContext) {
+        let position = ctx.scene.graph[ctx.handle].global_position();
+        let seed = ctx.plugins.get::<Game>().level.seed;
+        self.rng = Some(utils::seeded_rng(seed, ctx.handle, position));
+    }
+
+    fn on_update(&mut self, ctx: &mut ScriptContext) {
+        let game = ctx.plugins.get_mut::<Game>();
+        if game.is_paused() {
+            return;
+        }
+
+        let is_active = self.activation_radius.map_or(true, |radius| {
+            let position = ctx.scene.graph[ctx.handle].global_position();
+            game.level
+                .is_any_actor_within(position, radius, &ctx.scene.graph)
+        });
+        if is_active != self.is_active {
+            self.set_active(is_active, game.level.time, &mut ctx.scene.graph);
+        }
+        // Shots are fired by the server only.
+        if game.is_client() || !is_active {
+            return;
+        }
+
+        if *self.operable {
+            self.update_aim(ctx.handle, &game.level, &mut ctx.scene.graph);
+        }
+
+        let shots = self.pending_shots(game.level.time, &mut ctx.scene.graph);
+        if shots == 0 {
+            return;
+        }
+
+        let (position, aim_direction) = self.muzzle(ctx.handle, &ctx.scene.graph);
+        for _ in 0..shots {
+            self.shoot(position, aim_direction, game);
+        }
+
+        if let Some(sound) = ctx
+            .scene
+            .graph
+            .try_get_mut_of_type::<Sound>(*self.shot_sound)
+        {
+            sound.set_playback_time(0.0);
+            sound.play();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+    use fyrox::{
+        scene::{base::BaseBuilder, pivot::PivotBuilder, transform::TransformBuilder},
+        script::Script,
+    };
+    use std::time::{Duration, Instant};
+
+    fn add_node(position: Vector3<f32>, graph: &mut Graph) -> Handle<Node> {
+        PivotBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(position)
+                    .build(),
+            ),
+        )
+        .build(graph)
+    }
+
+    #[test]
+    fn resumed_animation_keeps_its_rhythm() {
+        // An animation, that was paused for a while, resumes at the same phase as an animation,
+        // that has been playing all the time.
+        let (speed, length, dt) = (1.5, 2.0, 1.0 / 60.0);
+        let mut continuous = 0.0;
+        for step in 1..=600 {
+            continuous = (continuous + dt * speed) % length;
+            let level_time = step as f32 * dt;
+            let resumed = animation_phase(level_time, speed, length);
+            let error = (resumed - continuous).abs();
+            assert!(error < 1e-3 || (length - error) < 1e-3);
+        }
+    }
+
+    #[test]
+    fn empty_animation_has_zero_phase() {
+        assert_eq!(animation_phase(10.0, 1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn dense_map_activation_fits_into_a_frame() {
+        let mut graph = Graph::new();
+        let mut level = Level::default();
+        // 8x8 actors with 10 meters between each other.
+        let mut actor_positions = Vec::new();
+        for i in 0..64 {
+            let position = Vector3::new((i % 8) as f32 * 10.0, 0.0, (i / 8) as f32 * 10.0);
+            let mut bot = Bot::default();
+            bot.actor.rigid_body = add_node(position, &mut graph);
+            let actor = PivotBuilder::new(BaseBuilder::new().with_script(Script::new(bot)))
+                .build(&mut graph);
+            level.actors.insert(actor);
+            actor_positions.push(position);
+        }
+        // 16x16 cannons with 5 meters between each other, the grid covers the actors.
+        let cannon_positions = (0..256)
+            .map(|i| {
+                Vector3::new(
+                    (i % 16) as f32 * 5.0 - 5.0,
+                    1.0,
+                    (i / 16) as f32 * 5.0 - 5.0,
+                )
+            })
+            .collect::<Vec<_>>();
+        graph.update_hierarchical_data();
+
+        let radius = 3.0;
+        let expected = cannon_positions
+            .iter()
+            .map(|cannon| {
+                actor_positions
+                    .iter()
+                    .any(|actor| actor.metric_distance(cannon) <= radius)
+            })
+            .collect::<Vec<_>>();
+        assert!(expected.iter().any(|active| *active));
+        assert!(expected.iter().any(|active| !*active));
+
+        let frames = 60;
+        let start = Instant::now();
+        for _ in 0..frames {
+            let active = cannon_positions
+                .iter()
+                .map(|position| level.is_any_actor_within(*position, radius, &graph))
+                .collect::<Vec<_>>();
+            assert_eq!(active, expected);
+        }
+        // Every cannon checks every actor each frame, it must fit into a 60 FPS frame even in
+        // debug builds.
+        let per_frame = start.elapsed() / frames;
+        assert!(
+            per_frame < Duration::from_millis(16),
+            "activation of 256 cannons took {per_frame:?} per frame"
+        );
     }
 }
@@ -1,32 +1,143 @@
 use crate::menu::Menu;
 use crate::{
-    actor::Actor,
+    actor::{self, Actor, ActorKind},
+    bot::Bot,
+    clock::ClockSync,
+    compression,
+    custom_message::{self, CustomMessageHandlers, CustomMessageSender, CustomMessageTag},
+    interpolation::Interpolator,
     level::Level,
-    net::{ClientMessage, InstanceDescriptor, PlayerDescriptor, ServerMessage},
+    net::{ClientMessage, InstanceDescriptor, PlayerDescriptor, PlayerResult, ServerMessage},
+    player::InputController,
+    quantization,
+    server::Server,
     Game,
 };
 use fyrox::{
-    core::{log::Log, net::NetStream, pool::Handle},
+    core::{color::Color, log::Log, net::NetStream, pool::Handle},
+    graph::{BaseSceneGraph, SceneGraph},
     plugin::PluginContext,
     resource::model::{Model, ModelResourceExtension},
-    scene::{rigidbody::RigidBody, Scene},
+    scene::{base::SceneNodeId, graph::Graph, node::Node, rigidbody::RigidBody, Scene},
 };
 use std::fmt::Formatter;
-use std::{fmt::Debug, io, net::ToSocketAddrs};
+use std::{
+    fmt::Debug,
+    io,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+};
+
+/// Amount of time (in seconds) between acknowledgements of received update ticks. It is much
+/// shorter than the max send lag of the server, so the acknowledgements do not make clients look
+/// slower than they are.
+const TICK_ACK_INTERVAL: f32 = 0.1;
+
+pub struct WinContext {
+    /// Remaining time (in seconds) before the next round starts automatically. The host starts
+    /// the next round manually if not set.
+    pub next_round_in: Option<f32>,
+    pub players: Vec<PlayerResult>,
+    /// Seed of the finished match, so players could share it.
+    pub seed: u64,
+}
+
+/// Path and match id of the level, that is loaded (or being loaded). Used to ignore duplicate
+/// [`ServerMessage::LoadLevel`] messages.
+#[derive(Default, Debug)]
+struct LoadedLevel {
+    level: Option<(PathBuf, u64)>,
+}
 
-pub struct FinishedPlayer {
+impl LoadedLevel {
+    /// Returns `true` if the level must be loaded and remembers it. Repeated requests for the same
+    /// path and match id are ignored, unless `force` is set.
+    fn begin_load(&mut self, path: &Path, match_id: u64, force: bool) -> bool {
+        let is_duplicate = self
+            .level
+            .as_ref()
+            .is_some_and(|(loaded, id)| loaded == path && *id == match_id);
+        if is_duplicate && !force {
+            return false;
+        }
+        self.level = Some((path.to_path_buf(), match_id));
+        true
+    }
+}
+
+/// Replicated information about an actor (player or bot) of the current match.
+#[derive(Clone, Debug)]
+pub struct PlayerInfo {
+    pub actor: Handle<Node>,
     pub name: String,
-    pub place: usize,
+    pub kind: ActorKind,
+    pub color: Color,
+    /// `true` if the actor is simulated by the server and its replicated state is interpolated.
+    /// Otherwise the actor is simulated locally, and replicated transforms of its nodes are
+    /// ignored.
+    pub is_remote: bool,
 }
 
-pub struct WinContext {
-    pub timer: f32,
-    pub players: Vec<FinishedPlayer>,
+/// Phase of the current round, as seen by the client.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum RoundPhase {
+    /// There's no match, the client is in the menu or in the lobby.
+    #[default]
+    Lobby,
+    /// The server has asked to load a level.
+    Loading,
+    InProgress,
+    /// The match has ended, the results are shown.
+    Finished,
+}
+
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct RoundState {
+    pub phase: RoundPhase,
+    /// Remaining time (in seconds) of the round, see [`Level::match_timer`].
+    pub match_timer: Option<f32>,
+    /// Seed of the current (or the last) match.
+    pub seed: u64,
+}
+
+/// The latest input of the local player, that is not sent to the server yet.
+struct PendingInput {
+    player: SceneNodeId,
+    state: InputController,
 }
 
 pub struct Client {
     connection: NetStream,
     pub win_context: Option<WinContext>,
+    /// Seed of the current (or the last) match.
+    pub match_seed: u64,
+    /// Smooths replicated transforms between snapshots of the server.
+    pub interpolator: Interpolator,
+    /// Amount of time (in seconds) after which the server is considered disconnected.
+    pub connection_timeout: f32,
+    idle_time: f32,
+    keep_alive_timer: f32,
+    /// Amount of input messages (per second) sent to the server. Input is sent with a fixed rate
+    /// regardless of the frame rate, only the latest state is sent.
+    pub input_send_rate: f32,
+    input_send_timer: f32,
+    input_sequence: u32,
+    pending_input: Option<PendingInput>,
+    last_sent_input: Option<InputController>,
+    local_player: Handle<Node>,
+    players: Vec<PlayerInfo>,
+    round_state: RoundState,
+    /// Estimation of the server clock, timers of the round are compensated for latency with it.
+    pub clock: ClockSync,
+    /// The local player is ready to start the match, see [`ClientMessage::Ready`].
+    ready: bool,
+    /// Sequence number of the latest received update tick and whether it was acknowledged.
+    last_tick: u32,
+    is_tick_acked: bool,
+    tick_ack_timer: f32,
+    loaded_level: LoadedLevel,
+    /// Player model, that was sent to the server last time, see [`ClientMessage::SelectModel`].
+    model: Option<PathBuf>,
 }
 
 impl Debug for Client {
@@ -35,7 +146,7 @@ impl Debug for Client {
     }
 }
 
-fn instantiate_objects(instances: Vec<InstanceDescriptor>, ctx: &mut PluginContext) {
+pub fn instantiate_objects(instances: Vec<InstanceDescriptor>, ctx: &mut PluginContext) {
     for new_instance in instances {
         ctx.task_pool.spawn_plugin_task(
             ctx.resource_manager.request::<Model>(&new_instance.path),
@@ -52,6 +163,10 @@ fn instantiate_objects(instances: Vec<InstanceDescriptor>, ctx: &mut PluginConte
                     if let Some(rigid_body) = scene.graph[instance].cast_mut::<RigidBody>() {
                         rigid_body.set_lin_vel(new_instance.velocity);
                     }
+
+                    if let Some(lifetime) = new_instance.lifetime {
+                        scene.graph[instance].set_lifetime(Some(lifetime));
+                    }
                 }
                 Err(err) => {
                     Log::err(format!(
@@ -76,9 +191,14 @@ fn add_players(players: Vec<PlayerDescriptor>, ctx: &mut PluginContext) {
                         .begin_instantiation(scene)
                         .with_ids(&player.instance.ids)
                         .finish();
+                    let mut colored_meshes = Vec::new();
                     if let Some(actor) = scene.graph.try_get_script_component_of_mut::<Actor>(root)
                     {
                         actor.kind = player.kind;
+                        actor.is_remote = player.is_remote;
+                        actor.color = player.color.into();
+                        actor.team = player.team;
+                        colored_meshes = (*actor.colored_meshes).clone();
                         let rigid_body = actor.rigid_body;
                         if let Some(rigid_body) = scene.graph.try_get_mut(rigid_body) {
                             rigid_body
@@ -86,6 +206,29 @@ fn add_players(players: Vec<PlayerDescriptor>, ctx: &mut PluginContext) {
                                 .set_position(player.instance.position);
                         }
                     }
+                    if let Some(bot) = scene.graph.try_get_script_of_mut::<Bot>(root) {
+                        bot.set_difficulty(player.difficulty);
+                    }
+                    actor::apply_actor_color(
+                        &colored_meshes,
+                        player.color.into(),
+                        &mut scene.graph,
+                    );
+                    if let (Some(client), Some(actor)) = (
+                        game.client.as_mut(),
+                        scene.graph.try_get_script_component_of::<Actor>(root),
+                    ) {
+                        client.add_player(
+                            PlayerInfo {
+                                actor: root,
+                                name: actor.name.clone(),
+                                kind: player.kind,
+                                color: player.color.into(),
+                                is_remote: player.is_remote,
+                            },
+                            &scene.graph,
+                        );
+                    }
                 }
                 Err(err) => {
                     Log::err(format!(
@@ -104,10 +247,34 @@ impl Client {
     where
         A: ToSocketAddrs + Debug,
     {
-        Ok(Self {
+        let mut client = Self {
             connection: NetStream::connect(server_addr)?,
             win_context: None,
-        })
+            match_seed: 0,
+            interpolator: Default::default(),
+            connection_timeout: 10.0,
+            idle_time: 0.0,
+            keep_alive_timer: 0.0,
+            input_send_rate: 60.0,
+            input_send_timer: 0.0,
+            input_sequence: 0,
+            pending_input: None,
+            last_sent_input: None,
+            local_player: Handle::NONE,
+            players: Default::default(),
+            round_state: Default::default(),
+            clock: Default::default(),
+            ready: false,
+            last_tick: 0,
+            is_tick_acked: true,
+            tick_ack_timer: 0.0,
+            loaded_level: Default::default(),
+            model: None,
+        };
+        client.send_message_to_server(ClientMessage::Hello {
+            supports_compression: true,
+        });
+        Ok(client)
     }
 
     pub fn send_message_to_server(&mut self, message: ClientMessage) {
@@ -117,83 +284,377 @@ impl Client {
         }
     }
 
+    /// Sends a custom message (see [`crate::custom_message`]) to the server. Too large payloads
+    /// are not sent.
+    pub fn send_custom_message(&mut self, tag: CustomMessageTag, data: Vec<u8>) {
+        if !custom_message::is_valid_payload(&data) {
+            Log::err(format!(
+                "Custom message with tag {tag} was not sent, its payload is too large ({} bytes).",
+                data.len()
+            ));
+            return;
+        }
+        self.send_message_to_server(ClientMessage::Custom { tag, data });
+    }
+
     pub fn read_messages(
         &mut self,
         level: &mut Level,
         menu: Option<&Menu>,
+        custom_message_handlers: &mut CustomMessageHandlers,
         ctx: &mut PluginContext,
     ) {
-        self.connection.process_input(|msg| match msg {
-            ServerMessage::LoadLevel { path } => {
-                ctx.async_scene_loader.request(path);
-            }
-            ServerMessage::UpdateTick(data) => {
-                if let Some(scene) = ctx.scenes.try_get_mut(level.scene) {
-                    for entry in data.nodes {
-                        if let Some((_, node)) = scene.graph.node_by_id_mut(entry.node) {
-                            let transform = node.local_transform_mut();
-                            if **transform.position() != entry.position {
-                                transform.set_position(entry.position);
-                            }
-                            if **transform.rotation() != entry.rotation {
-                                transform.set_rotation(entry.rotation);
-                            }
+        let mut received_any = false;
+        let mut pings = Vec::new();
+        self.connection.process_input(|msg| {
+            received_any = true;
+            let msg = match msg {
+                ServerMessage::CompressedUpdateTick { data } => {
+                    match compression::decompress_tick(&data) {
+                        Ok(tick) => ServerMessage::UpdateTick(tick),
+                        Err(err) => {
+                            Log::err(format!("Unable to decompress update tick: {err}"));
+                            return;
                         }
                     }
                 }
-            }
-            ServerMessage::Instantiate(instances) => {
-                instantiate_objects(instances, ctx);
-            }
-            ServerMessage::AddPlayers(players) => add_players(players, ctx),
-            ServerMessage::EndMatch => {
-                if let Some(scene) = ctx.scenes.try_get(level.scene) {
-                    let mut players = level
-                        .leaderboard
-                        .entries
-                        .values()
-                        .map(|e| {
-                            let actor = scene
+                msg => msg,
+            };
+            let msg = match msg {
+                ServerMessage::UpdateTick(tick) => {
+                    ServerMessage::UpdateTick(quantization::dequantize_tick(tick))
+                }
+                msg => msg,
+            };
+            match msg {
+                ServerMessage::LoadLevel {
+                    path,
+                    seed,
+                    match_id,
+                    force,
+                } => {
+                    if !self.loaded_level.begin_load(&path, match_id, force) {
+                        Log::warn(format!(
+                            "Ignoring duplicate request to load {} (match {match_id}).",
+                            path.display()
+                        ));
+                        return;
+                    }
+                    self.match_seed = seed;
+                    self.win_context = None;
+                    self.reset_match_state(RoundPhase::Loading);
+                    self.round_state.seed = seed;
+                    ctx.async_scene_loader.request(path);
+                }
+                ServerMessage::UpdateTick(data) => {
+                    self.last_tick = data.sequence;
+                    self.is_tick_acked = false;
+                    // The timer has been running on the server since the tick was made.
+                    let latency = self
+                        .clock
+                        .server_time()
+                        .map_or(0.0, |now| (now - data.server_time).max(0.0) as f32);
+                    let match_timer = data.match_timer.map(|timer| (timer - latency).max(0.0));
+                    level.match_timer = match_timer;
+                    level.set_replicated_sudden_death(data.sudden_death);
+                    level.time = data.level_time + latency;
+                    self.round_state.match_timer = match_timer;
+                    if let Some(scene) = ctx.scenes.try_get_mut(level.scene) {
+                        if self.interpolator.enabled {
+                            self.interpolator.begin_snapshot(&mut scene.graph);
+                        } else {
+                            self.interpolator.finish(&mut scene.graph);
+                        }
+                        for entry in data.nodes {
+                            // Locally simulated nodes are driven by the local simulation.
+                            if scene
                                 .graph
-                                .try_get_script_component_of::<Actor>(e.actor)
-                                .unwrap();
-
-                            FinishedPlayer {
-                                name: actor.name.clone(),
-                                place: e.finished_position,
+                                .node_by_id(entry.node)
+                                .is_some_and(|(handle, _)| self.interpolator.is_excluded(handle))
+                            {
+                                continue;
+                            }
+                            // Scale is rarely changed, so it is not interpolated.
+                            if let Some(scale) = entry.scale {
+                                if let Some((_, node)) = scene.graph.node_by_id_mut(entry.node) {
+                                    node.local_transform_mut().set_scale(scale);
+                                }
                             }
-                        })
-                        .collect::<Vec<_>>();
-                    players.sort_by_key(|e| e.place);
+                            if self.interpolator.enabled {
+                                if let Some((handle, _)) = scene.graph.node_by_id(entry.node) {
+                                    self.interpolator.push(
+                                        handle,
+                                        entry.position,
+                                        entry.rotation,
+                                        &scene.graph,
+                                    );
+                                }
+                            } else if let Some((_, node)) = scene.graph.node_by_id_mut(entry.node) {
+                                let transform = node.local_transform_mut();
+                                if **transform.position() != entry.position {
+                                    transform.set_position(entry.position);
+                                }
+                                if **transform.rotation() != entry.rotation {
+                                    transform.set_rotation(entry.rotation);
+                                }
+                            }
+                        }
+                        for entry in data.actors {
+                            let Some((handle, _)) = scene.graph.node_by_id(entry.node) else {
+                                continue;
+                            };
+                            if let Some(actor) =
+                                scene.graph.try_get_script_component_of_mut::<Actor>(handle)
+                            {
+                                actor.status_effects = entry.status_effects;
+                                actor.knocked_out = entry.knocked_out;
+                                actor.grounded = entry.grounded;
+                                actor.crouched = entry.crouched;
+                                actor.sliding = entry.sliding;
+                                actor.grabbing = entry.grabbing;
+                                actor.awaiting_respawn = entry.awaiting_respawn;
+                                actor.stunned = entry.stunned;
+                                actor.operated_cannon = entry.operated_cannon;
+                                let newly_eliminated = entry.eliminated && !actor.eliminated;
+                                actor.eliminated = entry.eliminated;
+                                let (collider, rigid_body) = (actor.collider, actor.rigid_body);
+                                let visuals = actor.status_effect_visuals();
+                                actor::apply_status_effect_visuals(visuals, &mut scene.graph);
+                                if newly_eliminated {
+                                    actor::apply_elimination(
+                                        handle,
+                                        collider,
+                                        rigid_body,
+                                        &mut scene.graph,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+                ServerMessage::Instantiate(instances) => {
+                    instantiate_objects(instances, ctx);
+                }
+                ServerMessage::Despawn { instance_id } => {
+                    // The node could be removed already (by its lifetime, for example), or it
+                    // is shared with the server on the host.
+                    if let Some(scene) = ctx.scenes.try_get_mut(level.scene) {
+                        if let Some((handle, _)) = scene.graph.node_by_id(instance_id) {
+                            self.interpolator.remove(handle);
+                            scene.graph.remove_node(handle);
+                        }
+                    }
+                }
+                ServerMessage::AddPlayers(players) => add_players(players, ctx),
+                ServerMessage::EndMatch => {
+                    if ctx.scenes.try_get(level.scene).is_some() {
+                        if let Some(menu) = menu {
+                            menu.set_menu_visibility(ctx.user_interfaces.first(), true);
+                            menu.set_main_menu_visibility(ctx.user_interfaces.first(), true);
+                        }
 
+                        ctx.scenes.remove(level.scene);
+                        level.scene = Handle::NONE;
+                        self.reset_match_state(RoundPhase::Finished);
+                    }
+                }
+                ServerMessage::MatchResults(results) => {
+                    // The countdown has been running on the server since the message was sent.
+                    let latency = self
+                        .clock
+                        .server_time()
+                        .map_or(0.0, |now| (now - results.server_time).max(0.0) as f32);
                     self.win_context = Some(WinContext {
-                        timer: 10.0,
-                        players,
+                        next_round_in: results.next_round_in.map(|time| (time - latency).max(0.0)),
+                        players: results.players,
+                        seed: results.seed,
                     });
+                }
+                ServerMessage::AbortMatch { reason } => {
+                    self.reset_match_state(RoundPhase::Lobby);
+                    Log::err(format!(
+                        "The match was aborted by the server. Reason: {reason}"
+                    ));
 
                     if let Some(menu) = menu {
                         menu.set_menu_visibility(ctx.user_interfaces.first(), true);
                         menu.set_main_menu_visibility(ctx.user_interfaces.first(), true);
+                        menu.show_message(ctx.user_interfaces.first_mut(), "Error", &reason);
                     }
 
-                    ctx.scenes.remove(level.scene);
-                    level.scene = Handle::NONE;
+                    if level.scene.is_some() {
+                        ctx.scenes.remove(level.scene);
+                        level.scene = Handle::NONE;
+                    }
+                }
+                ServerMessage::LeaderBoard(msg) => {
+                    level.leaderboard.entries =
+                        msg.players.into_iter().map(|e| (e.actor, e)).collect();
                 }
+                ServerMessage::KeepAlive => {}
+                ServerMessage::Ping { nonce } => pings.push(nonce),
+                ServerMessage::SyncClock {
+                    client_time,
+                    server_time,
+                } => self.clock.add_sample(client_time, server_time),
+                ServerMessage::Custom { tag, data } => {
+                    custom_message_handlers.handle(CustomMessageSender::Server, tag, &data, ctx)
+                }
+                // Unpacked above.
+                ServerMessage::CompressedUpdateTick { .. } => {}
             }
-            ServerMessage::LeaderBoard(msg) => {
-                level.leaderboard.entries = msg.players.into_iter().map(|e| (e.actor, e)).collect();
+        });
+        for nonce in pings {
+            self.send_message_to_server(ClientMessage::Pong { nonce });
+        }
+        if received_any {
+            self.idle_time = 0.0;
+        }
+    }
+
+    /// Returns `true` if there were no messages from the server for too long.
+    pub fn is_timed_out(&self) -> bool {
+        self.idle_time > self.connection_timeout
+    }
+
+    /// Returns the handle of the actor, that is controlled by this client. There's no local player
+    /// if there's no match or the actor is not spawned yet.
+    pub fn local_player(&self) -> Option<Handle<Node>> {
+        self.local_player.is_some().then_some(self.local_player)
+    }
+
+    /// Returns the state of the current round.
+    pub fn round_state(&self) -> RoundState {
+        self.round_state
+    }
+
+    /// Returns every actor (players and bots) of the current match, in the order of spawning.
+    pub fn players(&self) -> &[PlayerInfo] {
+        &self.players
+    }
+
+    fn add_player(&mut self, info: PlayerInfo, graph: &Graph) {
+        if info.kind == ActorKind::Player {
+            self.local_player = info.actor;
+        }
+        if !info.is_remote {
+            for handle in graph.traverse_handle_iter(info.actor) {
+                self.interpolator.exclude(handle);
             }
-        })
+        }
+        self.players.push(info);
     }
 
-    pub fn update(&mut self, dt: f32) {
-        if let Some(win_context) = self.win_context.as_mut() {
-            win_context.timer -= dt;
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
 
-            if win_context.timer <= 0.0 {
-                self.win_context.take();
+    /// Tells the server whether the local player is ready to start the match.
+    pub fn set_ready(&mut self, ready: bool) {
+        if self.ready != ready {
+            self.ready = ready;
+            self.send_message_to_server(ClientMessage::Ready { ready });
+        }
+    }
+
+    /// Tells the server which model should be used for the local player. The choice persists
+    /// across matches.
+    pub fn select_model(&mut self, path: &Path) {
+        if self.model.as_deref() != Some(path) {
+            self.model = Some(path.to_path_buf());
+            self.send_message_to_server(ClientMessage::SelectModel {
+                path: path.to_path_buf(),
+            });
+        }
+    }
+
+    /// Forgets everything about the current match, handles of its actors are invalid now.
+    fn reset_match_state(&mut self, phase: RoundPhase) {
+        // The server resets readiness of everyone on match start and end.
+        self.ready = false;
+        self.local_player = Handle::NONE;
+        self.players.clear();
+        self.round_state.phase = phase;
+        self.round_state.match_timer = None;
+    }
+
+    /// Stores the input state of the given player, it will be sent to the server on the next
+    /// input tick (see [`Self::input_send_rate`]).
+    pub fn queue_input(&mut self, player: SceneNodeId, state: InputController) {
+        if let Some(pending) = self.pending_input.as_ref() {
+            // A short key press could begin and end between two ticks, send the pending change
+            // right away, so it is not lost.
+            let has_unsent_buttons = self
+                .last_sent_input
+                .as_ref()
+                .map_or(true, |last| last.buttons_differ(&pending.state));
+            if pending.player == player
+                && has_unsent_buttons
+                && pending.state.buttons_differ(&state)
+            {
+                self.flush_input();
             }
         }
+        self.pending_input = Some(PendingInput { player, state });
+    }
+
+    /// Sends the pending input to the server, if it differs from the last sent one.
+    fn flush_input(&mut self) {
+        let Some(pending) = self.pending_input.take() else {
+            return;
+        };
+        if self.last_sent_input.as_ref() == Some(&pending.state) {
+            return;
+        }
+        self.input_sequence = self.input_sequence.wrapping_add(1);
+        self.send_message_to_server(ClientMessage::Input {
+            player: pending.player,
+            input_state: pending.state.clone(),
+            sequence: self.input_sequence,
+        });
+        self.last_sent_input = Some(pending.state);
+    }
+
+    fn update_input(&mut self, dt: f32) {
+        if is_input_tick(&mut self.input_send_timer, dt, self.input_send_rate) {
+            self.flush_input();
+        }
+    }
+
+    /// Returns the estimated offset (in seconds) between the server clock and the local clock, or
+    /// `None` if the clocks are not synchronized yet.
+    pub fn clock_offset(&self) -> Option<f64> {
+        self.clock.offset()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.update_input(dt);
+        self.tick_ack_timer -= dt;
+        if !self.is_tick_acked && self.tick_ack_timer <= 0.0 {
+            self.tick_ack_timer = TICK_ACK_INTERVAL;
+            self.is_tick_acked = true;
+            self.send_message_to_server(ClientMessage::TickAck {
+                sequence: self.last_tick,
+            });
+        }
+        if let Some(client_time) = self.clock.update(dt) {
+            self.send_message_to_server(ClientMessage::SyncClock { client_time });
+        }
+        self.idle_time += dt;
+        self.keep_alive_timer -= dt;
+        if self.keep_alive_timer <= 0.0 {
+            self.keep_alive_timer = Server::KEEP_ALIVE_INTERVAL;
+            self.send_message_to_server(ClientMessage::KeepAlive);
+        }
+
+        // The results are shown until the next round is loaded.
+        if let Some(time) = self
+            .win_context
+            .as_mut()
+            .and_then(|c| c.next_round_in.as_mut())
+        {
+            *time = (*time - dt).max(0.0);
+        }
     }
 
     pub fn on_scene_loaded(
@@ -202,9 +663,74 @@ impl Client {
         scene: Handle<Scene>,
         ctx: &mut PluginContext,
     ) {
+        // Handles of the previous level are invalid now.
+        self.interpolator = Default::default();
+        self.pending_input = None;
+        self.last_sent_input = None;
+        self.round_state.phase = RoundPhase::InProgress;
+
         let scene = &mut ctx.scenes[scene];
         if !has_server {
             scene.graph.physics.enabled.set_value_silent(false);
         }
+        self.send_message_to_server(ClientMessage::LevelLoaded);
+    }
+}
+
+/// Advances the input send timer by `dt` and returns `true` if input must be sent on this frame,
+/// so input is sent `rate` times per second regardless of the frame rate. Input is sent at most
+/// once per frame.
+fn is_input_tick(timer: &mut f32, dt: f32, rate: f32) -> bool {
+    let interval = 1.0 / rate.max(1.0);
+    *timer += dt;
+    if *timer >= interval {
+        // Do not accumulate more than one tick, otherwise a long frame would cause a burst of
+        // ticks.
+        *timer = (*timer - interval).min(interval);
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_load_level_is_ignored() {
+        let mut loaded_level = LoadedLevel::default();
+        let path = Path::new("data/levels/level1.rgs");
+        let loads = (0..2)
+            .filter(|_| loaded_level.begin_load(path, 1, false))
+            .count();
+        assert_eq!(loads, 1);
+    }
+
+    #[test]
+    fn new_match_or_force_reloads_level() {
+        let mut loaded_level = LoadedLevel::default();
+        let path = Path::new("data/levels/level1.rgs");
+        assert!(loaded_level.begin_load(path, 1, false));
+        assert!(loaded_level.begin_load(path, 1, true));
+        assert!(loaded_level.begin_load(path, 2, false));
+        assert!(loaded_level.begin_load(Path::new("data/levels/level2.rgs"), 2, false));
+    }
+
+    #[test]
+    fn input_is_sent_with_configured_rate() {
+        // Amount of input ticks in two seconds with the given frame rate.
+        let ticks = |fps: f32| {
+            let mut timer = 0.0;
+            (0..(2.0 * fps) as usize)
+                .filter(|_| is_input_tick(&mut timer, 1.0 / fps, 60.0))
+                .count()
+        };
+        for fps in [60.0, 75.0, 144.0, 240.0, 1000.0] {
+            let ticks = ticks(fps);
+            assert!((119..=121).contains(&ticks), "{fps} FPS: {ticks} ticks");
+        }
+        // Slow clients send input once per frame.
+        assert!((59..=61).contains(&ticks(30.0)));
     }
 }
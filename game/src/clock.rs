@@ -0,0 +1,133 @@
+//! Estimation of the server clock on clients. A client periodically sends its local time to the
+//! server, the server responds with both the client time and its own time. Assuming that the
+//! request and the response take the same time, the server time at the moment of the response is
+//! `server_time + round_trip_time / 2`, which gives the offset between the clocks. Samples with
+//! the shortest round trip are the most accurate ones (they were least affected by queueing), so
+//! the offset of the fastest recent sample is used.
+
+use std::time::Instant;
+
+/// Amount of recent samples, among which the fastest one is picked.
+const MAX_SAMPLES: usize = 8;
+
+/// Returns the offset (in seconds) between the server clock and the client clock, along with the
+/// round trip time of the exchange. `request_time` and `response_time` are client times, when the
+/// request was sent and when the response was received, `server_time` is the server time, when
+/// the request was handled.
+pub fn estimate_offset(request_time: f64, server_time: f64, response_time: f64) -> (f64, f64) {
+    let round_trip_time = (response_time - request_time).max(0.0);
+    let offset = server_time + round_trip_time * 0.5 - response_time;
+    (offset, round_trip_time)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Sample {
+    offset: f64,
+    round_trip_time: f64,
+}
+
+#[derive(Debug)]
+pub struct ClockSync {
+    epoch: Instant,
+    samples: Vec<Sample>,
+    /// Amount of time (in seconds) between two synchronization requests.
+    pub interval: f32,
+    timer: f32,
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            samples: Default::default(),
+            interval: Self::DEFAULT_INTERVAL,
+            timer: 0.0,
+        }
+    }
+}
+
+impl ClockSync {
+    pub const DEFAULT_INTERVAL: f32 = 2.0;
+
+    /// Returns the time (in seconds) of the local clock.
+    pub fn local_time(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64()
+    }
+
+    /// Advances the request timer and returns the local time, that must be sent to the server, if
+    /// it is time for a new request. Requests are sent more often until the first few samples
+    /// are collected, so the estimation becomes usable quickly.
+    pub fn update(&mut self, dt: f32) -> Option<f64> {
+        self.timer -= dt;
+        if self.timer > 0.0 {
+            return None;
+        }
+        self.timer = if self.samples.len() < MAX_SAMPLES / 2 {
+            self.interval * 0.1
+        } else {
+            self.interval
+        };
+        Some(self.local_time())
+    }
+
+    /// Adds a new sample from the response of the server.
+    pub fn add_sample(&mut self, request_time: f64, server_time: f64) {
+        let (offset, round_trip_time) =
+            estimate_offset(request_time, server_time, self.local_time());
+        if self.samples.len() >= MAX_SAMPLES {
+            self.samples.remove(0);
+        }
+        self.samples.push(Sample {
+            offset,
+            round_trip_time,
+        });
+    }
+
+    fn best_sample(&self) -> Option<&Sample> {
+        self.samples
+            .iter()
+            .min_by(|a, b| a.round_trip_time.total_cmp(&b.round_trip_time))
+    }
+
+    /// Returns the estimated offset (in seconds) between the server clock and the local clock, or
+    /// `None` if there are no samples yet.
+    pub fn offset(&self) -> Option<f64> {
+        self.best_sample().map(|sample| sample.offset)
+    }
+
+    /// Returns the round trip time (in seconds) of the sample, that is used for the estimation.
+    pub fn round_trip_time(&self) -> Option<f64> {
+        self.best_sample().map(|sample| sample.round_trip_time)
+    }
+
+    /// Returns the estimated current time of the server clock.
+    pub fn server_time(&self) -> Option<f64> {
+        self.offset().map(|offset| self.local_time() + offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_offset_of_symmetric_exchange() {
+        // The server clock is 100 seconds ahead, the request and the response take 50 ms each.
+        let (offset, round_trip_time) = estimate_offset(10.0, 110.05, 10.1);
+        assert!((offset - 100.0).abs() < 1e-9);
+        assert!((round_trip_time - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_offset_of_server_behind() {
+        let (offset, round_trip_time) = estimate_offset(50.0, 20.5, 51.0);
+        assert!((offset + 30.0).abs() < 1e-9);
+        assert!((round_trip_time - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_offset_never_has_negative_round_trip_time() {
+        let (_, round_trip_time) = estimate_offset(2.0, 5.0, 1.0);
+        assert_eq!(round_trip_time, 0.0);
+    }
+}
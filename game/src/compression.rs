@@ -0,0 +1,136 @@
+//! Optional compression of update ticks. Ticks of levels with many moving objects could be large
+//! even with delta compression, so the server could compress them with LZ4 before sending. It is
+//! enabled in the lobby config and used only for clients, that reported support for it when they
+//! connected (see [`crate::net::ClientMessage::Hello`]). On LAN the CPU cost is usually not worth
+//! it, so it is off by default.
+
+use crate::net::UpdateTickMessage;
+use fyrox::core::log::Log;
+
+/// Amount of compressed ticks, after which the compression ratio is logged.
+const LOG_INTERVAL: usize = 600;
+
+/// Serializes and compresses the given tick. Returns the compressed data and the size of the
+/// uncompressed data.
+pub fn compress_tick(tick: &UpdateTickMessage) -> bincode::Result<(Vec<u8>, usize)> {
+    let raw = bincode::serialize(tick)?;
+    Ok((lz4_flex::compress_prepend_size(&raw), raw.len()))
+}
+
+/// Decompresses and deserializes a tick, that was compressed by [`compress_tick`].
+pub fn decompress_tick(data: &[u8]) -> Result<UpdateTickMessage, String> {
+    let raw = lz4_flex::decompress_size_prepended(data).map_err(|err| err.to_string())?;
+    bincode::deserialize(&raw).map_err(|err| err.to_string())
+}
+
+/// Accumulates sizes of compressed ticks and periodically logs the compression ratio. It is used
+/// for quantized ticks as well (see [`crate::quantization`]).
+#[derive(Debug)]
+pub struct CompressionStats {
+    name: &'static str,
+    ticks: usize,
+    raw_bytes: usize,
+    compressed_bytes: usize,
+}
+
+impl CompressionStats {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            ticks: 0,
+            raw_bytes: 0,
+            compressed_bytes: 0,
+        }
+    }
+
+    pub fn record(&mut self, raw_size: usize, compressed_size: usize) {
+        self.ticks += 1;
+        self.raw_bytes += raw_size;
+        self.compressed_bytes += compressed_size;
+
+        if self.ticks >= LOG_INTERVAL {
+            Log::info(format!(
+                "{}: {} ticks, {} -> {} bytes ({:.1}% of the original size).",
+                self.name,
+                self.ticks,
+                self.raw_bytes,
+                self.compressed_bytes,
+                self.ratio() * 100.0
+            ));
+            *self = Self::new(self.name);
+        }
+    }
+
+    /// Returns the ratio of compressed size to raw size of the recorded ticks.
+    pub fn ratio(&self) -> f32 {
+        if self.raw_bytes == 0 {
+            1.0
+        } else {
+            self.compressed_bytes as f32 / self.raw_bytes as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{ActorState, NodeState, SoundState};
+    use fyrox::{
+        core::{
+            algebra::{UnitQuaternion, Vector3},
+            uuid::Uuid,
+        },
+        scene::base::SceneNodeId,
+    };
+
+    /// Returns a tick of a busy level: hundreds of moving rigid bodies, a few sounds and actors.
+    fn busy_tick() -> UpdateTickMessage {
+        let id = |i: usize| SceneNodeId(Uuid::from_u128(i as u128));
+        UpdateTickMessage {
+            nodes: (0..500)
+                .map(|i| NodeState {
+                    node: id(i),
+                    position: Vector3::new(i as f32 * 0.5, 2.0, (i % 20) as f32),
+                    rotation: UnitQuaternion::from_euler_angles(0.0, i as f32 * 0.01, 0.0),
+                    scale: None,
+                })
+                .collect(),
+            quantized_nodes: None,
+            sounds: (0..20)
+                .map(|i| SoundState {
+                    node: id(1000 + i),
+                    is_playing: i % 2 == 0,
+                })
+                .collect(),
+            actors: (0..16)
+                .map(|i| ActorState {
+                    node: id(2000 + i),
+                    grounded: true,
+                    ..Default::default()
+                })
+                .collect(),
+            match_timer: Some(120.0),
+            sudden_death: false,
+            level_time: 30.0,
+            server_time: 1234.5,
+            sequence: 42,
+        }
+    }
+
+    #[test]
+    fn busy_tick_is_smaller_after_compression() {
+        let tick = busy_tick();
+        let (compressed, raw_size) = compress_tick(&tick).unwrap();
+        let raw = bincode::serialize(&tick).unwrap();
+        assert_eq!(raw_size, raw.len());
+        assert!(
+            compressed.len() < raw_size,
+            "{} -> {} bytes",
+            raw_size,
+            compressed.len()
+        );
+
+        let decompressed = decompress_tick(&compressed).unwrap();
+        assert_eq!(bincode::serialize(&decompressed).unwrap(), raw);
+    }
+}
@@ -0,0 +1,87 @@
+//! Custom messages, that allow extensions of the game to send their own data over the existing
+//! connection without changing the protocol. A payload is an arbitrary array of bytes (serialized
+//! by the extension itself), that is marked with a tag. The tag is used to find a handler of the
+//! message on the receiving side, see [`crate::Game::register_custom_message_handler`].
+//!
+//! Custom messages are sent over the same reliable stream as the rest of the messages, so they're
+//! never lost and are received in the order of sending. However, they're handled when messages are
+//! read, which is not synchronized with update ticks: a custom message could be handled before or
+//! after the tick, that was made at the moment of sending. Payloads larger than
+//! [`MAX_CUSTOM_MESSAGE_SIZE`] are never sent, and are ignored when received.
+
+use fyrox::{core::log::Log, fxhash::FxHashMap, plugin::PluginContext};
+use std::fmt::{Debug, Formatter};
+
+/// Tag of a custom message, it must be unique per extension.
+pub type CustomMessageTag = u16;
+
+/// Max size (in bytes) of the payload of a custom message. Update ticks share the stream with
+/// custom messages, so large payloads delay the ticks.
+pub const MAX_CUSTOM_MESSAGE_SIZE: usize = 16 * 1024;
+
+/// Sender of a custom message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CustomMessageSender {
+    Server,
+    /// Index of the connection of the client on the server.
+    Client(usize),
+}
+
+pub type CustomMessageHandler = Box<dyn FnMut(CustomMessageSender, &[u8], &mut PluginContext)>;
+
+/// Returns `true` if the payload could be sent as a custom message.
+pub fn is_valid_payload(data: &[u8]) -> bool {
+    data.len() <= MAX_CUSTOM_MESSAGE_SIZE
+}
+
+#[derive(Default)]
+pub struct CustomMessageHandlers {
+    handlers: FxHashMap<CustomMessageTag, CustomMessageHandler>,
+}
+
+impl Debug for CustomMessageHandlers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CustomMessageHandlers({})", self.handlers.len())
+    }
+}
+
+impl CustomMessageHandlers {
+    /// Registers a handler of the messages with the given tag, a previous handler of the tag is
+    /// replaced.
+    pub fn register(&mut self, tag: CustomMessageTag, handler: CustomMessageHandler) {
+        if self.handlers.insert(tag, handler).is_some() {
+            Log::warn(format!(
+                "The handler of custom messages with tag {tag} was replaced."
+            ));
+        }
+    }
+
+    pub fn unregister(&mut self, tag: CustomMessageTag) {
+        self.handlers.remove(&tag);
+    }
+
+    /// Passes the payload to the handler of the tag. Messages without a handler and messages with
+    /// too large payloads are ignored.
+    pub fn handle(
+        &mut self,
+        sender: CustomMessageSender,
+        tag: CustomMessageTag,
+        data: &[u8],
+        ctx: &mut PluginContext,
+    ) {
+        if !is_valid_payload(data) {
+            Log::warn(format!(
+                "Custom message with tag {tag} from {sender:?} was ignored, its payload is too \
+                large ({} bytes).",
+                data.len()
+            ));
+            return;
+        }
+        match self.handlers.get_mut(&tag) {
+            Some(handler) => handler(sender, data, ctx),
+            None => Log::warn(format!(
+                "There's no handler of custom messages with tag {tag}."
+            )),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Debug information about actors, that is drawn next to every actor: speed, ground contact,
+//! in-air time, ragdoll state and, for bots, navigation state. It is toggled by the debug settings
+//! (see [`crate::DebugSettings::show_actor_info`]) and is off by default.
+
+use crate::{name_tag::OverlayContext, utils};
+use fyrox::{
+    core::{
+        algebra::{Vector2, Vector3},
+        color::Color,
+        pool::Handle,
+    },
+    fxhash::FxHashMap,
+    gui::{
+        brush::Brush,
+        canvas::CanvasBuilder,
+        font::Font,
+        message::MessageDirection,
+        text::{TextBuilder, TextMessage},
+        widget::{WidgetBuilder, WidgetMessage},
+        UiNode, UserInterface,
+    },
+    scene::node::Node,
+};
+use std::fmt::Write;
+
+/// Height (in meters) of a label above the center of an actor's body. It is lower than name tags,
+/// so they do not overlap.
+const LABEL_HEIGHT: f32 = 0.6;
+
+/// Amount of time (in seconds) between updates of the texts of labels. Positions of labels are
+/// updated every frame.
+const REFRESH_INTERVAL: f32 = 0.1;
+
+#[derive(Default, Debug)]
+pub struct ActorDebugOverlay {
+    /// Labels are positioned manually, so they're placed on a canvas.
+    canvas: Handle<UiNode>,
+    labels: FxHashMap<Handle<Node>, Handle<UiNode>>,
+    refresh_timer: f32,
+}
+
+fn format_vector(v: Vector3<f32>) -> String {
+    format!("({:.1}, {:.1}, {:.1})", v.x, v.y, v.z)
+}
+
+impl ActorDebugOverlay {
+    pub fn clear(&mut self, ui: &UserInterface) {
+        for (_, label) in self.labels.drain() {
+            ui.send_message(WidgetMessage::remove(label, MessageDirection::ToWidget));
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut OverlayContext, enabled: bool) {
+        let OverlayContext {
+            dt,
+            parent,
+            ref mut ui,
+            graph,
+            level,
+            resource_manager,
+        } = *ctx;
+        let Some((graph, camera)) = graph
+            .filter(|_| enabled)
+            .and_then(|g| utils::find_active_camera(g).map(|c| (g, c)))
+        else {
+            self.clear(ui);
+            return;
+        };
+
+        if self.canvas.is_none() {
+            self.canvas = CanvasBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+            ui.send_message(WidgetMessage::link(
+                self.canvas,
+                MessageDirection::ToWidget,
+                parent,
+            ));
+        }
+
+        self.labels.retain(|actor, label| {
+            let alive = level.actors.contains(actor);
+            if !alive {
+                ui.send_message(WidgetMessage::remove(*label, MessageDirection::ToWidget));
+            }
+            alive
+        });
+
+        self.refresh_timer -= dt;
+        let refresh = self.refresh_timer <= 0.0;
+        if refresh {
+            self.refresh_timer = REFRESH_INTERVAL;
+        }
+
+        let screen_size = ui.screen_size();
+        for view in level.iter_actors(graph) {
+            let Some(position) = view.position(graph) else {
+                continue;
+            };
+
+            let label = *self.labels.entry(view.handle).or_insert_with(|| {
+                let label = TextBuilder::new(
+                    WidgetBuilder::new()
+                        .with_visibility(false)
+                        .with_foreground(Brush::Solid(Color::GREEN)),
+                )
+                .with_font(resource_manager.request::<Font>("data/font.ttf"))
+                .with_font_size(14.0.into())
+                .build(&mut ui.build_ctx());
+                ui.send_message(WidgetMessage::link(
+                    label,
+                    MessageDirection::ToWidget,
+                    self.canvas,
+                ));
+                label
+            });
+
+            if refresh {
+                let mut text = format!(
+                    "{}\nSpeed: {:.2} m/s\nGrounded: {}\nIn air: {:.2} s\nRagdoll: {}\n\
+                    Stand up: {:.2} s",
+                    view.name(),
+                    view.speed(graph),
+                    view.is_grounded(graph),
+                    view.in_air_time(),
+                    view.is_ragdoll_enabled(graph),
+                    view.stand_up_timer()
+                );
+                if let Some(navigation) = view.bot_navigation(graph) {
+                    let _ = write!(
+                        text,
+                        "\nTarget: {}\nPath: {} point(s)\nNavmesh lock failures: {}\nStuck: {:.2} s\n\
+                        Agent radius: {:.2} m",
+                        format_vector(navigation.target),
+                        navigation.path_points,
+                        navigation.navmesh_lock_failures,
+                        navigation.stuck_time,
+                        navigation.agent_radius
+                    );
+                }
+                ui.send_message(TextMessage::text(label, MessageDirection::ToWidget, text));
+            }
+
+            let screen_position =
+                camera.project(position + Vector3::new(0.0, LABEL_HEIGHT, 0.0), screen_size);
+            ui.send_message(WidgetMessage::visibility(
+                label,
+                MessageDirection::ToWidget,
+                screen_position.is_some(),
+            ));
+            if let Some(screen_position) = screen_position {
+                let size = ui.node(label).actual_local_size();
+                ui.send_message(WidgetMessage::desired_position(
+                    label,
+                    MessageDirection::ToWidget,
+                    screen_position - Vector2::new(size.x * 0.5, size.y),
+                ));
+            }
+        }
+    }
+}
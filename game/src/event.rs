@@ -0,0 +1,64 @@
+//! Gameplay events, that could be emitted and observed by any script or system, instead of
+//! polling the state of the round.
+
+use fyrox::{core::pool::Handle, scene::node::Node};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    RoundStarted,
+    RoundEnded,
+    /// The time of the round has run out, the next qualified actor ends the round.
+    SuddenDeath,
+    /// The actor has reached a target and qualified at the given place.
+    ActorQualified {
+        actor: Handle<Node>,
+        place: usize,
+    },
+    /// The actor has not qualified for the next round.
+    ActorEliminated {
+        actor: Handle<Node>,
+    },
+    /// The actor was seriously hit by some body (hazard, projectile, other actor, etc.).
+    HazardHit {
+        actor: Handle<Node>,
+        hazard: Handle<Node>,
+    },
+    /// The grabber has grabbed the target.
+    GrabStarted {
+        grabber: Handle<Node>,
+        target: Handle<Node>,
+    },
+    /// The grabber tried to grab the target, but the target is already grabbed (or grabbing)
+    /// by someone else.
+    GrabRejected {
+        grabber: Handle<Node>,
+        target: Handle<Node>,
+    },
+}
+
+/// A per-frame queue of events. Events emitted during a frame become visible on the next frame
+/// and stay visible for exactly one frame, so every observer sees every event exactly once
+/// regardless of its update order.
+#[derive(Default, Debug)]
+pub struct EventQueue {
+    current: Vec<GameEvent>,
+    next: Vec<GameEvent>,
+}
+
+impl EventQueue {
+    pub fn emit(&mut self, event: GameEvent) {
+        self.next.push(event);
+    }
+
+    /// Returns events of the current frame.
+    pub fn events(&self) -> &[GameEvent] {
+        &self.current
+    }
+
+    /// Makes emitted events current, events of the previous frame are discarded. Must be called
+    /// once per frame.
+    pub fn swap(&mut self) {
+        self.current.clear();
+        std::mem::swap(&mut self.current, &mut self.next);
+    }
+}
@@ -0,0 +1,272 @@
+//! Grabbing of other actors. Grabs are resolved by the server once per frame in deterministic
+//! order of actors (see [`Level::ordered_actors`](crate::level::Level::ordered_actors)), so when
+//! two actors grab the same target in the same frame, only the first one gets it and the others
+//! are rejected. Every actor could be held by at most one grabber, which means there's at most
+//! one joint per target and conflicting constraints are never created.
+
+use crate::{
+    actor::{self, Actor},
+    event::{EventQueue, GameEvent},
+};
+use fyrox::{
+    core::pool::Handle,
+    fxhash::FxHashSet,
+    graph::{BaseSceneGraph, SceneGraph},
+    scene::{
+        base::BaseBuilder,
+        graph::Graph,
+        joint::{BallJoint, JointBuilder, JointParams},
+        node::Node,
+        transform::TransformBuilder,
+    },
+};
+
+/// Distance (in meters) between a grabber and its target, at which the grab breaks.
+const BREAK_DISTANCE: f32 = 3.0;
+
+#[derive(Debug)]
+struct Grab {
+    grabber: Handle<Node>,
+    target: Handle<Node>,
+    joint: Handle<Node>,
+}
+
+#[derive(Default, Debug)]
+pub struct Grabs {
+    grabs: Vec<Grab>,
+    /// Actors, whose grab was rejected. They must release the grab button before the next try,
+    /// so the rejection is reported once.
+    rejected: FxHashSet<Handle<Node>>,
+}
+
+struct Candidate {
+    rigid_body: Handle<Node>,
+    wants_grab: bool,
+    can_be_grabbed: bool,
+    grab_distance: f32,
+    team: Option<u32>,
+}
+
+fn candidate(handle: Handle<Node>, graph: &Graph) -> Option<Candidate> {
+    graph
+        .try_get_script_component_of::<Actor>(handle)
+        .map(|actor| Candidate {
+            rigid_body: actor.rigid_body,
+            wants_grab: actor.wants_grab,
+            can_be_grabbed: !actor.is_knocked_out() && !actor.is_eliminated(),
+            grab_distance: *actor.grab_distance,
+            team: actor.team,
+        })
+}
+
+fn distance(a: Handle<Node>, b: Handle<Node>, graph: &Graph) -> Option<f32> {
+    let a = graph.try_get(a)?.global_position();
+    let b = graph.try_get(b)?.global_position();
+    Some(a.metric_distance(&b))
+}
+
+impl Grabs {
+    /// Returns the actor, that is held by the given grabber.
+    pub fn target_of(&self, grabber: Handle<Node>) -> Option<Handle<Node>> {
+        self.grabs
+            .iter()
+            .find_map(|grab| (grab.grabber == grabber).then_some(grab.target))
+    }
+
+    /// Returns the actor, that holds the given target.
+    pub fn grabber_of(&self, target: Handle<Node>) -> Option<Handle<Node>> {
+        self.grabs
+            .iter()
+            .find_map(|grab| (grab.target == target).then_some(grab.grabber))
+    }
+
+    fn is_involved(&self, actor: Handle<Node>) -> bool {
+        self.grabs
+            .iter()
+            .any(|grab| grab.grabber == actor || grab.target == actor)
+    }
+
+    /// Releases finished grabs and resolves new ones. `actors` must be in deterministic order.
+    /// Teammates can't grab each other, unless `friendly_fire` is set.
+    pub fn update(
+        &mut self,
+        actors: &[Handle<Node>],
+        friendly_fire: bool,
+        events: &mut EventQueue,
+        graph: &mut Graph,
+    ) {
+        self.grabs.retain(|grab| {
+            let keep = match (
+                candidate(grab.grabber, graph),
+                candidate(grab.target, graph),
+            ) {
+                (Some(grabber), Some(target)) => {
+                    grabber.wants_grab
+                        && grabber.can_be_grabbed
+                        && target.can_be_grabbed
+                        && distance(grabber.rigid_body, target.rigid_body, graph)
+                            .map_or(false, |d| d < BREAK_DISTANCE)
+                }
+                _ => false,
+            };
+            if !keep && graph.is_valid_handle(grab.joint) {
+                graph.remove_node(grab.joint);
+            }
+            keep
+        });
+
+        self.rejected
+            .retain(|actor| candidate(*actor, graph).map_or(false, |actor| actor.wants_grab));
+
+        for grabber_handle in actors.iter().copied() {
+            if self.is_involved(grabber_handle) || self.rejected.contains(&grabber_handle) {
+                continue;
+            }
+
+            let Some(grabber) = candidate(grabber_handle, graph) else {
+                continue;
+            };
+            if !grabber.wants_grab || !grabber.can_be_grabbed {
+                continue;
+            }
+
+            // The closest actor in reach, ties are broken by the deterministic order.
+            let mut closest: Option<(Handle<Node>, Candidate, f32)> = None;
+            for target_handle in actors.iter().copied() {
+                if target_handle == grabber_handle {
+                    continue;
+                }
+                let Some(target) = candidate(target_handle, graph) else {
+                    continue;
+                };
+                let Some(d) = distance(grabber.rigid_body, target.rigid_body, graph) else {
+                    continue;
+                };
+                let is_teammate = grabber.team.is_some() && grabber.team == target.team;
+                if target.can_be_grabbed
+                    && (friendly_fire || !is_teammate)
+                    && d <= grabber.grab_distance
+                    && closest
+                        .as_ref()
+                        .map_or(true, |(_, _, closest)| d < *closest)
+                {
+                    closest = Some((target_handle, target, d));
+                }
+            }
+
+            let Some((target_handle, target, _)) = closest else {
+                continue;
+            };
+
+            if self.is_involved(target_handle) {
+                // Someone else was first, chained grabs are not allowed.
+                self.rejected.insert(grabber_handle);
+                events.emit(GameEvent::GrabRejected {
+                    grabber: grabber_handle,
+                    target: target_handle,
+                });
+                continue;
+            }
+
+            // Actors, that share the same rigid body, can't be connected by a joint.
+            let Some(anchor) =
+                actor::with_actor_pair(graph, grabber_handle, target_handle, |grabber, target| {
+                    (grabber.global_position() + target.global_position()).scale(0.5)
+                })
+            else {
+                continue;
+            };
+            let joint =
+                JointBuilder::new(BaseBuilder::new().with_local_transform(
+                    TransformBuilder::new().with_local_position(anchor).build(),
+                ))
+                .with_params(JointParams::BallJoint(BallJoint::default()))
+                .with_body1(grabber.rigid_body)
+                .with_body2(target.rigid_body)
+                .with_contacts_enabled(false)
+                .build(graph);
+
+            self.grabs.push(Grab {
+                grabber: grabber_handle,
+                target: target_handle,
+                joint,
+            });
+            events.emit(GameEvent::GrabStarted {
+                grabber: grabber_handle,
+                target: target_handle,
+            });
+        }
+
+        // Actors store the instance id of their target, so it could be replicated to clients.
+        for actor_handle in actors.iter().copied() {
+            let target_id = self
+                .target_of(actor_handle)
+                .and_then(|target| graph.try_get(target))
+                .map(|target| target.instance_id());
+            if let Some(actor) = graph.try_get_script_component_of_mut::<Actor>(actor_handle) {
+                actor.grabbing = target_id;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+    use fyrox::{
+        core::algebra::Vector3,
+        scene::{joint::Joint, pivot::PivotBuilder, rigidbody::RigidBodyBuilder},
+        script::Script,
+    };
+
+    fn add_actor(position: Vector3<f32>, wants_grab: bool, graph: &mut Graph) -> Handle<Node> {
+        let rigid_body = RigidBodyBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(position)
+                    .build(),
+            ),
+        )
+        .build(graph);
+        let bot = Bot {
+            actor: Actor {
+                rigid_body,
+                wants_grab,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        PivotBuilder::new(BaseBuilder::new().with_script(Script::new(bot))).build(graph)
+    }
+
+    #[test]
+    fn simultaneous_grabs_create_one_joint() {
+        let mut graph = Graph::new();
+        let first = add_actor(Vector3::new(-1.0, 0.0, 0.0), true, &mut graph);
+        let second = add_actor(Vector3::new(1.0, 0.0, 0.0), true, &mut graph);
+        let target = add_actor(Vector3::default(), false, &mut graph);
+        graph.update_hierarchical_data();
+
+        let mut grabs = Grabs::default();
+        let mut events = EventQueue::default();
+        grabs.update(&[first, second, target], true, &mut events, &mut graph);
+
+        let joints = graph
+            .linear_iter()
+            .filter(|node| node.cast::<Joint>().is_some())
+            .count();
+        assert_eq!(joints, 1);
+        assert_eq!(grabs.grabber_of(target), Some(first));
+        assert_eq!(grabs.target_of(second), None);
+        assert!(grabs.rejected.contains(&second));
+
+        // The rejected grabber does not retry while it holds the button.
+        grabs.update(&[first, second, target], true, &mut events, &mut graph);
+        let joints = graph
+            .linear_iter()
+            .filter(|node| node.cast::<Joint>().is_some())
+            .count();
+        assert_eq!(joints, 1);
+    }
+}
@@ -0,0 +1,273 @@
+//! Render-time interpolation of replicated transforms. Clients receive the state of the level with
+//! the network tick rate, which is usually lower than the frame rate, so setting transforms
+//! directly produces visible stepping. Instead, every node is smoothly moved from its previous
+//! state to the received one during the time between two snapshots.
+//!
+//! A snapshot could arrive before the previous interpolation has finished (jitter, lost packets).
+//! Such corrections are blended from the rendered transform as well, so nodes never jump, unless
+//! the error is larger than [`Interpolator::snap_distance`] (a teleport, for example), in which
+//! case smoothing would only make a node slide through the level.
+//!
+//! Nodes of actors, that are simulated locally (see [`crate::actor::Actor::is_remote`]), must never
+//! be interpolated, otherwise they would be dragged back to stale states and rubber-band. Such nodes
+//! are excluded with [`Interpolator::exclude`].
+//!
+//! The interpolator is always disabled on the host, including for actors of remote peers. The
+//! host's client shares the scene with the server, so every node there (remote actors as well)
+//! is moved by the local physics every frame, and update ticks carry nothing to interpolate.
+
+use fyrox::{
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        pool::Handle,
+    },
+    fxhash::{FxHashMap, FxHashSet},
+    scene::{graph::Graph, node::Node},
+};
+use std::time::Instant;
+
+#[derive(Debug)]
+struct Entry {
+    from_position: Vector3<f32>,
+    from_rotation: UnitQuaternion<f32>,
+    to_position: Vector3<f32>,
+    to_rotation: UnitQuaternion<f32>,
+}
+
+#[derive(Debug)]
+pub struct Interpolator {
+    entries: FxHashMap<Handle<Node>, Entry>,
+    /// Nodes, that are simulated locally and must not be interpolated.
+    excluded: FxHashSet<Handle<Node>>,
+    last_snapshot: Option<Instant>,
+    /// Smoothed amount of time (in seconds) between two snapshots.
+    interval: f32,
+    /// Amount of time (in seconds) passed since the last snapshot.
+    time: f32,
+    pub enabled: bool,
+    /// Min amount of time (in seconds) over which a correction is blended. Larger values hide
+    /// corrections better, but make replicated objects lag behind.
+    pub correction_time: f32,
+    /// Distance (in meters) between the rendered and the received position, beyond which a node
+    /// is moved to the received position instantly.
+    pub snap_distance: f32,
+}
+
+impl Default for Interpolator {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+            excluded: Default::default(),
+            last_snapshot: None,
+            interval: Self::DEFAULT_INTERVAL,
+            time: 0.0,
+            enabled: true,
+            correction_time: 0.0,
+            snap_distance: Self::DEFAULT_SNAP_DISTANCE,
+        }
+    }
+}
+
+impl Interpolator {
+    const DEFAULT_INTERVAL: f32 = 1.0 / 30.0;
+    const MAX_INTERVAL: f32 = 0.25;
+    pub const DEFAULT_SNAP_DISTANCE: f32 = 5.0;
+
+    /// Must be called when a new snapshot is received, before any [`Self::push`] call. Every node,
+    /// that is still moving, continues to its latest received state from where it is rendered
+    /// now, during the next interval.
+    pub fn begin_snapshot(&mut self, graph: &mut Graph) {
+        let snap_distance = self.snap_distance;
+        self.entries.retain(|handle, entry| {
+            let Some(node) = graph.try_get_mut(*handle) else {
+                return false;
+            };
+            let transform = node.local_transform_mut();
+            let position = **transform.position();
+            if position.metric_distance(&entry.to_position) > snap_distance {
+                transform.set_position(entry.to_position);
+                transform.set_rotation(entry.to_rotation);
+                return false;
+            }
+            entry.from_position = position;
+            entry.from_rotation = **transform.rotation();
+            true
+        });
+
+        let now = Instant::now();
+        if let Some(last_snapshot) = self.last_snapshot {
+            let interval = now
+                .duration_since(last_snapshot)
+                .as_secs_f32()
+                .min(Self::MAX_INTERVAL);
+            // Network jitter makes intervals uneven, smooth them out a bit.
+            self.interval += (interval - self.interval) * 0.2;
+        }
+        self.last_snapshot = Some(now);
+        self.time = 0.0;
+    }
+
+    /// Starts interpolation of the given node from its current transform to the given one.
+    /// Excluded nodes are ignored.
+    pub fn push(
+        &mut self,
+        handle: Handle<Node>,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+        graph: &Graph,
+    ) {
+        if self.is_excluded(handle) {
+            return;
+        }
+        let Some(node) = graph.try_get(handle) else {
+            return;
+        };
+        let transform = node.local_transform();
+        if transform.position().metric_distance(&position) > self.snap_distance {
+            self.entries.insert(
+                handle,
+                Entry {
+                    from_position: position,
+                    from_rotation: rotation,
+                    to_position: position,
+                    to_rotation: rotation,
+                },
+            );
+            return;
+        }
+        self.entries.insert(
+            handle,
+            Entry {
+                from_position: **transform.position(),
+                from_rotation: **transform.rotation(),
+                to_position: position,
+                to_rotation: rotation,
+            },
+        );
+    }
+
+    /// Stops the interpolation of the given node. Must be called when the node is removed.
+    pub fn remove(&mut self, handle: Handle<Node>) {
+        self.entries.remove(&handle);
+        self.excluded.remove(&handle);
+    }
+
+    /// Excludes the given node from the interpolation, its state is driven locally.
+    pub fn exclude(&mut self, handle: Handle<Node>) {
+        self.entries.remove(&handle);
+        self.excluded.insert(handle);
+    }
+
+    pub fn is_excluded(&self, handle: Handle<Node>) -> bool {
+        self.excluded.contains(&handle)
+    }
+
+    /// Moves every node to its latest received state and stops the interpolation.
+    pub fn finish(&mut self, graph: &mut Graph) {
+        for (handle, entry) in self.entries.drain() {
+            if let Some(node) = graph.try_get_mut(handle) {
+                let transform = node.local_transform_mut();
+                transform.set_position(entry.to_position);
+                transform.set_rotation(entry.to_rotation);
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, graph: &mut Graph) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        self.time += dt;
+        let duration = self.interval.max(self.correction_time);
+        let t = (self.time / duration.max(f32::EPSILON)).min(1.0);
+
+        for (handle, entry) in self.entries.iter() {
+            if let Some(node) = graph.try_get_mut(*handle) {
+                let transform = node.local_transform_mut();
+                transform.set_position(entry.from_position.lerp(&entry.to_position, t));
+                // Slerp is undefined for opposite rotations, jump straight to the target then.
+                let rotation = entry
+                    .from_rotation
+                    .try_slerp(&entry.to_rotation, t, f32::EPSILON)
+                    .unwrap_or(entry.to_rotation);
+                transform.set_rotation(rotation);
+            }
+        }
+
+        if t >= 1.0 {
+            self.entries.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fyrox::scene::{base::BaseBuilder, pivot::PivotBuilder};
+
+    #[test]
+    fn local_actor_is_not_interpolated() {
+        let mut graph = Graph::new();
+        let local = PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let remote = PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+
+        let mut interpolator = Interpolator::default();
+        interpolator.exclude(local);
+        interpolator.begin_snapshot(&mut graph);
+        let target = Vector3::new(1.0, 0.0, 0.0);
+        for handle in [local, remote] {
+            interpolator.push(handle, target, UnitQuaternion::identity(), &graph);
+        }
+        assert!(!interpolator.entries.contains_key(&local));
+        assert!(interpolator.entries.contains_key(&remote));
+
+        interpolator.update(Interpolator::MAX_INTERVAL, &mut graph);
+        assert_eq!(**graph[remote].local_transform().position(), target);
+        assert_eq!(
+            **graph[local].local_transform().position(),
+            Vector3::default()
+        );
+    }
+
+    #[test]
+    fn correction_converges_without_overshoot() {
+        let mut graph = Graph::new();
+        // The node is rendered at a mispredicted position.
+        let node = PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+        graph[node]
+            .local_transform_mut()
+            .set_position(Vector3::new(2.0, 0.0, 0.0));
+
+        let mut interpolator = Interpolator {
+            correction_time: 0.2,
+            ..Default::default()
+        };
+        let server_position = Vector3::new(1.0, 0.0, 0.0);
+        interpolator.begin_snapshot(&mut graph);
+        interpolator.push(node, server_position, UnitQuaternion::identity(), &graph);
+
+        let mut previous = 2.0;
+        for _ in 0..30 {
+            interpolator.update(1.0 / 60.0, &mut graph);
+            let x = graph[node].local_transform().position().x;
+            // The node moves towards the server state every frame and never passes it.
+            assert!(x <= previous && x >= server_position.x);
+            previous = x;
+        }
+        assert_eq!(**graph[node].local_transform().position(), server_position);
+        assert!(interpolator.entries.is_empty());
+    }
+
+    #[test]
+    fn large_error_snaps() {
+        let mut graph = Graph::new();
+        let node = PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let mut interpolator = Interpolator::default();
+        let target = Vector3::new(Interpolator::DEFAULT_SNAP_DISTANCE * 2.0, 0.0, 0.0);
+        interpolator.begin_snapshot(&mut graph);
+        interpolator.push(node, target, UnitQuaternion::identity(), &graph);
+        interpolator.update(1.0 / 60.0, &mut graph);
+        assert_eq!(**graph[node].local_transform().position(), target);
+    }
+}
@@ -1,65 +1,81 @@
-//! Jumper is platform that pushes actors (players or bots) up.
+//! Jumper is platform that pushes actors (players or bots) up, or in any other direction (bounce
+//! pads).
 
-use crate::{Bot, Game, Player};
+use crate::{utils, Game};
 use fyrox::{
     core::{
-        algebra::Vector3, reflect::prelude::*, type_traits::prelude::*,
+        algebra::Vector3, pool::Handle, reflect::prelude::*, type_traits::prelude::*,
         variable::InheritableVariable, visitor::prelude::*,
     },
-    scene::{collider::Collider, rigidbody::RigidBody},
+    scene::{node::Node, rigidbody::RigidBody},
     script::{ScriptContext, ScriptTrait},
 };
 use std::collections::HashSet;
 
-#[derive(Clone, Default, Debug, Visit, Reflect, TypeUuidProvider, ComponentProvider)]
+#[derive(Clone, Debug, Visit, Reflect, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "be8a29af-c10a-4518-a78b-955c8f48a8cd")]
 #[visit(optional)]
 pub struct Jumper {
+    #[reflect(description = "Speed (m/s) along the push direction, that is given to actors.")]
     push_force: InheritableVariable<f32>,
+    #[reflect(
+        description = "Direction of the push in local coordinates of the jumper, it rotates with \
+        the jumper. Velocity of an actor across the direction is preserved."
+    )]
+    push_direction: InheritableVariable<Vector3<f32>>,
+    /// Colliders of actors, that were touching the jumper on the previous update. Actors are
+    /// pushed only when they touch the jumper again, not while they rest on it.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    contacted_colliders: HashSet<Handle<Node>>,
+}
+
+impl Default for Jumper {
+    fn default() -> Self {
+        Self {
+            push_force: Default::default(),
+            push_direction: Vector3::y().into(),
+            contacted_colliders: Default::default(),
+        }
+    }
 }
 
 impl ScriptTrait for Jumper {
     fn on_update(&mut self, ctx: &mut ScriptContext) {
         let game = ctx.plugins.get::<Game>();
-        if game.is_client() {
+        if game.is_client() || game.is_paused() {
             return;
         }
 
-        if let Some(collider) = ctx.scene.graph[ctx.handle].cast::<Collider>() {
-            let mut contacted_colliders = HashSet::new();
-
-            for contact in collider.contacts(&ctx.scene.graph.physics) {
-                for actor in game.level.actors.iter() {
-                    let actor_script = ctx.scene.graph[*actor].script(0);
-
-                    if let Some(actor_collider) = actor_script
-                        .and_then(|s| s.query_component_ref::<Player>().map(|p| p.actor.collider))
-                        .or_else(|| {
-                            actor_script.and_then(|s| {
-                                s.query_component_ref::<Bot>().map(|b| b.actor.collider)
-                            })
-                        })
-                    {
-                        if contact.collider1 == actor_collider
-                            || contact.collider2 == actor_collider
-                        {
-                            contacted_colliders.insert(actor_collider);
-                        }
-                    }
-                }
+        let mut contacted_colliders = HashSet::new();
+        for contact in utils::actor_contacts(ctx.handle, &ctx.scene.graph) {
+            if game
+                .iter_actors(&ctx.scene.graph)
+                .any(|view| view.actor.collider == contact.other_collider)
+            {
+                contacted_colliders.insert(contact.other_collider);
             }
+        }
+
+        let direction = ctx.scene.graph[ctx.handle]
+            .global_transform()
+            .transform_vector(&*self.push_direction)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
 
-            for collider in contacted_colliders {
-                let parent = ctx.scene.graph[collider].parent();
-                if let Some(rigid_body) = ctx
-                    .scene
-                    .graph
-                    .try_get_mut(parent)
-                    .and_then(|p| p.cast_mut::<RigidBody>())
-                {
-                    let lin_vel = rigid_body.lin_vel();
-                    rigid_body.set_lin_vel(Vector3::new(lin_vel.x, *self.push_force, lin_vel.z));
-                }
+        let previous = std::mem::replace(&mut self.contacted_colliders, contacted_colliders);
+        for collider in self.contacted_colliders.difference(&previous) {
+            let parent = ctx.scene.graph[*collider].parent();
+            if let Some(rigid_body) = ctx
+                .scene
+                .graph
+                .try_get_mut(parent)
+                .and_then(|p| p.cast_mut::<RigidBody>())
+            {
+                // Replace the velocity along the direction, so pushes don't stack with falling speed.
+                let lin_vel = rigid_body.lin_vel();
+                let lin_vel = lin_vel - direction.scale(lin_vel.dot(&direction));
+                rigid_body.set_lin_vel(lin_vel + direction.scale(*self.push_force));
             }
         }
     }
@@ -0,0 +1,171 @@
+//! Remappable keys of the actions of the player. Keys are stored as UI key codes, because they
+//! could be serialized along with the rest of the settings. Every key could be bound to one action
+//! at most.
+
+use fyrox::{
+    gui::message::KeyCode,
+    keyboard::{self, PhysicalKey},
+    utils::translate_key_to_ui,
+};
+use serde::{Deserialize, Serialize};
+
+/// An action of the player, that could be bound to a key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sprint,
+    Crouch,
+    Grab,
+    Respawn,
+    Scoreboard,
+    SpectatorCamera,
+    PhysicsDebug,
+}
+
+impl KeyAction {
+    pub const ALL: [Self; 12] = [
+        Self::MoveForward,
+        Self::MoveBackward,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::Jump,
+        Self::Sprint,
+        Self::Crouch,
+        Self::Grab,
+        Self::Respawn,
+        Self::Scoreboard,
+        Self::SpectatorCamera,
+        Self::PhysicsDebug,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MoveForward => "Move Forward",
+            Self::MoveBackward => "Move Backward",
+            Self::MoveLeft => "Move Left",
+            Self::MoveRight => "Move Right",
+            Self::Jump => "Jump",
+            Self::Sprint => "Sprint",
+            Self::Crouch => "Crouch",
+            Self::Grab => "Grab",
+            Self::Respawn => "Respawn",
+            Self::Scoreboard => "Scoreboard",
+            Self::SpectatorCamera => "Spectator Camera",
+            Self::PhysicsDebug => "Physics Debug",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub move_forward: KeyCode,
+    pub move_backward: KeyCode,
+    pub move_left: KeyCode,
+    pub move_right: KeyCode,
+    pub jump: KeyCode,
+    pub sprint: KeyCode,
+    pub crouch: KeyCode,
+    pub grab: KeyCode,
+    pub respawn: KeyCode,
+    pub scoreboard: KeyCode,
+    pub spectator_camera: KeyCode,
+    pub physics_debug: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            move_forward: KeyCode::KeyW,
+            move_backward: KeyCode::KeyS,
+            move_left: KeyCode::KeyA,
+            move_right: KeyCode::KeyD,
+            jump: KeyCode::Space,
+            sprint: KeyCode::ShiftLeft,
+            crouch: KeyCode::ControlLeft,
+            grab: KeyCode::KeyE,
+            respawn: KeyCode::KeyR,
+            scoreboard: KeyCode::Tab,
+            spectator_camera: KeyCode::KeyC,
+            physics_debug: KeyCode::F1,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: KeyAction) -> KeyCode {
+        match action {
+            KeyAction::MoveForward => self.move_forward,
+            KeyAction::MoveBackward => self.move_backward,
+            KeyAction::MoveLeft => self.move_left,
+            KeyAction::MoveRight => self.move_right,
+            KeyAction::Jump => self.jump,
+            KeyAction::Sprint => self.sprint,
+            KeyAction::Crouch => self.crouch,
+            KeyAction::Grab => self.grab,
+            KeyAction::Respawn => self.respawn,
+            KeyAction::Scoreboard => self.scoreboard,
+            KeyAction::SpectatorCamera => self.spectator_camera,
+            KeyAction::PhysicsDebug => self.physics_debug,
+        }
+    }
+
+    fn key_mut(&mut self, action: KeyAction) -> &mut KeyCode {
+        match action {
+            KeyAction::MoveForward => &mut self.move_forward,
+            KeyAction::MoveBackward => &mut self.move_backward,
+            KeyAction::MoveLeft => &mut self.move_left,
+            KeyAction::MoveRight => &mut self.move_right,
+            KeyAction::Jump => &mut self.jump,
+            KeyAction::Sprint => &mut self.sprint,
+            KeyAction::Crouch => &mut self.crouch,
+            KeyAction::Grab => &mut self.grab,
+            KeyAction::Respawn => &mut self.respawn,
+            KeyAction::Scoreboard => &mut self.scoreboard,
+            KeyAction::SpectatorCamera => &mut self.spectator_camera,
+            KeyAction::PhysicsDebug => &mut self.physics_debug,
+        }
+    }
+
+    /// Returns the action, that is bound to the given physical key, if any.
+    pub fn action(&self, key: PhysicalKey) -> Option<KeyAction> {
+        let PhysicalKey::Code(key) = key else {
+            return None;
+        };
+        let key = translate_key_to_ui(key);
+        KeyAction::ALL
+            .into_iter()
+            .find(|action| self.key(*action) == key)
+    }
+
+    /// Binds the key to the action. If the key is already bound to some other action, the
+    /// actions swap their keys, so a key never triggers two actions. Returns the other action.
+    pub fn bind(&mut self, action: KeyAction, key: keyboard::KeyCode) -> Option<KeyAction> {
+        let key = translate_key_to_ui(key);
+        let previous = self.key(action);
+        let other = KeyAction::ALL
+            .into_iter()
+            .find(|other| *other != action && self.key(*other) == key);
+        if let Some(other) = other {
+            *self.key_mut(other) = previous;
+        }
+        *self.key_mut(action) = key;
+        other
+    }
+
+    /// Returns a short name of the key of the action, for example `W` instead of `KeyW`.
+    pub fn key_name(&self, action: KeyAction) -> String {
+        let name = format!("{:?}", self.key(action));
+        match name
+            .strip_prefix("Key")
+            .or_else(|| name.strip_prefix("Digit"))
+        {
+            Some(short) if !short.is_empty() => short.to_string(),
+            _ => name,
+        }
+    }
+}
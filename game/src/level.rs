@@ -1,10 +1,22 @@
-use crate::actor::Actor;
+use crate::{
+    actor::{Actor, ActorView},
+    event::{EventQueue, GameEvent},
+    grab::Grabs,
+    level_config::{LevelConfig, TimeoutResolution, WinCondition},
+    objective::{ObjectiveProgress, TargetProgress},
+};
 use fyrox::{
-    core::{pool::Handle, visitor::prelude::*},
+    core::{algebra::Vector3, pool::Handle, visitor::prelude::*},
     fxhash::FxHashMap,
     graph::BaseSceneGraph,
     plugin::PluginContext,
-    scene::{graph::Graph, node::Node, Scene},
+    rand::{rngs::StdRng, SeedableRng},
+    scene::{
+        animation::{absm::AnimationBlendingStateMachine, AnimationPlayer},
+        graph::Graph,
+        node::Node,
+        Scene, SceneContainer,
+    },
 };
 use serde::{Deserialize, Serialize};
 use std::{cmp::Ordering, collections::HashSet, sync::mpsc::Sender};
@@ -15,6 +27,11 @@ pub struct LeaderBoardEntry {
     pub finished: bool,
     pub real_time_position: usize,
     pub finished_position: usize,
+    /// `true` if the actor did not qualify for the next round.
+    pub eliminated: bool,
+    /// Progress of the actor on the objectives of the level (see [`crate::objective`]).
+    #[visit(skip)]
+    pub objectives: Vec<ObjectiveProgress>,
 }
 
 #[derive(Debug)]
@@ -39,13 +56,63 @@ impl Leaderboard {
             .unwrap_or_default()
     }
 
-    pub fn finish(&mut self, actor: Handle<Node>) {
-        let prev_position = self
+    pub fn is_eliminated(&self, actor: Handle<Node>) -> bool {
+        self.entries
+            .get(&actor)
+            .map(|e| e.eliminated)
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if the actor is not racing anymore, because it has either finished or was
+    /// eliminated.
+    pub fn is_out(&self, actor: Handle<Node>) -> bool {
+        self.is_finished(actor) || self.is_eliminated(actor)
+    }
+
+    /// Returns `true` if at least one actor was eliminated and there's nobody left racing.
+    pub fn is_everyone_out(&self) -> bool {
+        self.entries.values().any(|e| e.eliminated)
+            && self.entries.values().all(|e| e.finished || e.eliminated)
+    }
+
+    /// Eliminates the actor, that has not finished yet. Returns `true` if the actor was eliminated
+    /// by this call.
+    pub fn eliminate(&mut self, actor: Handle<Node>) -> bool {
+        let entry = self
             .entries
-            .iter()
-            .min_by_key(|(_, v)| v.finished_position)
-            .map(|e| e.1.finished_position)
-            .unwrap_or_default();
+            .entry(actor)
+            .or_insert_with(|| LeaderBoardEntry {
+                actor,
+                ..Default::default()
+            });
+        if entry.finished || entry.eliminated {
+            return false;
+        }
+        entry.eliminated = true;
+        true
+    }
+
+    /// Returns amount of actors, that have qualified (i.e. finished).
+    pub fn qualified_count(&self) -> usize {
+        self.entries.values().filter(|e| e.finished).count()
+    }
+
+    /// Eliminates every actor, that has not qualified. Returns newly eliminated actors.
+    pub fn eliminate_unqualified(&mut self) -> Vec<Handle<Node>> {
+        let mut eliminated = Vec::new();
+        for entry in self.entries.values_mut() {
+            if !entry.finished && !entry.eliminated {
+                entry.eliminated = true;
+                eliminated.push(entry.actor);
+            }
+        }
+        eliminated
+    }
+
+    /// Marks the actor as finished. Returns the place of the actor, if it wasn't finished before.
+    pub fn finish(&mut self, actor: Handle<Node>) -> Option<usize> {
+        // Places are taken by finished actors only, those that are still racing have no place.
+        let place = self.qualified_count() + 1;
         let entry = self
             .entries
             .entry(actor)
@@ -54,7 +121,6 @@ impl Leaderboard {
                 ..Default::default()
             });
         if !entry.finished {
-            let place = prev_position + 1;
             entry.finished_position = place;
             entry.finished = true;
             if let Some(sender) = self.sender.as_ref() {
@@ -62,15 +128,12 @@ impl Leaderboard {
                     .send(LeaderBoardEvent::Finished { actor, place })
                     .unwrap();
             }
+            return Some(place);
         }
+        None
     }
 
-    pub fn update(
-        &mut self,
-        actors: &HashSet<Handle<Node>>,
-        finish_point: Handle<Node>,
-        graph: &Graph,
-    ) {
+    pub fn update(&mut self, actors: &[Handle<Node>], finish_point: Handle<Node>, graph: &Graph) {
         let Some(finish_point) = graph.try_get(finish_point).map(|n| n.global_position()) else {
             return;
         };
@@ -100,15 +163,82 @@ impl Leaderboard {
     }
 }
 
+fn sort_by_instance_id(nodes: &HashSet<Handle<Node>>, graph: &Graph) -> Vec<Handle<Node>> {
+    let mut nodes = nodes
+        .iter()
+        .filter_map(|handle| graph.try_get(*handle).map(|n| (n.instance_id().0, *handle)))
+        .collect::<Vec<_>>();
+    nodes.sort_by_key(|(id, _)| *id);
+    nodes.into_iter().map(|(_, handle)| handle).collect()
+}
+
+/// Returns the node nearest to the position along with the distance to it. Ties are broken by the
+/// deterministic order of the nodes, see [`Level::ordered_actors`].
+fn nearest_node(
+    nodes: &HashSet<Handle<Node>>,
+    position: Vector3<f32>,
+    graph: &Graph,
+) -> Option<(Handle<Node>, f32)> {
+    let mut nearest: Option<(Handle<Node>, f32)> = None;
+    for handle in sort_by_instance_id(nodes, graph) {
+        let distance = graph[handle].global_position().metric_distance(&position);
+        if nearest.map_or(true, |(_, nearest)| distance < nearest) {
+            nearest = Some((handle, distance));
+        }
+    }
+    nearest
+}
+
 #[derive(Visit, Debug)]
 pub struct Level {
     pub scene: Handle<Scene>,
     pub targets: HashSet<Handle<Node>>,
     pub start_points: HashSet<Handle<Node>>,
+    /// A set of all actors on the level. Iteration order of the set is not deterministic, so any
+    /// logic that assigns targets, resolves simultaneous events (finishing, qualification) or
+    /// breaks ties must use [`Self::ordered_actors`] instead. The set itself is fine for lookups
+    /// and order-independent processing (rendering, physics interactions, etc.).
     pub actors: HashSet<Handle<Node>>,
     pub respawners: HashSet<Handle<Node>>,
+    /// A node with [`LevelConfig`] script, could be unassigned.
+    pub config: Handle<Node>,
     pub leaderboard: Leaderboard,
-    pub match_timer: f32,
+    /// Remaining time (in seconds) of the round. The round is not limited in time if not set.
+    pub match_timer: Option<f32>,
+    /// Defines what happens when the match timer runs out.
+    #[visit(skip)]
+    pub timeout_resolution: TimeoutResolution,
+    /// Amount of qualified actors at the beginning of the sudden death, if it is active. The next
+    /// qualified actor ends the round.
+    #[visit(skip)]
+    sudden_death_qualified: Option<usize>,
+    /// `true` if the server has reported sudden death, clients do not track qualifications.
+    #[visit(skip)]
+    replicated_sudden_death: bool,
+    /// Seed of the match. Every random decision, that affects gameplay (not cosmetics), must be
+    /// made using [`Self::rng`], so matches with the same seed are reproducible.
+    pub seed: u64,
+    #[visit(skip)]
+    pub rng: StdRng,
+    /// Current wind force (see [`LevelConfig`]). It is server-side only: wind is applied to
+    /// physics on the server and clients get its effect through replicated node states.
+    #[visit(skip)]
+    pub wind: Vector3<f32>,
+    #[visit(skip)]
+    pub paused: bool,
+    /// Simulation time (in seconds) of the level, it does not advance while the level is paused.
+    /// Periodic obstacles derive their phase from it, see [`crate::cannon::Cannon`]. On clients
+    /// this time is replicated from the server.
+    #[visit(skip)]
+    pub time: f32,
+    #[visit(skip)]
+    paused_nodes: Vec<Handle<Node>>,
+    /// Active grabs between actors, they're resolved on the server only.
+    #[visit(skip)]
+    pub grabs: Grabs,
+    /// Targets reached by actors, they're tracked on the server only.
+    #[visit(skip)]
+    pub target_progress: TargetProgress,
 }
 
 impl Default for Level {
@@ -119,36 +249,420 @@ impl Default for Level {
             start_points: Default::default(),
             actors: Default::default(),
             respawners: Default::default(),
+            config: Default::default(),
             leaderboard: Default::default(),
-            match_timer: 15.0 * 60.0,
+            match_timer: None,
+            timeout_resolution: Default::default(),
+            sudden_death_qualified: None,
+            replicated_sudden_death: false,
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+            wind: Default::default(),
+            paused: false,
+            time: 0.0,
+            paused_nodes: Default::default(),
+            grabs: Default::default(),
+            target_progress: Default::default(),
         }
     }
 }
 
 impl Level {
-    pub fn update(&mut self, ctx: &PluginContext) {
+    pub fn new(scene: Handle<Scene>, seed: u64) -> Self {
+        Self {
+            scene,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            ..Default::default()
+        }
+    }
+
+    /// Iterates over all actors on the level. Actors, that do not have an actor script (for
+    /// example if they're being destroyed) are skipped.
+    pub fn iter_actors<'a>(&'a self, graph: &'a Graph) -> impl Iterator<Item = ActorView<'a>> {
+        self.actors.iter().filter_map(|handle| {
+            graph
+                .try_get_script_component_of::<Actor>(*handle)
+                .map(|actor| ActorView {
+                    handle: *handle,
+                    actor,
+                })
+        })
+    }
+
+    /// Returns `true` if there's at least one actor within the given radius (in meters) around the
+    /// position.
+    pub fn is_any_actor_within(&self, position: Vector3<f32>, radius: f32, graph: &Graph) -> bool {
+        self.iter_actors(graph).any(|view| {
+            graph.try_get(view.actor.rigid_body).map_or(false, |body| {
+                body.global_position().metric_distance(&position) <= radius
+            })
+        })
+    }
+
+    /// Returns handles of all actors sorted by their instance ids. Instance ids are the same on
+    /// the server and on every client, so the order is deterministic across the network and
+    /// between runs.
+    pub fn ordered_actors(&self, graph: &Graph) -> Vec<Handle<Node>> {
+        sort_by_instance_id(&self.actors, graph)
+    }
+
+    /// Returns the first target of the level in deterministic order. See [`Self::ordered_actors`]
+    /// for more info.
+    pub fn first_target(&self, graph: &Graph) -> Option<Handle<Node>> {
+        sort_by_instance_id(&self.targets, graph).first().cloned()
+    }
+
+    /// Returns handles of all start points in deterministic order. See [`Self::ordered_actors`]
+    /// for more info.
+    pub fn ordered_start_points(&self, graph: &Graph) -> Vec<Handle<Node>> {
+        sort_by_instance_id(&self.start_points, graph)
+    }
+
+    /// Returns the target nearest to the position and the distance to it, or `None` if the level
+    /// has no targets.
+    pub fn nearest_target(
+        &self,
+        position: Vector3<f32>,
+        graph: &Graph,
+    ) -> Option<(Handle<Node>, f32)> {
+        nearest_node(&self.targets, position, graph)
+    }
+
+    /// Returns the start point nearest to the position and the distance to it, or `None` if the
+    /// level has no start points.
+    pub fn nearest_start_point(
+        &self,
+        position: Vector3<f32>,
+        graph: &Graph,
+    ) -> Option<(Handle<Node>, f32)> {
+        nearest_node(&self.start_points, position, graph)
+    }
+
+    /// Returns the respawn zone (its center) nearest to the position and the distance to it, or
+    /// `None` if the level has no respawn zones.
+    pub fn nearest_respawner(
+        &self,
+        position: Vector3<f32>,
+        graph: &Graph,
+    ) -> Option<(Handle<Node>, f32)> {
+        nearest_node(&self.respawners, position, graph)
+    }
+
+    pub fn config<'a>(&self, graph: &'a Graph) -> Option<&'a LevelConfig> {
+        graph.try_get_script_of::<LevelConfig>(self.config)
+    }
+
+    /// Returns amount of actors, that qualify for the next round or `None` if everyone qualifies.
+    /// Every peer has the same level config, so the count is known to clients without
+    /// replication. The count can't exceed the amount of actors on the level.
+    pub fn qualifier_count(&self, graph: &Graph) -> Option<usize> {
+        self.config(graph)
+            .and_then(|config| config.qualifier_count())
+            .map(|count| count.min(self.actors.len()))
+    }
+
+    /// Returns a view of the actor, that is controlled by this instance of the game.
+    pub fn local_player<'a>(&'a self, graph: &'a Graph) -> Option<ActorView<'a>> {
+        self.iter_actors(graph).find(|view| view.is_local_player())
+    }
+
+    /// Stops (or resumes) physics and animations of the level. Scripts are expected to check the
+    /// `paused` flag by themselves.
+    pub fn set_paused(&mut self, paused: bool, scenes: &mut SceneContainer) {
+        if self.paused == paused {
+            return;
+        }
+
+        let Some(scene) = scenes.try_get_mut(self.scene) else {
+            return;
+        };
+
+        self.paused = paused;
+
+        scene.graph.physics.enabled.set_value_silent(!paused);
+
+        if paused {
+            for (handle, node) in scene.graph.pair_iter_mut() {
+                if node.is_enabled()
+                    && (node.cast::<AnimationPlayer>().is_some()
+                        || node.cast::<AnimationBlendingStateMachine>().is_some())
+                {
+                    node.set_enabled(false);
+                    self.paused_nodes.push(handle);
+                }
+            }
+        } else {
+            for handle in self.paused_nodes.drain(..) {
+                if let Some(node) = scene.graph.try_get_mut(handle) {
+                    node.set_enabled(true);
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, events: &mut EventQueue, ctx: &PluginContext) {
+        if self.paused {
+            return;
+        }
+
+        self.time += ctx.dt;
+
         if let Some(scene) = ctx.scenes.try_get(self.scene) {
-            self.match_timer = (self.match_timer - ctx.dt).max(0.0);
+            let was_ended = self.is_match_ended();
+            if let Some(match_timer) = self.match_timer.as_mut() {
+                *match_timer = (*match_timer - ctx.dt).max(0.0);
+            }
+            if !was_ended && self.is_match_ended() {
+                self.resolve_timeout(events);
+            }
 
             self.leaderboard.update(
-                &self.actors,
-                self.targets.iter().next().cloned().unwrap_or_default(),
+                &self.ordered_actors(&scene.graph),
+                self.first_target(&scene.graph).unwrap_or_default(),
                 &scene.graph,
             );
+            self.update_objectives(events, &scene.graph);
+
+            if let Some(qualifier_count) = self.qualifier_count(&scene.graph) {
+                if !self.is_match_ended() && self.leaderboard.qualified_count() >= qualifier_count {
+                    for actor in self.leaderboard.eliminate_unqualified() {
+                        events.emit(GameEvent::ActorEliminated { actor });
+                    }
+                    self.match_timer = Some(0.0);
+                }
+            }
+
+            if let Some(qualified) = self.sudden_death_qualified {
+                if !self.is_match_ended() && self.leaderboard.qualified_count() > qualified {
+                    for actor in self.leaderboard.eliminate_unqualified() {
+                        events.emit(GameEvent::ActorEliminated { actor });
+                    }
+                    self.match_timer = Some(0.0);
+                }
+            }
+
+            if !self.is_match_ended()
+                && self.config(&scene.graph).map_or(false, |config| {
+                    config.win_condition() == WinCondition::LastStanding
+                })
+            {
+                self.resolve_last_standing(events, &scene.graph);
+            }
+
+            // Single-life rounds end once every actor is either finished or eliminated.
+            if !self.is_match_ended() && self.leaderboard.is_everyone_out() {
+                self.match_timer = Some(0.0);
+            }
+
+            if !was_ended && self.is_match_ended() {
+                events.emit(GameEvent::RoundEnded);
+            }
+        }
+    }
+
+    /// Tracks progress of actors on the objectives of the level and qualifies actors, that have
+    /// completed all of them.
+    fn update_objectives(&mut self, events: &mut EventQueue, graph: &Graph) {
+        let objectives = self
+            .config(graph)
+            .map(|config| config.objectives().to_vec())
+            .unwrap_or_default();
+        if objectives.is_empty() {
+            return;
+        }
+
+        let targets = sort_by_instance_id(&self.targets, graph);
+        let actors = self.ordered_actors(graph);
+        for (actor, progress) in self
+            .target_progress
+            .update(&objectives, &targets, &actors, graph)
+        {
+            let is_complete =
+                !progress.is_empty() && progress.iter().all(ObjectiveProgress::is_complete);
+            if let Some(entry) = self.leaderboard.entries.get_mut(&actor) {
+                entry.objectives = progress;
+            }
+            if is_complete && !self.is_match_ended() && !self.leaderboard.is_out(actor) {
+                if let Some(place) = self.leaderboard.finish(actor) {
+                    events.emit(GameEvent::ActorQualified { actor, place });
+                }
+            }
+        }
+    }
+
+    pub fn update_grabs(
+        &mut self,
+        friendly_fire: bool,
+        events: &mut EventQueue,
+        graph: &mut Graph,
+    ) {
+        if self.paused {
+            return;
         }
+        let actors = self.ordered_actors(graph);
+        self.grabs.update(&actors, friendly_fire, events, graph);
     }
 
+    /// Sets the time limit of the round. Must be called on the server only, clients receive the
+    /// timer from it.
+    pub fn set_time_limit(&mut self, time_limit: Option<f32>, resolution: TimeoutResolution) {
+        self.match_timer = time_limit;
+        self.timeout_resolution = resolution;
+        self.sudden_death_qualified = None;
+    }
+
+    /// Ends the round, when only one actor is left racing after the others were eliminated. The
+    /// last actor wins, see [`WinCondition::LastStanding`].
+    fn resolve_last_standing(&mut self, events: &mut EventQueue, graph: &Graph) {
+        let racing = self
+            .ordered_actors(graph)
+            .into_iter()
+            .filter(|actor| !self.leaderboard.is_out(*actor))
+            .collect::<Vec<_>>();
+        let anyone_eliminated = self.leaderboard.entries.values().any(|e| e.eliminated);
+        if racing.len() != 1 || !anyone_eliminated {
+            return;
+        }
+        for actor in racing {
+            if let Some(place) = self.leaderboard.finish(actor) {
+                events.emit(GameEvent::ActorQualified { actor, place });
+            }
+        }
+        self.match_timer = Some(0.0);
+    }
+
+    /// Resolves the round, when its time has run out.
+    fn resolve_timeout(&mut self, events: &mut EventQueue) {
+        match self.timeout_resolution {
+            TimeoutResolution::QualifyEveryone => {
+                let mut racing = self
+                    .leaderboard
+                    .entries
+                    .values()
+                    .filter(|e| !e.finished && !e.eliminated)
+                    .map(|e| (e.real_time_position, e.actor))
+                    .collect::<Vec<_>>();
+                racing.sort();
+                for (_, actor) in racing {
+                    if let Some(place) = self.leaderboard.finish(actor) {
+                        events.emit(GameEvent::ActorQualified { actor, place });
+                    }
+                }
+            }
+            TimeoutResolution::SuddenDeath => {
+                if !self.leaderboard.is_everyone_out() {
+                    self.match_timer = None;
+                    self.sudden_death_qualified = Some(self.leaderboard.qualified_count());
+                    events.emit(GameEvent::SuddenDeath);
+                }
+            }
+        }
+    }
+
+    /// Starts the final countdown of the round (the first actor has finished).
     pub fn sudden_death(&mut self) {
-        if self.match_timer > 60.0 {
-            self.match_timer = 60.0;
+        // Sudden death of the timeout resolution ends on the next qualification by itself.
+        if self.sudden_death_qualified.is_none() {
+            self.match_timer = Some(self.match_timer.map_or(60.0, |timer| timer.min(60.0)));
         }
     }
 
+    /// Returns `true` if the round continues until the next qualification after its time has run
+    /// out (see [`TimeoutResolution::SuddenDeath`]).
+    pub fn is_sudden_death(&self) -> bool {
+        self.sudden_death_qualified.is_some() || self.replicated_sudden_death
+    }
+
+    /// Sets the sudden death state received from the server.
+    pub fn set_replicated_sudden_death(&mut self, sudden_death: bool) {
+        self.replicated_sudden_death = sudden_death;
+    }
+
     pub fn is_time_critical(&self) -> bool {
-        self.match_timer <= 60.0
+        self.match_timer.map_or(false, |timer| timer <= 60.0)
     }
 
     pub fn is_match_ended(&self) -> bool {
-        self.match_timer <= 0.0
+        self.match_timer.map_or(false, |timer| timer <= 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fyrox::scene::{base::BaseBuilder, pivot::PivotBuilder, transform::TransformBuilder};
+
+    fn add_node(position: Vector3<f32>, graph: &mut Graph) -> Handle<Node> {
+        PivotBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(position)
+                    .build(),
+            ),
+        )
+        .build(graph)
+    }
+
+    #[test]
+    fn nearest_start_point_and_respawner() {
+        let mut graph = Graph::new();
+        let far = add_node(Vector3::new(10.0, 0.0, 0.0), &mut graph);
+        let near = add_node(Vector3::new(-3.0, 0.0, 0.0), &mut graph);
+        let respawner = add_node(Vector3::new(0.0, 0.0, 5.0), &mut graph);
+        graph.update_hierarchical_data();
+
+        let level = Level {
+            start_points: [far, near].into_iter().collect(),
+            respawners: [respawner].into_iter().collect(),
+            ..Default::default()
+        };
+        let (handle, distance) = level
+            .nearest_start_point(Vector3::default(), &graph)
+            .unwrap();
+        assert_eq!(handle, near);
+        assert!((distance - 3.0).abs() < 1e-5);
+        assert_eq!(
+            level
+                .nearest_respawner(Vector3::default(), &graph)
+                .map(|(handle, _)| handle),
+            Some(respawner)
+        );
+    }
+
+    #[test]
+    fn nearest_node_of_empty_set_is_none() {
+        let graph = Graph::new();
+        let level = Level::default();
+        assert!(level
+            .nearest_start_point(Vector3::default(), &graph)
+            .is_none());
+        assert!(level
+            .nearest_respawner(Vector3::default(), &graph)
+            .is_none());
+    }
+
+    #[test]
+    fn finish_places_follow_finish_order() {
+        let mut leaderboard = Leaderboard::default();
+        let actors = (0..5).map(|i| Handle::new(i, 1)).collect::<Vec<_>>();
+        // Everyone is racing at first.
+        for actor in actors.iter() {
+            leaderboard.entries.insert(
+                *actor,
+                LeaderBoardEntry {
+                    actor: *actor,
+                    ..Default::default()
+                },
+            );
+        }
+
+        assert_eq!(leaderboard.finish(actors[3]), Some(1));
+        assert_eq!(leaderboard.finish(actors[0]), Some(2));
+        assert_eq!(leaderboard.finish(actors[4]), Some(3));
+        assert_eq!(leaderboard.finish(actors[0]), None);
+        assert_eq!(leaderboard.entries[&actors[0]].finished_position, 2);
+        assert_eq!(leaderboard.qualified_count(), 3);
+        assert!(!leaderboard.is_finished(actors[1]));
     }
 }
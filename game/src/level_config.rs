@@ -0,0 +1,475 @@
+//! Per-level settings, that override global defaults. There should be only one level config per
+//! level.
+
+use crate::{
+    actor::{Actor, ActorKind, ActorMessage},
+    level::Level,
+    music::MusicIntensity,
+    objective::TargetObjective,
+    Game,
+};
+use fyrox::{
+    core::{
+        algebra::Vector3, log::Log, reflect::prelude::*, type_traits::prelude::*,
+        variable::InheritableVariable, visitor::prelude::*,
+    },
+    fxhash::FxHashMap,
+    graph::{BaseSceneGraph, SceneGraph},
+    rand::{rngs::StdRng, Rng},
+    resource::texture::TextureResource,
+    scene::{
+        graph::Graph,
+        rigidbody::{RigidBody, RigidBodyType},
+        sound::SoundBufferResource,
+    },
+    script::{ScriptContext, ScriptDeinitContext, ScriptMessageSender, ScriptTrait},
+};
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumString, VariantNames};
+
+/// Default gravity magnitude (m/s²).
+pub const DEFAULT_GRAVITY: f32 = 9.81;
+
+/// Defines what happens when the time limit of a round runs out.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Visit,
+    Reflect,
+    TypeUuidProvider,
+    AsRefStr,
+    EnumString,
+    VariantNames,
+    Serialize,
+    Deserialize,
+)]
+#[type_uuid(id = "0d6f3a4e-71b2-4a8c-9e5d-2c7b1f8a6e34")]
+pub enum TimeoutResolution {
+    /// Every actor, that is still racing, qualifies in the order of their current positions.
+    #[default]
+    QualifyEveryone,
+    /// The round continues until the next actor qualifies, the rest are eliminated.
+    SuddenDeath,
+}
+
+/// Defines how the winners of a round are decided, besides the time limit.
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Visit,
+    Reflect,
+    TypeUuidProvider,
+    AsRefStr,
+    EnumString,
+    VariantNames,
+    Serialize,
+    Deserialize,
+)]
+#[type_uuid(id = "9b2e6c14-5f3a-4d87-b0e1-7a4c8d2f5e69")]
+pub enum WinCondition {
+    /// Actors qualify by completing the objectives of the level (or by reaching a finish trigger),
+    /// the round ends once enough of them have qualified.
+    #[default]
+    Qualification,
+    /// The round ends once only one actor is left racing, that actor wins. Actors still could
+    /// qualify as usual before that.
+    LastStanding,
+}
+
+#[derive(Clone, Debug, Visit, Reflect, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "5f0b0b9e-3d2a-4c1e-9a57-8c4b2f6e1d73")]
+#[visit(optional)]
+pub struct LevelConfig {
+    #[reflect(
+        description = "Gravity magnitude (m/s²) of the level. Gravity always points down.",
+        min_value = 0.0,
+        max_value = 100.0
+    )]
+    gravity: InheritableVariable<f32>,
+    #[reflect(
+        description = "Linear damping (air resistance) of every dynamic rigid body on the level, \
+        including actors.",
+        min_value = 0.0,
+        max_value = 10.0
+    )]
+    drag: InheritableVariable<f32>,
+    #[reflect(description = "An image, that is shown in the lobby when the level is selected.")]
+    preview_image: InheritableVariable<Option<TextureResource>>,
+    #[reflect(
+        description = "A music track, that is played in the lobby when the level is selected."
+    )]
+    lobby_music: InheritableVariable<Option<SoundBufferResource>>,
+    #[reflect(
+        description = "A music track, that is played before the round starts and after it ends. \
+        The running round music is used if not set."
+    )]
+    calm_music: InheritableVariable<Option<SoundBufferResource>>,
+    #[reflect(
+        description = "A music track, that is played while the round is running. The menu music is \
+        used if not set."
+    )]
+    running_music: InheritableVariable<Option<SoundBufferResource>>,
+    #[reflect(
+        description = "A music track, that is played in the final seconds of the round and in \
+        sudden death. The running round music is used if not set."
+    )]
+    tense_music: InheritableVariable<Option<SoundBufferResource>>,
+    #[reflect(
+        description = "Amount of actors, that qualify for the next round by reaching the target. \
+        The round ends once that many actors have qualified, the rest are eliminated. Everyone \
+        qualifies if not set."
+    )]
+    qualifier_count: InheritableVariable<Option<u32>>,
+    #[reflect(
+        description = "Objectives made of groups of targets. An actor qualifies once it has \
+        completed every objective. Rounds without objectives are finished with triggers."
+    )]
+    objectives: InheritableVariable<Vec<TargetObjective>>,
+    #[reflect(
+        description = "Time limit (in seconds) of a round. Rounds are not limited if not set. \
+        Could be overridden by the lobby.",
+        min_value = 1.0
+    )]
+    time_limit: InheritableVariable<Option<f32>>,
+    #[reflect(description = "Defines what happens when the time limit runs out.")]
+    timeout_resolution: InheritableVariable<TimeoutResolution>,
+    #[reflect(description = "Defines how the winners of a round are decided.")]
+    win_condition: InheritableVariable<WinCondition>,
+    #[reflect(description = "Whether players fall into ragdoll on the level or not.")]
+    player_ragdoll: InheritableVariable<bool>,
+    #[reflect(description = "Whether bots fall into ragdoll on the level or not.")]
+    bot_ragdoll: InheritableVariable<bool>,
+    #[reflect(
+        description = "Actors, that touch a respawn zone (for example, fall below the kill plane), \
+        are eliminated instead of respawn. Useful for single-life rounds."
+    )]
+    eliminate_on_fall: InheritableVariable<bool>,
+    #[reflect(
+        description = "Height (world Y coordinate) of the kill plane. Actors, that fall below it, \
+        are respawned at the nearest start point (or eliminated, see eliminate on fall). There's \
+        no kill plane if not set."
+    )]
+    kill_plane: InheritableVariable<Option<f32>>,
+    #[reflect(
+        description = "Relative velocity (in m/s) of a serious impact (cannon ball, hazard, etc.), \
+        that eliminates an actor instead of knocking it down. Impacts never eliminate if not set.",
+        min_value = 0.0
+    )]
+    elimination_impact_velocity: InheritableVariable<Option<f32>>,
+    #[reflect(description = "Direction of the wind, it is normalized automatically.")]
+    wind_direction: InheritableVariable<Vector3<f32>>,
+    #[reflect(
+        description = "Force (in Newtons) applied by the wind to every dynamic body. Light objects \
+        are affected more than heavy ones.",
+        min_value = 0.0
+    )]
+    wind_strength: InheritableVariable<f32>,
+    #[reflect(
+        description = "Max strength of random gusts, that are added to the wind strength.",
+        min_value = 0.0
+    )]
+    gust_strength: InheritableVariable<f32>,
+    #[reflect(
+        description = "Amount of time (in seconds) between changes of gust strength.",
+        min_value = 0.1
+    )]
+    gust_interval: InheritableVariable<f32>,
+    #[reflect(
+        description = "Fraction of the wind force, that is applied to airborne actors. Keep it \
+        small, so movement stays controllable.",
+        min_value = 0.0,
+        max_value = 1.0
+    )]
+    actor_wind_factor: InheritableVariable<f32>,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    gust_timer: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    gust: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    target_gust: f32,
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self {
+            gravity: DEFAULT_GRAVITY.into(),
+            drag: 0.0.into(),
+            preview_image: Default::default(),
+            lobby_music: Default::default(),
+            calm_music: Default::default(),
+            running_music: Default::default(),
+            tense_music: Default::default(),
+            qualifier_count: Default::default(),
+            objectives: Default::default(),
+            time_limit: Default::default(),
+            timeout_resolution: Default::default(),
+            win_condition: Default::default(),
+            player_ragdoll: true.into(),
+            bot_ragdoll: true.into(),
+            eliminate_on_fall: false.into(),
+            kill_plane: Default::default(),
+            elimination_impact_velocity: Default::default(),
+            wind_direction: Vector3::x().into(),
+            wind_strength: 0.0.into(),
+            gust_strength: 0.0.into(),
+            gust_interval: 2.0.into(),
+            actor_wind_factor: 0.1.into(),
+            gust_timer: 0.0,
+            gust: 0.0,
+            target_gust: 0.0,
+        }
+    }
+}
+
+impl LevelConfig {
+    pub fn gravity(&self) -> f32 {
+        self.gravity.clamp(0.0, 100.0)
+    }
+
+    pub fn drag(&self) -> f32 {
+        self.drag.clamp(0.0, 10.0)
+    }
+
+    /// Searches for a level config in the given graph. Useful to read the config of a level, that
+    /// is not instantiated (for example, from a model resource).
+    pub fn find(graph: &Graph) -> Option<&LevelConfig> {
+        graph
+            .linear_iter()
+            .find_map(|node| node.try_get_script_component::<LevelConfig>())
+    }
+
+    pub fn preview_image(&self) -> Option<TextureResource> {
+        (*self.preview_image).clone()
+    }
+
+    pub fn lobby_music(&self) -> Option<SoundBufferResource> {
+        (*self.lobby_music).clone()
+    }
+
+    /// Returns the music track of the round for the given intensity. Missing layers fall back to
+    /// the running round music, the menu music is played if there's none.
+    pub fn round_music(&self, intensity: MusicIntensity) -> Option<SoundBufferResource> {
+        let layer = match intensity {
+            MusicIntensity::Calm => &self.calm_music,
+            MusicIntensity::Energetic => &self.running_music,
+            MusicIntensity::Tense => &self.tense_music,
+        };
+        (**layer).clone().or_else(|| (*self.running_music).clone())
+    }
+
+    pub fn qualifier_count(&self) -> Option<usize> {
+        self.qualifier_count.map(|count| count.max(1) as usize)
+    }
+
+    pub fn objectives(&self) -> &[TargetObjective] {
+        &self.objectives
+    }
+
+    pub fn time_limit(&self) -> Option<f32> {
+        self.time_limit.map(|limit| limit.max(1.0))
+    }
+
+    pub fn timeout_resolution(&self) -> TimeoutResolution {
+        *self.timeout_resolution
+    }
+
+    pub fn win_condition(&self) -> WinCondition {
+        *self.win_condition
+    }
+
+    /// Returns `true` if actors of the given kind are allowed to fall into ragdoll.
+    pub fn is_ragdoll_enabled(&self, kind: ActorKind) -> bool {
+        match kind {
+            ActorKind::Bot => *self.bot_ragdoll,
+            ActorKind::Player | ActorKind::RemotePlayer => *self.player_ragdoll,
+        }
+    }
+
+    /// Returns `true` if actors, that touch a respawn zone, must be eliminated instead of respawn.
+    pub fn eliminates_on_fall(&self) -> bool {
+        *self.eliminate_on_fall
+    }
+
+    pub fn kill_plane(&self) -> Option<f32> {
+        *self.kill_plane
+    }
+
+    pub fn elimination_impact_velocity(&self) -> Option<f32> {
+        self.elimination_impact_velocity
+            .map(|velocity| velocity.max(0.0))
+    }
+
+    /// Applies the drag to the given rigid body, if it is dynamic.
+    pub fn apply_drag(&self, rigid_body: &mut RigidBody) {
+        if rigid_body.body_type() == RigidBodyType::Dynamic {
+            rigid_body.set_lin_damping(self.drag());
+        }
+    }
+
+    /// Updates gusts and returns the current wind force. Gusts are chosen by the level's random
+    /// number generator, so they're the same for the same match seed.
+    fn update_wind(&mut self, dt: f32, rng: &mut StdRng) -> Vector3<f32> {
+        if *self.gust_strength > 0.0 {
+            self.gust_timer -= dt;
+            if self.gust_timer <= 0.0 {
+                self.gust_timer = self.gust_interval.max(0.1);
+                self.target_gust = rng.gen_range(-1.0..=1.0) * *self.gust_strength;
+            }
+            // Smoothly blend gusts to prevent sudden jerks.
+            self.gust +=
+                (self.target_gust - self.gust) * (dt / self.gust_interval.max(0.1)).min(1.0);
+        } else {
+            self.gust = 0.0;
+        }
+
+        let direction = self
+            .wind_direction
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_default();
+        direction.scale((*self.wind_strength + self.gust).max(0.0))
+    }
+
+    /// Applies the wind force to every dynamic body of the level. Actors are affected only when
+    /// airborne and only by a fraction of the wind, the force is applied to their root body only,
+    /// so ragdoll limbs are not blown away separately.
+    fn apply_wind(&self, wind: Vector3<f32>, level: &Level, graph: &mut Graph) {
+        if wind == Vector3::default() {
+            return;
+        }
+
+        // Bodies of actor hierarchies map to `None`, except the root body of an actor.
+        let mut actor_bodies = FxHashMap::default();
+        for view in level.iter_actors(graph) {
+            actor_bodies.extend(graph.traverse_handle_iter(view.handle).map(|h| (h, None)));
+            actor_bodies.insert(view.actor.rigid_body, Some(view.actor.in_air_time > 0.0));
+        }
+
+        let actor_wind = wind.scale(self.actor_wind_factor.clamp(0.0, 1.0));
+        for (handle, node) in graph.pair_iter_mut() {
+            let Some(rigid_body) = node.cast_mut::<RigidBody>() else {
+                continue;
+            };
+
+            if rigid_body.body_type() != RigidBodyType::Dynamic {
+                continue;
+            }
+
+            match actor_bodies.get(&handle) {
+                Some(Some(true)) => rigid_body.apply_force(actor_wind),
+                Some(_) => (),
+                None => rigid_body.apply_force(wind),
+            }
+        }
+    }
+
+    /// Respawns (or eliminates) every actor, that has fallen below the kill plane. Runs on the
+    /// server only, the new positions are replicated to clients as usual.
+    fn check_kill_plane(&self, level: &Level, graph: &Graph, message_sender: &ScriptMessageSender) {
+        let Some(kill_plane) = self.kill_plane() else {
+            return;
+        };
+
+        for actor_handle in level.ordered_actors(graph) {
+            let Some(actor) = graph.try_get_script_component_of::<Actor>(actor_handle) else {
+                continue;
+            };
+            if actor.is_knocked_out() || actor.is_eliminated() {
+                continue;
+            }
+            let Some(rigid_body) = graph.try_get(actor.rigid_body) else {
+                continue;
+            };
+            let position = rigid_body.global_position();
+            if position.y >= kill_plane {
+                continue;
+            }
+
+            let message = if self.eliminates_on_fall() {
+                ActorMessage::Eliminate
+            } else {
+                let respawn_position = level
+                    .nearest_start_point(position, graph)
+                    .map(|(start_point, _)| graph[start_point].global_position())
+                    .unwrap_or_default();
+                ActorMessage::RespawnAt(respawn_position)
+            };
+            message_sender.send_to_target(actor_handle, message);
+        }
+    }
+
+    fn apply_physics(&self, graph: &mut Graph) {
+        graph
+            .physics
+            .gravity
+            .set_value_and_mark_modified(Vector3::new(0.0, -self.gravity(), 0.0));
+
+        for node in graph.linear_iter_mut() {
+            if let Some(rigid_body) = node.cast_mut::<RigidBody>() {
+                self.apply_drag(rigid_body);
+            }
+        }
+    }
+}
+
+impl ScriptTrait for LevelConfig {
+    fn on_init(&mut self, ctx: &mut ScriptContext) {
+        let level = &mut ctx.plugins.get_mut::<Game>().level;
+        if level.config.is_some() && level.config != ctx.handle {
+            Log::warn("There's more than one level config on the level! Only one will be used.");
+        }
+        level.config = ctx.handle;
+        Log::info(format!("Level config {:?} added!", ctx.handle));
+    }
+
+    fn on_start(&mut self, ctx: &mut ScriptContext) {
+        // Every peer runs this on its own copy of the level, so physics parameters are the same
+        // on the server and on clients without any replication.
+        self.apply_physics(&mut ctx.scene.graph);
+
+        // The timer is driven by the server and replicated to clients. The lobby could override
+        // the rules of the level.
+        let game = ctx.plugins.get_mut::<Game>();
+        if let (Some(server), true) = (game.server.as_ref(), game.level.config == ctx.handle) {
+            let time_limit = match server.time_limit {
+                Some(limit) if limit > 0.0 => Some(limit),
+                Some(_) => None,
+                None => self.time_limit(),
+            };
+            let resolution = server
+                .timeout_resolution
+                .unwrap_or_else(|| self.timeout_resolution());
+            game.level.set_time_limit(time_limit, resolution);
+        }
+    }
+
+    fn on_update(&mut self, ctx: &mut ScriptContext) {
+        let game = ctx.plugins.get_mut::<Game>();
+        if game.is_client() || game.is_paused() || game.level.config != ctx.handle {
+            return;
+        }
+
+        let wind = self.update_wind(ctx.dt, &mut game.level.rng);
+        game.level.wind = wind;
+        self.apply_wind(wind, &game.level, &mut ctx.scene.graph);
+        self.check_kill_plane(&game.level, &ctx.scene.graph, ctx.message_sender);
+    }
+
+    fn on_deinit(&mut self, ctx: &mut ScriptDeinitContext) {
+        let level = &mut ctx.plugins.get_mut::<Game>().level;
+        if level.config == ctx.node_handle {
+            level.config = Default::default();
+        }
+        Log::info(format!("Level config {:?} destroyed!", ctx.node_handle));
+    }
+}
@@ -1,7 +1,8 @@
 //! Game project.
 use fyrox::{
-    core::{log::Log, pool::Handle, reflect::prelude::*, visitor::prelude::*},
+    core::{algebra::Vector3, log::Log, pool::Handle, reflect::prelude::*, visitor::prelude::*},
     event::{ElementState, Event, WindowEvent},
+    graph::{BaseSceneGraph, SceneGraph},
     gui::{
         inspector::editors::{
             inspectable::InspectablePropertyEditorDefinition, PropertyEditorDefinitionContainer,
@@ -11,16 +12,37 @@ use fyrox::{
     },
     keyboard::{KeyCode, PhysicalKey},
     plugin::{Plugin, PluginContext, PluginRegistrationContext},
-    scene::Scene,
+    resource::model::ModelResourceExtension,
+    scene::{graph::Graph, node::Node, sound::SoundBuffer, Scene, SceneContainer},
     window::Fullscreen,
 };
 use std::path::Path;
 
 use crate::{
-    actor::Actor, bot::Bot, camera::CameraController, cannon::Cannon, client::Client,
-    jumper::Jumper, level::Level, menu::Menu, player::Player, respawn::RespawnMode,
-    respawn::Respawner, server::Server, settings::Settings, start::StartPoint, target::Target,
-    trigger::Action, trigger::Trigger,
+    actor::{Actor, ActorView, ImpactSources},
+    bot::Bot,
+    camera::CameraController,
+    cannon::Cannon,
+    client::{self, Client},
+    custom_message::{CustomMessageHandler, CustomMessageHandlers, CustomMessageTag},
+    event::{EventQueue, GameEvent},
+    jumper::Jumper,
+    key_bindings::KeyAction,
+    level::Level,
+    level_config::{LevelConfig, TimeoutResolution, WinCondition},
+    menu::Menu,
+    net::{InstanceDescriptor, ServerMessage},
+    objective::TargetObjective,
+    player::Player,
+    projectile::Projectile,
+    respawn::RespawnMode,
+    respawn::Respawner,
+    server::{self, Server},
+    settings::Settings,
+    start::{self, StartPoint},
+    target::Target,
+    trigger::Action,
+    trigger::Trigger,
 };
 pub use fyrox;
 
@@ -29,11 +51,27 @@ pub mod bot;
 pub mod camera;
 pub mod cannon;
 pub mod client;
+pub mod clock;
+pub mod compression;
+pub mod custom_message;
+pub mod debug_overlay;
+pub mod event;
+pub mod grab;
+pub mod interpolation;
 pub mod jumper;
+pub mod key_bindings;
 pub mod level;
+pub mod level_config;
+pub mod lobby;
 pub mod menu;
+pub mod music;
+pub mod name_tag;
 pub mod net;
+pub mod objective;
 pub mod player;
+pub mod projectile;
+pub mod quantization;
+pub mod render_scale;
 pub mod respawn;
 pub mod server;
 pub mod settings;
@@ -47,6 +85,32 @@ pub struct DebugSettings {
     pub show_paths: bool,
     pub show_physics: bool,
     pub disable_ragdoll: bool,
+    /// Draw speed, ground contact, ragdoll state, etc. next to every actor.
+    pub show_actor_info: bool,
+    /// Bots hold their positions and make no decisions, while the rest of the level (physics,
+    /// players, etc.) is simulated as usual.
+    pub freeze_ai: bool,
+    /// Draw ground probes of bots and ground contacts of actors (see [`PROBE_DRAW_DISTANCE`]).
+    pub draw_probes: bool,
+}
+
+/// Max distance (in meters) from the camera, at which ground probes and contacts are drawn, so
+/// crowded levels stay responsive with [`DebugSettings::draw_probes`].
+pub const PROBE_DRAW_DISTANCE: f32 = 30.0;
+
+impl DebugSettings {
+    /// Returns `true` if any debug geometry is drawn in the scene of the level.
+    pub fn draws_lines(&self) -> bool {
+        self.show_physics || self.show_paths || self.draw_probes
+    }
+
+    /// Returns `true` if ground probes at the given position should be drawn.
+    pub fn should_draw_probes(&self, position: Vector3<f32>, graph: &Graph) -> bool {
+        self.draw_probes
+            && utils::find_active_camera(graph).map_or(false, |camera| {
+                camera.global_position().metric_distance(&position) <= PROBE_DRAW_DISTANCE
+            })
+    }
 }
 
 #[derive(Reflect, Debug)]
@@ -58,6 +122,16 @@ pub struct Game {
     server: Option<Server>,
     client: Option<Client>,
     settings: Settings,
+    events: EventQueue,
+    custom_message_handlers: CustomMessageHandlers,
+    /// `true` if the game was started by the editor with a scene to play. There's no networking
+    /// in this mode, the level is simulated locally as in single-player.
+    editor_play: bool,
+    /// Instances, that must be instantiated locally, because there's no server to broadcast them.
+    local_instances: Vec<InstanceDescriptor>,
+    /// `true` if debug geometry could be left in the scene of the level, it is cleared once after
+    /// every debug drawing is turned off.
+    has_debug_lines: bool,
 }
 
 impl Visit for Game {
@@ -97,12 +171,159 @@ impl Game {
             server: None,
             client: None,
             settings: Settings::load(),
+            events: Default::default(),
+            custom_message_handlers: Default::default(),
+            editor_play: false,
+            local_instances: Default::default(),
+            has_debug_lines: false,
+        }
+    }
+
+    /// Instantiates the given objects on every peer. In editor play they're instantiated locally.
+    pub fn instantiate(&mut self, instances: Vec<InstanceDescriptor>) {
+        if let Some(server) = self.server.as_mut() {
+            server.broadcast_message_to_clients(ServerMessage::Instantiate(instances));
+        } else if self.editor_play {
+            self.local_instances.extend(instances);
+        }
+    }
+
+    /// Registers a handler of custom messages with the given tag, see [`crate::custom_message`].
+    /// The same handler is used for the messages from the server and from clients.
+    pub fn register_custom_message_handler(
+        &mut self,
+        tag: CustomMessageTag,
+        handler: CustomMessageHandler,
+    ) {
+        self.custom_message_handlers.register(tag, handler);
+    }
+
+    pub fn unregister_custom_message_handler(&mut self, tag: CustomMessageTag) {
+        self.custom_message_handlers.unregister(tag);
+    }
+
+    /// Sends a custom message to every client. Does nothing if there's no server.
+    pub fn broadcast_custom_message(&mut self, tag: CustomMessageTag, data: Vec<u8>) {
+        if let Some(server) = self.server.as_mut() {
+            server.broadcast_custom_message(tag, data);
+        }
+    }
+
+    /// Sends a custom message to the server. Does nothing if there's no client.
+    pub fn send_custom_message_to_server(&mut self, tag: CustomMessageTag, data: Vec<u8>) {
+        if let Some(client) = self.client.as_mut() {
+            client.send_custom_message(tag, data);
+        }
+    }
+
+    /// Spawns the local player for editor play, unless the level already has one.
+    fn spawn_editor_player(scene: Handle<Scene>, ctx: &mut PluginContext) {
+        let graph = &ctx.scenes[scene].graph;
+        if graph.linear_iter().any(|node| node.has_script::<Player>()) {
+            return;
+        }
+        let start_points = graph
+            .linear_iter()
+            .filter(|node| node.has_script::<StartPoint>())
+            .map(|node| node.global_position())
+            .collect::<Vec<_>>();
+        let position = start::positions_or_fallback(start_points)[0];
+
+        let player_prefab = match server::load_actor_prefab::<Player>(
+            server::PLAYER_PREFAB_PATH,
+            &ctx.resource_manager,
+        ) {
+            Ok(player_prefab) => player_prefab,
+            Err(reason) => {
+                Log::err(format!("Unable to spawn the player. {reason}"));
+                return;
+            }
+        };
+
+        let scene = &mut ctx.scenes[scene];
+        let root = player_prefab.instantiate(scene);
+        if let Some(actor) = scene.graph.try_get_script_component_of::<Actor>(root) {
+            let rigid_body = actor.rigid_body;
+            if let Some(rigid_body) = scene.graph.try_get_mut(rigid_body) {
+                rigid_body.local_transform_mut().set_position(position);
+            }
         }
     }
 
+    /// Emits a gameplay event, it will be visible to everyone on the next frame.
+    pub fn emit(&mut self, event: GameEvent) {
+        self.events.emit(event);
+    }
+
+    /// Returns gameplay events of the current frame. Scripts should process them in `on_update`,
+    /// every event is visible only during one frame.
+    pub fn events(&self) -> &[GameEvent] {
+        self.events.events()
+    }
+
     pub fn is_client(&self) -> bool {
         self.server.is_none() && self.client.is_some()
     }
+
+    pub fn is_paused(&self) -> bool {
+        self.level.paused
+    }
+
+    /// Pauses or resumes the simulation of the current level. The game could be paused only in
+    /// single-player mode, in multiplayer this method does nothing when `paused` is `true`.
+    pub fn set_paused(&mut self, paused: bool, scenes: &mut SceneContainer) {
+        if paused
+            && !self.editor_play
+            && !self.server.as_ref().map_or(false, |s| s.is_single_player())
+        {
+            return;
+        }
+
+        self.level.set_paused(paused, scenes);
+    }
+
+    /// Plays the given music track (with crossfading) instead of the music of the current state
+    /// of the game, until [`Self::stop_music`] is called. The track is looped and its volume is
+    /// controlled by the music volume setting.
+    pub fn play_music(&mut self, path: impl AsRef<Path>, ctx: &mut PluginContext) {
+        let music = ctx.resource_manager.request::<SoundBuffer>(path);
+        if let Some(menu) = self.menu.as_mut() {
+            menu.play_music(music, ctx.scenes);
+        }
+    }
+
+    /// Switches back from the track of [`Self::play_music`] to the music of the current state of
+    /// the game.
+    pub fn stop_music(&mut self, ctx: &mut PluginContext) {
+        if let Some(menu) = self.menu.as_mut() {
+            menu.stop_music(ctx.scenes);
+        }
+    }
+
+    /// Iterates over all actors of the current level. See [`Level::iter_actors`] for more info.
+    pub fn iter_actors<'a>(&'a self, graph: &'a Graph) -> impl Iterator<Item = ActorView<'a>> {
+        self.level.iter_actors(graph)
+    }
+
+    /// Returns the start point nearest to the position. See [`Level::nearest_start_point`] for
+    /// more info.
+    pub fn nearest_start_point(
+        &self,
+        position: Vector3<f32>,
+        graph: &Graph,
+    ) -> Option<(Handle<Node>, f32)> {
+        self.level.nearest_start_point(position, graph)
+    }
+
+    /// Returns the respawn zone nearest to the position. See [`Level::nearest_respawner`] for more
+    /// info.
+    pub fn nearest_respawn(
+        &self,
+        position: Vector3<f32>,
+        graph: &Graph,
+    ) -> Option<(Handle<Node>, f32)> {
+        self.level.nearest_respawner(position, graph)
+    }
 }
 
 impl Plugin for Game {
@@ -117,20 +338,34 @@ impl Plugin for Game {
             .add::<Respawner>("Respawner")
             .add::<Cannon>("Cannon")
             .add::<Trigger>("Trigger")
-            .add::<Jumper>("Jumper");
+            .add::<Jumper>("Jumper")
+            .add::<Projectile>("Projectile")
+            .add::<LevelConfig>("Level Config");
     }
 
     fn register_property_editors(&self) -> PropertyEditorDefinitionContainer {
         let container = PropertyEditorDefinitionContainer::empty();
         container.insert(InspectablePropertyEditorDefinition::<Actor>::new());
+        container.insert(InspectablePropertyEditorDefinition::<ImpactSources>::new());
+        container.insert(InspectablePropertyEditorDefinition::<TargetObjective>::new());
+        container.register_inheritable_vec_collection::<TargetObjective>();
         container.register_inheritable_enum::<RespawnMode, _>();
         container.register_inheritable_enum::<Action, _>();
+        container.register_inheritable_enum::<TimeoutResolution, _>();
+        container.register_inheritable_enum::<WinCondition, _>();
         container
     }
 
-    fn init(&mut self, _scene_path: Option<&str>, ctx: PluginContext) {
+    fn init(&mut self, scene_path: Option<&str>, ctx: PluginContext) {
         Log::info("Game started!");
 
+        // The editor passes the scene, that is being edited, to play it right away.
+        if let Some(scene_path) = scene_path {
+            Log::info(format!("Playing {scene_path} from the editor."));
+            self.editor_play = true;
+            ctx.async_scene_loader.request(scene_path);
+        }
+
         ctx.task_pool.spawn_plugin_task(
             UserInterface::load_from_file("data/menu.ui", ctx.resource_manager.clone()),
             |result, game: &mut Game, ctx| match result {
@@ -138,39 +373,120 @@ impl Plugin for Game {
                     *ctx.user_interfaces.first_mut() = menu;
                     let menu = Some(Menu::new(ctx, game));
                     game.menu = menu;
+                    if let (true, Some(menu)) = (game.editor_play, game.menu.as_ref()) {
+                        menu.set_menu_visibility(ctx.user_interfaces.first(), false);
+                    }
                 }
                 Err(e) => Log::err(format!("Unable to load main menu! Reason: {:?}", e)),
             },
         );
     }
 
-    fn on_deinit(&mut self, _context: PluginContext) {
+    fn on_deinit(&mut self, context: PluginContext) {
+        if let Some(menu) = self.menu.as_mut() {
+            menu.silence_music(context.scenes);
+        }
         Log::info("Game stopped!");
     }
 
     fn update(&mut self, ctx: &mut PluginContext) {
+        self.events.swap();
+
         if let Some(server) = self.server.as_mut() {
             server.accept_connections();
 
-            server.read_messages(self.level.scene, ctx);
-            server.update(&mut self.level, ctx);
+            server.read_messages(&self.level, &mut self.custom_message_handlers, ctx);
+            server.update(&mut self.level, &mut self.events, ctx);
+        } else if self.editor_play {
+            self.level.update(&mut self.events, ctx);
+            if let Some(scene) = ctx.scenes.try_get_mut(self.level.scene) {
+                self.level
+                    .update_grabs(true, &mut self.events, &mut scene.graph);
+            }
+            if !self.local_instances.is_empty() {
+                client::instantiate_objects(std::mem::take(&mut self.local_instances), ctx);
+            }
         }
 
         if let Some(client) = self.client.as_mut() {
-            client.read_messages(&mut self.level, self.menu.as_ref(), ctx);
+            // The host simulates the level itself, there's nothing to interpolate. That applies
+            // to actors of remote peers too: they are moved by the host's physics every frame.
+            client.interpolator.enabled =
+                self.settings.read().render_interpolation && self.server.is_none();
+            client.input_send_rate = self.settings.read().input_send_rate();
+            client.select_model(&self.settings.read().player_model);
+            client.interpolator.correction_time = self.settings.read().correction_smoothing();
+            client.interpolator.snap_distance = self.settings.read().snap_distance();
+            client.read_messages(
+                &mut self.level,
+                self.menu.as_ref(),
+                &mut self.custom_message_handlers,
+                ctx,
+            );
             client.update(ctx.dt);
+            if let Some(scene) = ctx.scenes.try_get_mut(self.level.scene) {
+                client.interpolator.update(ctx.dt, &mut scene.graph);
+            }
+
+            if client.is_timed_out() {
+                Log::err("Connection to the server was lost: no messages received in time.");
+                self.client = None;
+
+                if self.level.scene.is_some() {
+                    ctx.scenes.remove(self.level.scene);
+                    self.level = Default::default();
+                }
+
+                if let Some(menu) = self.menu.as_ref() {
+                    menu.set_menu_visibility(ctx.user_interfaces.first(), true);
+                    menu.set_main_menu_visibility(ctx.user_interfaces.first(), true);
+                }
+            }
         }
 
-        if let Some(scene) = ctx.scenes.try_get_mut(self.level.scene) {
+        // The scene is not touched at all, when there's nothing to draw or to clear.
+        let draws_lines = self.debug_settings.draws_lines();
+        let debug_scene = if draws_lines || self.has_debug_lines {
+            ctx.scenes.try_get_mut(self.level.scene)
+        } else {
+            None
+        };
+        if let Some(scene) = debug_scene {
             scene.drawing_context.clear_lines();
+            self.has_debug_lines = draws_lines;
 
             if self.debug_settings.show_physics {
                 scene.graph.physics.draw(&mut scene.drawing_context);
             }
+
+            if self.debug_settings.draw_probes {
+                for view in self.level.iter_actors(&scene.graph) {
+                    let Some(position) = view.position(&scene.graph) else {
+                        continue;
+                    };
+                    if self
+                        .debug_settings
+                        .should_draw_probes(position, &scene.graph)
+                    {
+                        utils::draw_ground_contacts(
+                            view.actor.collider,
+                            &scene.graph,
+                            &mut scene.drawing_context,
+                        );
+                    }
+                }
+            }
         }
 
         if let Some(menu) = self.menu.as_mut() {
-            menu.update(ctx, &self.server, &self.client, &mut self.level);
+            menu.update(
+                ctx,
+                &mut self.server,
+                &self.client,
+                &mut self.level,
+                &self.settings,
+                &self.debug_settings,
+            );
         }
     }
 
@@ -180,12 +496,33 @@ impl Plugin for Game {
             ..
         } = event
         {
+            // Keys, that are bound to actions in the settings, must not trigger anything else.
+            if let Some(menu) = self.menu.as_ref() {
+                if menu.capture_key(event, ctx.user_interfaces.first(), &mut self.settings) {
+                    return;
+                }
+            }
+            let action = self.settings.read().key_bindings.action(event.physical_key);
+            match action {
+                // The scoreboard is shown while its key is held.
+                Some(KeyAction::Scoreboard) => {
+                    if let Some(menu) = self.menu.as_mut() {
+                        menu.set_scoreboard_shown(
+                            ctx.user_interfaces.first(),
+                            event.state == ElementState::Pressed,
+                        );
+                    }
+                }
+                Some(KeyAction::PhysicsDebug)
+                    if event.state == ElementState::Pressed && !event.repeat =>
+                {
+                    self.debug_settings.show_physics = !self.debug_settings.show_physics
+                }
+                _ => (),
+            }
             if let PhysicalKey::Code(key_code) = event.physical_key {
                 if event.state == ElementState::Pressed {
                     match key_code {
-                        KeyCode::F1 => {
-                            self.debug_settings.show_physics = !self.debug_settings.show_physics
-                        }
                         KeyCode::F2 => {
                             self.debug_settings.show_paths = !self.debug_settings.show_paths
                         }
@@ -195,19 +532,48 @@ impl Plugin for Game {
                         }
                         KeyCode::Escape => {
                             if let Some(menu) = self.menu.as_ref() {
+                                let was_active = menu.is_active(ctx.user_interfaces.first());
                                 menu.switch_visibility(
                                     ctx.user_interfaces.first(),
                                     self.client.is_some(),
                                 );
+                                self.set_paused(!was_active, ctx.scenes);
                             }
                         }
                         KeyCode::F4 => {
-                            self.level.match_timer = 3.0;
+                            self.level.match_timer = Some(3.0);
+                        }
+                        KeyCode::F5 => {
+                            self.debug_settings.show_actor_info =
+                                !self.debug_settings.show_actor_info
+                        }
+                        KeyCode::F6 => {
+                            self.debug_settings.freeze_ai = !self.debug_settings.freeze_ai
+                        }
+                        KeyCode::F7 => {
+                            self.debug_settings.draw_probes = !self.debug_settings.draw_probes
                         }
                         _ => (),
                     }
                 }
             }
+        } else if let Event::WindowEvent {
+            event: WindowEvent::Focused(false),
+            ..
+        } = event
+        {
+            // The key release is never received without focus.
+            if let Some(menu) = self.menu.as_mut() {
+                menu.set_scoreboard_shown(ctx.user_interfaces.first(), false);
+            }
+            if self.settings.read().pause_on_focus_loss && !self.is_paused() {
+                self.set_paused(true, ctx.scenes);
+                if let Some(menu) = self.menu.as_ref() {
+                    if self.is_paused() && !menu.is_active(ctx.user_interfaces.first()) {
+                        menu.switch_visibility(ctx.user_interfaces.first(), self.client.is_some());
+                    }
+                }
+            }
         }
     }
 
@@ -256,10 +622,10 @@ impl Plugin for Game {
     ) {
         self.settings.read().apply_sound_volume(&ctx.scenes[scene]);
 
-        self.level = Level {
-            scene,
-            ..Default::default()
-        };
+        // The seed comes from the server along with the level, so every peer has the same one.
+        let seed = self.client.as_ref().map_or(0, |client| client.match_seed);
+        self.level = Level::new(scene, seed);
+        self.events.emit(GameEvent::RoundStarted);
 
         if let Some(menu) = self.menu.as_ref() {
             self.level.leaderboard.sender = Some(menu.sender.clone());
@@ -271,5 +637,8 @@ impl Plugin for Game {
         if let Some(client) = self.client.as_mut() {
             client.on_scene_loaded(self.server.is_some(), scene, ctx);
         }
+        if self.editor_play {
+            Self::spawn_editor_player(scene, ctx);
+        }
     }
 }
@@ -0,0 +1,277 @@
+//! Lobby (server) configuration and its named presets.
+
+use crate::{
+    bot::BotDifficulty,
+    level_config::TimeoutResolution,
+    server::{AutoStart, Server},
+};
+use fyrox::core::log::Log;
+use ron::ser::to_string_pretty;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+const PRESETS_PATH: &str = "lobby_presets.ron";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LobbyConfig {
+    /// Fill free start points with bots, `bot_count` is ignored if set.
+    pub add_bots: bool,
+    /// Exact amount of bots in a match.
+    pub bot_count: usize,
+    pub bot_difficulty: BotDifficulty,
+    /// Network snapshot rate (in Hz).
+    pub snapshot_rate: f32,
+    /// Movement (in meters), rotation (in radians) and scale change of a node, that are too small
+    /// to be sent to clients.
+    pub position_epsilon: f32,
+    pub rotation_epsilon: f32,
+    pub scale_epsilon: f32,
+    /// Levels to play, only the first one is used for now.
+    pub levels: Vec<PathBuf>,
+    /// Seed of matches, a random one is used for every match if not set.
+    pub seed: Option<u64>,
+    /// Amount of players, that starts the match automatically (after a countdown). Auto-start is
+    /// disabled if not set.
+    pub auto_start_players: Option<usize>,
+    /// Auto-start countdown is cancelled if amount of players drops below this value.
+    pub min_players: usize,
+    /// Duration (in seconds) of the auto-start countdown, so last-second joiners could make it.
+    pub auto_start_delay: f32,
+    /// Amount of teams, actors are split between them in the order of spawning. Less than two
+    /// teams means a free-for-all match.
+    pub team_count: u32,
+    /// Whether teammates can knock each other down. If not set, it is on for free-for-all matches
+    /// and off for team matches.
+    pub friendly_fire: Option<bool>,
+    /// Time limit (in seconds) of rounds, zero disables the limit. The limit of the level is used
+    /// if not set.
+    pub time_limit: Option<f32>,
+    /// What happens when the time limit runs out. The mode of the level is used if not set.
+    pub timeout_resolution: Option<TimeoutResolution>,
+    /// Whether players could wait for the respawn button after a fall.
+    pub allow_manual_respawn: bool,
+    /// Max amount of time (in seconds) a player could wait for manual respawn.
+    pub max_manual_respawn_wait: f32,
+    /// Compress update ticks. It helps internet hosts of levels with many moving objects, on LAN
+    /// the CPU cost is usually not worth it.
+    pub compress_ticks: bool,
+    /// Send positions and rotations of nodes with reduced precision (see [`crate::quantization`]).
+    /// It nearly halves the size of node updates, the full precision is useful to measure the
+    /// difference.
+    pub quantize_ticks: bool,
+    /// Max amount of time (in seconds) a client could lag behind, before update ticks are dropped
+    /// for it.
+    pub max_send_lag: f32,
+    /// Amount of time (in seconds) after which a client, that can't catch up, is disconnected.
+    pub backpressure_timeout: f32,
+    /// Assign start points in a random (but seeded) order every round, instead of the order of
+    /// the level.
+    pub shuffle_start_points: bool,
+    /// Start the next round automatically after the results are shown. Otherwise the host starts
+    /// it manually.
+    pub auto_advance: bool,
+    /// Amount of time (in seconds) the results of a match are shown before the next round.
+    pub results_duration: f32,
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        Self {
+            add_bots: true,
+            bot_count: 0,
+            bot_difficulty: BotDifficulty::Normal,
+            snapshot_rate: 60.0,
+            position_epsilon: Server::DEFAULT_POSITION_EPSILON,
+            rotation_epsilon: Server::DEFAULT_ROTATION_EPSILON,
+            scale_epsilon: Server::DEFAULT_SCALE_EPSILON,
+            levels: Default::default(),
+            seed: None,
+            auto_start_players: None,
+            min_players: 1,
+            auto_start_delay: 10.0,
+            team_count: 0,
+            friendly_fire: None,
+            time_limit: None,
+            timeout_resolution: None,
+            allow_manual_respawn: true,
+            max_manual_respawn_wait: Server::DEFAULT_MAX_MANUAL_RESPAWN_WAIT,
+            compress_ticks: false,
+            quantize_ticks: false,
+            max_send_lag: Server::DEFAULT_MAX_SEND_LAG,
+            backpressure_timeout: Server::DEFAULT_BACKPRESSURE_TIMEOUT,
+            shuffle_start_points: true,
+            auto_advance: true,
+            results_duration: Server::DEFAULT_RESULTS_DURATION,
+        }
+    }
+}
+
+impl LobbyConfig {
+    pub fn apply(&self, server: &mut Server) {
+        server.add_bots = self.add_bots;
+        server.bot_count = self.bot_count.min(Server::MAX_BOTS);
+        server.bot_difficulty = self.bot_difficulty;
+        server.set_snapshot_interval(1.0 / self.snapshot_rate.max(1.0));
+        server.position_epsilon = self.position_epsilon.max(0.0);
+        server.rotation_epsilon = self.rotation_epsilon.max(0.0);
+        server.scale_epsilon = self.scale_epsilon.max(0.0);
+        server.seed = self.seed;
+        server.auto_start = self.auto_start_players.map(|target_players| AutoStart {
+            target_players,
+            min_players: self.min_players,
+            grace_period: self.auto_start_delay,
+        });
+        server.team_count = self.team_count;
+        server.friendly_fire = self.friendly_fire.unwrap_or(self.team_count < 2);
+        server.time_limit = self.time_limit;
+        server.timeout_resolution = self.timeout_resolution;
+        server.allow_manual_respawn = self.allow_manual_respawn;
+        server.max_manual_respawn_wait = self.max_manual_respawn_wait.max(0.0);
+        server.compress_ticks = self.compress_ticks;
+        server.quantize_ticks = self.quantize_ticks;
+        server.max_send_lag = self.max_send_lag.max(0.0);
+        server.backpressure_timeout = self.backpressure_timeout.max(0.0);
+        server.shuffle_start_points = self.shuffle_start_points;
+        server.results_duration = self.auto_advance.then_some(self.results_duration.max(0.0));
+        server.playlist = self.levels.clone();
+    }
+
+    /// Removes levels, that are not in the list of available levels.
+    pub fn validate_levels(&mut self, available_levels: &[PathBuf]) {
+        self.levels.retain(|level| {
+            let exists = available_levels.contains(level);
+            if !exists {
+                Log::warn(format!(
+                    "Level {} from the lobby preset does not exist, it will be ignored.",
+                    level.display()
+                ));
+            }
+            exists
+        });
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LobbyPreset {
+    pub name: String,
+    pub config: LobbyConfig,
+    /// Built-in presets are never saved to disk.
+    #[serde(skip)]
+    pub built_in: bool,
+}
+
+impl LobbyPreset {
+    fn built_in(name: &str, config: LobbyConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            config,
+            built_in: true,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LobbyPresets {
+    pub presets: Vec<LobbyPreset>,
+}
+
+impl LobbyPresets {
+    /// Loads user presets from disk, built-in presets always go first.
+    pub fn load() -> Self {
+        let mut presets = vec![
+            LobbyPreset::built_in("Default", Default::default()),
+            LobbyPreset::built_in(
+                "Chaos",
+                LobbyConfig {
+                    add_bots: true,
+                    bot_difficulty: BotDifficulty::Hard,
+                    snapshot_rate: 30.0,
+                    ..Default::default()
+                },
+            ),
+            LobbyPreset::built_in(
+                "Competitive",
+                LobbyConfig {
+                    add_bots: false,
+                    bot_difficulty: BotDifficulty::Normal,
+                    snapshot_rate: 60.0,
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        match File::open(PRESETS_PATH) {
+            Ok(mut file) => {
+                let mut file_content = String::new();
+                match file.read_to_string(&mut file_content) {
+                    Ok(_) => match ron::from_str::<Vec<LobbyPreset>>(&file_content) {
+                        Ok(user_presets) => presets.extend(user_presets),
+                        Err(err) => Log::err(format!(
+                            "Unable to deserialize lobby presets. Reason {:?}",
+                            err
+                        )),
+                    },
+                    Err(err) => Log::err(format!(
+                        "Unable to read lobby presets file content. Reason {:?}",
+                        err
+                    )),
+                }
+            }
+            // It is fine to have no user presets.
+            Err(err) => Log::info(format!("No lobby presets were loaded. Reason {:?}", err)),
+        }
+
+        Self { presets }
+    }
+
+    /// Adds a new preset or replaces a user preset with the same name and saves user presets to
+    /// disk. Built-in presets can't be replaced.
+    pub fn add(&mut self, name: String, config: LobbyConfig) -> bool {
+        if let Some(existing) = self.presets.iter_mut().find(|p| p.name == name) {
+            if existing.built_in {
+                Log::warn(format!("Unable to overwrite built-in lobby preset {name}!"));
+                return false;
+            }
+            existing.config = config;
+        } else {
+            self.presets.push(LobbyPreset {
+                name,
+                config,
+                built_in: false,
+            });
+        }
+
+        self.save();
+
+        true
+    }
+
+    fn save(&self) {
+        let user_presets = self
+            .presets
+            .iter()
+            .filter(|p| !p.built_in)
+            .collect::<Vec<_>>();
+
+        match to_string_pretty(&user_presets, Default::default()) {
+            Ok(serialized) => match File::create(PRESETS_PATH) {
+                Ok(mut file) => {
+                    Log::verify(file.write_all(serialized.as_bytes()));
+                }
+                Err(err) => Log::err(format!(
+                    "Unable to write lobby presets file on disk. Reason {:?}",
+                    err
+                )),
+            },
+            Err(err) => Log::err(format!(
+                "Unable to serialize lobby presets. Reason {:?}",
+                err
+            )),
+        }
+    }
+}
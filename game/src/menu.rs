@@ -1,40 +1,64 @@
 use crate::{
-    actor::{Actor, ActorKind},
-    client::Client,
-    level::{LeaderBoardEvent, Level},
-    server::Server,
-    settings::Settings,
-    utils, Game,
+    actor::Actor,
+    camera::{RagdollCameraMode, SpectatorTransition},
+    client::{Client, RoundPhase},
+    debug_overlay::ActorDebugOverlay,
+    key_bindings::{KeyAction, KeyBindings},
+    level::{LeaderBoardEntry, LeaderBoardEvent, Level},
+    level_config::LevelConfig,
+    lobby::{LobbyConfig, LobbyPresets},
+    music::{MusicIntensity, MusicManager},
+    name_tag::{NameTags, OverlayContext},
+    player::Player,
+    render_scale::RenderScaler,
+    server::{self, Server},
+    settings::{Settings, SettingsData},
+    start::StartPoint,
+    utils, DebugSettings, Game,
 };
 use fyrox::{
     asset::manager::ResourceManager,
     core::visitor::prelude::*,
-    core::{log::Log, pool::Handle},
+    core::{color::Color, log::Log, pool::Handle},
     engine::GraphicsContext,
+    event::{ElementState, KeyEvent},
     graph::{BaseSceneGraph, SceneGraph},
     gui::{
         animation::AnimationPlayerMessage,
-        button::ButtonMessage,
-        check_box::CheckBoxMessage,
+        border::BorderBuilder,
+        brush::Brush,
+        button::{ButtonBuilder, ButtonMessage},
+        check_box::{CheckBox, CheckBoxBuilder, CheckBoxMessage},
+        dropdown_list::{DropdownListBuilder, DropdownListMessage},
         font::Font,
-        list_view::{ListView, ListViewMessage},
+        grid::{Column, GridBuilder, Row},
+        image::{Image, ImageBuilder, ImageMessage},
+        list_view::{ListViewBuilder, ListViewMessage},
         message::{MessageDirection, UiMessage},
-        scroll_bar::ScrollBarMessage,
+        messagebox::{MessageBoxBuilder, MessageBoxButtons, MessageBoxMessage, MessageBoxResult},
+        scroll_bar::{ScrollBarBuilder, ScrollBarMessage},
         selector::SelectorMessage,
+        stack_panel::StackPanelBuilder,
         text::{TextBuilder, TextMessage},
+        text_box::TextBoxBuilder,
         widget::{WidgetBuilder, WidgetMessage},
-        BuildContext, HorizontalAlignment, Thickness, UiNode, UserInterface, VerticalAlignment,
+        window::{WindowBuilder, WindowMessage, WindowTitle},
+        BuildContext, HorizontalAlignment, Orientation, Thickness, UiNode, UserInterface,
+        VerticalAlignment,
     },
+    keyboard::{KeyCode, PhysicalKey},
     plugin::PluginContext,
-    resource::model::Model,
-    scene::{graph::Graph, node::Node, Scene, SceneContainer},
+    resource::{model::Model, texture::TextureResource},
+    scene::{graph::Graph, node::Node, sound::SoundBufferResource, Scene, SceneContainer},
 };
 use std::{
+    cell::Cell,
     ffi::OsStr,
     fmt::Debug,
     net::ToSocketAddrs,
     path::PathBuf,
     sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
 };
 
 pub fn make_text_widget(
@@ -52,6 +76,189 @@ pub fn make_text_widget(
         .build(ctx)
 }
 
+/// Amount of seconds before the end of a round, that are announced.
+const FINAL_SECONDS: f32 = 10.0;
+
+fn make_slider(ctx: &mut BuildContext, value: f32, min: f32, max: f32) -> Handle<UiNode> {
+    ScrollBarBuilder::new(
+        WidgetBuilder::new()
+            .on_column(1)
+            .with_height(30.0)
+            .with_margin(Thickness::uniform(2.0)),
+    )
+    .with_min(min)
+    .with_max(max)
+    .with_step((max - min) / 100.0)
+    .with_value(value.clamp(min, max))
+    .build(ctx)
+}
+
+fn make_check_box(ctx: &mut BuildContext, checked: bool) -> Handle<UiNode> {
+    CheckBoxBuilder::new(
+        WidgetBuilder::new()
+            .on_column(1)
+            .with_width(30.0)
+            .with_height(30.0)
+            .with_margin(Thickness::uniform(2.0))
+            .with_horizontal_alignment(HorizontalAlignment::Left),
+    )
+    .checked(Some(checked))
+    .build(ctx)
+}
+
+/// Creates a row with a label on the left side and the given editor on the right side. The
+/// editor must be created with `on_column(1)`.
+fn make_settings_row(
+    ctx: &mut BuildContext,
+    name: &str,
+    resource_manager: &ResourceManager,
+    editor: Handle<UiNode>,
+) -> Handle<UiNode> {
+    let label = make_text_widget(ctx, name, resource_manager, HorizontalAlignment::Left);
+    GridBuilder::new(WidgetBuilder::new().with_child(label).with_child(editor))
+        .add_row(Row::auto())
+        .add_column(Column::stretch())
+        .add_column(Column::stretch())
+        .build(ctx)
+}
+
+/// Creates a button with the given text, that could be placed in a settings row. Returns the
+/// button and its text.
+fn make_settings_button(
+    ctx: &mut BuildContext,
+    text: &str,
+    resource_manager: &ResourceManager,
+) -> (Handle<UiNode>, Handle<UiNode>) {
+    let text = make_text_widget(ctx, text, resource_manager, HorizontalAlignment::Center);
+    let button = ButtonBuilder::new(
+        WidgetBuilder::new()
+            .on_column(1)
+            .with_height(30.0)
+            .with_margin(Thickness::uniform(2.0)),
+    )
+    .with_content(text)
+    .build(ctx);
+    (button, text)
+}
+
+fn make_text_box(
+    ctx: &mut BuildContext,
+    text: &str,
+    resource_manager: &ResourceManager,
+) -> Handle<UiNode> {
+    TextBoxBuilder::new(
+        WidgetBuilder::new()
+            .on_column(1)
+            .with_height(30.0)
+            .with_margin(Thickness::uniform(2.0)),
+    )
+    .with_text(text)
+    .with_font(resource_manager.request::<Font>("data/font.ttf"))
+    .with_font_size(28.0.into())
+    .with_vertical_text_alignment(VerticalAlignment::Center)
+    .build(ctx)
+}
+
+fn make_dropdown_list(
+    ctx: &mut BuildContext,
+    items: &[String],
+    selected: usize,
+    resource_manager: &ResourceManager,
+) -> Handle<UiNode> {
+    let items = items
+        .iter()
+        .map(|item| make_text_widget(ctx, item, resource_manager, HorizontalAlignment::Center))
+        .collect::<Vec<_>>();
+    DropdownListBuilder::new(
+        WidgetBuilder::new()
+            .on_column(1)
+            .with_height(30.0)
+            .with_margin(Thickness::uniform(2.0)),
+    )
+    .with_items(items)
+    .with_selected(selected)
+    .build(ctx)
+}
+
+/// Adds a panel with the given rows to the right side of the given menu. It is used for settings,
+/// that are not present in the menu layout.
+fn add_extra_settings_panel(
+    ui: &mut UserInterface,
+    menu: Handle<UiNode>,
+    rows: impl IntoIterator<Item = Handle<UiNode>>,
+) -> Handle<UiNode> {
+    add_settings_panel(ui, menu, HorizontalAlignment::Right, rows)
+}
+
+fn add_settings_panel(
+    ui: &mut UserInterface,
+    menu: Handle<UiNode>,
+    alignment: HorizontalAlignment,
+    rows: impl IntoIterator<Item = Handle<UiNode>>,
+) -> Handle<UiNode> {
+    let panel = StackPanelBuilder::new(
+        WidgetBuilder::new()
+            .with_width(500.0)
+            .with_horizontal_alignment(alignment)
+            .with_vertical_alignment(VerticalAlignment::Center)
+            .with_children(rows),
+    )
+    .build(&mut ui.build_ctx());
+    ui.send_message(WidgetMessage::link(panel, MessageDirection::ToWidget, menu));
+    panel
+}
+
+fn show_message_box(ui: &mut UserInterface, title: &str, text: &str) {
+    open_message_box(ui, title, text, MessageBoxButtons::Ok);
+}
+
+/// Opens a modal message box, that is removed when closed. The result is reported with
+/// [`MessageBoxMessage::Close`] from the returned widget.
+fn open_message_box(
+    ui: &mut UserInterface,
+    title: &str,
+    text: &str,
+    buttons: MessageBoxButtons,
+) -> Handle<UiNode> {
+    let message_box = MessageBoxBuilder::new(
+        WindowBuilder::new(WidgetBuilder::new().with_width(400.0).with_height(200.0))
+            .with_title(WindowTitle::text(title))
+            .with_remove_on_close(true)
+            .open(false),
+    )
+    .with_text(text)
+    .with_buttons(buttons)
+    .build(&mut ui.build_ctx());
+    ui.send_message(WindowMessage::open_modal(
+        message_box,
+        MessageDirection::ToWidget,
+        true,
+        true,
+    ));
+    message_box
+}
+
+/// Returns the background image of the menu and its default texture. The image is created behind
+/// every other widget if the menu layout has none.
+fn find_or_create_background(ui: &mut UserInterface) -> (Handle<UiNode>, Option<TextureResource>) {
+    let background = ui.find_handle_by_name_from_root("Background");
+    if let Some(image) = ui.try_get_of_type::<Image>(background) {
+        return (background, (*image.texture).clone());
+    }
+
+    let background = ImageBuilder::new(
+        WidgetBuilder::new()
+            .with_name("Background")
+            .with_visibility(false),
+    )
+    .build(&mut ui.build_ctx());
+    ui.send_message(WidgetMessage::lowermost(
+        background,
+        MessageDirection::ToWidget,
+    ));
+    (background, None)
+}
+
 fn set_visibility(ui: &UserInterface, pairs: &[(Handle<UiNode>, bool)]) {
     for (widget, visibility) in pairs {
         ui.send_message(WidgetMessage::visibility(
@@ -71,10 +278,33 @@ struct ServerMenu {
     start: Handle<UiNode>,
     server_address_input: Handle<UiNode>,
     add_bots_check_box: Handle<UiNode>,
+    bot_count_input: Handle<UiNode>,
+    shuffle_start_points_check_box: Handle<UiNode>,
+    auto_advance_check_box: Handle<UiNode>,
+    results_duration: Handle<UiNode>,
     server_address: String,
     level_selector: Handle<UiNode>,
     available_levels: Vec<PathBuf>,
     selected_level: Option<usize>,
+    snapshot_rate_selector: Handle<UiNode>,
+    seed_input: Handle<UiNode>,
+    /// `true` if the seed input contains something, that is not a number.
+    invalid_seed: bool,
+    auto_start_input: Handle<UiNode>,
+    auto_start_delay: Handle<UiNode>,
+    auto_start_text: Handle<UiNode>,
+    preset_selector: Handle<UiNode>,
+    preset_name_input: Handle<UiNode>,
+    save_preset: Handle<UiNode>,
+    preset_name: String,
+    /// A message box, that asks the host to start the match while some players are not ready.
+    force_start_confirmation: Handle<UiNode>,
+    #[visit(skip)]
+    config: LobbyConfig,
+    #[visit(skip)]
+    presets: LobbyPresets,
+    #[visit(skip)]
+    players_list_revision: Cell<Option<u64>>,
 }
 
 impl ServerMenu {
@@ -122,6 +352,155 @@ impl ServerMenu {
             true,
         ));
 
+        let snapshot_rate_names = Self::SNAPSHOT_RATES
+            .iter()
+            .map(|rate| format!("{rate} Hz"))
+            .collect::<Vec<_>>();
+        let snapshot_rate_selector = make_dropdown_list(
+            &mut ui.build_ctx(),
+            &snapshot_rate_names,
+            0,
+            resource_manager,
+        );
+        let snapshot_rate_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Snapshot Rate",
+            resource_manager,
+            snapshot_rate_selector,
+        );
+
+        // Empty seed means that a random one will be used for every match.
+        let seed_input = make_text_box(&mut ui.build_ctx(), "", resource_manager);
+        let seed_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Seed (empty - random)",
+            resource_manager,
+            seed_input,
+        );
+
+        // The count is used only if free start points aren't filled with bots.
+        let bot_count_input = make_text_box(
+            &mut ui.build_ctx(),
+            &LobbyConfig::default().bot_count.to_string(),
+            resource_manager,
+        );
+        let bot_count_row = make_settings_row(
+            &mut ui.build_ctx(),
+            &format!("Bot Count (max {})", Server::MAX_BOTS),
+            resource_manager,
+            bot_count_input,
+        );
+
+        // Empty amount of players means that the match is started manually.
+        let auto_start_input = make_text_box(&mut ui.build_ctx(), "", resource_manager);
+        let auto_start_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Auto-Start Players (empty - off)",
+            resource_manager,
+            auto_start_input,
+        );
+        let auto_start_delay = make_slider(
+            &mut ui.build_ctx(),
+            LobbyConfig::default().auto_start_delay,
+            0.0,
+            60.0,
+        );
+        let auto_start_delay_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Auto-Start Delay",
+            resource_manager,
+            auto_start_delay,
+        );
+        let auto_start_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "",
+            resource_manager,
+            HorizontalAlignment::Center,
+        );
+        let shuffle_start_points_check_box = make_check_box(
+            &mut ui.build_ctx(),
+            LobbyConfig::default().shuffle_start_points,
+        );
+        let shuffle_start_points_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Shuffle Start Points",
+            resource_manager,
+            shuffle_start_points_check_box,
+        );
+        let auto_advance_check_box =
+            make_check_box(&mut ui.build_ctx(), LobbyConfig::default().auto_advance);
+        let auto_advance_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Auto-Advance Rounds",
+            resource_manager,
+            auto_advance_check_box,
+        );
+        let results_duration = make_slider(
+            &mut ui.build_ctx(),
+            LobbyConfig::default().results_duration,
+            0.0,
+            60.0,
+        );
+        let results_duration_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Results Duration",
+            resource_manager,
+            results_duration,
+        );
+
+        let presets = LobbyPresets::load();
+        let preset_selector = make_dropdown_list(
+            &mut ui.build_ctx(),
+            &Self::preset_names(&presets),
+            0,
+            resource_manager,
+        );
+        let preset_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Preset",
+            resource_manager,
+            preset_selector,
+        );
+        let preset_name = "My Preset".to_string();
+        let preset_name_input = make_text_box(&mut ui.build_ctx(), &preset_name, resource_manager);
+        let preset_name_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Preset Name",
+            resource_manager,
+            preset_name_input,
+        );
+        let save_preset_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "Save Preset",
+            resource_manager,
+            HorizontalAlignment::Center,
+        );
+        let save_preset = ButtonBuilder::new(
+            WidgetBuilder::new()
+                .with_height(30.0)
+                .with_margin(Thickness::uniform(2.0)),
+        )
+        .with_content(save_preset_text)
+        .build(&mut ui.build_ctx());
+        add_extra_settings_panel(
+            ui,
+            self_handle,
+            [
+                bot_count_row,
+                snapshot_rate_row,
+                seed_row,
+                auto_start_row,
+                auto_start_delay_row,
+                shuffle_start_points_row,
+                auto_advance_row,
+                results_duration_row,
+                preset_row,
+                preset_name_row,
+                save_preset,
+                auto_start_text,
+            ],
+        );
+
         Self {
             self_handle,
             main_menu,
@@ -130,10 +509,216 @@ impl ServerMenu {
             start: ui.find_handle_by_name_from_root("SVStart"),
             server_address_input: ui.find_handle_by_name_from_root("SVServerAddress"),
             add_bots_check_box: ui.find_handle_by_name_from_root("SVAddBotsCheckBox"),
+            bot_count_input,
+            shuffle_start_points_check_box,
+            auto_advance_check_box,
+            results_duration,
             level_selector,
             server_address: "127.0.0.1:10001".to_string(),
             selected_level: available_levels.first().map(|_| 0),
             available_levels,
+            snapshot_rate_selector,
+            seed_input,
+            invalid_seed: false,
+            auto_start_input,
+            auto_start_delay,
+            auto_start_text,
+            preset_selector,
+            preset_name_input,
+            save_preset,
+            preset_name,
+            force_start_confirmation: Default::default(),
+            config: Default::default(),
+            presets,
+            players_list_revision: Default::default(),
+        }
+    }
+
+    /// Network snapshot rates (in Hz) that could be selected in the lobby.
+    const SNAPSHOT_RATES: [f32; 4] = [60.0, 30.0, 20.0, 10.0];
+
+    /// Applies lobby settings to a newly created server.
+    fn configure_server(&self, server: &mut Server) {
+        self.config.apply(server);
+    }
+
+    fn selected_level_path(&self) -> Option<&PathBuf> {
+        self.selected_level
+            .and_then(|index| self.available_levels.get(index))
+    }
+
+    /// Loads the selected level in background and previews its theme (background image and
+    /// music) in the lobby.
+    fn request_level_theme(&self, ctx: &mut PluginContext) {
+        let Some(path) = self.selected_level_path().cloned() else {
+            return;
+        };
+
+        ctx.task_pool.spawn_plugin_task(
+            ctx.resource_manager.request::<Model>(&path),
+            move |result, game: &mut Game, ctx| {
+                let Some(menu) = game.menu.as_mut() else {
+                    return;
+                };
+
+                // The selection could be changed while the level was loading.
+                if menu.server_menu.selected_level_path() != Some(&path) {
+                    return;
+                }
+
+                let (preview_image, lobby_music) = match result {
+                    Ok(model) => {
+                        let model = model.data_ref();
+                        let graph = &model.get_scene().graph;
+
+                        // Such levels are still playable, but the host should know about it
+                        // before starting the round.
+                        if !graph.linear_iter().any(|n| n.has_script::<StartPoint>()) {
+                            show_message_box(
+                                ctx.user_interfaces.first_mut(),
+                                "Warning",
+                                &format!(
+                                    "Level {} has no start points, every player will be \
+                                    spawned at the origin of the level.",
+                                    path.display()
+                                ),
+                            );
+                        }
+
+                        LevelConfig::find(graph)
+                            .map(|config| (config.preview_image(), config.lobby_music()))
+                            .unwrap_or_default()
+                    }
+                    Err(err) => {
+                        Log::err(format!(
+                            "Unable to load {} level to preview it. Reason: {:?}",
+                            path.display(),
+                            err
+                        ));
+                        Default::default()
+                    }
+                };
+
+                menu.set_level_theme(preview_image, lobby_music, ctx);
+            },
+        );
+    }
+
+    fn preset_names(presets: &LobbyPresets) -> Vec<String> {
+        presets.presets.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// Applies the preset with the given index to the lobby and syncs the UI with it.
+    fn apply_preset(&mut self, index: usize, ui: &UserInterface, server: &mut Option<Server>) {
+        let Some(preset) = self.presets.presets.get(index) else {
+            return;
+        };
+
+        let mut config = preset.config.clone();
+        config.validate_levels(&self.available_levels);
+
+        ui.send_message(CheckBoxMessage::checked(
+            self.add_bots_check_box,
+            MessageDirection::ToWidget,
+            Some(config.add_bots),
+        ));
+        ui.send_message(TextMessage::text(
+            self.bot_count_input,
+            MessageDirection::ToWidget,
+            config.bot_count.to_string(),
+        ));
+        if let Some(rate_index) = Self::SNAPSHOT_RATES
+            .iter()
+            .position(|rate| *rate == config.snapshot_rate)
+        {
+            ui.send_message(DropdownListMessage::selection(
+                self.snapshot_rate_selector,
+                MessageDirection::ToWidget,
+                Some(rate_index),
+            ));
+        }
+        if let Some(level_index) = config
+            .levels
+            .first()
+            .and_then(|level| self.available_levels.iter().position(|l| l == level))
+        {
+            ui.send_message(SelectorMessage::current(
+                self.level_selector,
+                MessageDirection::ToWidget,
+                Some(level_index),
+            ));
+        }
+
+        ui.send_message(TextMessage::text(
+            self.seed_input,
+            MessageDirection::ToWidget,
+            config.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+        ));
+        ui.send_message(TextMessage::text(
+            self.auto_start_input,
+            MessageDirection::ToWidget,
+            config
+                .auto_start_players
+                .map(|players| players.to_string())
+                .unwrap_or_default(),
+        ));
+        ui.send_message(ScrollBarMessage::value(
+            self.auto_start_delay,
+            MessageDirection::ToWidget,
+            config.auto_start_delay,
+        ));
+        ui.send_message(CheckBoxMessage::checked(
+            self.shuffle_start_points_check_box,
+            MessageDirection::ToWidget,
+            Some(config.shuffle_start_points),
+        ));
+        ui.send_message(CheckBoxMessage::checked(
+            self.auto_advance_check_box,
+            MessageDirection::ToWidget,
+            Some(config.auto_advance),
+        ));
+        ui.send_message(ScrollBarMessage::value(
+            self.results_duration,
+            MessageDirection::ToWidget,
+            config.results_duration,
+        ));
+
+        if let Some(server) = server {
+            config.apply(server);
+        }
+
+        self.config = config;
+        self.players_list_revision.set(None);
+    }
+
+    fn save_preset(&mut self, ctx: &mut PluginContext) {
+        let mut config = self.config.clone();
+        config.levels = self
+            .selected_level
+            .and_then(|i| self.available_levels.get(i))
+            .cloned()
+            .into_iter()
+            .collect();
+
+        if self.presets.add(self.preset_name.clone(), config) {
+            let items = Self::preset_names(&self.presets)
+                .iter()
+                .map(|name| {
+                    make_text_widget(
+                        &mut ctx.user_interfaces.first_mut().build_ctx(),
+                        name,
+                        ctx.resource_manager,
+                        HorizontalAlignment::Center,
+                    )
+                })
+                .collect::<Vec<_>>();
+            ctx.user_interfaces
+                .first()
+                .send_message(DropdownListMessage::items(
+                    self.preset_selector,
+                    MessageDirection::ToWidget,
+                    items,
+                ));
         }
     }
 
@@ -143,22 +728,46 @@ impl ServerMenu {
         message: &UiMessage,
         server: &mut Option<Server>,
     ) {
-        let ui = ctx.user_interfaces.first();
+        if let Some(ButtonMessage::Click) = message.data() {
+            if message.destination() == self.save_preset {
+                self.save_preset(ctx);
+                return;
+            }
+        }
 
         if let Some(ButtonMessage::Click) = message.data() {
             if message.destination() == self.start {
-                ui.send_message(WidgetMessage::visibility(
-                    self.self_handle,
-                    MessageDirection::ToWidget,
-                    false,
-                ));
+                let unready_players = server.as_ref().map_or(0, |s| s.unready_players());
+                if unready_players > 0 {
+                    self.force_start_confirmation = open_message_box(
+                        ctx.user_interfaces.first_mut(),
+                        "Players Not Ready",
+                        &format!(
+                            "{unready_players} player(s) are not ready yet. Start the game anyway?"
+                        ),
+                        MessageBoxButtons::YesNo,
+                    );
+                } else {
+                    self.start_game(ctx, server);
+                }
+                return;
+            }
+        }
 
-                if let Some(selected_level) = self.selected_level {
-                    if let Some(server) = server.as_mut() {
-                        server.start_game(&self.available_levels[selected_level]);
-                    }
+        if let Some(MessageBoxMessage::Close(result)) = message.data() {
+            if message.destination() == self.force_start_confirmation {
+                self.force_start_confirmation = Handle::NONE;
+                if matches!(result, MessageBoxResult::Yes) {
+                    self.start_game(ctx, server);
                 }
-            } else if message.destination() == self.back {
+                return;
+            }
+        }
+
+        let ui = ctx.user_interfaces.first();
+
+        if let Some(ButtonMessage::Click) = message.data() {
+            if message.destination() == self.back {
                 ui.send_message(WidgetMessage::visibility(
                     self.self_handle,
                     MessageDirection::ToWidget,
@@ -176,55 +785,244 @@ impl ServerMenu {
                 && message.direction() == MessageDirection::FromWidget
             {
                 self.server_address = text.clone();
+            } else if message.destination() == self.preset_name_input
+                && message.direction() == MessageDirection::FromWidget
+            {
+                self.preset_name = text.clone();
+            } else if message.destination() == self.seed_input
+                && message.direction() == MessageDirection::FromWidget
+            {
+                let text = text.trim();
+                let seed = if text.is_empty() {
+                    Ok(None)
+                } else {
+                    text.parse::<u64>().map(Some)
+                };
+                self.invalid_seed = seed.is_err();
+                self.config.seed = seed.unwrap_or_default();
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
+            } else if message.destination() == self.bot_count_input
+                && message.direction() == MessageDirection::FromWidget
+            {
+                // Anything, that is not a number, means no bots.
+                self.config.bot_count = text
+                    .trim()
+                    .parse::<usize>()
+                    .unwrap_or_default()
+                    .min(Server::MAX_BOTS);
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
+                self.players_list_revision.set(None);
+            } else if message.destination() == self.auto_start_input
+                && message.direction() == MessageDirection::FromWidget
+            {
+                // Anything, that is not a positive number, disables auto-start.
+                self.config.auto_start_players = text
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|players| *players > 0);
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
+            }
+        } else if let Some(ScrollBarMessage::Value(value)) = message.data() {
+            if message.destination() == self.auto_start_delay
+                && message.direction() == MessageDirection::FromWidget
+            {
+                self.config.auto_start_delay = *value;
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
+            } else if message.destination() == self.results_duration
+                && message.direction() == MessageDirection::FromWidget
+            {
+                self.config.results_duration = *value;
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
             }
         } else if let Some(SelectorMessage::Current(selected)) = message.data() {
             if message.destination() == self.level_selector
                 && message.direction() == MessageDirection::FromWidget
             {
                 self.selected_level = *selected;
+                self.request_level_theme(ctx);
             }
         } else if let Some(CheckBoxMessage::Check(Some(value))) = message.data() {
             if message.destination() == self.add_bots_check_box
                 && message.direction() == MessageDirection::FromWidget
             {
+                self.config.add_bots = *value;
                 if let Some(server) = server {
                     server.add_bots = *value;
                 }
+                self.players_list_revision.set(None);
+            } else if message.destination() == self.shuffle_start_points_check_box
+                && message.direction() == MessageDirection::FromWidget
+            {
+                self.config.shuffle_start_points = *value;
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
+            } else if message.destination() == self.auto_advance_check_box
+                && message.direction() == MessageDirection::FromWidget
+            {
+                self.config.auto_advance = *value;
+                if let Some(server) = server {
+                    self.config.apply(server);
+                }
+            }
+        } else if let Some(DropdownListMessage::SelectionChanged(Some(index))) = message.data() {
+            if message.destination() == self.snapshot_rate_selector
+                && message.direction() == MessageDirection::FromWidget
+            {
+                if let Some(rate) = Self::SNAPSHOT_RATES.get(*index) {
+                    self.config.snapshot_rate = *rate;
+                    if let Some(server) = server {
+                        self.config.apply(server);
+                    }
+                }
+            } else if message.destination() == self.preset_selector
+                && message.direction() == MessageDirection::FromWidget
+            {
+                self.apply_preset(*index, ui, server);
             }
         }
     }
 
-    pub fn update(&self, ctx: &mut PluginContext, server: &Option<Server>) {
+    /// Asks clients to load the selected level. The lobby is hidden on success, otherwise the
+    /// reason is shown to the host.
+    fn start_game(&self, ctx: &mut PluginContext, server: &mut Option<Server>) {
+        let result = match (self.selected_level, server.as_mut()) {
+            _ if self.invalid_seed => Err(
+                "Unable to start the game: the seed must be a non-negative integer \
+                or empty for a random seed."
+                    .to_string(),
+            ),
+            (Some(selected_level), Some(server)) => self
+                .available_levels
+                .get(selected_level)
+                .ok_or_else(|| "Unable to start the game: unknown level.".to_string())
+                .and_then(|level| server.start_game(level)),
+            (None, _) => Err("Unable to start the game: no level selected.".to_string()),
+            (_, None) => Err("Unable to start the game: server is not running.".to_string()),
+        };
+
+        match result {
+            Ok(()) => {
+                ctx.user_interfaces
+                    .first()
+                    .send_message(WidgetMessage::visibility(
+                        self.self_handle,
+                        MessageDirection::ToWidget,
+                        false,
+                    ));
+            }
+            Err(reason) => {
+                // Do not retry every frame, the countdown will begin again.
+                if let Some(server) = server {
+                    server.cancel_auto_start();
+                }
+                Log::err(&reason);
+                show_message_box(ctx.user_interfaces.first_mut(), "Error", &reason);
+            }
+        }
+    }
+
+    pub fn update(&self, ctx: &mut PluginContext, server: &mut Option<Server>) {
+        if server
+            .as_ref()
+            .map_or(false, |server| server.is_auto_start_due())
+        {
+            self.start_game(ctx, server);
+        }
+
         let Some(server) = server else {
             return;
         };
 
-        let player_entries_count = ctx
-            .user_interfaces
-            .first()
-            .node(self.players_list)
-            .query_component::<ListView>()
-            .unwrap()
-            .items()
-            .len();
-        if server.connections().len() != player_entries_count {
-            let new_player_entries = server
+        let auto_start_text = server
+            .auto_start_countdown()
+            .map(|time| format!("Starting in {}", time.ceil() as u32))
+            .unwrap_or_default();
+        ctx.user_interfaces.first().send_message(TextMessage::text(
+            self.auto_start_text,
+            MessageDirection::ToWidget,
+            auto_start_text,
+        ));
+
+        if self.players_list_revision.get() != Some(server.lobby_revision()) {
+            self.players_list_revision
+                .set(Some(server.lobby_revision()));
+
+            let mut new_player_entries = server
                 .connections()
                 .iter()
                 .enumerate()
                 .map(|(n, e)| {
-                    make_text_widget(
-                        &mut ctx.user_interfaces.first_mut().build_ctx(),
-                        &format!(
-                            "{} - {}",
-                            e.string_peer_address(),
-                            if n == 0 { "Host" } else { "Peer" }
-                        ),
+                    let ui_ctx = &mut ctx.user_interfaces.first_mut().build_ctx();
+                    let swatch = BorderBuilder::new(
+                        WidgetBuilder::new()
+                            .with_width(24.0)
+                            .with_height(24.0)
+                            .with_margin(Thickness::uniform(2.0))
+                            .with_background(Brush::Solid(e.color())),
+                    )
+                    .build(ui_ctx);
+                    let mut description = format!(
+                        "{} - {} ({})",
+                        e.string_peer_address(),
+                        if n == Server::HOST_CONNECTION {
+                            "Host"
+                        } else {
+                            "Peer"
+                        },
+                        server::player_model_name(e.model())
+                    );
+                    if let Some(ping) = e.ping_text() {
+                        description += &format!(" - {ping}");
+                    }
+                    let text = make_text_widget(
+                        ui_ctx,
+                        &description,
                         ctx.resource_manager,
                         HorizontalAlignment::Left,
-                    )
+                    );
+                    let mut row = WidgetBuilder::new().with_child(swatch).with_child(text);
+                    // The host starts the match, so only peers have the ready mark.
+                    if n != Server::HOST_CONNECTION {
+                        let ready_mark = CheckBoxBuilder::new(
+                            WidgetBuilder::new()
+                                .with_enabled(false)
+                                .with_width(24.0)
+                                .with_height(24.0)
+                                .with_margin(Thickness::uniform(2.0)),
+                        )
+                        .checked(Some(e.is_ready()))
+                        .build(ui_ctx);
+                        row = row.with_child(ready_mark);
+                    }
+                    StackPanelBuilder::new(row)
+                        .with_orientation(Orientation::Horizontal)
+                        .build(ui_ctx)
                 })
                 .collect::<Vec<_>>();
+            // The level isn't loaded yet, so the amount of free start points is unknown.
+            let bots_text = if server.add_bots {
+                format!("Bots - fill free start points (max {})", Server::MAX_BOTS)
+            } else {
+                format!("Bots - {}", server.bots_to_spawn(0, 0))
+            };
+            new_player_entries.push(make_text_widget(
+                &mut ctx.user_interfaces.first_mut().build_ctx(),
+                &bots_text,
+                ctx.resource_manager,
+                HorizontalAlignment::Left,
+            ));
             ctx.user_interfaces
                 .first()
                 .send_message(ListViewMessage::items(
@@ -244,6 +1042,36 @@ pub struct SettingsMenu {
     music_volume: Handle<UiNode>,
     mouse_sens: Handle<UiNode>,
     mouse_smoothness: Handle<UiNode>,
+    mouse_sens_vertical: Handle<UiNode>,
+    aim_sensitivity: Handle<UiNode>,
+    invert_y: Handle<UiNode>,
+    toggle_sprint: Handle<UiNode>,
+    pause_on_focus_loss: Handle<UiNode>,
+    field_of_view: Handle<UiNode>,
+    render_interpolation: Handle<UiNode>,
+    name_tag_distance: Handle<UiNode>,
+    name_tag_fade: Handle<UiNode>,
+    camera_min_pitch: Handle<UiNode>,
+    camera_max_pitch: Handle<UiNode>,
+    camera_auto_center_speed: Handle<UiNode>,
+    input_send_rate: Handle<UiNode>,
+    manual_respawn: Handle<UiNode>,
+    correction_smoothing: Handle<UiNode>,
+    snap_distance: Handle<UiNode>,
+    ragdoll_camera: Handle<UiNode>,
+    spectator_transition: Handle<UiNode>,
+    spectator_transition_speed: Handle<UiNode>,
+    player_model: Handle<UiNode>,
+    /// Models, that are listed in [`Self::player_model`].
+    player_models: Vec<PathBuf>,
+    /// Buttons (and their texts) of the actions of the player, a click on a button waits for a
+    /// new key of the action.
+    #[visit(skip)]
+    key_buttons: Vec<(KeyAction, Handle<UiNode>, Handle<UiNode>)>,
+    reset_keys: Handle<UiNode>,
+    /// The action, that waits for a new key, see [`Self::capture_key`].
+    #[visit(skip)]
+    capturing: Cell<Option<KeyAction>>,
     back: Handle<UiNode>,
     reset: Handle<UiNode>,
 }
@@ -300,18 +1128,381 @@ impl SettingsMenu {
         set_sb_value(ui, mouse_sens, settings.mouse_sensitivity);
         set_sb_value(ui, mouse_smoothness, settings.mouse_smoothness);
 
+        // Settings, that are not present in the menu layout, are placed in a separate panel.
+        let menu = ui.find_handle_by_name_from_root("SettingsMenu");
+        let ctx = &mut ui.build_ctx();
+        let mouse_sens_vertical = make_slider(
+            ctx,
+            settings.mouse_sensitivity_vertical,
+            *SettingsData::SENSITIVITY_RANGE.start(),
+            *SettingsData::SENSITIVITY_RANGE.end(),
+        );
+        let aim_sensitivity = make_slider(
+            ctx,
+            settings.aim_sensitivity(),
+            *SettingsData::SENSITIVITY_RANGE.start(),
+            *SettingsData::SENSITIVITY_RANGE.end(),
+        );
+        let invert_y = make_check_box(ctx, settings.invert_y);
+        let toggle_sprint = make_check_box(ctx, settings.toggle_sprint);
+        let pause_on_focus_loss = make_check_box(ctx, settings.pause_on_focus_loss);
+        let field_of_view = make_slider(
+            ctx,
+            settings.field_of_view(),
+            *SettingsData::FIELD_OF_VIEW_RANGE.start(),
+            *SettingsData::FIELD_OF_VIEW_RANGE.end(),
+        );
+        let render_interpolation = make_check_box(ctx, settings.render_interpolation);
+        let name_tag_distance = make_slider(
+            ctx,
+            settings.name_tag_distance,
+            *SettingsData::NAME_TAG_DISTANCE_RANGE.start(),
+            *SettingsData::NAME_TAG_DISTANCE_RANGE.end(),
+        );
+        let name_tag_fade = make_slider(ctx, settings.name_tag_fade, 0.0, 1.0);
+        let (min_pitch, max_pitch) = settings.camera_pitch_limits();
+        let camera_min_pitch = make_slider(
+            ctx,
+            min_pitch,
+            *SettingsData::PITCH_RANGE.start(),
+            *SettingsData::PITCH_RANGE.end(),
+        );
+        let camera_max_pitch = make_slider(
+            ctx,
+            max_pitch,
+            *SettingsData::PITCH_RANGE.start(),
+            *SettingsData::PITCH_RANGE.end(),
+        );
+        let camera_auto_center_speed = make_slider(
+            ctx,
+            settings.camera_auto_center_speed(),
+            *SettingsData::AUTO_CENTER_SPEED_RANGE.start(),
+            *SettingsData::AUTO_CENTER_SPEED_RANGE.end(),
+        );
+        let input_send_rate = make_slider(
+            ctx,
+            settings.input_send_rate(),
+            *SettingsData::INPUT_SEND_RATE_RANGE.start(),
+            *SettingsData::INPUT_SEND_RATE_RANGE.end(),
+        );
+        let manual_respawn = make_check_box(ctx, settings.manual_respawn);
+        let correction_smoothing = make_slider(
+            ctx,
+            settings.correction_smoothing(),
+            *SettingsData::CORRECTION_SMOOTHING_RANGE.start(),
+            *SettingsData::CORRECTION_SMOOTHING_RANGE.end(),
+        );
+        let snap_distance = make_slider(
+            ctx,
+            settings.snap_distance(),
+            *SettingsData::SNAP_DISTANCE_RANGE.start(),
+            *SettingsData::SNAP_DISTANCE_RANGE.end(),
+        );
+        let ragdoll_camera_names = RagdollCameraMode::ALL
+            .iter()
+            .map(|mode| mode.name().to_string())
+            .collect::<Vec<_>>();
+        let ragdoll_camera = make_dropdown_list(
+            ctx,
+            &ragdoll_camera_names,
+            RagdollCameraMode::ALL
+                .iter()
+                .position(|mode| *mode == settings.ragdoll_camera)
+                .unwrap_or_default(),
+            resource_manager,
+        );
+        let spectator_transition_names = SpectatorTransition::ALL
+            .iter()
+            .map(|mode| mode.name().to_string())
+            .collect::<Vec<_>>();
+        let spectator_transition = make_dropdown_list(
+            ctx,
+            &spectator_transition_names,
+            SpectatorTransition::ALL
+                .iter()
+                .position(|mode| *mode == settings.spectator_transition)
+                .unwrap_or_default(),
+            resource_manager,
+        );
+        let spectator_transition_speed = make_slider(
+            ctx,
+            settings.spectator_transition_speed(),
+            *SettingsData::SPECTATOR_TRANSITION_SPEED_RANGE.start(),
+            *SettingsData::SPECTATOR_TRANSITION_SPEED_RANGE.end(),
+        );
+        let player_models = server::player_models();
+        let player_model_names = player_models
+            .iter()
+            .map(|path| server::player_model_name(path))
+            .collect::<Vec<_>>();
+        let player_model = make_dropdown_list(
+            ctx,
+            &player_model_names,
+            player_models
+                .iter()
+                .position(|path| *path == settings.player_model)
+                .unwrap_or_default(),
+            resource_manager,
+        );
+        let rows = [
+            make_settings_row(
+                ctx,
+                "Vertical Mouse Sens.",
+                resource_manager,
+                mouse_sens_vertical,
+            ),
+            make_settings_row(ctx, "Aim Sensitivity", resource_manager, aim_sensitivity),
+            make_settings_row(ctx, "Invert Y", resource_manager, invert_y),
+            make_settings_row(ctx, "Toggle Sprint", resource_manager, toggle_sprint),
+            make_settings_row(
+                ctx,
+                "Pause On Focus Loss",
+                resource_manager,
+                pause_on_focus_loss,
+            ),
+            make_settings_row(ctx, "Field Of View", resource_manager, field_of_view),
+            make_settings_row(
+                ctx,
+                "Render Interpolation",
+                resource_manager,
+                render_interpolation,
+            ),
+            make_settings_row(
+                ctx,
+                "Name Tag Distance",
+                resource_manager,
+                name_tag_distance,
+            ),
+            make_settings_row(ctx, "Name Tag Fade", resource_manager, name_tag_fade),
+            make_settings_row(ctx, "Camera Min Pitch", resource_manager, camera_min_pitch),
+            make_settings_row(ctx, "Camera Max Pitch", resource_manager, camera_max_pitch),
+            make_settings_row(
+                ctx,
+                "Camera Auto-Center",
+                resource_manager,
+                camera_auto_center_speed,
+            ),
+            make_settings_row(ctx, "Input Send Rate", resource_manager, input_send_rate),
+            make_settings_row(ctx, "Manual Respawn", resource_manager, manual_respawn),
+            make_settings_row(
+                ctx,
+                "Correction Smoothing",
+                resource_manager,
+                correction_smoothing,
+            ),
+            make_settings_row(ctx, "Snap Distance", resource_manager, snap_distance),
+            make_settings_row(ctx, "Ragdoll Camera", resource_manager, ragdoll_camera),
+            make_settings_row(
+                ctx,
+                "Spectator Transition",
+                resource_manager,
+                spectator_transition,
+            ),
+            make_settings_row(
+                ctx,
+                "Spectator Trans. Speed",
+                resource_manager,
+                spectator_transition_speed,
+            ),
+            make_settings_row(ctx, "Player Model", resource_manager, player_model),
+        ];
+        add_extra_settings_panel(ui, menu, rows);
+
+        let ctx = &mut ui.build_ctx();
+        let mut key_rows = Vec::new();
+        let key_buttons = KeyAction::ALL
+            .into_iter()
+            .map(|action| {
+                let (button, text) = make_settings_button(
+                    ctx,
+                    &settings.key_bindings.key_name(action),
+                    resource_manager,
+                );
+                key_rows.push(make_settings_row(
+                    ctx,
+                    action.name(),
+                    resource_manager,
+                    button,
+                ));
+                (action, button, text)
+            })
+            .collect::<Vec<_>>();
+        let (reset_keys, _) = make_settings_button(ctx, "Reset", resource_manager);
+        key_rows.push(make_settings_row(
+            ctx,
+            "Default Keys",
+            resource_manager,
+            reset_keys,
+        ));
+        add_settings_panel(ui, menu, HorizontalAlignment::Left, key_rows);
+
         Self {
-            menu: ui.find_handle_by_name_from_root("SettingsMenu"),
+            menu,
             graphics_quality,
             sound_volume,
             music_volume,
             mouse_sens,
             mouse_smoothness,
+            mouse_sens_vertical,
+            aim_sensitivity,
+            invert_y,
+            toggle_sprint,
+            pause_on_focus_loss,
+            field_of_view,
+            render_interpolation,
+            name_tag_distance,
+            name_tag_fade,
+            camera_min_pitch,
+            camera_max_pitch,
+            camera_auto_center_speed,
+            input_send_rate,
+            manual_respawn,
+            correction_smoothing,
+            snap_distance,
+            ragdoll_camera,
+            spectator_transition,
+            spectator_transition_speed,
+            player_model,
+            player_models,
+            key_buttons,
+            reset_keys,
+            capturing: Default::default(),
             back: ui.find_handle_by_name_from_root("SettingsBack"),
             reset: ui.find_handle_by_name_from_root("SettingsReset"),
         }
     }
 
+    /// Sets the values of every widget of the menu from the settings.
+    fn sync_widgets(&self, ui: &UserInterface, settings: &SettingsData) {
+        ui.send_message(SelectorMessage::current(
+            self.graphics_quality,
+            MessageDirection::ToWidget,
+            Some(settings.graphics_quality),
+        ));
+
+        let (min_pitch, max_pitch) = settings.camera_pitch_limits();
+        for (handle, value) in [
+            (self.sound_volume, settings.sound_volume),
+            (self.music_volume, settings.music_volume),
+            (self.mouse_sens, settings.mouse_sensitivity),
+            (self.mouse_smoothness, settings.mouse_smoothness),
+            (
+                self.mouse_sens_vertical,
+                settings.mouse_sensitivity_vertical,
+            ),
+            (self.aim_sensitivity, settings.aim_sensitivity()),
+            (self.field_of_view, settings.field_of_view()),
+            (self.name_tag_distance, settings.name_tag_distance),
+            (self.name_tag_fade, settings.name_tag_fade),
+            (self.camera_min_pitch, min_pitch),
+            (self.camera_max_pitch, max_pitch),
+            (
+                self.camera_auto_center_speed,
+                settings.camera_auto_center_speed(),
+            ),
+            (self.input_send_rate, settings.input_send_rate()),
+            (self.correction_smoothing, settings.correction_smoothing()),
+            (self.snap_distance, settings.snap_distance()),
+            (
+                self.spectator_transition_speed,
+                settings.spectator_transition_speed(),
+            ),
+        ] {
+            ui.send_message(ScrollBarMessage::value(
+                handle,
+                MessageDirection::ToWidget,
+                value,
+            ));
+        }
+
+        for (handle, value) in [
+            (self.invert_y, settings.invert_y),
+            (self.toggle_sprint, settings.toggle_sprint),
+            (self.pause_on_focus_loss, settings.pause_on_focus_loss),
+            (self.render_interpolation, settings.render_interpolation),
+            (self.manual_respawn, settings.manual_respawn),
+        ] {
+            ui.send_message(CheckBoxMessage::checked(
+                handle,
+                MessageDirection::ToWidget,
+                Some(value),
+            ));
+        }
+
+        for (handle, index) in [
+            (
+                self.ragdoll_camera,
+                RagdollCameraMode::ALL
+                    .iter()
+                    .position(|mode| *mode == settings.ragdoll_camera),
+            ),
+            (
+                self.spectator_transition,
+                SpectatorTransition::ALL
+                    .iter()
+                    .position(|mode| *mode == settings.spectator_transition),
+            ),
+            (
+                self.player_model,
+                self.player_models
+                    .iter()
+                    .position(|path| *path == settings.player_model),
+            ),
+        ] {
+            ui.send_message(DropdownListMessage::selection(
+                handle,
+                MessageDirection::ToWidget,
+                Some(index.unwrap_or_default()),
+            ));
+        }
+
+        self.sync_key_buttons(ui, settings);
+    }
+
+    fn sync_key_buttons(&self, ui: &UserInterface, settings: &SettingsData) {
+        for (action, _, text) in self.key_buttons.iter() {
+            let name = if self.capturing.get() == Some(*action) {
+                "Press a key...".to_string()
+            } else {
+                settings.key_bindings.key_name(*action)
+            };
+            ui.send_message(TextMessage::text(*text, MessageDirection::ToWidget, name));
+        }
+    }
+
+    /// Binds the pressed key to the action, that waits for a new key. Escape cancels the
+    /// binding. Returns `true` if the key was consumed.
+    pub fn capture_key(
+        &self,
+        event: &KeyEvent,
+        ui: &UserInterface,
+        settings: &mut Settings,
+    ) -> bool {
+        let Some(action) = self.capturing.get() else {
+            return false;
+        };
+        if event.state != ElementState::Pressed {
+            return true;
+        }
+        self.capturing.set(None);
+        match event.physical_key {
+            PhysicalKey::Code(KeyCode::Escape) => (),
+            PhysicalKey::Code(key) => {
+                if let Some(other) = settings.write().key_bindings.bind(action, key) {
+                    Log::warn(format!(
+                        "The key of {} was already used by {}, the actions have swapped their \
+                        keys.",
+                        action.name(),
+                        other.name()
+                    ));
+                }
+            }
+            PhysicalKey::Unidentified(_) => Log::warn("Unknown key can't be bound."),
+        }
+        self.sync_key_buttons(ui, &settings.read());
+        true
+    }
+
     pub fn handle_ui_message(
         &self,
         message: &UiMessage,
@@ -330,9 +1521,34 @@ impl SettingsMenu {
                 settings.apply_graphics_settings(graphics_context);
             }
         } else if let Some(ButtonMessage::Click) = message.data() {
-            if message.destination() == self.back {
+            if let Some((action, ..)) = self
+                .key_buttons
+                .iter()
+                .find(|(_, button, _)| *button == message.destination())
+            {
+                self.capturing.set(Some(*action));
+                self.sync_key_buttons(ui, &settings.read());
+            } else if message.destination() == self.reset_keys {
+                let mut settings = settings.write();
+                settings.key_bindings = Default::default();
+                self.capturing.set(None);
+                self.sync_key_buttons(ui, &settings);
+            } else if message.destination() == self.back {
+                self.capturing.set(None);
                 set_visibility(ui, &[(self.menu, false), (main_menu, true)]);
             } else if message.destination() == self.reset {
+                // Defaults are written to the disk as any other change of the settings.
+                let mut settings = settings.write();
+                *settings = SettingsData::default();
+                settings.apply_graphics_settings(graphics_context);
+                if let Some(scene) = scenes.try_get(game_scene) {
+                    settings.apply_sound_volume(scene);
+                }
+                if let Some(scene) = scenes.try_get(menu_scene) {
+                    settings.apply_music_volume(scene);
+                }
+                self.capturing.set(None);
+                self.sync_widgets(ui, &settings);
             }
         } else if let Some(ScrollBarMessage::Value(value)) = message.data() {
             if message.destination() == self.sound_volume {
@@ -341,16 +1557,106 @@ impl SettingsMenu {
                 if let Some(scene) = scenes.try_get(game_scene) {
                     settings.apply_sound_volume(scene);
                 }
-            } else if message.destination() == self.music_volume {
-                let mut settings = settings.write();
-                if let Some(scene) = scenes.try_get(menu_scene) {
-                    settings.apply_music_volume(scene);
+            } else if message.destination() == self.music_volume {
+                let mut settings = settings.write();
+                settings.music_volume = *value;
+                if let Some(scene) = scenes.try_get(menu_scene) {
+                    settings.apply_music_volume(scene);
+                }
+            } else if message.destination() == self.mouse_sens {
+                settings.write().mouse_sensitivity = *value;
+            } else if message.destination() == self.mouse_smoothness {
+                settings.write().mouse_smoothness = *value;
+            } else if message.destination() == self.mouse_sens_vertical
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().mouse_sensitivity_vertical = *value;
+            } else if message.destination() == self.aim_sensitivity
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().aim_sensitivity = *value;
+            } else if message.destination() == self.field_of_view
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().field_of_view = *value;
+            } else if message.destination() == self.name_tag_distance
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().name_tag_distance = *value;
+            } else if message.destination() == self.name_tag_fade
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().name_tag_fade = *value;
+            } else if message.destination() == self.camera_min_pitch
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().camera_min_pitch = *value;
+            } else if message.destination() == self.camera_max_pitch
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().camera_max_pitch = *value;
+            } else if message.destination() == self.camera_auto_center_speed
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().camera_auto_center_speed = *value;
+            } else if message.destination() == self.input_send_rate
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().input_send_rate = *value;
+            } else if message.destination() == self.correction_smoothing
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().correction_smoothing = *value;
+            } else if message.destination() == self.snap_distance
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().snap_distance = *value;
+            } else if message.destination() == self.spectator_transition_speed
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().spectator_transition_speed = *value;
+            }
+        } else if let Some(CheckBoxMessage::Check(Some(value))) = message.data() {
+            if message.destination() == self.invert_y
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().invert_y = *value;
+            } else if message.destination() == self.toggle_sprint
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().toggle_sprint = *value;
+            } else if message.destination() == self.pause_on_focus_loss
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().pause_on_focus_loss = *value;
+            } else if message.destination() == self.render_interpolation
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().render_interpolation = *value;
+            } else if message.destination() == self.manual_respawn
+                && message.direction() == MessageDirection::FromWidget
+            {
+                settings.write().manual_respawn = *value;
+            }
+        } else if let Some(DropdownListMessage::SelectionChanged(Some(index))) = message.data() {
+            if message.destination() == self.ragdoll_camera
+                && message.direction() == MessageDirection::FromWidget
+            {
+                if let Some(mode) = RagdollCameraMode::ALL.get(*index) {
+                    settings.write().ragdoll_camera = *mode;
+                }
+            } else if message.destination() == self.spectator_transition
+                && message.direction() == MessageDirection::FromWidget
+            {
+                if let Some(mode) = SpectatorTransition::ALL.get(*index) {
+                    settings.write().spectator_transition = *mode;
+                }
+            } else if message.destination() == self.player_model
+                && message.direction() == MessageDirection::FromWidget
+            {
+                if let Some(path) = self.player_models.get(*index) {
+                    settings.write().player_model = path.clone();
                 }
-                settings.music_volume = *value;
-            } else if message.destination() == self.mouse_sens {
-                settings.write().mouse_sensitivity = *value;
-            } else if message.destination() == self.mouse_smoothness {
-                settings.write().mouse_smoothness = *value;
             }
         }
     }
@@ -363,20 +1669,207 @@ struct InGameMenu {
     finished_text_animation: Handle<UiNode>,
     match_timer_text: Handle<UiNode>,
     player_position: Handle<UiNode>,
+    status_effects_text: Handle<UiNode>,
+    spectator_text: Handle<UiNode>,
+    qualified_text: Handle<UiNode>,
+    objectives_text: Handle<UiNode>,
+    /// Crosshair, that is shown while the local player aims with a cannon.
+    reticle: Handle<UiNode>,
+    /// Standings of the round, shown while the scoreboard key is held.
+    scoreboard: Handle<UiNode>,
+    scoreboard_list: Handle<UiNode>,
+    #[visit(skip)]
+    scoreboard_rows: Vec<String>,
+    #[visit(skip)]
+    show_scoreboard: bool,
+    #[visit(skip)]
+    name_tags: NameTags,
+    #[visit(skip)]
+    debug_overlay: ActorDebugOverlay,
 }
 
 impl InGameMenu {
-    fn new(ui: &UserInterface) -> Self {
+    fn new(ui: &mut UserInterface, resource_manager: &ResourceManager) -> Self {
+        let root = ui.find_handle_by_name_from_root("InGameMenuRoot");
+
+        let status_effects_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "",
+            resource_manager,
+            HorizontalAlignment::Left,
+        );
+        ui.send_message(WidgetMessage::vertical_alignment(
+            status_effects_text,
+            MessageDirection::ToWidget,
+            VerticalAlignment::Bottom,
+        ));
+        ui.send_message(WidgetMessage::link(
+            status_effects_text,
+            MessageDirection::ToWidget,
+            root,
+        ));
+
+        let spectator_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "",
+            resource_manager,
+            HorizontalAlignment::Center,
+        );
+        ui.send_message(WidgetMessage::vertical_alignment(
+            spectator_text,
+            MessageDirection::ToWidget,
+            VerticalAlignment::Top,
+        ));
+        ui.send_message(WidgetMessage::link(
+            spectator_text,
+            MessageDirection::ToWidget,
+            root,
+        ));
+
+        let qualified_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "",
+            resource_manager,
+            HorizontalAlignment::Right,
+        );
+        ui.send_message(WidgetMessage::vertical_alignment(
+            qualified_text,
+            MessageDirection::ToWidget,
+            VerticalAlignment::Top,
+        ));
+        ui.send_message(WidgetMessage::link(
+            qualified_text,
+            MessageDirection::ToWidget,
+            root,
+        ));
+
+        let objectives_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "",
+            resource_manager,
+            HorizontalAlignment::Right,
+        );
+        ui.send_message(WidgetMessage::vertical_alignment(
+            objectives_text,
+            MessageDirection::ToWidget,
+            VerticalAlignment::Bottom,
+        ));
+        ui.send_message(WidgetMessage::link(
+            objectives_text,
+            MessageDirection::ToWidget,
+            root,
+        ));
+
+        let reticle = make_text_widget(
+            &mut ui.build_ctx(),
+            "+",
+            resource_manager,
+            HorizontalAlignment::Center,
+        );
+        ui.send_message(WidgetMessage::visibility(
+            reticle,
+            MessageDirection::ToWidget,
+            false,
+        ));
+        ui.send_message(WidgetMessage::link(
+            reticle,
+            MessageDirection::ToWidget,
+            root,
+        ));
+
+        // The scoreboard is never hit by the mouse, so it does not steal any input from the game.
+        let scoreboard_list = ListViewBuilder::new(
+            WidgetBuilder::new()
+                .with_hit_test_visibility(false)
+                .with_margin(Thickness::uniform(8.0)),
+        )
+        .build(&mut ui.build_ctx());
+        let scoreboard = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_visibility(false)
+                .with_hit_test_visibility(false)
+                .with_width(600.0)
+                .with_horizontal_alignment(HorizontalAlignment::Center)
+                .with_vertical_alignment(VerticalAlignment::Center)
+                .with_background(Brush::Solid(Color::from_rgba(0, 0, 0, 180)))
+                .with_child(scoreboard_list),
+        )
+        .build(&mut ui.build_ctx());
+        ui.send_message(WidgetMessage::link(
+            scoreboard,
+            MessageDirection::ToWidget,
+            root,
+        ));
+
         Self {
-            root: ui.find_handle_by_name_from_root("InGameMenuRoot"),
+            root,
             finished_text: ui.find_handle_by_name_from_root("FinishedText"),
             match_timer_text: ui.find_handle_by_name_from_root("MatchTimer"),
             finished_text_animation: ui
                 .find_handle_by_name_from_root("FinishedTextAnimationPlayer"),
             player_position: ui.find_handle_by_name_from_root("PlayerPosition"),
+            status_effects_text,
+            spectator_text,
+            qualified_text,
+            objectives_text,
+            reticle,
+            scoreboard,
+            scoreboard_list,
+            scoreboard_rows: Default::default(),
+            show_scoreboard: false,
+            name_tags: Default::default(),
+            debug_overlay: Default::default(),
+        }
+    }
+
+    /// Shows the scoreboard while the key is held and hides it once the key is released.
+    fn set_scoreboard_shown(&mut self, ui: &UserInterface, shown: bool) {
+        if self.show_scoreboard != shown {
+            self.show_scoreboard = shown;
+            ui.send_message(WidgetMessage::visibility(
+                self.scoreboard,
+                MessageDirection::ToWidget,
+                shown,
+            ));
         }
     }
 
+    /// Rebuilds the rows of the scoreboard, when the standings change. The leaderboard is driven by
+    /// the server and replicated to clients, so every peer shows the same standings.
+    fn update_scoreboard(
+        &mut self,
+        ui: &mut UserInterface,
+        graph: Option<&Graph>,
+        level: &Level,
+        resource_manager: &ResourceManager,
+    ) {
+        let rows = match graph {
+            Some(graph) if self.show_scoreboard => scoreboard_rows(level, graph),
+            _ => Vec::new(),
+        };
+        if rows == self.scoreboard_rows {
+            return;
+        }
+
+        let items = rows
+            .iter()
+            .map(|row| {
+                make_text_widget(
+                    &mut ui.build_ctx(),
+                    row,
+                    resource_manager,
+                    HorizontalAlignment::Left,
+                )
+            })
+            .collect::<Vec<_>>();
+        ui.send_message(ListViewMessage::items(
+            self.scoreboard_list,
+            MessageDirection::ToWidget,
+            items,
+        ));
+        self.scoreboard_rows = rows;
+    }
+
     fn on_leaderboard_event(
         &self,
         ui: &mut UserInterface,
@@ -425,13 +1918,23 @@ impl InGameMenu {
         }
     }
 
-    fn update(&self, ui: &UserInterface, graph: Option<&Graph>, level: &Level) {
-        let minutes = (level.match_timer / 60.0) as u32;
-        let seconds = (level.match_timer % 60.0) as u32;
+    fn update(
+        &self,
+        ui: &UserInterface,
+        graph: Option<&Graph>,
+        level: &Level,
+        key_bindings: &KeyBindings,
+    ) {
+        // Rounds without a time limit (or in sudden death) have no timer.
+        let text = level.match_timer.map_or_else(String::new, |timer| {
+            let minutes = (timer / 60.0) as u32;
+            let seconds = (timer % 60.0) as u32;
+            format!("{minutes}:{seconds:02}")
+        });
         ui.send_message(TextMessage::text(
             self.match_timer_text,
             MessageDirection::ToWidget,
-            format!("{minutes}:{seconds}"),
+            text,
         ));
 
         ui.send_message(WidgetMessage::visibility(
@@ -441,25 +1944,174 @@ impl InGameMenu {
         ));
 
         if let Some(graph) = graph {
-            for (actor, entry) in &level.leaderboard.entries {
-                if let Some(actor_ref) = graph.try_get_script_component_of::<Actor>(*actor) {
-                    if actor_ref.kind == ActorKind::Player {
-                        ui.send_message(TextMessage::text(
-                            self.player_position,
-                            MessageDirection::ToWidget,
+            let local_player = level.local_player(graph);
+
+            if let Some(entry) =
+                local_player.and_then(|player| level.leaderboard.entries.get(&player.handle))
+            {
+                ui.send_message(TextMessage::text(
+                    self.player_position,
+                    MessageDirection::ToWidget,
+                    format!(
+                        "Place: {} of {}",
+                        entry.real_time_position + 1,
+                        level.actors.len()
+                    ),
+                ));
+            }
+
+            let qualified_text = level
+                .qualifier_count(graph)
+                .map(|count| {
+                    format!(
+                        "{}/{count} qualified",
+                        level.leaderboard.qualified_count().min(count)
+                    )
+                })
+                .unwrap_or_default();
+            ui.send_message(TextMessage::text(
+                self.qualified_text,
+                MessageDirection::ToWidget,
+                qualified_text,
+            ));
+
+            let objectives_text = local_player
+                .and_then(|player| level.leaderboard.entries.get(&player.handle))
+                .map(|entry| {
+                    entry
+                        .objectives
+                        .iter()
+                        .map(|progress| {
                             format!(
-                                "Place: {} of {}",
-                                entry.real_time_position + 1,
-                                level.actors.len()
-                            ),
-                        ));
+                                "Targets {}: {}/{}",
+                                progress.group, progress.reached, progress.required
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            ui.send_message(TextMessage::text(
+                self.objectives_text,
+                MessageDirection::ToWidget,
+                objectives_text,
+            ));
+
+            let spectator_text = local_player
+                .and_then(|view| {
+                    graph
+                        .try_get_script_of::<Player>(view.handle)
+                        .and_then(|player| {
+                            player.spectator_hud_text(view.handle, level, key_bindings, graph)
+                        })
+                })
+                .unwrap_or_default();
+            ui.send_message(TextMessage::text(
+                self.spectator_text,
+                MessageDirection::ToWidget,
+                spectator_text,
+            ));
 
-                        break;
+            let status_effects = local_player
+                .map(|player| {
+                    if player.actor.awaiting_respawn {
+                        return format!(
+                            "Knocked Out! Press {} to respawn",
+                            key_bindings.key_name(KeyAction::Respawn)
+                        );
                     }
-                }
-            }
+                    if player.actor.is_knocked_out() {
+                        return "Knocked Out!".to_string();
+                    }
+                    if player.actor.is_aiming() {
+                        return format!(
+                            "Aiming! Press {} to leave the cannon",
+                            key_bindings.key_name(KeyAction::Jump)
+                        );
+                    }
+
+                    player
+                        .actor
+                        .status_effects
+                        .iter()
+                        .map(|effect| effect.as_ref().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .unwrap_or_default();
+            ui.send_message(TextMessage::text(
+                self.status_effects_text,
+                MessageDirection::ToWidget,
+                status_effects,
+            ));
+
+            ui.send_message(WidgetMessage::visibility(
+                self.reticle,
+                MessageDirection::ToWidget,
+                local_player.map_or(false, |player| player.actor.is_aiming()),
+            ));
+        }
+    }
+}
+
+/// Returns the rows of the scoreboard, see [`standing_rows`].
+fn scoreboard_rows(level: &Level, graph: &Graph) -> Vec<String> {
+    let local_player = level.local_player(graph).map(|view| view.handle);
+    let standings = level
+        .ordered_actors(graph)
+        .into_iter()
+        .filter_map(|handle| {
+            let actor = graph.try_get_script_component_of::<Actor>(handle)?;
+            let entry = level.leaderboard.entries.get(&handle);
+            Some(Standing {
+                name: actor.name.clone(),
+                is_local: local_player == Some(handle),
+                entry: entry.cloned().unwrap_or_default(),
+            })
+        })
+        .collect();
+    standing_rows(standings)
+}
+
+/// An actor on the scoreboard.
+struct Standing {
+    name: String,
+    is_local: bool,
+    entry: LeaderBoardEntry,
+}
+
+/// Returns the rows of the scoreboard: finished actors by their places first, then actors, that are
+/// still racing, by their current positions, and eliminated actors last.
+fn standing_rows(mut standings: Vec<Standing>) -> Vec<String> {
+    standings.sort_by_key(|standing| {
+        let entry = &standing.entry;
+        if entry.finished {
+            (0, entry.finished_position)
+        } else if entry.eliminated {
+            (2, 0)
+        } else {
+            (1, entry.real_time_position)
         }
+    });
+
+    let mut rows = vec![format!("Standings ({} actors)", standings.len())];
+    for Standing {
+        name,
+        is_local,
+        entry,
+    } in standings
+    {
+        let you = if is_local { " (you)" } else { "" };
+        let row = if entry.finished {
+            format!("{}. {name}{you} - Finished", entry.finished_position)
+        } else if entry.eliminated {
+            format!("{name}{you} - Eliminated")
+        } else {
+            format!("{name}{you} - Racing")
+        };
+        rows.push(row);
     }
+    rows
 }
 
 #[derive(Visit, Debug)]
@@ -474,6 +2126,9 @@ pub struct Menu {
     background: Handle<UiNode>,
     server_menu: ServerMenu,
     settings_menu: SettingsMenu,
+    /// Ready toggle of a peer, that is connected to someone else's lobby.
+    ready_panel: Handle<UiNode>,
+    ready_check_box: Handle<UiNode>,
     scene: Handle<Scene>,
     click_begin_sound: Handle<Node>,
     click_end_sound: Handle<Node>,
@@ -487,6 +2142,27 @@ pub struct Menu {
     clock_ticking: Handle<Node>,
     win_camera: Handle<Node>,
     main_camera: Handle<Node>,
+    #[visit(skip)]
+    music: MusicManager,
+    #[visit(skip)]
+    default_background: Option<TextureResource>,
+    /// `true` if the lobby shows the theme of the selected level.
+    has_level_theme: bool,
+    /// `true` if the music of a round is played instead of the menu music.
+    has_round_music: bool,
+    /// Places of players and the seed of the last match.
+    results_text: Handle<UiNode>,
+    is_showing_results: bool,
+    /// Whole seconds before the next round, that are shown with the results.
+    #[visit(skip)]
+    results_countdown: Option<u32>,
+    /// Lets the host start the next round without waiting for the countdown.
+    next_round: Handle<UiNode>,
+    /// The match timer on the previous frame, used to announce the final seconds of the round.
+    #[visit(skip)]
+    last_match_timer: Option<f32>,
+    #[visit(skip)]
+    render_scaler: RenderScaler,
 }
 
 impl Default for Menu {
@@ -503,6 +2179,8 @@ impl Default for Menu {
             background: Default::default(),
             server_menu: Default::default(),
             settings_menu: Default::default(),
+            ready_panel: Default::default(),
+            ready_check_box: Default::default(),
             scene: Default::default(),
             click_begin_sound: Default::default(),
             click_end_sound: Default::default(),
@@ -514,6 +2192,16 @@ impl Default for Menu {
             clock_ticking: Default::default(),
             win_camera: Default::default(),
             main_camera: Default::default(),
+            music: Default::default(),
+            default_background: Default::default(),
+            has_level_theme: false,
+            has_round_music: false,
+            results_text: Default::default(),
+            is_showing_results: false,
+            results_countdown: None,
+            next_round: Default::default(),
+            last_match_timer: None,
+            render_scaler: Default::default(),
         }
     }
 }
@@ -531,6 +2219,38 @@ where
     }
 }
 
+/// Amount of attempts to connect the client of the host to its own server.
+const LOCAL_CONNECT_ATTEMPTS: usize = 3;
+
+/// Delay between attempts to connect the client of the host to its own server.
+const LOCAL_CONNECT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Connects the client of the host to the server, that has just been started. Returns the reason
+/// of the last failure, if every attempt has failed.
+fn connect_local_client(server_addr: &str) -> Result<Client, String> {
+    let mut attempt = 1;
+    loop {
+        match Client::try_connect(server_addr) {
+            Ok(client) => return Ok(client),
+            Err(err) if attempt < LOCAL_CONNECT_ATTEMPTS => {
+                Log::warn(format!(
+                    "Unable to connect to the local server (attempt {attempt}). Reason: {:?}",
+                    err
+                ));
+                attempt += 1;
+                std::thread::sleep(LOCAL_CONNECT_RETRY_DELAY);
+            }
+            Err(err) => {
+                return Err(format!(
+                    "The server was started, but the game could not connect to it after {} \
+                    attempts, so the server was shut down. Reason: {:?}",
+                    LOCAL_CONNECT_ATTEMPTS, err
+                ))
+            }
+        }
+    }
+}
+
 impl Menu {
     pub fn new(ctx: &mut PluginContext, game: &mut Game) -> Self {
         let settings = &game.settings;
@@ -550,6 +2270,8 @@ impl Menu {
                     this.clock_ticking = scene.graph.find_handle_by_name_from_root("ClockTicking");
                     this.main_camera = scene.graph.find_handle_by_name_from_root("Camera");
                     this.win_camera = scene.graph.find_handle_by_name_from_root("WinCamera");
+                    this.music = MusicManager::new(&scene.graph);
+                    game.settings.read().apply_music_volume(&scene);
                     this.scene = ctx.scenes.add(scene);
                 }
             },
@@ -558,6 +2280,48 @@ impl Menu {
         let ui = ctx.user_interfaces.first_mut();
         let main_menu = ui.find_handle_by_name_from_root("MainMenu");
         let server_menu = ui.find_handle_by_name_from_root("ServerMenu");
+        let (background, default_background) = find_or_create_background(ui);
+        let results_text = TextBuilder::new(
+            WidgetBuilder::new()
+                .with_visibility(false)
+                .with_margin(Thickness::uniform(20.0))
+                .with_vertical_alignment(VerticalAlignment::Top)
+                .with_horizontal_alignment(HorizontalAlignment::Center),
+        )
+        .with_horizontal_text_alignment(HorizontalAlignment::Center)
+        .with_font(ctx.resource_manager.request::<Font>("data/font.ttf"))
+        .with_font_size(32.0.into())
+        .build(&mut ui.build_ctx());
+        let next_round_text = make_text_widget(
+            &mut ui.build_ctx(),
+            "Next Round",
+            ctx.resource_manager,
+            HorizontalAlignment::Center,
+        );
+        let next_round = ButtonBuilder::new(
+            WidgetBuilder::new()
+                .with_visibility(false)
+                .with_width(200.0)
+                .with_height(40.0)
+                .with_margin(Thickness::uniform(20.0))
+                .with_vertical_alignment(VerticalAlignment::Bottom)
+                .with_horizontal_alignment(HorizontalAlignment::Center),
+        )
+        .with_content(next_round_text)
+        .build(&mut ui.build_ctx());
+        let ready_check_box = make_check_box(&mut ui.build_ctx(), false);
+        let ready_row = make_settings_row(
+            &mut ui.build_ctx(),
+            "Ready",
+            ctx.resource_manager,
+            ready_check_box,
+        );
+        let ready_panel = add_extra_settings_panel(ui, main_menu, [ready_row]);
+        ui.send_message(WidgetMessage::visibility(
+            ready_panel,
+            MessageDirection::ToWidget,
+            false,
+        ));
         let (sender, receiver) = mpsc::channel();
         Self {
             debug_text: ui.find_handle_by_name_from_root("DebugText"),
@@ -567,9 +2331,11 @@ impl Menu {
             start_as_client: ui.find_handle_by_name_from_root("Client"),
             main_menu,
             main_menu_root: ui.find_handle_by_name_from_root("MainMenuRoot"),
-            background: ui.find_handle_by_name_from_root("Background"),
+            background,
             server_menu: ServerMenu::new(server_menu, main_menu, ui, ctx.resource_manager),
             settings_menu: SettingsMenu::new(ui, ctx.resource_manager, settings),
+            ready_panel,
+            ready_check_box,
             scene: Default::default(),
             click_begin_sound: Default::default(),
             click_end_sound: Default::default(),
@@ -577,10 +2343,124 @@ impl Menu {
             finished_sound: Default::default(),
             sender,
             receiver,
-            in_game_menu: InGameMenu::new(ui),
+            in_game_menu: InGameMenu::new(ui, ctx.resource_manager),
             clock_ticking: Default::default(),
             win_camera: Default::default(),
             main_camera: Default::default(),
+            music: Default::default(),
+            default_background,
+            has_level_theme: false,
+            has_round_music: false,
+            results_text,
+            is_showing_results: false,
+            results_countdown: None,
+            next_round,
+            last_match_timer: None,
+            render_scaler: Default::default(),
+        }
+    }
+
+    /// Shows the given level preview image and plays the level music (with crossfading). Default
+    /// background and music are used if a level has none.
+    fn set_level_theme(
+        &mut self,
+        preview_image: Option<TextureResource>,
+        lobby_music: Option<SoundBufferResource>,
+        ctx: &mut PluginContext,
+    ) {
+        let texture = preview_image.or_else(|| self.default_background.clone());
+        let ui = ctx.user_interfaces.first();
+        ui.send_message(WidgetMessage::visibility(
+            self.background,
+            MessageDirection::ToWidget,
+            texture.is_some(),
+        ));
+        ui.send_message(ImageMessage::texture(
+            self.background,
+            MessageDirection::ToWidget,
+            texture,
+        ));
+
+        if let Some(scene) = ctx.scenes.try_get_mut(self.scene) {
+            self.music.crossfade_to(lobby_music, &mut scene.graph);
+        }
+
+        self.has_level_theme = true;
+    }
+
+    /// Shifts the music of the running round according to its state. Every peer follows the
+    /// replicated round state, so the music shifts in sync.
+    fn update_round_music(
+        &mut self,
+        ctx: &mut PluginContext,
+        client: &Option<Client>,
+        level: &Level,
+    ) {
+        let music = if level.scene.is_some() {
+            // There's no client when the level is played from the editor.
+            let phase = client
+                .as_ref()
+                .map_or(RoundPhase::InProgress, |client| client.round_state().phase);
+            let intensity = MusicIntensity::of_round(phase, level);
+            ctx.scenes
+                .try_get(level.scene)
+                .and_then(|scene| level.config(&scene.graph))
+                .and_then(|config| config.round_music(intensity))
+        } else if self.has_round_music {
+            // Back to the menu music after the round.
+            None
+        } else {
+            return;
+        };
+
+        self.has_round_music = level.scene.is_some();
+        if let Some(scene) = ctx.scenes.try_get_mut(self.scene) {
+            self.music.crossfade_to(music, &mut scene.graph);
+        }
+    }
+
+    /// Passes the key to the settings menu, if it waits for a new key binding. Returns `true` if
+    /// the key was consumed.
+    pub fn capture_key(
+        &self,
+        event: &KeyEvent,
+        ui: &UserInterface,
+        settings: &mut Settings,
+    ) -> bool {
+        self.settings_menu.capture_key(event, ui, settings)
+    }
+
+    /// Shows the scoreboard of the round while `shown` is `true`. The scoreboard is a part of the
+    /// in-game menu, so it is never shown outside of a round.
+    pub fn set_scoreboard_shown(&mut self, ui: &UserInterface, shown: bool) {
+        self.in_game_menu.set_scoreboard_shown(ui, shown);
+    }
+
+    /// Plays the given track instead of the current music, see [`MusicManager::play`].
+    pub fn play_music(&mut self, music: SoundBufferResource, scenes: &mut SceneContainer) {
+        if let Some(scene) = scenes.try_get_mut(self.scene) {
+            self.music.play(music, &mut scene.graph);
+        }
+    }
+
+    /// Switches back from the track of [`Self::play_music`] to the current music.
+    pub fn stop_music(&mut self, scenes: &mut SceneContainer) {
+        if let Some(scene) = scenes.try_get_mut(self.scene) {
+            self.music.stop(&mut scene.graph);
+        }
+    }
+
+    /// Stops all music immediately, it is used when the game is stopped.
+    pub fn silence_music(&mut self, scenes: &mut SceneContainer) {
+        if let Some(scene) = scenes.try_get_mut(self.scene) {
+            self.music.stop_all(&mut scene.graph);
+        }
+    }
+
+    fn reset_level_theme(&mut self, ctx: &mut PluginContext) {
+        if self.has_level_theme {
+            self.set_level_theme(None, None, ctx);
+            self.has_level_theme = false;
         }
     }
 
@@ -610,7 +2490,13 @@ impl Menu {
                 if let Some(window_target) = ctx.window_target {
                     window_target.exit();
                 }
+            } else if message.destination() == self.next_round {
+                if let Some(Err(reason)) = server.as_mut().map(|s| s.start_next_round()) {
+                    Log::err(&reason);
+                    show_message_box(ctx.user_interfaces.first_mut(), "Error", &reason);
+                }
             } else if message.destination() == self.start_as_server {
+                self.server_menu.request_level_theme(ctx);
                 set_visibility(
                     ctx.user_interfaces.first(),
                     &[
@@ -624,16 +2510,39 @@ impl Menu {
                     Server::LOCALHOST.to_string(),
                 ));
 
-                // Try to start the server and the client.
-                match Server::new(&self.server_menu.server_address) {
-                    Ok(new_server) => {
+                // Try to start the server and the client. The host plays through its own client,
+                // so the server is shut down if the client can't connect to it.
+                let address = self.server_menu.server_address.clone();
+                let result = Server::new(&address)
+                    .map_err(|err| format!("Unable to create a server. Reason: {:?}", err))
+                    .and_then(|mut new_server| {
+                        self.server_menu.configure_server(&mut new_server);
+                        let new_client = connect_local_client(&address)?;
+                        new_server.accept_connections();
+                        Ok((new_server, new_client))
+                    });
+                match result {
+                    Ok((new_server, new_client)) => {
                         *server = Some(new_server);
-                        *client = try_connect_to_server(&self.server_menu.server_address);
-                        let server = server.as_mut().unwrap();
-                        server.accept_connections();
+                        *client = Some(new_client);
+                    }
+                    Err(reason) => {
+                        Log::err(&reason);
+                        *server = None;
+                        *client = None;
+                        set_visibility(
+                            ctx.user_interfaces.first(),
+                            &[
+                                (self.server_menu.self_handle, false),
+                                (self.main_menu, true),
+                            ],
+                        );
+                        self.reset_level_theme(ctx);
+                        show_message_box(ctx.user_interfaces.first_mut(), "Error", &reason);
                     }
-                    Err(err) => Log::err(format!("Unable to create a server. Reason: {:?}", err)),
                 }
+            } else if message.destination() == self.server_menu.back {
+                self.reset_level_theme(ctx);
             } else if message.destination() == self.start_as_client {
                 *client = try_connect_to_server(&self.server_menu.server_address);
             } else if message.destination() == self.settings {
@@ -644,6 +2553,16 @@ impl Menu {
             }
         }
 
+        if let Some(CheckBoxMessage::Check(Some(value))) = message.data() {
+            if message.destination() == self.ready_check_box
+                && message.direction() == MessageDirection::FromWidget
+            {
+                if let Some(client) = client {
+                    client.set_ready(*value);
+                }
+            }
+        }
+
         if let Some(scene) = ctx.scenes.try_get_mut(self.scene) {
             let graph = &mut scene.graph;
             if let Some(WidgetMessage::MouseDown { .. }) = message.data() {
@@ -662,6 +2581,11 @@ impl Menu {
         ));
     }
 
+    /// Shows a modal message box with the given text, it is removed when closed.
+    pub fn show_message(&self, ui: &mut UserInterface, title: &str, text: &str) {
+        show_message_box(ui, title, text)
+    }
+
     pub fn set_main_menu_visibility(&self, ui: &UserInterface, visible: bool) {
         ui.send_message(WidgetMessage::visibility(
             self.main_menu,
@@ -678,11 +2602,19 @@ impl Menu {
                 (self.main_menu_root, !is_visible),
                 (self.main_menu, !is_visible),
                 (self.server_menu.self_handle, false),
-                (self.background, !is_client_running),
+                (
+                    self.background,
+                    !is_client_running && self.has_background(ui),
+                ),
             ],
         );
     }
 
+    fn has_background(&self, ui: &UserInterface) -> bool {
+        ui.try_get_of_type::<Image>(self.background)
+            .map_or(false, |image| image.texture.is_some())
+    }
+
     pub fn is_active(&self, ui: &UserInterface) -> bool {
         ui.try_get(self.main_menu_root)
             .map(|n| n.is_globally_visible())
@@ -690,29 +2622,108 @@ impl Menu {
     }
 
     pub fn update(
-        &self,
+        &mut self,
         ctx: &mut PluginContext,
-        server: &Option<Server>,
+        server: &mut Option<Server>,
         client: &Option<Client>,
         level: &mut Level,
+        settings: &Settings,
+        debug_settings: &DebugSettings,
     ) {
         self.server_menu.update(ctx, server);
 
+        // The host starts the match, so only peers in a lobby could mark themselves as ready.
+        let ui = ctx.user_interfaces.first();
+        let is_peer_in_lobby = client.is_some() && server.is_none() && level.scene.is_none();
+        if ui.node(self.ready_panel).visibility() != is_peer_in_lobby {
+            ui.send_message(WidgetMessage::visibility(
+                self.ready_panel,
+                MessageDirection::ToWidget,
+                is_peer_in_lobby,
+            ));
+        }
+        // Readiness is reset at the end of every match.
+        let is_ready = client.as_ref().map_or(false, |client| client.is_ready());
+        if ui
+            .try_get_of_type::<CheckBox>(self.ready_check_box)
+            .map_or(false, |check_box| *check_box.checked != Some(is_ready))
+        {
+            ui.send_message(CheckBoxMessage::checked(
+                self.ready_check_box,
+                MessageDirection::ToWidget,
+                Some(is_ready),
+            ));
+        }
+
+        // Level theme is a lobby-only thing.
+        if level.scene.is_some() {
+            self.reset_level_theme(ctx);
+        }
+        self.update_round_music(ctx, client, level);
+
+        if let Some(scene) = ctx.scenes.try_get_mut(self.scene) {
+            self.music.update(ctx.dt, &mut scene.graph);
+        }
+
+        self.render_scaler.update(
+            settings.read().render_scale(),
+            ctx.scenes.try_get_mut(level.scene),
+            ctx.user_interfaces.first_mut(),
+        );
+
         if let GraphicsContext::Initialized(graphics_context) = ctx.graphics_context {
             let fps = graphics_context.renderer.get_statistics().frames_per_second;
+            let resolution = self.render_scaler.resolution();
             ctx.user_interfaces.first().send_message(TextMessage::text(
                 self.debug_text,
                 MessageDirection::ToWidget,
-                format!("FPS: {fps}"),
+                format!("FPS: {fps}\nResolution: {}x{}", resolution.x, resolution.y),
             ));
         }
 
         if let Some(scene) = ctx.scenes.try_get_mut(self.scene) {
             scene.graph[self.root_scene_node].set_visibility(level.scene.is_none());
 
-            let mut is_in_win_state = false;
-            if let Some(client) = client {
-                is_in_win_state = client.win_context.is_some();
+            let win_context = client.as_ref().and_then(|c| c.win_context.as_ref());
+            let is_in_win_state = win_context.is_some();
+            let countdown = win_context
+                .and_then(|c| c.next_round_in)
+                .map(|time| time.ceil() as u32);
+
+            if self.is_showing_results != is_in_win_state || self.results_countdown != countdown {
+                self.is_showing_results = is_in_win_state;
+                self.results_countdown = countdown;
+                let ui = ctx.user_interfaces.first();
+                if let Some(win_context) = win_context {
+                    let mut text = String::new();
+                    for player in win_context.players.iter() {
+                        if player.eliminated {
+                            text += &format!("{} (eliminated)\n", player.name);
+                        } else {
+                            text += &format!("{}. {}\n", player.place, player.name);
+                        }
+                    }
+                    text += &format!("Seed: {}", win_context.seed);
+                    match countdown {
+                        Some(countdown) => text += &format!("\nNext round in {countdown}"),
+                        None => text += "\nWaiting for the host to start the next round",
+                    }
+                    ui.send_message(TextMessage::text(
+                        self.results_text,
+                        MessageDirection::ToWidget,
+                        text,
+                    ));
+                }
+                ui.send_message(WidgetMessage::visibility(
+                    self.results_text,
+                    MessageDirection::ToWidget,
+                    is_in_win_state,
+                ));
+                ui.send_message(WidgetMessage::visibility(
+                    self.next_round,
+                    MessageDirection::ToWidget,
+                    server.as_ref().map_or(false, |s| s.is_showing_results()),
+                ));
             }
 
             scene.graph[self.win_camera].set_enabled(is_in_win_state);
@@ -723,7 +2734,41 @@ impl Menu {
             ctx.user_interfaces.first(),
             ctx.scenes.try_get_mut(level.scene).map(|s| &s.graph),
             level,
+            &settings.read().key_bindings,
+        );
+        self.in_game_menu.update_scoreboard(
+            ctx.user_interfaces.first_mut(),
+            ctx.scenes.try_get(level.scene).map(|s| &s.graph),
+            level,
+            ctx.resource_manager,
         );
+        let mut overlay_ctx = OverlayContext {
+            dt: ctx.dt,
+            parent: self.in_game_menu.root,
+            ui: ctx.user_interfaces.first_mut(),
+            graph: ctx.scenes.try_get(level.scene).map(|s| &s.graph),
+            level,
+            resource_manager: ctx.resource_manager,
+        };
+        self.in_game_menu
+            .name_tags
+            .update(&mut overlay_ctx, &settings.read());
+        self.in_game_menu
+            .debug_overlay
+            .update(&mut overlay_ctx, debug_settings.show_actor_info);
+
+        // Announce the final seconds of the round. The timer is replicated, so it works the same on
+        // clients.
+        let crossed_final_seconds = matches!(
+            (self.last_match_timer, level.match_timer),
+            (Some(last), Some(current)) if last > FINAL_SECONDS && current <= FINAL_SECONDS
+        );
+        self.last_match_timer = level.match_timer;
+        if crossed_final_seconds {
+            if let Some(scene) = ctx.scenes.try_get_mut(self.scene) {
+                utils::try_play_sound(self.clock_ticking, &mut scene.graph);
+            }
+        }
 
         while let Ok(event) = self.receiver.try_recv() {
             if let Some(game_scene) = ctx.scenes.try_get_mut(level.scene) {
@@ -750,3 +2795,52 @@ impl Menu {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Leaderboard;
+
+    #[test]
+    fn scoreboard_shows_places_in_finish_order() {
+        let names = ["Ann", "Bob", "Cid", "Dan", "Eve"];
+        let actors = (0..names.len() as u32)
+            .map(|i| Handle::new(i, 1))
+            .collect::<Vec<_>>();
+        let mut leaderboard = Leaderboard::default();
+        for (position, actor) in actors.iter().enumerate() {
+            leaderboard.entries.insert(
+                *actor,
+                LeaderBoardEntry {
+                    actor: *actor,
+                    real_time_position: position,
+                    ..Default::default()
+                },
+            );
+        }
+        leaderboard.finish(actors[2]);
+        leaderboard.finish(actors[4]);
+        leaderboard.eliminate(actors[0]);
+
+        let standings = actors
+            .iter()
+            .zip(names)
+            .map(|(actor, name)| Standing {
+                name: name.to_string(),
+                is_local: name == "Dan",
+                entry: leaderboard.entries[actor].clone(),
+            })
+            .collect();
+        assert_eq!(
+            standing_rows(standings),
+            [
+                "Standings (5 actors)",
+                "1. Cid - Finished",
+                "2. Eve - Finished",
+                "Bob - Racing",
+                "Dan (you) - Racing",
+                "Ann - Eliminated",
+            ]
+        );
+    }
+}
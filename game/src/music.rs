@@ -0,0 +1,181 @@
+//! Menu music, that could be temporarily replaced with some other track (for example, with the
+//! theme of a level selected in the lobby or with the music of a running round). Tracks are always
+//! switched with crossfading to prevent audio pops. Other systems could temporarily play their own
+//! track over the music of the current state (for example, a stinger at the end of a round), see
+//! [`MusicManager::play`].
+
+use crate::{client::RoundPhase, level::Level};
+use fyrox::{
+    core::pool::Handle,
+    graph::BaseSceneGraph,
+    scene::{
+        base::BaseBuilder,
+        graph::Graph,
+        node::Node,
+        sound::{Sound, SoundBufferResource, SoundBuilder, Status},
+    },
+};
+
+/// Intensity of the music of a round, see [`crate::level_config::LevelConfig::round_music`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MusicIntensity {
+    /// The round is not running yet (or is already over).
+    Calm,
+    /// The round is running.
+    Energetic,
+    /// The final seconds of the round or sudden death.
+    Tense,
+}
+
+impl MusicIntensity {
+    /// Picks intensity of the music for the current state of the round. The match timer is
+    /// replicated and compensated for latency (see [`crate::clock`]), so every peer shifts at about
+    /// the same moment.
+    pub fn of_round(phase: RoundPhase, level: &Level) -> Self {
+        if phase != RoundPhase::InProgress {
+            Self::Calm
+        } else if level.is_time_critical() || level.is_sudden_death() {
+            Self::Tense
+        } else {
+            Self::Energetic
+        }
+    }
+}
+
+/// Duration (in seconds) of crossfading between tracks.
+const CROSSFADE_TIME: f32 = 1.5;
+const MUSIC_BUS: &str = "Music";
+
+#[derive(Debug)]
+struct Track {
+    node: Handle<Node>,
+    /// Gain of the track at the moment it was added.
+    base_gain: f32,
+    /// Fade factor in [0; 1] range.
+    fade: f32,
+    fading_in: bool,
+    /// Temporary tracks are removed when fully faded out.
+    temporary: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MusicManager {
+    tracks: Vec<Track>,
+    /// Music of the current state of the game, the default music is used if not set.
+    requested: Option<SoundBufferResource>,
+    /// A track, that is played instead of the requested music until it is stopped.
+    overridden: Option<SoundBufferResource>,
+}
+
+impl MusicManager {
+    /// Uses every sound of the music bus in the given graph as default music.
+    pub fn new(graph: &Graph) -> Self {
+        let tracks = graph
+            .pair_iter()
+            .filter_map(|(handle, node)| {
+                let sound = node.cast::<Sound>()?;
+                (sound.audio_bus() == MUSIC_BUS).then(|| Track {
+                    node: handle,
+                    base_gain: sound.gain(),
+                    fade: 1.0,
+                    fading_in: true,
+                    temporary: false,
+                })
+            })
+            .collect();
+
+        Self {
+            tracks,
+            ..Default::default()
+        }
+    }
+
+    /// Smoothly switches to the given track, or back to the default music if there's none. The
+    /// switch is postponed while an overriding track is played.
+    pub fn crossfade_to(&mut self, music: Option<SoundBufferResource>, graph: &mut Graph) {
+        self.requested = music;
+        self.switch(graph);
+    }
+
+    /// Smoothly switches to the given track, that overrides the music of the current state until
+    /// [`Self::stop`] is called.
+    pub fn play(&mut self, music: SoundBufferResource, graph: &mut Graph) {
+        self.overridden = Some(music);
+        self.switch(graph);
+    }
+
+    /// Smoothly switches from the overriding track back to the music of the current state.
+    pub fn stop(&mut self, graph: &mut Graph) {
+        self.overridden = None;
+        self.switch(graph);
+    }
+
+    /// Stops every track immediately and removes temporary ones.
+    pub fn stop_all(&mut self, graph: &mut Graph) {
+        for track in self.tracks.drain(..) {
+            if track.temporary {
+                graph.remove_node(track.node);
+            } else if let Some(sound) = graph.try_get_mut_of_type::<Sound>(track.node) {
+                sound.stop();
+            }
+        }
+        self.requested = None;
+        self.overridden = None;
+    }
+
+    fn switch(&mut self, graph: &mut Graph) {
+        let music = self.overridden.clone().or_else(|| self.requested.clone());
+        let current = self
+            .tracks
+            .iter()
+            .find(|track| track.temporary && track.fading_in)
+            .and_then(|track| graph.try_get_of_type::<Sound>(track.node))
+            .and_then(|sound| sound.buffer());
+        if current == music {
+            return;
+        }
+
+        for track in self.tracks.iter_mut() {
+            track.fading_in = music.is_none() && !track.temporary;
+        }
+
+        if let Some(buffer) = music {
+            let node = SoundBuilder::new(BaseBuilder::new().with_name("LevelMusic"))
+                .with_buffer(Some(buffer))
+                .with_looping(true)
+                .with_gain(0.0)
+                .with_status(Status::Playing)
+                .with_audio_bus(MUSIC_BUS.to_string())
+                .build(graph);
+            self.tracks.push(Track {
+                node,
+                base_gain: 1.0,
+                fade: 0.0,
+                fading_in: true,
+                temporary: true,
+            });
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, graph: &mut Graph) {
+        let step = dt / CROSSFADE_TIME;
+        self.tracks.retain_mut(|track| {
+            track.fade = if track.fading_in {
+                (track.fade + step).min(1.0)
+            } else {
+                (track.fade - step).max(0.0)
+            };
+
+            if track.temporary && !track.fading_in && track.fade <= 0.0 {
+                graph.remove_node(track.node);
+                return false;
+            }
+
+            if let Some(sound) = graph.try_get_mut_of_type::<Sound>(track.node) {
+                sound.set_gain(track.base_gain * track.fade);
+            }
+
+            true
+        });
+    }
+}
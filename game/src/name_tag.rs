@@ -0,0 +1,225 @@
+//! Floating name tags above actors. Tags fade out with distance and are hidden when an actor is
+//! behind level geometry, so the screen stays readable in crowded places.
+
+use crate::{level::Level, settings::SettingsData, utils};
+use fyrox::{
+    asset::manager::ResourceManager,
+    core::{
+        algebra::{Point3, Vector2, Vector3},
+        arrayvec::ArrayVec,
+        pool::Handle,
+    },
+    fxhash::{FxHashMap, FxHashSet},
+    graph::{BaseSceneGraph, SceneGraph},
+    gui::{
+        canvas::CanvasBuilder,
+        font::Font,
+        message::MessageDirection,
+        text::TextBuilder,
+        widget::{WidgetBuilder, WidgetMessage},
+        HorizontalAlignment, UiNode, UserInterface, VerticalAlignment,
+    },
+    scene::{
+        collider::Collider,
+        graph::{physics::RayCastOptions, Graph},
+        node::Node,
+    },
+};
+
+/// Height (in meters) of a tag above the center of an actor's body.
+const TAG_HEIGHT: f32 = 1.2;
+
+/// Amount of time (in seconds) between occlusion checks. Ray casts are relatively expensive, and
+/// there could be dozens of actors on a level.
+const OCCLUSION_CHECK_INTERVAL: f32 = 0.2;
+
+/// Everything, that is needed to update widgets, that are drawn over actors.
+pub struct OverlayContext<'a> {
+    pub dt: f32,
+    /// A widget, to which widgets of an overlay are attached.
+    pub parent: Handle<UiNode>,
+    pub ui: &'a mut UserInterface,
+    /// Graph of the level scene, if it is loaded.
+    pub graph: Option<&'a Graph>,
+    pub level: &'a Level,
+    pub resource_manager: &'a ResourceManager,
+}
+
+#[derive(Debug)]
+struct NameTag {
+    widget: Handle<UiNode>,
+    occluded: bool,
+}
+
+#[derive(Default, Debug)]
+pub struct NameTags {
+    /// Tags are positioned manually, so they're placed on a canvas.
+    canvas: Handle<UiNode>,
+    tags: FxHashMap<Handle<Node>, NameTag>,
+    occlusion_timer: f32,
+}
+
+/// Returns `true` if there's something (except actors) between the camera and the given point.
+fn is_occluded(
+    camera_position: Vector3<f32>,
+    point: Vector3<f32>,
+    actor_colliders: &FxHashSet<Handle<Node>>,
+    graph: &Graph,
+) -> bool {
+    let delta = point - camera_position;
+    let distance = delta.norm();
+    let Some(direction) = delta.try_normalize(f32::EPSILON) else {
+        return false;
+    };
+
+    let mut buffer = ArrayVec::<_, 64>::new();
+    graph.physics.cast_ray(
+        RayCastOptions {
+            ray_origin: Point3::from(camera_position),
+            ray_direction: direction,
+            max_len: distance,
+            groups: Default::default(),
+            sort_results: false,
+        },
+        &mut buffer,
+    );
+
+    buffer.iter().any(|intersection| {
+        if actor_colliders.contains(&intersection.collider) {
+            return false;
+        }
+
+        // Filter out ragdoll colliders, they're parts of actors as well.
+        graph
+            .try_get_of_type::<Collider>(intersection.collider)
+            .map_or(true, |collider| {
+                collider.collision_groups().memberships.0 & 0b0000_0010 == 0
+            })
+    })
+}
+
+impl NameTags {
+    fn remove_tag(tag: NameTag, ui: &UserInterface) {
+        ui.send_message(WidgetMessage::remove(
+            tag.widget,
+            MessageDirection::ToWidget,
+        ));
+    }
+
+    pub fn clear(&mut self, ui: &UserInterface) {
+        for (_, tag) in self.tags.drain() {
+            Self::remove_tag(tag, ui);
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut OverlayContext, settings: &SettingsData) {
+        let OverlayContext {
+            dt,
+            parent,
+            ref mut ui,
+            graph,
+            level,
+            resource_manager,
+        } = *ctx;
+        let Some((graph, camera)) =
+            graph.and_then(|g| utils::find_active_camera(g).map(|c| (g, c)))
+        else {
+            self.clear(ui);
+            return;
+        };
+
+        if self.canvas.is_none() {
+            self.canvas = CanvasBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+            ui.send_message(WidgetMessage::link(
+                self.canvas,
+                MessageDirection::ToWidget,
+                parent,
+            ));
+        }
+
+        // Remove tags of actors, that were destroyed or eliminated.
+        self.tags.retain(|actor, tag| {
+            let alive = level.actors.contains(actor) && !level.leaderboard.is_eliminated(*actor);
+            if !alive {
+                ui.send_message(WidgetMessage::remove(
+                    tag.widget,
+                    MessageDirection::ToWidget,
+                ));
+            }
+            alive
+        });
+
+        self.occlusion_timer -= dt;
+        let check_occlusion = self.occlusion_timer <= 0.0;
+        if check_occlusion {
+            self.occlusion_timer = OCCLUSION_CHECK_INTERVAL;
+        }
+
+        let actor_colliders = level
+            .iter_actors(graph)
+            .map(|view| view.actor.collider)
+            .collect::<FxHashSet<_>>();
+
+        let camera_position = camera.global_position();
+        let screen_size = ui.screen_size();
+
+        for view in level.iter_actors(graph) {
+            if view.is_local_player() || level.leaderboard.is_eliminated(view.handle) {
+                continue;
+            }
+
+            let Some(position) = view.position(graph) else {
+                continue;
+            };
+            let tag_position = position + Vector3::new(0.0, TAG_HEIGHT, 0.0);
+
+            let tag = self.tags.entry(view.handle).or_insert_with(|| {
+                let widget = TextBuilder::new(WidgetBuilder::new().with_visibility(false))
+                    .with_horizontal_text_alignment(HorizontalAlignment::Center)
+                    .with_vertical_text_alignment(VerticalAlignment::Center)
+                    .with_text(view.name())
+                    .with_font(resource_manager.request::<Font>("data/font.ttf"))
+                    .with_font_size(20.0.into())
+                    .build(&mut ui.build_ctx());
+                ui.send_message(WidgetMessage::link(
+                    widget,
+                    MessageDirection::ToWidget,
+                    self.canvas,
+                ));
+                NameTag {
+                    widget,
+                    occluded: false,
+                }
+            });
+
+            if check_occlusion {
+                tag.occluded = is_occluded(camera_position, tag_position, &actor_colliders, graph);
+            }
+
+            let opacity = settings.name_tag_opacity(camera_position.metric_distance(&tag_position));
+            let screen_position = camera.project(tag_position, screen_size);
+
+            let visible = opacity > 0.0 && !tag.occluded && screen_position.is_some();
+            ui.send_message(WidgetMessage::visibility(
+                tag.widget,
+                MessageDirection::ToWidget,
+                visible,
+            ));
+
+            if let (true, Some(screen_position)) = (visible, screen_position) {
+                // Center the tag above the actor.
+                let size = ui.node(tag.widget).actual_local_size();
+                ui.send_message(WidgetMessage::desired_position(
+                    tag.widget,
+                    MessageDirection::ToWidget,
+                    screen_position - Vector2::new(size.x * 0.5, size.y),
+                ));
+                ui.send_message(WidgetMessage::opacity(
+                    tag.widget,
+                    MessageDirection::ToWidget,
+                    Some(opacity),
+                ));
+            }
+        }
+    }
+}
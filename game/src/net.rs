@@ -1,8 +1,15 @@
 use crate::level::LeaderBoardEntry;
-use crate::{actor::ActorKind, player::InputController};
+use crate::{
+    actor::{ActorKind, StatusEffects},
+    bot::BotDifficulty,
+    custom_message::CustomMessageTag,
+    player::InputController,
+    quantization::QuantizedNodes,
+};
 use fyrox::{
     core::{
         algebra::{UnitQuaternion, Vector3},
+        color::Color,
         pool::Handle,
     },
     fxhash::FxHashMap,
@@ -16,6 +23,36 @@ pub struct NodeState {
     pub node: SceneNodeId,
     pub position: Vector3<f32>,
     pub rotation: UnitQuaternion<f32>,
+    /// Local scale of the node. Delta updates include it only when the scale has changed, so
+    /// it costs nothing for the vast majority of nodes; a node, that is scaled continuously, costs
+    /// 12 more bytes per update.
+    pub scale: Option<Vector3<f32>>,
+}
+
+impl NodeState {
+    /// Returns `true` if the position differs by more than `position_epsilon` (in meters), the
+    /// rotation differs by more than `rotation_epsilon` (in radians) or the scale differs by more
+    /// than `scale_epsilon`.
+    pub fn differs(
+        &self,
+        other: &Self,
+        position_epsilon: f32,
+        rotation_epsilon: f32,
+        scale_epsilon: f32,
+    ) -> bool {
+        self.position.metric_distance(&other.position) > position_epsilon
+            || self.rotation.angle_to(&other.rotation) > rotation_epsilon
+            || self.scale_differs(other, scale_epsilon)
+    }
+
+    /// Returns `true` if the scale differs by more than `epsilon`. Unknown scale is never
+    /// different.
+    pub fn scale_differs(&self, other: &Self, epsilon: f32) -> bool {
+        match (self.scale, other.scale) {
+            (Some(a), Some(b)) => a.metric_distance(&b) > epsilon,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -25,12 +62,45 @@ pub struct InstanceDescriptor {
     pub rotation: UnitQuaternion<f32>,
     pub velocity: Vector3<f32>, // Rigid body only.
     pub ids: FxHashMap<Handle<Node>, SceneNodeId>,
+    /// Lifetime (in seconds) of the instance, after which it will be removed automatically.
+    pub lifetime: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PlayerDescriptor {
     pub instance: InstanceDescriptor,
     pub kind: ActorKind,
+    /// `true` if the actor is simulated on the host, and the receiving client is not the host.
+    pub is_remote: bool,
+    /// Used only for bots.
+    pub difficulty: BotDifficulty,
+    pub color: ActorColor,
+    /// Team of the actor, `None` in free-for-all matches.
+    pub team: Option<u32>,
+}
+
+/// RGB color of an actor.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ActorColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl From<Color> for ActorColor {
+    fn from(color: Color) -> Self {
+        Self {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+        }
+    }
+}
+
+impl From<ActorColor> for Color {
+    fn from(color: ActorColor) -> Self {
+        Color::opaque(color.r, color.g, color.b)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
@@ -39,10 +109,66 @@ pub struct SoundState {
     pub is_playing: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Per-actor state that is needed by clients to render actors correctly.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ActorState {
+    pub node: SceneNodeId,
+    pub status_effects: StatusEffects,
+    pub knocked_out: bool,
+    pub eliminated: bool,
+    pub grounded: bool,
+    pub crouched: bool,
+    pub sliding: bool,
+    /// Instance id of the actor, that is held by this actor.
+    pub grabbing: Option<SceneNodeId>,
+    /// `true` if the actor waits for the respawn button.
+    pub awaiting_respawn: bool,
+    /// `true` if the actor is stunned after a hard landing.
+    pub stunned: bool,
+    /// Instance id of the cannon, that is operated by the actor.
+    pub operated_cannon: Option<SceneNodeId>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UpdateTickMessage {
     pub nodes: Vec<NodeState>,
+    /// Node states quantized by [`crate::quantization::quantize_tick`]. Clients decode them into
+    /// [`Self::nodes`] right after receiving the tick.
+    pub quantized_nodes: Option<QuantizedNodes>,
     pub sounds: Vec<SoundState>,
+    pub actors: Vec<ActorState>,
+    /// Remaining time (in seconds) of the round, see [`crate::level::Level::match_timer`].
+    pub match_timer: Option<f32>,
+    /// `true` if the round is in sudden death, see [`crate::level::Level::is_sudden_death`].
+    pub sudden_death: bool,
+    /// Simulation time (in seconds) of the level, see [`crate::level::Level::time`].
+    pub level_time: f32,
+    /// Time of the server clock (see [`crate::server::Server::clock`]) when the tick was made.
+    pub server_time: f64,
+    /// Sequence number of the tick, clients acknowledge received ticks with it (see
+    /// [`ClientMessage::TickAck`]).
+    pub sequence: u32,
+}
+
+/// Result of an actor in a finished match.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerResult {
+    pub name: String,
+    pub place: usize,
+    pub eliminated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MatchResultsMessage {
+    /// Results sorted by place, eliminated actors go last.
+    pub players: Vec<PlayerResult>,
+    /// Seed of the finished match, so players could share it.
+    pub seed: u64,
+    /// Remaining time (in seconds) before the next round starts automatically. The host starts
+    /// the next round manually if not set.
+    pub next_round_in: Option<f32>,
+    /// Time of the server clock (see [`crate::server::Server::clock`]) when the message was sent.
+    pub server_time: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -53,19 +179,99 @@ pub struct LeaderBoardMessage {
 /// A message sent from the server to a client.
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ServerMessage {
-    LoadLevel { path: PathBuf },
+    LoadLevel {
+        path: PathBuf,
+        /// Seed of the match, see [`crate::level::Level::seed`].
+        seed: u64,
+        /// Unique (per server) id of the match. Clients ignore repeated requests to load the same
+        /// level for the same match, so a new round on the same map must have a new id.
+        match_id: u64,
+        /// Reload the level even if it is already loaded (or being loaded) for the same match.
+        force: bool,
+    },
     UpdateTick(UpdateTickMessage),
+    /// [`Self::UpdateTick`] compressed by [`crate::compression::compress_tick`]. It is sent only to
+    /// clients, that support compression, and is unpacked before handling, so it is never handled
+    /// directly.
+    CompressedUpdateTick {
+        data: Vec<u8>,
+    },
     AddPlayers(Vec<PlayerDescriptor>),
     Instantiate(Vec<InstanceDescriptor>),
+    /// The node was removed from the scene on the server, clients must remove it as well.
+    Despawn {
+        instance_id: SceneNodeId,
+    },
     EndMatch,
+    /// Results of the last match. It is sent right after [`Self::EndMatch`] and to players, that
+    /// join while the results are shown.
+    MatchResults(MatchResultsMessage),
+    /// The match could not be started (or continued), clients should return to the main menu.
+    AbortMatch {
+        reason: String,
+    },
     LeaderBoard(LeaderBoardMessage),
+    /// Sent periodically to let clients know that the server is still alive.
+    KeepAlive,
+    /// Asks the client to respond with [`ClientMessage::Pong`], so the server could measure the
+    /// round-trip time of the connection.
+    Ping {
+        nonce: u32,
+    },
+    /// A response to [`ClientMessage::SyncClock`].
+    SyncClock {
+        /// The client time from the request.
+        client_time: f64,
+        /// Time of the server clock when the request was handled.
+        server_time: f64,
+    },
+    /// A message of an extension of the game, see [`crate::custom_message`].
+    Custom {
+        tag: CustomMessageTag,
+        data: Vec<u8>,
+    },
 }
 
 /// A message sent from a client to the server.
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ClientMessage {
+    /// Sent right after connecting to tell the server about the capabilities of the client.
+    Hello {
+        /// The client could unpack [`ServerMessage::CompressedUpdateTick`].
+        supports_compression: bool,
+    },
     Input {
         player: SceneNodeId,
         input_state: InputController,
+        /// Sequence number of the input, it is incremented for every input message sent by a
+        /// client. The server ignores inputs, that are older than the last received one.
+        sequence: u32,
+    },
+    /// Sent periodically to let the server know that the client is still alive.
+    KeepAlive,
+    /// A response to [`ServerMessage::Ping`] with the same nonce.
+    Pong { nonce: u32 },
+    /// Sent when the client has loaded the level. The server responds with a full snapshot of the
+    /// level state, because the client could miss some updates while it was loading.
+    LevelLoaded,
+    /// Acknowledges the latest received update tick. The server stops sending ticks to clients,
+    /// that are too far behind, see [`crate::server::Server::max_send_lag`].
+    TickAck { sequence: u32 },
+    /// Marks the player as ready (or not) to start the match. It is reset at the end of every
+    /// match.
+    Ready { ready: bool },
+    /// Selects the model of the player's actor (see [`crate::server::player_models`]). Unknown
+    /// models are rejected by the server, and the default model is used.
+    SelectModel { path: PathBuf },
+    /// Asks the server for its time, so the client could estimate the offset between the clocks
+    /// (see [`crate::clock`]).
+    SyncClock {
+        /// Time of the client clock when the request was sent.
+        client_time: f64,
+    },
+    /// A message of an extension of the game, see [`crate::custom_message`].
+    Custom {
+        tag: CustomMessageTag,
+        data: Vec<u8>,
     },
 }
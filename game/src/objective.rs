@@ -0,0 +1,205 @@
+//! Objectives made of groups of targets. Targets with the same group id (see
+//! [`Target::group`](crate::target::Target::group)) count toward a shared objective of the level,
+//! for example "reach every target of group 1" or "reach any 2 targets of group 2". An actor
+//! qualifies once it has completed every objective of the level. Progress is tracked by the server
+//! and replicated to clients with the leaderboard.
+
+use crate::{actor::Actor, target::Target};
+use fyrox::{
+    core::{pool::Handle, reflect::prelude::*, type_traits::prelude::*, visitor::prelude::*},
+    fxhash::{FxHashMap, FxHashSet},
+    graph::{BaseSceneGraph, SceneGraph},
+    scene::{graph::Graph, node::Node},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Visit, Reflect, TypeUuidProvider)]
+#[type_uuid(id = "7c2e9b41-5d8a-4f3e-b6a1-93e0d4c58f27")]
+pub struct TargetObjective {
+    #[reflect(description = "Group of targets, that must be reached.")]
+    pub group: u32,
+    #[reflect(
+        description = "Amount of targets of the group, that must be reached. Zero means every \
+        target of the group."
+    )]
+    pub required: u32,
+}
+
+/// Progress of an actor on an objective.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct ObjectiveProgress {
+    pub group: u32,
+    pub reached: u32,
+    pub required: u32,
+}
+
+impl ObjectiveProgress {
+    pub fn is_complete(&self) -> bool {
+        self.reached >= self.required
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct TargetProgress {
+    /// Grouped targets, that were reached by every actor.
+    reached: FxHashMap<Handle<Node>, FxHashSet<Handle<Node>>>,
+}
+
+impl TargetProgress {
+    /// Records targets, that are reached by actors at the moment, and returns the progress of
+    /// every actor on the given objectives. Objectives with groups without any target are ignored.
+    /// `targets` and `actors` must be in deterministic order.
+    pub fn update(
+        &mut self,
+        objectives: &[TargetObjective],
+        targets: &[Handle<Node>],
+        actors: &[Handle<Node>],
+        graph: &Graph,
+    ) -> Vec<(Handle<Node>, Vec<ObjectiveProgress>)> {
+        let mut group_sizes = FxHashMap::<u32, u32>::default();
+        let grouped_targets = targets
+            .iter()
+            .filter_map(|handle| {
+                let target = graph.try_get_script_of::<Target>(*handle)?;
+                let group = (*target.group)?;
+                *group_sizes.entry(group).or_default() += 1;
+                Some((*handle, group, *target.reach_radius))
+            })
+            .collect::<Vec<_>>();
+
+        let mut result = Vec::with_capacity(actors.len());
+        for actor_handle in actors.iter().copied() {
+            let Some(actor) = graph.try_get_script_component_of::<Actor>(actor_handle) else {
+                continue;
+            };
+            let Some(actor_position) = graph
+                .try_get(actor.rigid_body)
+                .map(|rigid_body| rigid_body.global_position())
+            else {
+                continue;
+            };
+
+            let reached = self.reached.entry(actor_handle).or_default();
+            for (target_handle, _, reach_radius) in grouped_targets.iter() {
+                let target_position = graph[*target_handle].global_position();
+                if actor_position.metric_distance(&target_position) <= *reach_radius {
+                    reached.insert(*target_handle);
+                }
+            }
+
+            let progress = objectives
+                .iter()
+                .filter_map(|objective| {
+                    let group_size = *group_sizes.get(&objective.group)?;
+                    let required = if objective.required == 0 {
+                        group_size
+                    } else {
+                        objective.required.min(group_size)
+                    };
+                    let reached_count = grouped_targets
+                        .iter()
+                        .filter(|(handle, group, _)| {
+                            *group == objective.group && reached.contains(handle)
+                        })
+                        .count() as u32;
+                    Some(ObjectiveProgress {
+                        group: objective.group,
+                        reached: reached_count,
+                        required,
+                    })
+                })
+                .collect::<Vec<_>>();
+            result.push((actor_handle, progress));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+    use fyrox::{
+        core::algebra::Vector3,
+        scene::{
+            base::BaseBuilder, pivot::PivotBuilder, rigidbody::RigidBodyBuilder,
+            transform::TransformBuilder,
+        },
+        script::Script,
+    };
+
+    fn add_target(group: u32, x: f32, graph: &mut Graph) -> Handle<Node> {
+        let target = Target {
+            group: Some(group).into(),
+            ..Default::default()
+        };
+        PivotBuilder::new(
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vector3::new(x, 0.0, 0.0))
+                        .build(),
+                )
+                .with_script(Script::new(target)),
+        )
+        .build(graph)
+    }
+
+    fn move_to(node: Handle<Node>, x: f32, graph: &mut Graph) {
+        graph[node]
+            .local_transform_mut()
+            .set_position(Vector3::new(x, 0.0, 0.0));
+        graph.update_hierarchical_data();
+    }
+
+    #[test]
+    fn group_is_complete_once_every_target_is_reached() {
+        let mut graph = Graph::new();
+        let targets = [
+            add_target(1, 10.0, &mut graph),
+            add_target(1, 20.0, &mut graph),
+            add_target(2, 30.0, &mut graph),
+            add_target(2, 40.0, &mut graph),
+        ];
+        let rigid_body = RigidBodyBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let mut bot = Bot::default();
+        bot.actor.rigid_body = rigid_body;
+        let actor =
+            PivotBuilder::new(BaseBuilder::new().with_script(Script::new(bot))).build(&mut graph);
+        graph.update_hierarchical_data();
+
+        // Every target of group 1 and any one target of group 2.
+        let objectives = [
+            TargetObjective {
+                group: 1,
+                required: 0,
+            },
+            TargetObjective {
+                group: 2,
+                required: 1,
+            },
+        ];
+        let mut progress = TargetProgress::default();
+        let mut completion = |graph: &Graph| {
+            let result = progress.update(&objectives, &targets, &[actor], graph);
+            result[0]
+                .1
+                .iter()
+                .map(|p| (p.reached, p.required, p.is_complete()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(completion(&graph), [(0, 2, false), (0, 1, false)]);
+
+        // Partially reached group is not complete.
+        move_to(rigid_body, 10.0, &mut graph);
+        assert_eq!(completion(&graph), [(1, 2, false), (0, 1, false)]);
+
+        // Reached targets are remembered after the actor has left them.
+        move_to(rigid_body, 20.0, &mut graph);
+        assert_eq!(completion(&graph), [(2, 2, true), (0, 1, false)]);
+
+        move_to(rigid_body, 40.0, &mut graph);
+        assert_eq!(completion(&graph), [(2, 2, true), (1, 1, true)]);
+    }
+}
@@ -3,7 +3,11 @@
 use crate::actor::ActorKind;
 use crate::{
     actor::{Actor, ActorMessage},
-    net::ClientMessage,
+    camera::{RagdollCameraMode, SpectatorTransition},
+    cannon::Cannon,
+    key_bindings::{KeyAction, KeyBindings},
+    level::Level,
+    settings::SettingsData,
     CameraController, Event, Game,
 };
 use fyrox::{
@@ -18,8 +22,7 @@ use fyrox::{
     },
     event::{DeviceEvent, ElementState, MouseButton, WindowEvent},
     graph::{BaseSceneGraph, SceneGraph},
-    keyboard::{KeyCode, PhysicalKey},
-    scene::{camera::Camera, node::Node, rigidbody::RigidBody},
+    scene::{camera::Camera, graph::Graph, node::Node, rigidbody::RigidBody},
     script::{
         ScriptContext, ScriptDeinitContext, ScriptMessageContext, ScriptMessagePayload, ScriptTrait,
     },
@@ -27,65 +30,127 @@ use fyrox::{
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
 
-#[derive(Clone, Default, Debug, Visit, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Visit, Serialize, Deserialize)]
 pub struct InputController {
     pub move_forward: bool,
     pub move_backward: bool,
     pub move_left: bool,
     pub move_right: bool,
     pub jump: bool,
+    pub sprint: bool,
+    pub crouch: bool,
+    pub grab: bool,
+    /// Respawn button, it is used only when the actor waits for a manual respawn.
+    pub respawn: bool,
+    /// Respawn preference of the player (see [`crate::settings::SettingsData::manual_respawn`]),
+    /// it travels with the input, so the server knows it for every player.
+    pub manual_respawn: bool,
+    /// Ragdoll camera preference of the player (see
+    /// [`crate::settings::SettingsData::ragdoll_camera`]). The camera of every player is driven by
+    /// the server, so the preference travels with the input as well.
+    pub ragdoll_camera: RagdollCameraMode,
+    /// Spectator camera transition preferences of the player (see
+    /// [`crate::settings::SettingsData::spectator_transition`]).
+    pub spectator_transition: SpectatorTransition,
+    pub spectator_transition_speed: f32,
     pub target_pitch: f32,
     pub target_yaw: f32,
 }
 
+impl Default for InputController {
+    fn default() -> Self {
+        Self {
+            move_forward: false,
+            move_backward: false,
+            move_left: false,
+            move_right: false,
+            jump: false,
+            sprint: false,
+            crouch: false,
+            grab: false,
+            respawn: false,
+            manual_respawn: false,
+            ragdoll_camera: Default::default(),
+            spectator_transition: Default::default(),
+            spectator_transition_speed: 1.0,
+            target_pitch: 0.0,
+            target_yaw: 0.0,
+        }
+    }
+}
+
 impl InputController {
+    /// Returns `true` if any of the buttons has different state in the given input. Look angles
+    /// are not compared.
+    pub fn buttons_differ(&self, other: &Self) -> bool {
+        self.move_forward != other.move_forward
+            || self.move_backward != other.move_backward
+            || self.move_left != other.move_left
+            || self.move_right != other.move_right
+            || self.jump != other.jump
+            || self.sprint != other.sprint
+            || self.crouch != other.crouch
+            || self.grab != other.grab
+            || self.respawn != other.respawn
+            || self.manual_respawn != other.manual_respawn
+            || self.ragdoll_camera != other.ragdoll_camera
+            || self.spectator_transition != other.spectator_transition
+            || self.spectator_transition_speed != other.spectator_transition_speed
+    }
+
+    /// Copies the preferences of the player from the settings, returns `true` if any of them has
+    /// changed, so the input must be sent to the server.
+    pub fn sync_preferences(&mut self, settings: &SettingsData) -> bool {
+        let previous = self.clone();
+        self.manual_respawn = settings.manual_respawn;
+        self.ragdoll_camera = settings.ragdoll_camera;
+        self.spectator_transition = settings.spectator_transition;
+        self.spectator_transition_speed = settings.spectator_transition_speed();
+        self.buttons_differ(&previous)
+    }
+
+    /// Handles the given OS event. `aiming` selects the aim sensitivity from the settings instead
+    /// of the regular one, see [`Actor::is_aiming`].
     pub fn on_os_event(
         &mut self,
         event: &Event<()>,
         pitch_range: &Range<f32>,
+        aiming: bool,
         dt: f32,
-        mouse_sens: f32,
         game: &Game,
-        spectator_target: &mut Handle<Node>,
     ) -> bool {
         if let Event::WindowEvent { event, .. } = event {
             match event {
                 WindowEvent::KeyboardInput { event, .. } => {
-                    if let PhysicalKey::Code(keycode) = event.physical_key {
+                    let settings = game.settings.read();
+                    if let Some(action) = settings.key_bindings.action(event.physical_key) {
                         let state = event.state == ElementState::Pressed;
-                        match keycode {
-                            KeyCode::KeyW => {
-                                self.move_forward = state;
-                                return true;
-                            }
-                            KeyCode::KeyS => {
-                                self.move_backward = state;
-                                return true;
-                            }
-                            KeyCode::KeyA => {
-                                self.move_left = state;
-                                return true;
-                            }
-                            KeyCode::KeyD => {
-                                self.move_right = state;
-                                return true;
-                            }
-                            KeyCode::Space => {
-                                self.jump = state;
-                                return true;
-                            }
-                            _ => (),
-                        }
-                    }
-                }
-                WindowEvent::MouseInput { state, button, .. } => {
-                    if *button == MouseButton::Left && *state == ElementState::Pressed {
-                        for actor in &game.level.actors {
-                            if *actor != *spectator_target {
-                                *spectator_target = *actor;
-                                break;
+                        match action {
+                            KeyAction::MoveForward => self.move_forward = state,
+                            KeyAction::MoveBackward => self.move_backward = state,
+                            KeyAction::MoveLeft => self.move_left = state,
+                            KeyAction::MoveRight => self.move_right = state,
+                            KeyAction::Jump => self.jump = state,
+                            KeyAction::Crouch => self.crouch = state,
+                            KeyAction::Grab => self.grab = state,
+                            KeyAction::Respawn => self.respawn = state,
+                            // Handled by the menu, the key does not control the player.
+                            KeyAction::Scoreboard => return false,
+                            // Handled by the spectator camera, see `on_spectator_event`.
+                            KeyAction::SpectatorCamera => return false,
+                            // Handled by the game, see `Game::on_os_event`.
+                            KeyAction::PhysicsDebug => return false,
+                            KeyAction::Sprint => {
+                                if settings.toggle_sprint {
+                                    if state && !event.repeat {
+                                        self.sprint = !self.sprint;
+                                    }
+                                } else {
+                                    self.sprint = state;
+                                }
                             }
                         }
+                        return true;
                     }
                 }
                 _ => (),
@@ -95,15 +160,64 @@ impl InputController {
             ..
         } = event
         {
-            self.target_yaw -= delta.0 as f32 * mouse_sens * dt;
-            self.target_pitch = (self.target_pitch + delta.1 as f32 * mouse_sens * dt)
-                .clamp(pitch_range.start.to_radians(), pitch_range.end.to_radians());
+            self.on_mouse_motion(*delta, &game.settings.read(), pitch_range, aiming, dt);
             return true;
         }
         false
     }
+
+    /// Turns the camera by the given mouse motion. `aiming` selects the aim sensitivity, see
+    /// [`Self::on_os_event`].
+    fn on_mouse_motion(
+        &mut self,
+        delta: (f64, f64),
+        settings: &SettingsData,
+        pitch_range: &Range<f32>,
+        aiming: bool,
+        dt: f32,
+    ) {
+        // Pitch range of the player is further limited by the limits from the settings.
+        let (min_pitch, max_pitch) = settings.camera_pitch_limits();
+        let min_pitch = pitch_range.start.max(min_pitch);
+        let max_pitch = pitch_range.end.min(max_pitch).max(min_pitch);
+        let (horizontal_sensitivity, vertical_sensitivity) = if aiming {
+            (
+                settings.aim_sensitivity(),
+                settings.aim_vertical_sensitivity(),
+            )
+        } else {
+            (
+                settings.horizontal_sensitivity(),
+                settings.vertical_sensitivity(),
+            )
+        };
+        self.target_yaw -= delta.0 as f32 * horizontal_sensitivity * dt;
+        self.target_pitch = (self.target_pitch + delta.1 as f32 * vertical_sensitivity * dt)
+            .clamp(min_pitch.to_radians(), max_pitch.to_radians());
+    }
+}
+
+/// Camera mode of a player, that has finished the race or was eliminated.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Visit)]
+pub enum SpectatorMode {
+    /// Follows one of the actors, that are still racing.
+    #[default]
+    Follow,
+    /// Free-flying observer camera.
+    FreeCam,
 }
 
+/// Speed (m/s) of the free spectator camera.
+const FREE_CAM_SPEED: f32 = 10.0;
+
+/// Amount of time (in seconds) without mouse movement, after which the camera starts returning to
+/// the neutral pitch (if auto-centering is enabled in the settings).
+const AUTO_CENTER_DELAY: f32 = 1.5;
+
+/// Max distance to an operated cannon (as a multiple of its operate distance), beyond which the
+/// operator leaves it. Physics could push the operator a bit, it must not drop the control.
+const CANNON_LEAVE_DISTANCE_FACTOR: f32 = 1.5;
+
 #[derive(Clone, Visit, Debug, Reflect, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "deb77c1d-668d-4716-a8f7-04ed09b0b9f6")]
 #[visit(optional)]
@@ -126,6 +240,19 @@ pub struct Player {
     pitch: f32,
     #[reflect(hidden)]
     spectator_target: Handle<Node>,
+    #[reflect(hidden)]
+    spectator_mode: SpectatorMode,
+    /// The actor, that the camera has been moved to (the player itself, if it is not spectating).
+    /// A transition of the camera starts when the followed actor changes. It is `None` until the
+    /// first update, so there's no transition at the start of a round.
+    #[reflect(hidden)]
+    #[visit(skip)]
+    camera_target: Option<Handle<Node>>,
+    #[reflect(hidden)]
+    free_cam_position: Vector3<f32>,
+    #[reflect(hidden)]
+    #[visit(skip)]
+    look_idle_time: f32,
 }
 
 impl Default for Player {
@@ -144,6 +271,288 @@ impl Default for Player {
             },
             pitch: 0.0,
             spectator_target: Default::default(),
+            spectator_mode: Default::default(),
+            camera_target: None,
+            free_cam_position: Default::default(),
+            look_idle_time: 0.0,
+        }
+    }
+}
+
+impl Player {
+    /// Returns `true` if the player watches other actors. It happens when the player is out of the
+    /// round, or waits for the respawn button after a knock out. Movement of the player is ignored
+    /// in both cases, but the respawn button is still sent to the server.
+    fn is_spectating(&self, self_handle: Handle<Node>, level: &Level) -> bool {
+        level.leaderboard.is_out(self_handle) || self.actor.awaiting_respawn
+    }
+
+    /// Returns actors, that could be spectated, in deterministic order. These are the actors that
+    /// are still racing, or all the other actors if nobody is racing.
+    fn spectatable_actors(
+        level: &Level,
+        graph: &Graph,
+        self_handle: Handle<Node>,
+    ) -> Vec<Handle<Node>> {
+        let others = level
+            .ordered_actors(graph)
+            .into_iter()
+            .filter(|actor| *actor != self_handle)
+            .collect::<Vec<_>>();
+        let racing = others
+            .iter()
+            .cloned()
+            .filter(|actor| !level.leaderboard.is_out(*actor))
+            .collect::<Vec<_>>();
+        if racing.is_empty() {
+            others
+        } else {
+            racing
+        }
+    }
+
+    fn cycle_spectator_target(
+        &mut self,
+        level: &Level,
+        graph: &Graph,
+        self_handle: Handle<Node>,
+        forward: bool,
+    ) {
+        let actors = Self::spectatable_actors(level, graph, self_handle);
+        if actors.is_empty() {
+            self.spectator_target = Handle::NONE;
+            return;
+        }
+
+        let next = match actors.iter().position(|a| *a == self.spectator_target) {
+            Some(index) if forward => (index + 1) % actors.len(),
+            Some(index) => (index + actors.len() - 1) % actors.len(),
+            None => 0,
+        };
+        self.spectator_target = actors[next];
+    }
+
+    /// Picks a new spectator target if the current one is out of the race or has been removed.
+    fn validate_spectator_target(
+        &mut self,
+        level: &Level,
+        graph: &Graph,
+        self_handle: Handle<Node>,
+    ) {
+        let actors = Self::spectatable_actors(level, graph, self_handle);
+        if !actors.contains(&self.spectator_target) {
+            self.cycle_spectator_target(level, graph, self_handle, true);
+        }
+    }
+
+    fn on_spectator_event(
+        &mut self,
+        event: &Event<()>,
+        level: &Level,
+        key_bindings: &KeyBindings,
+        graph: &Graph,
+        self_handle: Handle<Node>,
+    ) {
+        let Event::WindowEvent { event, .. } = event else {
+            return;
+        };
+
+        match event {
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => match button {
+                MouseButton::Left => self.cycle_spectator_target(level, graph, self_handle, true),
+                MouseButton::Right => self.cycle_spectator_target(level, graph, self_handle, false),
+                _ => (),
+            },
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.state == ElementState::Pressed
+                    && !event.repeat
+                    && key_bindings.action(event.physical_key) == Some(KeyAction::SpectatorCamera)
+                {
+                    self.spectator_mode = match self.spectator_mode {
+                        SpectatorMode::Follow => {
+                            // Start flying from the current camera position.
+                            if let Some(camera_controller) =
+                                graph.try_get_script_component_of::<CameraController>(self.camera)
+                            {
+                                self.free_cam_position = camera_controller.target_position;
+                            }
+                            SpectatorMode::FreeCam
+                        }
+                        SpectatorMode::FreeCam => SpectatorMode::Follow,
+                    };
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Slowly moves the target pitch towards the neutral pitch of the camera, when the player does
+    /// not look around for some time. Returns `true` if the target pitch was changed.
+    fn update_auto_center(&mut self, dt: f32, game: &Game, graph: &Graph) -> bool {
+        let speed = game.settings.read().camera_auto_center_speed();
+        if speed <= 0.0 || self.actor.kind != ActorKind::Player {
+            return false;
+        }
+
+        self.look_idle_time += dt;
+        if self.look_idle_time < AUTO_CENTER_DELAY {
+            return false;
+        }
+
+        let Some(camera_controller) =
+            graph.try_get_script_component_of::<CameraController>(self.camera)
+        else {
+            return false;
+        };
+
+        let neutral_pitch =
+            camera_controller.clamp_pitch(camera_controller.neutral_pitch.to_radians());
+        let delta = neutral_pitch - self.input_controller.target_pitch;
+        if delta.abs() <= f32::EPSILON {
+            return false;
+        }
+
+        let step = speed.to_radians() * dt;
+        self.input_controller.target_pitch += delta.clamp(-step, step);
+        true
+    }
+
+    fn update_free_cam(&mut self, dt: f32) {
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.pitch);
+        let forward = rotation * Vector3::z();
+        let side = rotation * Vector3::x();
+
+        let mut direction = Vector3::default();
+        if self.input_controller.move_forward {
+            direction += forward;
+        }
+        if self.input_controller.move_backward {
+            direction -= forward;
+        }
+        if self.input_controller.move_left {
+            direction += side;
+        }
+        if self.input_controller.move_right {
+            direction -= side;
+        }
+
+        let speed = if self.input_controller.sprint {
+            2.0 * FREE_CAM_SPEED
+        } else {
+            FREE_CAM_SPEED
+        };
+
+        if let Some(direction) = direction.try_normalize(f32::EPSILON) {
+            self.free_cam_position += direction.scale(speed * dt);
+        }
+    }
+
+    /// Takes control of the nearest free operable cannon when the grab button is pressed. The
+    /// operated cannon is left on jump, or when the actor can't operate it anymore (knocked out,
+    /// out of the race, too far, or the cannon is gone).
+    fn update_cannon_control(
+        &mut self,
+        grab_pressed: bool,
+        can_operate: bool,
+        level: &Level,
+        graph: &Graph,
+        self_handle: Handle<Node>,
+    ) {
+        let Some(position) = graph
+            .try_get(self.actor.rigid_body)
+            .map(|rigid_body| rigid_body.global_position())
+        else {
+            return;
+        };
+
+        if let Some(cannon) = self.actor.operated_cannon {
+            let in_range = graph.node_by_id(cannon).map_or(false, |(_, node)| {
+                node.try_get_script::<Cannon>().map_or(false, |cannon| {
+                    node.global_position().metric_distance(&position)
+                        <= cannon.operate_distance() * CANNON_LEAVE_DISTANCE_FACTOR
+                })
+            });
+            if !can_operate || !in_range || self.input_controller.jump {
+                // The jump, that is used to leave the cannon, must not make the actor jump.
+                self.input_controller.jump = false;
+                self.actor.operated_cannon = None;
+            }
+            return;
+        }
+
+        if !grab_pressed || !can_operate {
+            return;
+        }
+
+        let is_taken = |cannon| {
+            level.actors.iter().any(|actor| {
+                *actor != self_handle
+                    && graph
+                        .try_get_script_component_of::<Actor>(*actor)
+                        .map_or(false, |actor| actor.operated_cannon == Some(cannon))
+            })
+        };
+        self.actor.operated_cannon = graph
+            .linear_iter()
+            .filter_map(|node| {
+                let cannon = node.try_get_script::<Cannon>()?;
+                let distance = node.global_position().metric_distance(&position);
+                (cannon.is_operable() && distance <= cannon.operate_distance())
+                    .then(|| (node.instance_id(), distance))
+            })
+            .filter(|(cannon, _)| !is_taken(*cannon))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(cannon, _)| cannon);
+    }
+
+    /// Returns a text for spectator HUD or `None` if the player is not spectating.
+    pub fn spectator_hud_text(
+        &self,
+        self_handle: Handle<Node>,
+        level: &Level,
+        key_bindings: &KeyBindings,
+        graph: &Graph,
+    ) -> Option<String> {
+        if !self.is_spectating(self_handle, level) {
+            return None;
+        }
+
+        let text = match self.spectator_mode {
+            SpectatorMode::FreeCam => format!(
+                "Free Camera\nWASD - move, {} - follow players",
+                key_bindings.key_name(KeyAction::SpectatorCamera)
+            ),
+            SpectatorMode::Follow => {
+                let target = graph.try_get_script_component_of::<Actor>(self.spectator_target)?;
+                let status = if target.awaiting_respawn {
+                    "Waiting for respawn".to_string()
+                } else if target.is_knocked_out() {
+                    "Knocked Out".to_string()
+                } else if let Some(entry) = level.leaderboard.entries.get(&self.spectator_target) {
+                    format!("Place: {}", entry.real_time_position + 1)
+                } else {
+                    "Racing".to_string()
+                };
+                format!(
+                    "Spectating: {} ({status})\nLMB/RMB - switch player, {} - free camera",
+                    target.name,
+                    key_bindings.key_name(KeyAction::SpectatorCamera)
+                )
+            }
+        };
+
+        if self.actor.awaiting_respawn {
+            Some(format!(
+                "{text}\n{} - respawn",
+                key_bindings.key_name(KeyAction::Respawn)
+            ))
+        } else {
+            Some(text)
         }
     }
 }
@@ -166,6 +575,8 @@ impl ScriptTrait for Player {
         ctx.message_dispatcher
             .subscribe_to::<ActorMessage>(ctx.handle);
 
+        self.actor.on_start(ctx);
+
         // Disable camera for remote players, because multiple camera will.
         if let Some(camera_controller) = ctx
             .scene
@@ -203,21 +614,35 @@ impl ScriptTrait for Player {
             return;
         }
 
+        if self.is_spectating(ctx.handle, &game.level) {
+            self.on_spectator_event(
+                event,
+                &game.level,
+                &game.settings.read().key_bindings,
+                &ctx.scene.graph,
+                ctx.handle,
+            );
+        }
+
+        if let Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { .. },
+            ..
+        } = event
+        {
+            self.look_idle_time = 0.0;
+        }
+
         let this = &ctx.scene.graph[ctx.handle];
         if self.input_controller.on_os_event(
             event,
             &self.pitch_range,
+            self.actor.is_aiming(),
             ctx.dt,
-            game.settings.read().mouse_sensitivity,
             game,
-            &mut self.spectator_target,
         ) {
-            if !game.level.leaderboard.is_finished(ctx.handle) {
+            if !game.level.leaderboard.is_out(ctx.handle) {
                 if let Some(client) = game.client.as_mut() {
-                    client.send_message_to_server(ClientMessage::Input {
-                        player: this.instance_id(),
-                        input_state: self.input_controller.clone(),
-                    })
+                    client.queue_input(this.instance_id(), self.input_controller.clone());
                 }
             }
         }
@@ -226,26 +651,103 @@ impl ScriptTrait for Player {
     fn on_update(&mut self, ctx: &mut ScriptContext) {
         let game = ctx.plugins.get_mut::<Game>();
 
+        let is_out = game.level.leaderboard.is_out(ctx.handle);
+        let is_spectating = self.is_spectating(ctx.handle, &game.level);
+
+        if game
+            .client
+            .as_ref()
+            .and_then(|client| client.local_player())
+            == Some(ctx.handle)
+            && self
+                .input_controller
+                .sync_preferences(&game.settings.read())
+        {
+            if let Some(client) = game.client.as_mut() {
+                client.queue_input(
+                    ctx.scene.graph[ctx.handle].instance_id(),
+                    self.input_controller.clone(),
+                );
+            }
+        }
+
         if game.is_client() {
+            // The camera of a client is driven by the server, so the new pitch must be sent to it.
+            if !is_spectating && self.update_auto_center(ctx.dt, game, &ctx.scene.graph) {
+                if let Some(client) = game.client.as_mut() {
+                    client.queue_input(
+                        ctx.scene.graph[ctx.handle].instance_id(),
+                        self.input_controller.clone(),
+                    );
+                }
+            }
+            self.actor
+                .update_client_animation(ctx.dt, &mut ctx.scene.graph);
             return;
         }
 
-        let finished = game.level.leaderboard.is_finished(ctx.handle);
+        if !is_spectating {
+            self.update_auto_center(ctx.dt, game, &ctx.scene.graph);
+        }
+
         let response_speed = (1.0 - game.settings.read().mouse_smoothness).clamp(0.1, 1.0);
         self.pitch += (self.input_controller.target_pitch - self.pitch) * response_speed;
         self.yaw += (self.input_controller.target_yaw - self.yaw) * response_speed;
 
+        if is_spectating {
+            self.validate_spectator_target(&game.level, &ctx.scene.graph, ctx.handle);
+            if self.spectator_mode == SpectatorMode::FreeCam {
+                self.update_free_cam(ctx.dt);
+            }
+        } else {
+            // The camera returns to the player after respawn.
+            self.spectator_mode = SpectatorMode::Follow;
+        }
+
         let self_position = ctx.scene.graph[self.actor.rigid_body].global_position();
-        let spectator_target_position = ctx
+        let spectator_target = ctx
             .scene
             .graph
-            .try_get_script_component_of::<Actor>(self.spectator_target)
-            .and_then(|n| {
-                ctx.scene
-                    .graph
-                    .try_get(n.rigid_body)
-                    .map(|n| n.global_position())
-            });
+            .try_get_script_component_of::<Actor>(self.spectator_target);
+        let spectator_target_position = spectator_target.and_then(|n| {
+            ctx.scene
+                .graph
+                .try_get(n.rigid_body)
+                .map(|n| n.global_position())
+        });
+        let ragdoll_position = if !is_spectating {
+            self.actor.ragdoll_center_of_mass(&ctx.scene.graph)
+        } else if self.spectator_mode == SpectatorMode::Follow {
+            spectator_target.and_then(|n| n.ragdoll_center_of_mass(&ctx.scene.graph))
+        } else {
+            None
+        };
+
+        // A new target (selected by the player, picked after the elimination of the previous one,
+        // or the player itself after respawn) starts a transition of the camera, even if the
+        // previous one is not finished yet.
+        let camera_target = if !is_spectating {
+            ctx.handle
+        } else if self.spectator_mode == SpectatorMode::Follow {
+            self.spectator_target
+        } else {
+            Handle::NONE
+        };
+        let begin_transition = camera_target.is_some()
+            && self
+                .camera_target
+                .map_or(false, |previous| previous != camera_target);
+        self.camera_target = Some(camera_target);
+
+        let aim_anchor = self
+            .actor
+            .operated_cannon
+            .and_then(|cannon| ctx.scene.graph.node_by_id(cannon))
+            .and_then(|(handle, node)| {
+                node.try_get_script::<Cannon>()
+                    .map(|cannon| cannon.camera_anchor(handle))
+            })
+            .unwrap_or_default();
 
         if let Some(camera_controller) = ctx
             .scene
@@ -254,7 +756,20 @@ impl ScriptTrait for Player {
         {
             camera_controller.pitch = self.pitch;
             camera_controller.yaw = self.yaw;
-            if let (true, Some(spectator_target_position)) = (finished, spectator_target_position) {
+            camera_controller.ragdoll_position = ragdoll_position;
+            camera_controller.ragdoll_mode = self.input_controller.ragdoll_camera;
+            camera_controller.aim_anchor = aim_anchor;
+            if begin_transition {
+                camera_controller.begin_transition(
+                    self.input_controller.spectator_transition,
+                    self.input_controller.spectator_transition_speed,
+                );
+            }
+            if is_spectating && self.spectator_mode == SpectatorMode::FreeCam {
+                camera_controller.target_position = self.free_cam_position;
+            } else if let (true, Some(spectator_target_position)) =
+                (is_spectating, spectator_target_position)
+            {
                 // Spectate a player.
                 camera_controller.target_position = spectator_target_position;
             } else {
@@ -262,13 +777,44 @@ impl ScriptTrait for Player {
             }
         }
 
+        // Camera stays interactive while the game is paused.
+        if game.is_paused() {
+            return;
+        }
+
         let has_ground_contact = self.actor.has_ground_contact(&ctx.scene.graph);
         let is_in_jump_state = self.actor.is_in_jump_state(&ctx.scene.graph);
 
         self.actor.target_desired_velocity = Vector3::default();
 
+        let ignore_input = is_out || self.actor.is_knocked_out();
+
+        let grab_pressed = self.input_controller.grab && !self.actor.wants_grab;
+        self.update_cannon_control(
+            grab_pressed,
+            !ignore_input,
+            &game.level,
+            &ctx.scene.graph,
+            ctx.handle,
+        );
+        // The operator stands still and aims the cannon with the camera.
+        let ignore_input = ignore_input || self.actor.is_aiming();
+        if self.actor.is_aiming() {
+            self.actor.aim = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw)
+                * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.pitch);
+        }
+
+        self.actor.wants_grab = !ignore_input && self.input_controller.grab;
+        self.actor.manual_respawn = self.input_controller.manual_respawn;
+        self.actor.wants_respawn = self.input_controller.respawn;
+        self.actor.set_crouch(
+            !ignore_input && self.input_controller.crouch,
+            self.input_controller.sprint,
+            &mut ctx.scene.graph,
+        );
+
         if let Some(rigid_body) = ctx.scene.graph[self.actor.rigid_body].cast_mut::<RigidBody>() {
-            if !finished {
+            if !ignore_input {
                 let forward_vec = rigid_body.look_vector();
                 let side_vec = rigid_body.side_vector();
 
@@ -290,10 +836,15 @@ impl ScriptTrait for Player {
                 .actor
                 .target_desired_velocity
                 .try_normalize(f32::EPSILON)
-                .map(|v| v.scale(self.actor.speed))
+                .map(|v| v.scale(self.actor.movement_speed(self.input_controller.sprint)))
                 .unwrap_or_default();
 
-            if !finished
+            // The slide keeps the momentum of the actor regardless of the input.
+            if let Some(slide_velocity) = self.actor.slide_velocity() {
+                self.actor.target_desired_velocity = slide_velocity;
+            }
+
+            if !ignore_input
                 && self.input_controller.jump
                 && has_ground_contact
                 && !is_in_jump_state
@@ -306,18 +857,21 @@ impl ScriptTrait for Player {
                 self.actor.target_desired_velocity.y = 0.0;
             }
 
-            let is_moving = self.input_controller.move_left
-                || self.input_controller.move_right
-                || self.input_controller.move_forward
-                || self.input_controller.move_backward;
+            let is_moving = !ignore_input
+                && (self.input_controller.move_left
+                    || self.input_controller.move_right
+                    || self.input_controller.move_forward
+                    || self.input_controller.move_backward);
 
             if is_moving {
-                rigid_body
-                    .local_transform_mut()
-                    .set_rotation(UnitQuaternion::from_axis_angle(
+                self.actor.turn_towards(
+                    rigid_body,
+                    UnitQuaternion::from_axis_angle(
                         &Vector3::y_axis(),
                         self.input_controller.target_yaw,
-                    ));
+                    ),
+                    ctx.dt,
+                );
 
                 // Apply additional rotation to model - it will turn in front of walking direction.
                 let angle: f32 = if self.input_controller.move_left {
@@ -366,3 +920,27 @@ impl ScriptTrait for Player {
         self.actor.on_message(message, ctx);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invert_y_flips_pitch_in_every_mode() {
+        let pitch_range = -90.0..90.0;
+        for aiming in [false, true] {
+            let pitch = |invert_y| {
+                let settings = SettingsData {
+                    invert_y,
+                    ..Default::default()
+                };
+                let mut input = InputController::default();
+                input.on_mouse_motion((0.0, 10.0), &settings, &pitch_range, aiming, 0.01);
+                input.target_pitch
+            };
+            let (regular, inverted) = (pitch(false), pitch(true));
+            assert!(regular > 0.0, "aiming: {aiming}");
+            assert_eq!(inverted, -regular, "aiming: {aiming}");
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! A projectile (a cannon ball, for example), that pushes actors it hits. The push goes through
+//! the knockback of the actor (see [`ActorMessage::Knockback`]), so the actor is stunned as on any
+//! other serious impact. Projectiles are simulated by the server only, their motion and removal is
+//! replicated to clients as for any other node.
+//!
+//! The script must be attached to the root rigid body of a projectile prefab in the editor, with
+//! its collider assigned. `data/models/cannon_ball.rgs` does not have it yet, so balls of cannons
+//! do not push actors until the prefab is updated.
+
+use crate::{
+    actor::{Actor, ActorMessage},
+    utils, Game,
+};
+use fyrox::{
+    core::{
+        algebra::Vector3, pool::Handle, reflect::prelude::*, type_traits::prelude::*,
+        variable::InheritableVariable, visitor::prelude::*,
+    },
+    fxhash::FxHashSet,
+    graph::BaseSceneGraph,
+    scene::{node::Node, rigidbody::RigidBody},
+    script::{ScriptContext, ScriptTrait},
+};
+
+#[derive(Clone, Debug, Visit, Reflect, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "36138deb-520e-4d04-b49f-f339a1b3c5f3")]
+#[visit(optional)]
+pub struct Projectile {
+    #[reflect(description = "Collider of the projectile, that hits actors.")]
+    collider: InheritableVariable<Handle<Node>>,
+    #[reflect(
+        description = "Impulse, that pushes an actor hit by the projectile along the direction \
+        of the flight.",
+        min_value = 0.0
+    )]
+    impulse: InheritableVariable<f32>,
+    #[reflect(description = "Remove the projectile right after it has hit an actor.")]
+    despawn_on_hit: InheritableVariable<bool>,
+    /// Every actor is pushed once by the same projectile, even if the contact lasts for a few
+    /// frames.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    hit_actors: FxHashSet<Handle<Node>>,
+}
+
+impl Default for Projectile {
+    fn default() -> Self {
+        Self {
+            collider: Default::default(),
+            impulse: 5.0.into(),
+            despawn_on_hit: true.into(),
+            hit_actors: Default::default(),
+        }
+    }
+}
+
+impl ScriptTrait for Projectile {
+    fn on_update(&mut self, ctx: &mut ScriptContext) {
+        let game = ctx.plugins.get::<Game>();
+        if game.is_client() || game.is_paused() {
+            return;
+        }
+
+        let graph = &ctx.scene.graph;
+        let velocity = graph
+            .try_get_of_type::<RigidBody>(ctx.handle)
+            .map(|body| body.lin_vel())
+            .unwrap_or_default();
+        let contacts = utils::actor_contacts(*self.collider, graph)
+            .map(|contact| contact.other_collider)
+            .collect::<Vec<_>>();
+        if contacts.is_empty() {
+            return;
+        }
+
+        // Actors are pushed in the same order on every run.
+        let hits = game
+            .level
+            .ordered_actors(graph)
+            .into_iter()
+            .filter(|actor| !self.hit_actors.contains(actor))
+            .filter(|actor| {
+                graph
+                    .try_get_script_component_of::<Actor>(*actor)
+                    .map_or(false, |actor| contacts.contains(&actor.collider))
+            })
+            .collect::<Vec<_>>();
+        if hits.is_empty() {
+            return;
+        }
+
+        let direction = velocity
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+        for actor in hits {
+            self.hit_actors.insert(actor);
+            ctx.message_sender.send_to_target(
+                actor,
+                ActorMessage::Knockback {
+                    impulse: direction.scale(*self.impulse),
+                },
+            );
+        }
+
+        // Removal of the node is propagated to clients, see the lifetime of instances.
+        if *self.despawn_on_hit {
+            ctx.scene.graph[ctx.handle].set_lifetime(Some(0.0));
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Optional quantization of update ticks. Position and rotation of a node state take 28 bytes with
+//! full precision, which adds up on levels with many rigid bodies. Quantized ones take 17 bytes:
+//! positions are stored as fixed-point values relative to the origin of the level and rotations
+//! as "smallest three" compressed quaternions. It is enabled in the lobby config and used for
+//! remote clients only, the host's own client always gets full precision states.
+//!
+//! Decoded states are within [`POSITION_TOLERANCE`] and [`ROTATION_TOLERANCE`] of the original
+//! ones. Nodes, that are too far from the origin to be quantized, are sent with full precision.
+//! Position epsilon of the server (see [`crate::server::Server::position_epsilon`]) should not be
+//! less than [`POSITION_STEP`], otherwise changes, that can't be seen after quantization, are
+//! still sent.
+
+use crate::{
+    level::Level,
+    net::{NodeState, UpdateTickMessage},
+};
+use fyrox::{
+    core::algebra::{Quaternion, UnitQuaternion, Vector3},
+    scene::{base::SceneNodeId, graph::Graph},
+};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_1_SQRT_2;
+
+/// Size (in meters) of a step of quantized positions.
+pub const POSITION_STEP: f32 = 0.001;
+
+/// Max distance (in meters) between a decoded position and the original one, for positions within
+/// a few kilometers around the origin.
+pub const POSITION_TOLERANCE: f32 = POSITION_STEP;
+
+/// Max angle (in radians) between a decoded rotation and the original one.
+pub const ROTATION_TOLERANCE: f32 = 0.001;
+
+/// Amount of bits of a quantized coordinate, positions must be within ±8 km around the origin.
+const POSITION_BITS: u32 = 24;
+
+const POSITION_LIMIT: f32 = ((1 << (POSITION_BITS - 1)) - 1) as f32;
+
+/// Amount of bits of every of the three quantized components of a rotation.
+const ROTATION_BITS: u32 = 20;
+
+const ROTATION_MAX: u64 = (1 << ROTATION_BITS) - 1;
+
+/// Every component of a unit quaternion, except the largest one, is within this range.
+const COMPONENT_RANGE: f32 = FRAC_1_SQRT_2;
+
+/// [`NodeState`] with quantized position and rotation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuantizedNodeState {
+    pub node: SceneNodeId,
+    /// Three 24-bit signed coordinates in [`POSITION_STEP`] units, relative to the origin.
+    pub position: [u8; 9],
+    /// Index of the largest (omitted) component in the two high bits, followed by three 20-bit
+    /// components.
+    pub rotation: u64,
+    pub scale: Option<Vector3<f32>>,
+}
+
+impl QuantizedNodeState {
+    /// Quantizes the state, returns `None` if the node is too far from the origin.
+    pub fn new(state: &NodeState, origin: Vector3<f32>) -> Option<Self> {
+        Some(Self {
+            node: state.node,
+            position: encode_position(state.position, origin)?,
+            rotation: encode_rotation(&state.rotation),
+            scale: state.scale,
+        })
+    }
+
+    pub fn decode(&self, origin: Vector3<f32>) -> NodeState {
+        NodeState {
+            node: self.node,
+            position: decode_position(&self.position, origin),
+            rotation: decode_rotation(self.rotation),
+            scale: self.scale,
+        }
+    }
+}
+
+/// Quantized node states of an update tick.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QuantizedNodes {
+    pub origin: Vector3<f32>,
+    pub nodes: Vec<QuantizedNodeState>,
+}
+
+/// Returns the origin of quantized positions on the level: the center of its start points. Start
+/// points are usually in the middle of the level, and they never move, so the origin is the same
+/// for the whole round.
+pub fn level_origin(level: &Level, graph: &Graph) -> Vector3<f32> {
+    let start_points = level.ordered_start_points(graph);
+    if start_points.is_empty() {
+        return Vector3::default();
+    }
+    let sum = start_points.iter().fold(Vector3::default(), |sum, p| {
+        sum + graph[*p].global_position()
+    });
+    sum.unscale(start_points.len() as f32)
+}
+
+/// Returns a copy of the tick with quantized node states. States of nodes, that are too far from
+/// the origin, are kept as is.
+pub fn quantize_tick(tick: &UpdateTickMessage, origin: Vector3<f32>) -> UpdateTickMessage {
+    let mut quantized = tick.clone();
+    let mut nodes = Vec::new();
+    quantized
+        .nodes
+        .retain(|state| match QuantizedNodeState::new(state, origin) {
+            Some(state) => {
+                nodes.push(state);
+                false
+            }
+            None => true,
+        });
+    quantized.quantized_nodes = Some(QuantizedNodes { origin, nodes });
+    quantized
+}
+
+/// Decodes quantized node states of the tick (if any) and adds them to the regular ones.
+pub fn dequantize_tick(mut tick: UpdateTickMessage) -> UpdateTickMessage {
+    if let Some(quantized) = tick.quantized_nodes.take() {
+        tick.nodes.extend(
+            quantized
+                .nodes
+                .iter()
+                .map(|state| state.decode(quantized.origin)),
+        );
+    }
+    tick
+}
+
+fn encode_position(position: Vector3<f32>, origin: Vector3<f32>) -> Option<[u8; 9]> {
+    let mut packed = [0; 9];
+    for (axis, bytes) in packed.chunks_exact_mut(3).enumerate() {
+        let value = ((position[axis] - origin[axis]) / POSITION_STEP).round();
+        if !value.is_finite() || value.abs() > POSITION_LIMIT {
+            return None;
+        }
+        bytes.copy_from_slice(&(value as i32).to_le_bytes()[..3]);
+    }
+    Some(packed)
+}
+
+fn decode_position(packed: &[u8; 9], origin: Vector3<f32>) -> Vector3<f32> {
+    let mut position = origin;
+    for (axis, bytes) in packed.chunks_exact(3).enumerate() {
+        // Shifts extend the sign of the 24-bit value.
+        let value = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+        position[axis] += value as f32 * POSITION_STEP;
+    }
+    position
+}
+
+fn encode_rotation(rotation: &UnitQuaternion<f32>) -> u64 {
+    let coords = rotation.coords;
+    let largest = coords.iamax();
+    // `q` and `-q` are the same rotation, so the largest component is made positive and could be
+    // restored from the rest.
+    let sign = coords[largest].signum();
+    let mut packed = largest as u64;
+    for (index, component) in coords.iter().enumerate() {
+        if index != largest {
+            let normalized = (component * sign / COMPONENT_RANGE).clamp(-1.0, 1.0) * 0.5 + 0.5;
+            let value = (normalized * ROTATION_MAX as f32).round() as u64;
+            packed = (packed << ROTATION_BITS) | value.min(ROTATION_MAX);
+        }
+    }
+    packed
+}
+
+fn decode_rotation(packed: u64) -> UnitQuaternion<f32> {
+    let largest = ((packed >> (3 * ROTATION_BITS)) & 0b11) as usize;
+    let mut coords = [0.0; 4];
+    let mut shift = 3 * ROTATION_BITS;
+    let mut sum_squared = 0.0;
+    for (index, component) in coords.iter_mut().enumerate() {
+        if index != largest {
+            shift -= ROTATION_BITS;
+            let normalized = ((packed >> shift) & ROTATION_MAX) as f32 / ROTATION_MAX as f32;
+            *component = (normalized * 2.0 - 1.0) * COMPONENT_RANGE;
+            sum_squared += *component * *component;
+        }
+    }
+    coords[largest] = (1.0 - sum_squared).max(0.0).sqrt();
+    // Coordinates of a quaternion are stored as (i, j, k, w).
+    UnitQuaternion::new_normalize(Quaternion::new(coords[3], coords[0], coords[1], coords[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fyrox::rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_state(rng: &mut StdRng, origin: Vector3<f32>) -> NodeState {
+        let axis = Vector3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        NodeState {
+            node: Default::default(),
+            position: origin + axis.scale(rng.gen_range(0.0..500.0)),
+            rotation: UnitQuaternion::new(axis.scale(rng.gen_range(-10.0..10.0))),
+            scale: None,
+        }
+    }
+
+    #[test]
+    fn decoded_states_are_within_tolerance() {
+        let mut rng = StdRng::seed_from_u64(123);
+        let origin = Vector3::new(12.5, -40.0, 300.0);
+        for _ in 0..10_000 {
+            let state = random_state(&mut rng, origin);
+            let decoded = QuantizedNodeState::new(&state, origin)
+                .unwrap()
+                .decode(origin);
+            assert!(decoded.position.metric_distance(&state.position) <= POSITION_TOLERANCE);
+            assert!(decoded.rotation.angle_to(&state.rotation) <= ROTATION_TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn opposite_quaternions_decode_to_the_same_rotation() {
+        let rotation = UnitQuaternion::from_euler_angles(0.3, -1.2, 2.5);
+        let opposite = UnitQuaternion::new_unchecked(-rotation.into_inner());
+        let decoded = decode_rotation(encode_rotation(&opposite));
+        assert!(decoded.angle_to(&rotation) <= ROTATION_TOLERANCE);
+        let identity = decode_rotation(encode_rotation(&UnitQuaternion::identity()));
+        assert!(identity.angle() <= ROTATION_TOLERANCE);
+    }
+
+    #[test]
+    fn far_nodes_are_not_quantized() {
+        let origin = Vector3::new(1.0, 2.0, 3.0);
+        let state = NodeState {
+            node: Default::default(),
+            position: origin + Vector3::new(0.0, -10_000.0, 0.0),
+            rotation: Default::default(),
+            scale: None,
+        };
+        assert!(QuantizedNodeState::new(&state, origin).is_none());
+        let state = NodeState {
+            position: Vector3::new(f32::NAN, 0.0, 0.0),
+            ..state
+        };
+        assert!(QuantizedNodeState::new(&state, origin).is_none());
+    }
+}
@@ -0,0 +1,113 @@
+//! Rendering of the level at reduced resolution for weak hardware. The scene is rendered into an
+//! offscreen texture, that is smaller than the window, and the texture is stretched over the
+//! whole window by an image behind every other widget, so the UI stays sharp.
+
+use fyrox::{
+    core::{algebra::Vector2, pool::Handle},
+    gui::{
+        image::{ImageBuilder, ImageMessage},
+        message::MessageDirection,
+        widget::{WidgetBuilder, WidgetMessage},
+        UiNode, UserInterface,
+    },
+    resource::texture::{TextureResource, TextureResourceExtension},
+    scene::Scene,
+};
+
+/// Min render scale, lower values make the image unreadable.
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+
+#[derive(Default, Debug)]
+pub struct RenderScaler {
+    image: Handle<UiNode>,
+    target: Option<TextureResource>,
+    /// Size (in pixels) of the offscreen texture.
+    target_size: Vector2<u32>,
+    /// Size (in pixels) of the image, that was rendered during the last update.
+    resolution: Vector2<u32>,
+}
+
+impl RenderScaler {
+    /// Returns the effective resolution (in pixels) of the level.
+    pub fn resolution(&self) -> Vector2<u32> {
+        self.resolution
+    }
+
+    /// Makes the scene rendered at the given fraction of the size of the window. The scale of 1.0
+    /// (or larger) renders the scene directly into the window.
+    pub fn update(&mut self, scale: f32, scene: Option<&mut Scene>, ui: &mut UserInterface) {
+        let screen_size = ui.screen_size();
+        let full_size = Vector2::new(screen_size.x.max(1.0) as u32, screen_size.y.max(1.0) as u32);
+
+        let scene = match scene {
+            Some(scene) if scale < 1.0 => scene,
+            scene => {
+                self.disable(scene, ui);
+                self.resolution = full_size;
+                return;
+            }
+        };
+
+        let scale = scale.max(MIN_RENDER_SCALE);
+        let size = Vector2::new(
+            ((screen_size.x * scale) as u32).max(1),
+            ((screen_size.y * scale) as u32).max(1),
+        );
+
+        if self.image.is_none() {
+            // Render targets are upside down, so the image is flipped.
+            self.image = ImageBuilder::new(WidgetBuilder::new().with_hit_test_visibility(false))
+                .with_flip(true)
+                .build(&mut ui.build_ctx());
+            ui.send_message(WidgetMessage::lowermost(
+                self.image,
+                MessageDirection::ToWidget,
+            ));
+        }
+
+        if self.target.is_none() || self.target_size != size {
+            self.target = Some(TextureResource::new_render_target(size.x, size.y));
+            self.target_size = size;
+            ui.send_message(ImageMessage::texture(
+                self.image,
+                MessageDirection::ToWidget,
+                self.target.clone(),
+            ));
+            ui.send_message(WidgetMessage::visibility(
+                self.image,
+                MessageDirection::ToWidget,
+                true,
+            ));
+        }
+
+        ui.send_message(WidgetMessage::width(
+            self.image,
+            MessageDirection::ToWidget,
+            screen_size.x,
+        ));
+        ui.send_message(WidgetMessage::height(
+            self.image,
+            MessageDirection::ToWidget,
+            screen_size.y,
+        ));
+
+        // A new level has no render target, so it is set on every update.
+        scene.rendering_options.render_target = self.target.clone();
+        self.resolution = size;
+    }
+
+    fn disable(&mut self, scene: Option<&mut Scene>, ui: &UserInterface) {
+        if let Some(scene) = scene {
+            if self.target.is_some() && scene.rendering_options.render_target == self.target {
+                scene.rendering_options.render_target = None;
+            }
+        }
+        if self.target.take().is_some() {
+            ui.send_message(WidgetMessage::visibility(
+                self.image,
+                MessageDirection::ToWidget,
+                false,
+            ));
+        }
+    }
+}
@@ -3,15 +3,15 @@
 
 use crate::{
     actor::{Actor, ActorMessage},
-    Game,
+    start, Game,
 };
 use fyrox::{
     core::{
-        math::aabb::AxisAlignedBoundingBox, pool::Handle, reflect::prelude::*,
+        algebra::Vector3, math::aabb::AxisAlignedBoundingBox, pool::Handle, reflect::prelude::*,
         type_traits::prelude::*, variable::InheritableVariable, visitor::prelude::*,
     },
     graph::{BaseSceneGraph, SceneGraph},
-    rand::{seq::SliceRandom, thread_rng},
+    rand::seq::SliceRandom,
     scene::{collider::Collider, node::Node},
     script::{ScriptContext, ScriptDeinitContext, ScriptTrait},
 };
@@ -43,6 +43,27 @@ pub enum RespawnMode {
 pub struct Respawner {
     mode: InheritableVariable<RespawnMode>,
     pub collider: InheritableVariable<Handle<Node>>,
+    #[reflect(
+        description = "Amount of time (in seconds) during which an actor stays knocked out \
+        before respawn. Zero means instant respawn.",
+        min_value = 0.0
+    )]
+    respawn_delay: InheritableVariable<f32>,
+}
+
+impl Respawner {
+    fn respawn_message(&self, position: Vector3<f32>, eliminate: bool) -> ActorMessage {
+        if eliminate {
+            ActorMessage::Eliminate
+        } else if *self.respawn_delay > 0.0 {
+            ActorMessage::KnockOut {
+                respawn_position: position,
+                duration: *self.respawn_delay,
+            }
+        } else {
+            ActorMessage::RespawnAt(position)
+        }
+    }
 }
 
 impl ScriptTrait for Respawner {
@@ -63,36 +84,46 @@ impl ScriptTrait for Respawner {
     }
 
     fn on_update(&mut self, ctx: &mut ScriptContext) {
-        let game = ctx.plugins.get::<Game>();
-        if game.is_client() {
+        let game = ctx.plugins.get_mut::<Game>();
+        if game.is_client() || game.is_paused() {
             return;
         }
+        let level = &mut game.level;
 
         let self_bounds = AxisAlignedBoundingBox::unit()
             .transform(&ctx.scene.graph[ctx.handle].global_transform());
 
-        let start_points = game
-            .level
-            .start_points
-            .iter()
-            .map(|p| ctx.scene.graph[*p].global_position())
+        // Start points are chosen by the level's random number generator, so their order must be
+        // deterministic for the same seed to give the same respawns.
+        let start_points = level
+            .ordered_start_points(&ctx.scene.graph)
+            .into_iter()
+            .map(|p| ctx.scene.graph[p].global_position())
             .collect::<Vec<_>>();
+        let start_points = start::positions_or_fallback(start_points);
+        let eliminate = level
+            .config(&ctx.scene.graph)
+            .map_or(false, |config| config.eliminates_on_fall());
 
-        for actor_handle in game.level.actors.iter() {
+        for actor_handle in level.ordered_actors(&ctx.scene.graph).iter() {
             if let Some(actor_script) = ctx
                 .scene
                 .graph
                 .try_get_script_component_of::<Actor>(*actor_handle)
             {
+                if actor_script.is_knocked_out() || actor_script.is_eliminated() {
+                    continue;
+                }
+
                 match *self.mode {
                     RespawnMode::OnEnterBoundingBox => {
                         let rigid_body = actor_script.rigid_body;
                         if let Some(rigid_body) = ctx.scene.graph.try_get(rigid_body) {
                             if self_bounds.is_contains_point(rigid_body.global_position()) {
-                                if let Some(start_point) = start_points.choose(&mut thread_rng()) {
+                                if let Some(start_point) = start_points.choose(&mut level.rng) {
                                     ctx.message_sender.send_to_target(
                                         *actor_handle,
-                                        ActorMessage::RespawnAt(*start_point),
+                                        self.respawn_message(*start_point, eliminate),
                                     );
                                 }
                             }
@@ -107,12 +138,10 @@ impl ScriptTrait for Respawner {
                                     && (contact.collider1 == actor_script.collider
                                         || contact.collider2 == actor_script.collider)
                                 {
-                                    if let Some(start_point) =
-                                        start_points.choose(&mut thread_rng())
-                                    {
+                                    if let Some(start_point) = start_points.choose(&mut level.rng) {
                                         ctx.message_sender.send_to_target(
                                             *actor_handle,
-                                            ActorMessage::RespawnAt(*start_point),
+                                            self.respawn_message(*start_point, eliminate),
                                         );
                                     }
                                 }
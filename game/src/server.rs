@@ -1,41 +1,529 @@
 use crate::{
-    actor::ActorKind,
-    level::Level,
+    actor::{Actor, ActorKind},
+    bot::{Bot, BotDifficulty, SIMULATION_STEP},
+    compression::{self, CompressionStats},
+    custom_message::{self, CustomMessageHandlers, CustomMessageSender, CustomMessageTag},
+    event::EventQueue,
+    level::{LeaderBoardEntry, Level},
+    level_config::TimeoutResolution,
     net::{
-        ClientMessage, InstanceDescriptor, LeaderBoardMessage, NodeState, PlayerDescriptor,
-        ServerMessage, SoundState, UpdateTickMessage,
+        ActorState, ClientMessage, InstanceDescriptor, LeaderBoardMessage, MatchResultsMessage,
+        NodeState, PlayerDescriptor, PlayerResult, ServerMessage, SoundState, UpdateTickMessage,
     },
     player::Player,
-    start::StartPoint,
+    quantization,
+    start::{self, StartPoint},
 };
 use fyrox::graph::SceneGraphNode;
 use fyrox::{
+    asset::manager::ResourceManager,
     core::{
+        algebra::Vector3,
+        color::Color,
         futures::executor::block_on,
         log::Log,
         net::{NetListener, NetStream},
         pool::Handle,
     },
     fxhash::FxHashMap,
-    graph::SceneGraph,
+    graph::{BaseSceneGraph, SceneGraph},
     plugin::PluginContext,
-    resource::model::{Model, ModelResourceExtension},
+    rand::{thread_rng, Rng},
+    resource::model::{Model, ModelResource, ModelResourceExtension},
     scene::{
+        base::SceneNodeId,
+        graph::Graph,
         node::Node,
         sound::{Sound, Status},
         Scene,
     },
+    script::ScriptTrait,
 };
 use std::fmt::{Debug, Formatter};
 use std::net::SocketAddr;
-use std::{io, net::ToSocketAddrs, path::Path};
+use std::{
+    collections::{hash_map::Entry, vec_deque, VecDeque},
+    io,
+    net::ToSocketAddrs,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+pub const PLAYER_PREFAB_PATH: &str = "data/models/player.rgs";
+const BOT_PREFAB_PATH: &str = "data/models/bot.rgs";
+/// Directory with additional player models (skins), every prefab in it could be selected by
+/// players.
+const PLAYER_MODELS_DIR: &str = "data/models/players";
+
+/// Amount of consecutive ticks with exceeded message budget, after which a warning is logged.
+const BUDGET_WARN_TICKS: u32 = 60;
+
+/// Max amount of ticks worth of messages (see [`Server::max_messages_per_tick`]), that could wait
+/// to be handled.
+const MAX_DEFERRED_TICKS: usize = 16;
+
+/// Takes at most `budget` messages from the front of the queue, the rest stays in the queue for the
+/// next ticks, see [`Server::max_messages_per_tick`].
+fn take_budget<T>(queue: &mut VecDeque<T>, budget: usize) -> vec_deque::Drain<'_, T> {
+    let count = queue.len().min(budget);
+    queue.drain(..count)
+}
+
+/// Interval (in seconds) between two pings of a connection.
+const PING_INTERVAL: f32 = 1.0;
+
+/// Amount of time (in seconds) after which an unanswered ping is considered lost.
+const PING_TIMEOUT: f64 = 3.0;
+
+/// Checks that the path points to an existing scene file, so clients are never asked to load
+/// something, that can't be loaded.
+fn validate_level_path(path: &Path) -> Result<(), String> {
+    if path.extension().map_or(true, |ext| ext != "rgs") {
+        return Err(format!(
+            "Unable to start the game: {} is not a scene (*.rgs) file.",
+            path.display()
+        ));
+    }
+    if !path.is_file() {
+        return Err(format!(
+            "Unable to start the game: level {} does not exist.",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Returns paths of every player model, that could be selected by players. The default model
+/// goes first.
+pub fn player_models() -> Vec<PathBuf> {
+    let mut models = std::fs::read_dir(PLAYER_MODELS_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "rgs"))
+        .collect::<Vec<_>>();
+    models.sort();
+    models.insert(0, PLAYER_PREFAB_PATH.into());
+    models
+}
+
+/// Returns a human-readable name of the given player model.
+pub fn player_model_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Loads an actor prefab and checks that its root node has a script of the given type, so it
+/// could be spawned safely.
+pub fn load_actor_prefab<T: ScriptTrait>(
+    path: &str,
+    resource_manager: &ResourceManager,
+) -> Result<ModelResource, String> {
+    let prefab = block_on(resource_manager.request::<Model>(path))
+        .map_err(|err| format!("Unable to load {path} prefab. Reason: {err:?}"))?;
+
+    let has_script = {
+        let model = prefab.data_ref();
+        let graph = &model.get_scene().graph;
+        graph[graph.get_root()].has_script::<T>()
+    };
+
+    if has_script {
+        Ok(prefab)
+    } else {
+        Err(format!(
+            "{path} prefab does not have {} script!",
+            std::any::type_name::<T>()
+        ))
+    }
+}
+
+fn node_state(node: &Node) -> NodeState {
+    NodeState {
+        node: node.instance_id(),
+        position: **node.local_transform().position(),
+        rotation: **node.local_transform().rotation(),
+        scale: Some(**node.local_transform().scale()),
+    }
+}
+
+fn sound_state(node: &Node) -> Option<SoundState> {
+    node.component_ref::<Sound>().map(|sound| SoundState {
+        node: sound.instance_id(),
+        is_playing: sound.status() == Status::Playing,
+    })
+}
+
+fn actor_state(handle: Handle<Node>, graph: &Graph) -> Option<ActorState> {
+    graph
+        .try_get_script_component_of::<Actor>(handle)
+        .map(|actor| ActorState {
+            node: graph[handle].instance_id(),
+            status_effects: actor.status_effects,
+            knocked_out: actor.is_knocked_out(),
+            eliminated: actor.is_eliminated(),
+            grounded: actor.grounded,
+            crouched: actor.crouched,
+            sliding: actor.sliding,
+            grabbing: actor.grabbing,
+            awaiting_respawn: actor.awaiting_respawn,
+            stunned: actor.is_stunned(),
+            operated_cannon: actor.operated_cannon,
+        })
+}
+
+/// Compresses the tick, returns `None` if it could not be compressed, so the tick is sent as is.
+fn compress_message(
+    tick: &UpdateTickMessage,
+    stats: &mut CompressionStats,
+) -> Option<ServerMessage> {
+    match compression::compress_tick(tick) {
+        Ok((data, raw_size)) => {
+            stats.record(raw_size, data.len());
+            Some(ServerMessage::CompressedUpdateTick { data })
+        }
+        Err(err) => {
+            Log::err(format!("Unable to compress update tick: {err}"));
+            None
+        }
+    }
+}
+
+/// Quantizes node states of the tick, see [`quantization::quantize_tick`]. Sizes of the tick with
+/// and without quantization are recorded, so the saved bandwidth could be measured.
+fn quantize_message(
+    tick: &UpdateTickMessage,
+    origin: Vector3<f32>,
+    stats: &mut CompressionStats,
+) -> UpdateTickMessage {
+    let quantized = quantization::quantize_tick(tick, origin);
+    if let (Ok(raw_size), Ok(quantized_size)) = (
+        bincode::serialized_size(tick),
+        bincode::serialized_size(&quantized),
+    ) {
+        stats.record(raw_size as usize, quantized_size as usize);
+    }
+    quantized
+}
+
+/// Collects the state of every node of the graph, without any delta compression. Obstacles are
+/// driven by animations and scripts on the server, so their transforms (angles of rotators,
+/// heights of hazards, etc.) fully describe them.
+fn full_snapshot(
+    level: &Level,
+    server_time: f64,
+    sequence: u32,
+    graph: &Graph,
+) -> UpdateTickMessage {
+    let mut snapshot = UpdateTickMessage {
+        nodes: Default::default(),
+        quantized_nodes: None,
+        sounds: Default::default(),
+        actors: Default::default(),
+        match_timer: level.match_timer,
+        sudden_death: level.is_sudden_death(),
+        level_time: level.time,
+        server_time,
+        sequence,
+    };
+
+    for (handle, node) in graph.pair_iter() {
+        snapshot.nodes.push(node_state(node));
+        snapshot.sounds.extend(sound_state(node));
+        snapshot.actors.extend(actor_state(handle, graph));
+    }
+
+    snapshot
+}
+
+/// An actor, that is spawned at the start of a match.
+struct ActorSpawn {
+    path: PathBuf,
+    ids: FxHashMap<Handle<Node>, SceneNodeId>,
+    /// Index of the connection of the player, that controls the actor. `None` for bots.
+    owner: Option<usize>,
+    /// Used only for bots.
+    difficulty: BotDifficulty,
+    color: Color,
+    team: Option<u32>,
+}
+
+/// Returns descriptors of the given actors for the client of the given connection. Actors are
+/// placed on the start points in the given order, start points are shared if there are more
+/// actors than start points (see [`start::spawn_position`]).
+fn player_descriptors(
+    spawns: &[ActorSpawn],
+    start_points: &[Vector3<f32>],
+    connection_num: usize,
+) -> Vec<PlayerDescriptor> {
+    spawns
+        .iter()
+        .enumerate()
+        .filter_map(|(index, spawn)| {
+            let position = start::spawn_position(start_points, index)?;
+            Some(PlayerDescriptor {
+                instance: InstanceDescriptor {
+                    path: spawn.path.clone(),
+                    position,
+                    rotation: Default::default(),
+                    velocity: Default::default(),
+                    ids: spawn.ids.clone(),
+                    lifetime: None,
+                },
+                kind: match spawn.owner {
+                    Some(owner) if owner == connection_num => ActorKind::Player,
+                    Some(_) => ActorKind::RemotePlayer,
+                    None => ActorKind::Bot,
+                },
+                is_remote: connection_num != Server::HOST_CONNECTION,
+                difficulty: spawn.difficulty,
+                color: spawn.color.into(),
+                team: spawn.team,
+            })
+        })
+        .collect()
+}
+
+/// Node state, that was sent to clients last time, and the state, that was observed on the last
+/// snapshot.
+struct TrackedNodeState {
+    sent: NodeState,
+    observed: NodeState,
+}
+
+impl TrackedNodeState {
+    /// Returns the state, that must be sent to clients, if any. Changes below the epsilons are not
+    /// sent, but once a node stops moving, its exact state is sent, so clients settle exactly.
+    fn update(
+        &mut self,
+        current: NodeState,
+        position_epsilon: f32,
+        rotation_epsilon: f32,
+        scale_epsilon: f32,
+    ) -> Option<NodeState> {
+        let has_moved = current.differs(
+            &self.sent,
+            position_epsilon,
+            rotation_epsilon,
+            scale_epsilon,
+        );
+        let has_settled = self.observed == current && self.sent != current;
+        let update = (has_moved || has_settled).then(|| {
+            let mut update = current.clone();
+            if update.scale == self.sent.scale {
+                // Most nodes are never scaled, do not waste bandwidth on them.
+                update.scale = None;
+            }
+            self.sent = current.clone();
+            update
+        });
+        self.observed = current;
+        update
+    }
+}
+
+/// A connection to a client.
+pub struct Connection {
+    stream: NetStream,
+    /// Amount of time (in seconds) since the last message from the client.
+    idle_time: f32,
+    /// Color of the player's actor. It is assigned at connect time and stays the same across
+    /// rounds.
+    color: Color,
+    /// Sequence number of the last input received from the client.
+    last_input_sequence: Option<u32>,
+    /// The client could unpack compressed update ticks, see [`crate::compression`].
+    supports_compression: bool,
+    /// The player is ready to start the match.
+    ready: bool,
+    /// Player model, that was selected by the player. The default model is used if not set.
+    model: Option<PathBuf>,
+    /// Sequence number of the latest update tick, that the client has received.
+    acked_tick: u32,
+    /// Sequence number of the latest update tick, that was sent to the client. Ticks are not sent
+    /// while the client is behind, so it is used instead of the current sequence to measure the
+    /// lag. Otherwise a skipped client could never catch up.
+    last_sent_tick: u32,
+    /// Amount of update ticks, that were not sent to the client, because it is too far behind.
+    dropped_ticks: u32,
+    /// Amount of time (in seconds) the client has been too far behind.
+    backpressure_time: f32,
+    /// Received messages, that are not handled yet, see [`Server::max_messages_per_tick`].
+    pending_messages: VecDeque<ClientMessage>,
+    /// Amount of consecutive ticks, in which the client has sent more messages than the budget.
+    budget_exhausted_ticks: u32,
+    /// Nonce and time (of the server clock) of the last ping, that is not answered yet.
+    pending_ping: Option<(u32, f64)>,
+    /// Round-trip time (in seconds), that was measured by the last answered ping.
+    round_trip_time: Option<f32>,
+    /// The last ping was not answered in [`PING_TIMEOUT`].
+    ping_timed_out: bool,
+}
+
+impl Connection {
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Returns the path of the player model, that is used for the actor of the player.
+    pub fn model(&self) -> &Path {
+        self.model
+            .as_deref()
+            .unwrap_or(Path::new(PLAYER_PREFAB_PATH))
+    }
+
+    /// Returns the round-trip time (in seconds) of the connection. It is `None` if it is not
+    /// measured yet or if the client does not answer pings, see [`Self::is_ping_timed_out`].
+    pub fn round_trip_time(&self) -> Option<f32> {
+        if self.ping_timed_out {
+            None
+        } else {
+            self.round_trip_time
+        }
+    }
+
+    /// Returns `true` if the client has not answered the last ping in time.
+    pub fn is_ping_timed_out(&self) -> bool {
+        self.ping_timed_out
+    }
+
+    /// Returns the round-trip time as text (for example, `42 ms` or `timeout`). It is `None` if
+    /// there was no answer yet.
+    pub fn ping_text(&self) -> Option<String> {
+        if self.ping_timed_out {
+            Some("timeout".to_string())
+        } else {
+            self.round_trip_time
+                .map(|time| format!("{} ms", (time * 1000.0).round() as u32))
+        }
+    }
+
+    /// Returns `true` if the client has not acknowledged too many of the ticks sent to it.
+    fn is_behind(&self, max_pending_ticks: u32) -> bool {
+        self.last_sent_tick.wrapping_sub(self.acked_tick) > max_pending_ticks
+    }
+}
+
+impl Deref for Connection {
+    type Target = NetStream;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl DerefMut for Connection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stream
+    }
+}
+
+/// Automatic start of a match, once enough players have joined the lobby.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoStart {
+    /// Amount of players, that starts the countdown.
+    pub target_players: usize,
+    /// The countdown is cancelled if amount of players drops below this value.
+    pub min_players: usize,
+    /// Duration (in seconds) of the countdown. Players could still join during it.
+    pub grace_period: f32,
+}
 
 pub struct Server {
     listener: NetListener,
-    connections: Vec<NetStream>,
-    previous_node_states: FxHashMap<Handle<Node>, NodeState>,
+    connections: Vec<Connection>,
+    previous_node_states: FxHashMap<Handle<Node>, TrackedNodeState>,
     previous_sound_states: FxHashMap<Handle<Node>, SoundState>,
+    previous_actor_states: FxHashMap<Handle<Node>, ActorState>,
+    /// Fill start points, that are not taken by players, with bots. [`Self::bot_count`] is ignored
+    /// if set.
     pub add_bots: bool,
+    /// Exact amount of bots in a match, it is clamped to [`Self::MAX_BOTS`].
+    pub bot_count: usize,
+    pub bot_difficulty: BotDifficulty,
+    /// Movement (in meters) of a node, that is too small to be sent to clients.
+    pub position_epsilon: f32,
+    /// Rotation (in radians) of a node, that is too small to be sent to clients.
+    pub rotation_epsilon: f32,
+    /// Change of scale of a node, that is too small to be sent to clients.
+    pub scale_epsilon: f32,
+    /// Amount of time (in seconds) after which a silent connection is dropped.
+    pub connection_timeout: f32,
+    keep_alive_timer: f32,
+    ping_timer: f32,
+    /// Nonce of the last sent ping, every ping has its own.
+    ping_nonce: u32,
+    snapshot_interval: f32,
+    snapshot_timer: f32,
+    /// Increases every time the list of connections or their metadata changes, so observers could
+    /// refresh their views.
+    lobby_revision: u64,
+    /// Seed for the next match, a random one is used if not set.
+    pub seed: Option<u64>,
+    /// Seed of the current (or the last) match.
+    match_seed: u64,
+    /// Assign start points to players in an order, that is picked by the seed of the match,
+    /// instead of the order of the level.
+    pub shuffle_start_points: bool,
+    /// The match starts automatically when enough players have joined, if set.
+    pub auto_start: Option<AutoStart>,
+    /// Amount of teams, see [`Self::team_of`].
+    pub team_count: u32,
+    /// If `false`, actors of the same team can't knock each other down.
+    pub friendly_fire: bool,
+    /// Overrides the time limit of levels (in seconds), zero disables the limit. The limit of a
+    /// level is used if not set.
+    pub time_limit: Option<f32>,
+    /// Overrides the timeout resolution of levels, if set.
+    pub timeout_resolution: Option<TimeoutResolution>,
+    /// If `false`, every actor respawns automatically regardless of the preference of its player.
+    pub allow_manual_respawn: bool,
+    /// Max amount of time (in seconds) an actor could wait for manual respawn, after which it is
+    /// respawned anyway, so nobody could stall the round.
+    pub max_manual_respawn_wait: f32,
+    /// Compress update ticks for clients, that support it. The host's own client never gets
+    /// compressed ticks.
+    pub compress_ticks: bool,
+    compression_stats: CompressionStats,
+    /// Quantize node states of update ticks for remote clients, see [`crate::quantization`]. The
+    /// host's own client always gets full precision states.
+    pub quantize_ticks: bool,
+    quantization_stats: CompressionStats,
+    /// Max amount of time (in seconds) a client could lag behind the update ticks. Ticks are not
+    /// sent to a client, that is further behind, until it catches up, so the send buffer of its
+    /// connection does not grow. Reliable messages (spawns, level load, etc.) are always sent.
+    pub max_send_lag: f32,
+    /// Amount of time (in seconds) after which a client, that can't catch up, is disconnected.
+    pub backpressure_timeout: f32,
+    /// Max amount of messages of a client, that are handled per tick. A client, that has more
+    /// than [`MAX_DEFERRED_TICKS`] ticks worth of unhandled messages, is disconnected.
+    pub max_messages_per_tick: usize,
+    /// Amount of time (in seconds) the results of a match are shown, before the next round starts
+    /// automatically. The host starts the next round manually if not set.
+    pub results_duration: Option<f32>,
+    /// Levels, that are played one after another. The last played level is replayed, if it is not
+    /// in the playlist.
+    pub playlist: Vec<PathBuf>,
+    /// The latest started level.
+    current_level: Option<PathBuf>,
+    /// Results of the last match, while they're shown.
+    results: Option<MatchResultsMessage>,
+    /// Remaining time (in seconds) before the next round starts automatically.
+    next_round_timer: Option<f32>,
+    tick_sequence: u32,
+    /// Id of the latest started match, see [`ServerMessage::LoadLevel`].
+    match_id: u64,
+    auto_start_timer: Option<f32>,
+    /// `true` if clients were asked to load a level, but the level is not loaded yet.
+    is_loading_level: bool,
+    started: Instant,
 }
 
 impl Debug for Server {
@@ -46,6 +534,32 @@ impl Debug for Server {
 
 impl Server {
     pub const LOCALHOST: &'static str = "127.0.0.1:10001";
+    pub const KEEP_ALIVE_INTERVAL: f32 = 1.0;
+    /// Index of the connection of the host's own client. The host connects to its server first.
+    pub const HOST_CONNECTION: usize = 0;
+    pub const DEFAULT_POSITION_EPSILON: f32 = 0.001;
+    pub const DEFAULT_ROTATION_EPSILON: f32 = 0.001;
+    pub const DEFAULT_SCALE_EPSILON: f32 = 0.001;
+    pub const DEFAULT_MAX_MANUAL_RESPAWN_WAIT: f32 = 10.0;
+    pub const DEFAULT_MAX_SEND_LAG: f32 = 1.0;
+    pub const DEFAULT_BACKPRESSURE_TIMEOUT: f32 = 10.0;
+    pub const DEFAULT_RESULTS_DURATION: f32 = 10.0;
+    pub const DEFAULT_MAX_MESSAGES_PER_TICK: usize = 64;
+    /// Max amount of bots in a match, every bot is simulated by the server.
+    pub const MAX_BOTS: usize = 16;
+    /// Snapshots can't be sent more often than the simulation is stepped.
+    pub const MIN_SNAPSHOT_INTERVAL: f32 = SIMULATION_STEP;
+    /// Colors of actors. Players get the colors in connection order, bots get the rest.
+    pub const ACTOR_COLORS: [Color; 8] = [
+        Color::opaque(230, 60, 60),
+        Color::opaque(60, 120, 230),
+        Color::opaque(60, 190, 80),
+        Color::opaque(240, 200, 40),
+        Color::opaque(170, 80, 220),
+        Color::opaque(240, 130, 30),
+        Color::opaque(40, 200, 200),
+        Color::opaque(240, 110, 180),
+    ];
 
     pub fn new<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
         Ok(Self {
@@ -53,7 +567,47 @@ impl Server {
             connections: Default::default(),
             previous_node_states: Default::default(),
             previous_sound_states: Default::default(),
+            previous_actor_states: Default::default(),
             add_bots: true,
+            bot_count: 0,
+            bot_difficulty: Default::default(),
+            position_epsilon: Self::DEFAULT_POSITION_EPSILON,
+            rotation_epsilon: Self::DEFAULT_ROTATION_EPSILON,
+            scale_epsilon: Self::DEFAULT_SCALE_EPSILON,
+            connection_timeout: 10.0,
+            keep_alive_timer: 0.0,
+            ping_timer: 0.0,
+            ping_nonce: 0,
+            snapshot_interval: Self::MIN_SNAPSHOT_INTERVAL,
+            snapshot_timer: 0.0,
+            lobby_revision: 0,
+            seed: None,
+            match_seed: 0,
+            shuffle_start_points: true,
+            auto_start: None,
+            team_count: 0,
+            friendly_fire: true,
+            time_limit: None,
+            timeout_resolution: None,
+            allow_manual_respawn: true,
+            max_manual_respawn_wait: Self::DEFAULT_MAX_MANUAL_RESPAWN_WAIT,
+            compress_ticks: false,
+            compression_stats: CompressionStats::new("Tick compression"),
+            quantize_ticks: false,
+            quantization_stats: CompressionStats::new("Tick quantization"),
+            max_send_lag: Self::DEFAULT_MAX_SEND_LAG,
+            backpressure_timeout: Self::DEFAULT_BACKPRESSURE_TIMEOUT,
+            max_messages_per_tick: Self::DEFAULT_MAX_MESSAGES_PER_TICK,
+            results_duration: Some(Self::DEFAULT_RESULTS_DURATION),
+            playlist: Default::default(),
+            current_level: None,
+            results: None,
+            next_round_timer: None,
+            tick_sequence: 0,
+            match_id: 0,
+            auto_start_timer: None,
+            is_loading_level: false,
+            started: Instant::now(),
         })
     }
 
@@ -66,163 +620,869 @@ impl Server {
         }
     }
 
-    pub fn start_game(&mut self, path: &Path) {
-        self.broadcast_message_to_clients(ServerMessage::LoadLevel {
+    /// Sends a custom message (see [`crate::custom_message`]) to every client. Too large payloads
+    /// are not sent.
+    pub fn broadcast_custom_message(&mut self, tag: CustomMessageTag, data: Vec<u8>) {
+        if !custom_message::is_valid_payload(&data) {
+            Log::err(format!(
+                "Custom message with tag {tag} was not sent, its payload is too large ({} bytes).",
+                data.len()
+            ));
+            return;
+        }
+        self.broadcast_message_to_clients(ServerMessage::Custom { tag, data });
+    }
+
+    /// Returns max amount of update ticks, that a client could leave unacknowledged.
+    fn max_pending_ticks(&self) -> u32 {
+        (self.max_send_lag.max(0.0) / self.snapshot_interval).ceil() as u32
+    }
+
+    /// Sends the tick to every client, compressing it for those that support compression. Node
+    /// states are quantized for remote clients, if the quantization origin is set. Clients, that
+    /// are too far behind, are skipped.
+    fn broadcast_tick(
+        &mut self,
+        mut tick: UpdateTickMessage,
+        quantization_origin: Option<Vector3<f32>>,
+    ) {
+        self.tick_sequence = self.tick_sequence.wrapping_add(1);
+        tick.sequence = self.tick_sequence;
+        let max_pending_ticks = self.max_pending_ticks();
+
+        let quantized = quantization_origin
+            .map(|origin| quantize_message(&tick, origin, &mut self.quantization_stats));
+        let remote_tick = quantized.as_ref().unwrap_or(&tick);
+        let compressed = if self.compress_ticks
+            && self
+                .connections
+                .iter()
+                .enumerate()
+                .any(|(i, c)| i != Self::HOST_CONNECTION && c.supports_compression)
+        {
+            compress_message(remote_tick, &mut self.compression_stats)
+        } else {
+            None
+        };
+        let remote = quantized.map(ServerMessage::UpdateTick);
+        let raw = ServerMessage::UpdateTick(tick);
+        for (i, client_connection) in self.connections.iter_mut().enumerate() {
+            if client_connection.is_behind(max_pending_ticks) {
+                if client_connection.dropped_ticks == 0 {
+                    Log::warn(format!(
+                        "Connection {} is too far behind, update ticks are dropped until it \
+                        catches up.",
+                        client_connection.string_peer_address()
+                    ));
+                }
+                client_connection.dropped_ticks += 1;
+                continue;
+            }
+            let message = match compressed {
+                Some(ref compressed)
+                    if i != Self::HOST_CONNECTION && client_connection.supports_compression =>
+                {
+                    compressed
+                }
+                _ if i != Self::HOST_CONNECTION => remote.as_ref().unwrap_or(&raw),
+                _ => &raw,
+            };
+            match client_connection.send_message(message) {
+                Ok(_) => client_connection.last_sent_tick = self.tick_sequence,
+                Err(err) => Log::err(format!("Unable to send server message: {}", err)),
+            }
+        }
+    }
+
+    /// Returns max amount of time (in seconds) an actor could wait for manual respawn, or `None`
+    /// if manual respawn is not allowed.
+    pub fn manual_respawn_wait(&self) -> Option<f32> {
+        self.allow_manual_respawn
+            .then_some(self.max_manual_respawn_wait.max(0.0))
+    }
+
+    /// Returns the time (in seconds) of the server clock. Clients estimate it to align their timers
+    /// with the server, see [`crate::clock`].
+    pub fn clock(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    pub fn snapshot_interval(&self) -> f32 {
+        self.snapshot_interval
+    }
+
+    /// Sets the interval (in seconds) between two consecutive state snapshots. Changes of the
+    /// scene are accumulated between snapshots and only the latest state is sent.
+    pub fn set_snapshot_interval(&mut self, interval: f32) {
+        if interval < Self::MIN_SNAPSHOT_INTERVAL {
+            Log::warn(format!(
+                "Snapshot interval {interval} is less than physics step, clamping to {}.",
+                Self::MIN_SNAPSHOT_INTERVAL
+            ));
+        }
+        self.snapshot_interval = interval.max(Self::MIN_SNAPSHOT_INTERVAL);
+    }
+
+    /// Picks the seed and the id of a new match on the given level and returns the message, that
+    /// asks clients to load the level.
+    fn begin_match(&mut self, path: &Path) -> ServerMessage {
+        let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+        self.match_id = self.match_id.wrapping_add(1);
+        self.match_seed = seed;
+        ServerMessage::LoadLevel {
             path: path.to_path_buf(),
+            seed,
+            match_id: self.match_id,
+            force: false,
+        }
+    }
+
+    /// Asks every connected client to load the given level. Fails if there are no connected
+    /// clients (for example, if the host's own client failed to connect), because nobody will
+    /// load the level and the match will never start.
+    pub fn start_game(&mut self, path: &Path) -> Result<(), String> {
+        if self.connections.is_empty() {
+            return Err("Unable to start the game: waiting for players to connect.".to_string());
+        }
+
+        validate_level_path(path)?;
+
+        let message = self.begin_match(path);
+
+        Log::info(format!(
+            "Starting {} with {} player(s). Seed: {}",
+            path.display(),
+            self.connections.len(),
+            self.match_seed
+        ));
+
+        self.broadcast_message_to_clients(message);
+
+        self.auto_start_timer = None;
+        self.is_loading_level = true;
+        self.current_level = Some(path.to_path_buf());
+        self.results = None;
+        self.next_round_timer = None;
+        self.reset_readiness();
+
+        Ok(())
+    }
+
+    /// Returns the level after the latest played one in the playlist.
+    fn next_level(&self) -> Option<PathBuf> {
+        let current = self.current_level.as_ref()?;
+        match self.playlist.iter().position(|level| level == current) {
+            Some(index) => self
+                .playlist
+                .get((index + 1) % self.playlist.len())
+                .cloned(),
+            None => Some(current.clone()),
+        }
+    }
+
+    /// Starts the next level of the playlist right away, without waiting for the end of the
+    /// results countdown. Does nothing if the results are not shown.
+    pub fn start_next_round(&mut self) -> Result<(), String> {
+        if self.results.is_none() {
+            return Ok(());
+        }
+        let Some(level) = self.next_level() else {
+            return Err("Unable to start the next round: no level was played.".to_string());
+        };
+        self.start_game(&level)
+    }
+
+    /// Returns `true` if the results of the last match are shown, and the host could start the
+    /// next round.
+    pub fn is_showing_results(&self) -> bool {
+        self.results.is_some()
+    }
+
+    /// Remembers and broadcasts the results of the finished match, and begins the countdown of the
+    /// next round.
+    fn finish_match(&mut self, level: &Level, graph: &Graph) {
+        let players = player_results(level.leaderboard.entries.values().filter_map(|e| {
+            let actor = graph.try_get_script_component_of::<Actor>(e.actor)?;
+            Some((e, actor.name.clone()))
+        }));
+
+        self.next_round_timer = self.results_duration.map(|duration| duration.max(0.0));
+        self.results = Some(MatchResultsMessage {
+            players,
+            seed: self.match_seed,
+            next_round_in: None,
+            server_time: 0.0,
         });
+        self.broadcast_message_to_clients(ServerMessage::EndMatch);
+        if let Some(message) = self.results_message() {
+            self.broadcast_message_to_clients(message);
+        }
+        self.reset_readiness();
     }
 
-    pub fn update(&mut self, level: &mut Level, ctx: &mut PluginContext) {
-        level.update(ctx);
+    /// Returns the results of the last match with the current time of the countdown.
+    fn results_message(&self) -> Option<ServerMessage> {
+        let mut results = self.results.clone()?;
+        results.next_round_in = self.next_round_timer.map(|timer| timer.max(0.0));
+        results.server_time = self.clock();
+        Some(ServerMessage::MatchResults(results))
+    }
 
-        if let Some(scene) = ctx.scenes.try_get_mut(level.scene) {
-            if level.is_match_ended() {
-                self.broadcast_message_to_clients(ServerMessage::EndMatch);
+    fn update_next_round(&mut self, dt: f32) {
+        let Some(timer) = self.next_round_timer.as_mut() else {
+            return;
+        };
+        *timer -= dt;
+        if *timer <= 0.0 {
+            self.next_round_timer = None;
+            if let Err(reason) = self.start_next_round() {
+                Log::err(reason);
             }
+        }
+    }
+
+    /// Runs the auto-start countdown in the lobby (when no level is loaded). The countdown begins
+    /// once the target amount of players has joined and is cancelled if too many players leave.
+    fn update_auto_start(&mut self, dt: f32, in_lobby: bool) {
+        let Some(auto_start) = self
+            .auto_start
+            .filter(|_| in_lobby && !self.is_loading_level)
+        else {
+            self.auto_start_timer = None;
+            return;
+        };
+
+        let players = self.connections.len();
+        let target_players = auto_start.target_players.max(1);
+        self.auto_start_timer = match self.auto_start_timer {
+            Some(_) if players < auto_start.min_players.min(target_players) => {
+                Log::info("Auto-start is cancelled, because there are not enough players.");
+                None
+            }
+            Some(timer) => Some(timer - dt),
+            None if players >= target_players => {
+                Log::info(format!(
+                    "The lobby has {players} player(s), starting in {:.0} seconds.",
+                    auto_start.grace_period
+                ));
+                Some(auto_start.grace_period.max(0.0))
+            }
+            None => None,
+        };
+    }
+
+    /// Returns amount of time (in seconds) before the automatic start of the match or `None` if
+    /// the countdown is not running.
+    pub fn auto_start_countdown(&self) -> Option<f32> {
+        self.auto_start_timer.map(|timer| timer.max(0.0))
+    }
+
+    /// Returns `true` if the countdown has ended and the match should be started.
+    pub fn is_auto_start_due(&self) -> bool {
+        self.auto_start_timer.map_or(false, |timer| timer <= 0.0)
+    }
+
+    /// Stops the countdown, it begins again on the next update if there are enough players.
+    pub fn cancel_auto_start(&mut self) {
+        self.auto_start_timer = None;
+    }
+
+    /// Returns amount of players, that are not ready to start the match. The host is not counted,
+    /// because the host starts the match.
+    pub fn unready_players(&self) -> usize {
+        self.connections
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| *i != Self::HOST_CONNECTION && !c.ready)
+            .count()
+    }
+
+    fn reset_readiness(&mut self) {
+        if self.connections.iter().any(|c| c.ready) {
+            for connection in self.connections.iter_mut() {
+                connection.ready = false;
+            }
+            self.lobby_revision += 1;
+        }
+    }
+
+    pub fn lobby_revision(&self) -> u64 {
+        self.lobby_revision
+    }
+
+    fn is_color_taken(&self, color: Color) -> bool {
+        self.connections.iter().any(|c| c.color == color)
+    }
+
+    /// Returns the first color, that is not used by any connection.
+    fn free_color(&self) -> Color {
+        Self::ACTOR_COLORS
+            .iter()
+            .cloned()
+            .find(|color| !self.is_color_taken(*color))
+            .unwrap_or(Self::ACTOR_COLORS[self.connections.len() % Self::ACTOR_COLORS.len()])
+    }
+
+    /// Tries to change the color of a player. Fails if the color is already taken by someone
+    /// else, since colors are assigned in connection order.
+    pub fn request_color(&mut self, connection: usize, color: Color) -> bool {
+        if self.is_color_taken(color) {
+            return false;
+        }
+        if let Some(connection) = self.connections.get_mut(connection) {
+            connection.color = color;
+            self.lobby_revision += 1;
+            true
+        } else {
+            false
+        }
+    }
 
-            self.broadcast_message_to_clients(ServerMessage::LeaderBoard(LeaderBoardMessage {
-                players: level.leaderboard.entries.values().cloned().collect(),
+    /// Sends a new ping to every connection, that has answered the last one. Connections, that
+    /// have not answered in time, are marked as timed out and get a new ping as well.
+    fn send_pings(&mut self) {
+        let now = self.clock();
+        for connection in self.connections.iter_mut() {
+            if let Some((_, sent)) = connection.pending_ping {
+                if now - sent < PING_TIMEOUT {
+                    continue;
+                }
+                if !connection.ping_timed_out {
+                    connection.ping_timed_out = true;
+                    self.lobby_revision += 1;
+                }
+            }
+            self.ping_nonce = self.ping_nonce.wrapping_add(1);
+            connection.pending_ping = Some((self.ping_nonce, now));
+            Log::verify(connection.send_message(&ServerMessage::Ping {
+                nonce: self.ping_nonce,
             }));
+        }
+    }
 
-            let mut tick_data = UpdateTickMessage {
-                nodes: Default::default(),
-                sounds: Default::default(),
-            };
+    fn update_connections(&mut self, dt: f32) {
+        self.keep_alive_timer -= dt;
+        if self.keep_alive_timer <= 0.0 {
+            self.keep_alive_timer = Self::KEEP_ALIVE_INTERVAL;
+            self.broadcast_message_to_clients(ServerMessage::KeepAlive);
+        }
 
-            for (handle, node) in scene.graph.pair_iter() {
-                let current_state = NodeState {
-                    node: node.instance_id(),
-                    position: **node.local_transform().position(),
-                    rotation: **node.local_transform().rotation(),
-                };
+        self.ping_timer -= dt;
+        if self.ping_timer <= 0.0 {
+            self.ping_timer = PING_INTERVAL;
+            self.send_pings();
+        }
+
+        let timeout = self.connection_timeout;
+        let backpressure_timeout = self.backpressure_timeout;
+        let max_pending_messages = self.max_messages_per_tick.max(1) * MAX_DEFERRED_TICKS;
+        let count = self.connections.len();
+        self.connections.retain_mut(|connection| {
+            connection.idle_time += dt;
+            if connection.dropped_ticks > 0 {
+                connection.backpressure_time += dt;
+            } else {
+                connection.backpressure_time = 0.0;
+            }
+            if connection.idle_time > timeout {
+                Log::warn(format!(
+                    "Connection {} timed out: no messages received in {:.1} seconds.",
+                    connection.string_peer_address(),
+                    connection.idle_time
+                ));
+                false
+            } else if connection.pending_messages.len() > max_pending_messages {
+                Log::warn(format!(
+                    "Connection {} is dropped: it has sent {} message(s), that could not be \
+                    handled in time.",
+                    connection.string_peer_address(),
+                    connection.pending_messages.len()
+                ));
+                false
+            } else if connection.backpressure_time > backpressure_timeout {
+                Log::warn(format!(
+                    "Connection {} is dropped: it could not catch up in {:.1} seconds, {} update \
+                    tick(s) were not sent to it.",
+                    connection.string_peer_address(),
+                    connection.backpressure_time,
+                    connection.dropped_ticks
+                ));
+                false
+            } else {
+                true
+            }
+        });
+        if self.connections.len() != count {
+            self.lobby_revision += 1;
+        }
+    }
+
+    pub fn update(&mut self, level: &mut Level, events: &mut EventQueue, ctx: &mut PluginContext) {
+        self.update_connections(ctx.dt);
+        self.update_auto_start(ctx.dt, level.scene.is_none());
+        self.update_next_round(ctx.dt);
+
+        level.update(events, ctx);
+        if let Some(scene) = ctx.scenes.try_get_mut(level.scene) {
+            level.update_grabs(self.friendly_fire, events, &mut scene.graph);
+        }
+
+        if let Some(scene) = ctx.scenes.try_get_mut(level.scene) {
+            // The timer stays expired until the level is unloaded, the match ends only once.
+            if level.is_match_ended() && self.results.is_none() {
+                self.finish_match(level, &scene.graph);
+            }
+
+            // The overshoot is carried over, so the average rate matches the configured one. The
+            // timer is clamped, so a long frame does not cause a burst of snapshots.
+            self.snapshot_timer += ctx.dt;
+            if self.snapshot_timer >= self.snapshot_interval {
+                self.snapshot_timer =
+                    (self.snapshot_timer - self.snapshot_interval).min(self.snapshot_interval);
+                self.send_snapshot(level, &scene.graph);
+            }
+        }
+    }
+
+    /// Returns the state of the node, that must be sent to clients, if any. Dead simple delta
+    /// compression: the state is compared with the one, that was sent last time. A node, that is
+    /// seen for the first time (a spawned instance, for example), is always sent with its full
+    /// state.
+    fn node_update(&mut self, handle: Handle<Node>, current: NodeState) -> Option<NodeState> {
+        match self.previous_node_states.entry(handle) {
+            Entry::Occupied(mut tracked) => tracked.get_mut().update(
+                current,
+                self.position_epsilon,
+                self.rotation_epsilon,
+                self.scale_epsilon,
+            ),
+            Entry::Vacant(entry) => {
+                entry.insert(TrackedNodeState {
+                    sent: current.clone(),
+                    observed: current.clone(),
+                });
+                Some(current)
+            }
+        }
+    }
+
+    /// Sends the changes of the level since the last snapshot to every client.
+    fn send_snapshot(&mut self, level: &Level, graph: &Graph) {
+        // Despawns must be sent before the tick, otherwise a node with a reused handle could
+        // be mixed up with a removed one.
+        self.purge_despawned_nodes(graph);
+
+        self.broadcast_message_to_clients(ServerMessage::LeaderBoard(LeaderBoardMessage {
+            players: level.leaderboard.entries.values().cloned().collect(),
+        }));
 
-                // Dead simple delta compression.
+        let mut tick_data = UpdateTickMessage {
+            nodes: Default::default(),
+            quantized_nodes: None,
+            sounds: Default::default(),
+            actors: Default::default(),
+            match_timer: level.match_timer,
+            sudden_death: level.is_sudden_death(),
+            level_time: level.time,
+            server_time: self.clock(),
+            // Assigned when the tick is sent.
+            sequence: 0,
+        };
+
+        for (handle, node) in graph.pair_iter() {
+            let current_state = node_state(node);
+
+            if let Some(update) = self.node_update(handle, current_state) {
+                tick_data.nodes.push(update);
+            }
+
+            if let Some(current_state) = sound_state(node) {
                 let prev_state = self
-                    .previous_node_states
+                    .previous_sound_states
                     .entry(handle)
                     .or_insert(current_state.clone());
 
                 if *prev_state != current_state {
-                    tick_data.nodes.push(current_state.clone());
+                    tick_data.sounds.push(current_state.clone());
                     *prev_state = current_state;
                 }
+            }
 
-                if let Some(sound) = node.component_ref::<Sound>() {
-                    let current_state = SoundState {
-                        node: sound.instance_id(),
-                        is_playing: sound.status() == Status::Playing,
-                    };
-
-                    let prev_state = self
-                        .previous_sound_states
-                        .entry(handle)
-                        .or_insert(current_state.clone());
+            if let Some(current_state) = actor_state(handle, graph) {
+                let prev_state = self
+                    .previous_actor_states
+                    .entry(handle)
+                    .or_insert(current_state.clone());
 
-                    if *prev_state != current_state {
-                        tick_data.sounds.push(current_state.clone());
-                        *prev_state = current_state;
-                    }
+                if *prev_state != current_state {
+                    tick_data.actors.push(current_state.clone());
+                    *prev_state = current_state;
                 }
             }
-
-            self.broadcast_message_to_clients(ServerMessage::UpdateTick(tick_data));
         }
+
+        let quantization_origin = self
+            .quantize_ticks
+            .then(|| quantization::level_origin(level, graph));
+        self.broadcast_tick(tick_data, quantization_origin);
     }
 
-    pub fn read_messages(&mut self, scene: Handle<Scene>, ctx: &mut PluginContext) {
-        for player in self.connections.iter_mut() {
-            player.process_input::<ClientMessage>(|msg| match msg {
-                ClientMessage::Input {
-                    player,
-                    input_state,
-                } => {
-                    let scene = &mut ctx.scenes[scene];
-                    if let Some((_, player_node)) = scene.graph.node_by_id_mut(player) {
-                        if let Some(player_ref) = player_node.try_get_script_mut::<Player>() {
-                            player_ref.input_controller = input_state;
+    pub fn read_messages(
+        &mut self,
+        level: &Level,
+        custom_message_handlers: &mut CustomMessageHandlers,
+        ctx: &mut PluginContext,
+    ) {
+        let scene = level.scene;
+        let server_time = self.clock();
+        let tick_sequence = self.tick_sequence;
+        let max_pending_ticks = self.max_pending_ticks();
+        let budget = self.max_messages_per_tick.max(1);
+        let mut custom_messages = Vec::new();
+        for (i, connection) in self.connections.iter_mut().enumerate() {
+            let mut received_any = false;
+            let mut queue = std::mem::take(&mut connection.pending_messages);
+            connection.process_input::<ClientMessage>(|msg| {
+                received_any = true;
+                queue.push_back(msg);
+            });
+            let mut clock_requests = Vec::new();
+            let mut pongs = Vec::new();
+            let mut needs_full_snapshot = false;
+            let mut last_input_sequence = connection.last_input_sequence;
+            let mut supports_compression = connection.supports_compression;
+            let mut ready = connection.ready;
+            let mut model = connection.model.clone();
+            let mut acked_tick = connection.acked_tick;
+            let mut handle_message = |msg: ClientMessage| {
+                match msg {
+                    ClientMessage::Input {
+                        player,
+                        input_state,
+                        sequence,
+                    } => {
+                        // Sequence numbers wrap around, so the difference is used to tell whether
+                        // the input is newer than the last one.
+                        if let Some(last) = last_input_sequence {
+                            if (sequence.wrapping_sub(last) as i32) <= 0 {
+                                return;
+                            }
+                        }
+                        last_input_sequence = Some(sequence);
+
+                        let scene = &mut ctx.scenes[scene];
+                        if let Some((_, player_node)) = scene.graph.node_by_id_mut(player) {
+                            if let Some(player_ref) = player_node.try_get_script_mut::<Player>() {
+                                player_ref.input_controller = input_state;
+                            }
+                        } else {
+                            Log::err("No such player!");
                         }
+                    }
+                    ClientMessage::Hello {
+                        supports_compression: supported,
+                    } => supports_compression = supported,
+                    ClientMessage::Ready { ready: is_ready } => ready = is_ready,
+                    ClientMessage::SelectModel { path } => {
+                        // Only known models could be selected, the path comes from the network.
+                        if player_models().contains(&path) {
+                            model = Some(path);
+                        } else {
+                            Log::warn(format!(
+                                "Unknown player model {} was rejected.",
+                                path.display()
+                            ));
+                            model = None;
+                        }
+                    }
+                    ClientMessage::TickAck { sequence } => {
+                        // Sequence numbers wrap around, see the input above.
+                        if (sequence.wrapping_sub(acked_tick) as i32) > 0 {
+                            acked_tick = sequence;
+                        }
+                    }
+                    ClientMessage::KeepAlive => {}
+                    ClientMessage::Pong { nonce } => pongs.push(nonce),
+                    ClientMessage::LevelLoaded => needs_full_snapshot = true,
+                    ClientMessage::SyncClock { client_time } => clock_requests.push(client_time),
+                    ClientMessage::Custom { tag, data } => custom_messages.push((i, tag, data)),
+                }
+            };
+            // Only a limited amount of messages is handled per tick, so a flooding client can't
+            // starve the rest. The remaining messages are handled on the next ticks.
+            for msg in take_budget(&mut queue, budget) {
+                handle_message(msg);
+            }
+            if queue.is_empty() {
+                connection.budget_exhausted_ticks = 0;
+            } else {
+                connection.budget_exhausted_ticks += 1;
+                if connection.budget_exhausted_ticks == BUDGET_WARN_TICKS {
+                    Log::warn(format!(
+                        "Connection {} has exceeded its message budget for {} ticks in a row, {} \
+                        message(s) are deferred.",
+                        connection.string_peer_address(),
+                        BUDGET_WARN_TICKS,
+                        queue.len()
+                    ));
+                }
+            }
+            connection.pending_messages = queue;
+            for client_time in clock_requests {
+                Log::verify(connection.send_message(&ServerMessage::SyncClock {
+                    client_time,
+                    server_time,
+                }));
+            }
+            // Answers to older pings are ignored, their timing is unknown.
+            for nonce in pongs {
+                if let Some((expected, sent)) = connection.pending_ping {
+                    if nonce == expected {
+                        let ping_text = connection.ping_text();
+                        connection.pending_ping = None;
+                        connection.round_trip_time = Some((server_time - sent).max(0.0) as f32);
+                        connection.ping_timed_out = false;
+                        if connection.ping_text() != ping_text {
+                            self.lobby_revision += 1;
+                        }
+                    }
+                }
+            }
+            connection.last_input_sequence = last_input_sequence;
+            connection.supports_compression = supports_compression;
+            connection.acked_tick = acked_tick;
+            // Ticks are delta-compressed, so a client that missed some of them needs the full
+            // state of the level.
+            if connection.dropped_ticks > 0 && !connection.is_behind(max_pending_ticks) {
+                Log::info(format!(
+                    "Connection {} has caught up after {} dropped update tick(s).",
+                    connection.string_peer_address(),
+                    connection.dropped_ticks
+                ));
+                connection.dropped_ticks = 0;
+                needs_full_snapshot = true;
+            }
+            if connection.ready != ready {
+                connection.ready = ready;
+                self.lobby_revision += 1;
+            }
+            if connection.model != model {
+                connection.model = model;
+                self.lobby_revision += 1;
+            }
+            if received_any {
+                connection.idle_time = 0.0;
+            }
+            // Delta-compressed updates are relative to the previous state, that the client could
+            // miss while it was loading the level.
+            if needs_full_snapshot {
+                if let Some(scene) = ctx.scenes.try_get(scene) {
+                    let mut snapshot =
+                        full_snapshot(level, server_time, tick_sequence, &scene.graph);
+                    if self.quantize_ticks && i != Self::HOST_CONNECTION {
+                        let origin = quantization::level_origin(level, &scene.graph);
+                        snapshot =
+                            quantize_message(&snapshot, origin, &mut self.quantization_stats);
+                    }
+                    let message = if self.compress_ticks
+                        && i != Self::HOST_CONNECTION
+                        && connection.supports_compression
+                    {
+                        compress_message(&snapshot, &mut self.compression_stats)
                     } else {
-                        Log::err("No such player!");
+                        None
+                    };
+                    let message = message.unwrap_or(ServerMessage::UpdateTick(snapshot));
+                    match connection.send_message(&message) {
+                        Ok(_) => connection.last_sent_tick = tick_sequence,
+                        Err(err) => Log::err(format!("Unable to send server message: {}", err)),
                     }
                 }
-            });
+            }
+        }
+        // Handlers could access the scene, so they're called after every connection is processed.
+        for (connection, tag, data) in custom_messages {
+            custom_message_handlers.handle(
+                CustomMessageSender::Client(connection),
+                tag,
+                &data,
+                ctx,
+            );
         }
     }
 
+    /// Removes the states of the nodes, that no longer exist in the given graph, and asks clients
+    /// to remove such nodes as well. Temporary objects (projectiles, effects, etc.) are removed by
+    /// the server (when their lifetime ends, for example), and this is the only place where their
+    /// removal is propagated to clients.
+    fn purge_despawned_nodes(&mut self, graph: &Graph) {
+        let mut despawned = Vec::new();
+        self.previous_node_states.retain(|handle, state| {
+            let alive = graph.is_valid_handle(*handle);
+            if !alive {
+                despawned.push(state.sent.node);
+            }
+            alive
+        });
+        self.previous_sound_states
+            .retain(|handle, _| graph.is_valid_handle(*handle));
+        self.previous_actor_states
+            .retain(|handle, _| graph.is_valid_handle(*handle));
+
+        for instance_id in despawned {
+            self.broadcast_message_to_clients(ServerMessage::Despawn { instance_id });
+        }
+    }
+
+    /// Forgets the states of every node. Must be called when the level changes, because handles
+    /// of the old level are meaningless for the new one.
+    fn clear_states(&mut self) {
+        self.previous_node_states.clear();
+        self.previous_sound_states.clear();
+        self.previous_actor_states.clear();
+    }
+
     pub fn on_scene_loaded(&mut self, scene: Handle<Scene>, ctx: &mut PluginContext) {
+        self.is_loading_level = false;
+        self.clear_states();
+
         let scene = &mut ctx.scenes[scene];
         let players_to_spawn = self.connections.len();
+        if players_to_spawn == 0 {
+            Log::warn("The level was loaded, but there are no connected players to spawn.");
+            return;
+        }
 
-        let start_points = scene
-            .graph
-            .linear_iter()
-            .filter(|n| n.has_script::<StartPoint>())
-            .map(|n| n.global_position())
-            .collect::<Vec<_>>();
+        let start_points = self.start_positions(&scene.graph);
+        if players_to_spawn > start_points.len() {
+            Log::warn(format!(
+                "There are {} start point(s) for {players_to_spawn} players, some start points \
+                are shared.",
+                start_points.len()
+            ));
+        }
 
-        let player_prefab = block_on(
-            ctx.resource_manager
-                .request::<Model>("data/models/player.rgs"),
-        )
-        .unwrap();
+        let default_prefab =
+            match load_actor_prefab::<Player>(PLAYER_PREFAB_PATH, &ctx.resource_manager) {
+                Ok(player_prefab) => player_prefab,
+                Err(reason) => {
+                    Log::err(format!("Unable to start the match. {reason}"));
+                    self.broadcast_message_to_clients(ServerMessage::AbortMatch { reason });
+                    return;
+                }
+            };
 
+        let mut spawns = Vec::new();
         for player_num in 0..players_to_spawn {
-            let ids = player_prefab.generate_ids();
-
-            if let Some(position) = start_points.get(player_num) {
-                for (connection_num, connection) in self.connections.iter_mut().enumerate() {
-                    connection
-                        .send_message(&ServerMessage::AddPlayers(vec![PlayerDescriptor {
-                            instance: InstanceDescriptor {
-                                path: "data/models/player.rgs".into(),
-                                position: *position,
-                                rotation: Default::default(),
-                                velocity: Default::default(),
-                                ids: ids.clone(),
-                            },
-                            kind: if player_num != connection_num {
-                                ActorKind::RemotePlayer
-                            } else {
-                                ActorKind::Player
-                            },
-                        }]))
-                        .unwrap();
+            let model = self.connections[player_num].model();
+            let (model_path, player_prefab) = if model == Path::new(PLAYER_PREFAB_PATH) {
+                (model.to_path_buf(), default_prefab.clone())
+            } else {
+                let path = model.to_string_lossy();
+                match load_actor_prefab::<Player>(&path, &ctx.resource_manager) {
+                    Ok(player_prefab) => (model.to_path_buf(), player_prefab),
+                    Err(reason) => {
+                        Log::warn(format!(
+                            "Unable to use the selected player model, the default one is used \
+                            instead. {reason}"
+                        ));
+                        (PLAYER_PREFAB_PATH.into(), default_prefab.clone())
+                    }
                 }
-            }
+            };
+            spawns.push(ActorSpawn {
+                path: model_path,
+                ids: player_prefab.generate_ids(),
+                owner: Some(player_num),
+                difficulty: Default::default(),
+                color: self.connections[player_num].color,
+                team: self.team_of(player_num),
+            });
         }
 
-        if self.add_bots {
-            let bot_prefab =
-                block_on(ctx.resource_manager.request::<Model>("data/models/bot.rgs")).unwrap();
-
-            for i in players_to_spawn..start_points.len() {
-                let ids = bot_prefab.generate_ids();
-
-                if let Some(position) = start_points.get(i) {
-                    for connection in self.connections.iter_mut() {
-                        connection
-                            .send_message(&ServerMessage::AddPlayers(vec![PlayerDescriptor {
-                                instance: InstanceDescriptor {
-                                    path: "data/models/bot.rgs".into(),
-                                    position: *position,
-                                    rotation: Default::default(),
-                                    velocity: Default::default(),
-                                    ids: ids.clone(),
-                                },
-                                kind: ActorKind::Bot,
-                            }]))
-                            .unwrap();
+        let bot_count = self.bots_to_spawn(players_to_spawn, start_points.len());
+        if bot_count > 0 {
+            match load_actor_prefab::<Bot>(BOT_PREFAB_PATH, &ctx.resource_manager) {
+                Ok(bot_prefab) => {
+                    let mut bot_colors = Self::ACTOR_COLORS
+                        .iter()
+                        .cloned()
+                        .filter(|color| !self.is_color_taken(*color))
+                        .collect::<Vec<_>>();
+                    if bot_colors.is_empty() {
+                        bot_colors = Self::ACTOR_COLORS.to_vec();
+                    }
+
+                    // Bots go after players and share start points the same way.
+                    for bot_num in 0..bot_count {
+                        spawns.push(ActorSpawn {
+                            path: BOT_PREFAB_PATH.into(),
+                            ids: bot_prefab.generate_ids(),
+                            owner: None,
+                            difficulty: self.bot_difficulty,
+                            color: bot_colors[bot_num % bot_colors.len()],
+                            team: self.team_of(players_to_spawn + bot_num),
+                        });
                     }
                 }
+                // The match is still playable without bots.
+                Err(reason) => Log::err(format!("Unable to spawn bots. {reason}")),
+            }
+        }
+
+        for (connection_num, connection) in self.connections.iter_mut().enumerate() {
+            for descriptor in player_descriptors(&spawns, &start_points, connection_num) {
+                Log::verify(connection.send_message(&ServerMessage::AddPlayers(vec![descriptor])));
             }
         }
     }
 
+    /// Returns positions of the start points of the scene in the order, in which they are
+    /// assigned to actors. Levels without start points get a single start point at the origin,
+    /// see [`start::positions_or_fallback`].
+    fn start_positions(&self, graph: &Graph) -> Vec<Vector3<f32>> {
+        // Linear iteration order is defined by the scene file, so every player gets the same
+        // start point on every run with the same seed.
+        let start_points = graph
+            .linear_iter()
+            .filter(|n| n.has_script::<StartPoint>())
+            .map(|n| n.global_position())
+            .collect::<Vec<_>>();
+        if start_points.is_empty() {
+            Log::warn(
+                "There are no start points on the level! Players will be spawned at the origin.",
+            );
+        }
+        start::assignment_order(
+            start::positions_or_fallback(start_points),
+            self.match_seed,
+            self.shuffle_start_points,
+        )
+    }
+
+    /// Returns the team of an actor with the given spawn index. Actors are dealt to teams one by
+    /// one, so teams differ by one actor at most. Matches with less than two teams are
+    /// free-for-all, there are no teams then.
+    pub fn team_of(&self, spawn_index: usize) -> Option<u32> {
+        (self.team_count >= 2).then(|| (spawn_index % self.team_count as usize) as u32)
+    }
+
+    /// Returns the amount of bots, that will join a match with the given amount of players and
+    /// start points.
+    pub fn bots_to_spawn(&self, players: usize, start_points: usize) -> usize {
+        let count = if self.add_bots {
+            start_points.saturating_sub(players)
+        } else {
+            self.bot_count
+        };
+        count.min(Self::MAX_BOTS)
+    }
+
     pub fn address(&self) -> SocketAddr {
         self.listener.local_address().unwrap()
     }
 
-    pub fn connections(&self) -> &[NetStream] {
+    pub fn connections(&self) -> &[Connection] {
         &self.connections
     }
 
@@ -231,6 +1491,346 @@ impl Server {
     }
 
     pub fn accept_connections(&mut self) {
-        self.connections.extend(self.listener.accept_connections())
+        for stream in self.listener.accept_connections() {
+            let color = self.free_color();
+            let mut connection = Connection {
+                stream,
+                idle_time: 0.0,
+                color,
+                last_input_sequence: None,
+                supports_compression: false,
+                ready: false,
+                model: None,
+                acked_tick: self.tick_sequence,
+                last_sent_tick: self.tick_sequence,
+                dropped_ticks: 0,
+                backpressure_time: 0.0,
+                pending_messages: Default::default(),
+                budget_exhausted_ticks: 0,
+                pending_ping: None,
+                round_trip_time: None,
+                ping_timed_out: false,
+            };
+            // Late joiners see the results of the last match as well.
+            if let Some(message) = self.results_message() {
+                Log::verify(connection.send_message(&message));
+            }
+            self.connections.push(connection);
+            self.lobby_revision += 1;
+        }
+    }
+}
+
+/// Returns results of the match sorted by place: finished actors go first, then actors, that are
+/// neither finished nor eliminated, and eliminated actors go last.
+fn player_results<'a>(
+    entries: impl Iterator<Item = (&'a LeaderBoardEntry, String)>,
+) -> Vec<PlayerResult> {
+    let mut players = entries
+        .map(|(entry, name)| PlayerResult {
+            name,
+            place: entry.finished_position,
+            eliminated: entry.eliminated,
+        })
+        .collect::<Vec<_>>();
+    // Actors, that have not finished, have no place.
+    players.sort_by_key(|e| (e.eliminated, e.place == 0, e.place));
+    players
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Leaderboard;
+    use fyrox::{
+        core::task::TaskPool,
+        scene::{base::BaseBuilder, pivot::PivotBuilder, sound::SoundBuilder},
+        script::Script,
+    };
+    use std::sync::Arc;
+
+    /// Returns a server without connections, that listens on a random local port.
+    fn test_server() -> Server {
+        Server::new("127.0.0.1:0").unwrap()
+    }
+
+    fn add_actor(graph: &mut Graph) -> Handle<Node> {
+        PivotBuilder::new(BaseBuilder::new().with_script(Script::new(Bot::default()))).build(graph)
+    }
+
+    #[test]
+    fn message_budget_is_respected() {
+        // A flooding connection sends many messages at once.
+        let mut queue = (0..100).collect::<VecDeque<_>>();
+        let mut handled = Vec::new();
+        for _ in 0..4 {
+            let taken = take_budget(&mut queue, 30).collect::<Vec<_>>();
+            assert!(taken.len() <= 30);
+            handled.extend(taken);
+        }
+        // Deferred messages are handled in order on the next ticks.
+        assert!(queue.is_empty());
+        assert_eq!(handled, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn only_existing_scene_files_are_valid_levels() {
+        let dir = std::env::temp_dir().join(format!("fish_fall_levels_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let level = dir.join("level.rgs");
+        let not_a_scene = dir.join("level.txt");
+        std::fs::write(&level, []).unwrap();
+        std::fs::write(&not_a_scene, []).unwrap();
+
+        assert!(validate_level_path(&level).is_ok());
+        assert!(validate_level_path(&not_a_scene).is_err());
+        assert!(validate_level_path(&dir.join("missing.rgs")).is_err());
+        assert!(validate_level_path(&dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn qualification_and_results_follow_finish_order() {
+        let names = ["Ann", "Bob", "Cid", "Dan", "Eve"];
+        let actors = (0..names.len() as u32)
+            .map(|i| Handle::new(i, 1))
+            .collect::<Vec<_>>();
+        let mut leaderboard = Leaderboard::default();
+        for actor in actors.iter() {
+            leaderboard.entries.insert(
+                *actor,
+                LeaderBoardEntry {
+                    actor: *actor,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // Two actors qualify, the rest is eliminated once the second one has finished.
+        let qualifier_count = 2;
+        for actor in [actors[3], actors[1]] {
+            assert!(leaderboard.qualified_count() < qualifier_count);
+            leaderboard.finish(actor);
+        }
+        assert_eq!(leaderboard.qualified_count(), qualifier_count);
+        assert_eq!(leaderboard.eliminate_unqualified().len(), 3);
+
+        let results = player_results(
+            actors
+                .iter()
+                .zip(names)
+                .map(|(actor, name)| (&leaderboard.entries[actor], name.to_string())),
+        );
+        let summary = results
+            .iter()
+            .map(|r| (r.name.as_str(), r.place, r.eliminated))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("Dan", 1, false),
+                ("Bob", 2, false),
+                ("Ann", 0, true),
+                ("Cid", 0, true),
+                ("Eve", 0, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn racing_actors_are_listed_after_finished_ones() {
+        let finished = LeaderBoardEntry {
+            finished: true,
+            finished_position: 1,
+            ..Default::default()
+        };
+        let racing = LeaderBoardEntry::default();
+        let results = player_results(
+            [
+                (&racing, "Racer".to_string()),
+                (&finished, "Winner".to_string()),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(results[0].name, "Winner");
+        assert_eq!(results[1].name, "Racer");
+    }
+
+    #[test]
+    fn despawned_temporaries_leave_no_tracked_states() {
+        let mut server = test_server();
+        let level = Level::default();
+        let mut graph = Graph::new();
+        server.send_snapshot(&level, &graph);
+        let persistent_nodes = server.previous_node_states.len();
+
+        for _ in 0..10 {
+            let temporaries = (0..50)
+                .map(|i| {
+                    if i % 2 == 0 {
+                        SoundBuilder::new(BaseBuilder::new()).build(&mut graph)
+                    } else {
+                        add_actor(&mut graph)
+                    }
+                })
+                .collect::<Vec<_>>();
+            server.send_snapshot(&level, &graph);
+            assert_eq!(server.previous_node_states.len(), persistent_nodes + 50);
+            assert_eq!(server.previous_sound_states.len(), 25);
+            assert_eq!(server.previous_actor_states.len(), 25);
+
+            // Handles of removed nodes are reused by the next batch with another generation.
+            for temporary in temporaries {
+                graph.remove_node(temporary);
+            }
+            server.send_snapshot(&level, &graph);
+            assert_eq!(server.previous_node_states.len(), persistent_nodes);
+            assert!(server.previous_sound_states.is_empty());
+            assert!(server.previous_actor_states.is_empty());
+        }
+    }
+
+    #[test]
+    fn only_real_moves_and_settling_are_sent() {
+        let at = |x: f32| NodeState {
+            node: Default::default(),
+            position: Vector3::new(x, 0.0, 0.0),
+            rotation: Default::default(),
+            scale: Some(Vector3::repeat(1.0)),
+        };
+        let mut tracked = TrackedNodeState {
+            sent: at(0.0),
+            observed: at(0.0),
+        };
+        let mut update = |state| {
+            tracked.update(
+                state,
+                Server::DEFAULT_POSITION_EPSILON,
+                Server::DEFAULT_ROTATION_EPSILON,
+                Server::DEFAULT_SCALE_EPSILON,
+            )
+        };
+
+        // Jitter below the epsilon is not sent.
+        let jitter = Server::DEFAULT_POSITION_EPSILON * 0.5;
+        for step in 0..10 {
+            let sign = if step % 2 == 0 { 1.0 } else { -1.0 };
+            assert_eq!(update(at(sign * jitter)), None);
+        }
+
+        // A real move is sent, an unchanged scale is omitted.
+        let moved = update(at(1.0)).unwrap();
+        assert_eq!(moved.position, at(1.0).position);
+        assert_eq!(moved.scale, None);
+
+        // A tiny drift is not sent until the node comes to rest, then the exact state is sent once.
+        assert_eq!(update(at(1.0 + jitter)), None);
+        assert_eq!(
+            update(at(1.0 + jitter)).map(|state| state.position),
+            Some(at(1.0 + jitter).position)
+        );
+        assert_eq!(update(at(1.0 + jitter)), None);
+    }
+
+    #[test]
+    fn new_nodes_are_sent_before_they_move() {
+        let mut server = test_server();
+        let mut graph = Graph::new();
+        let node = PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let state = node_state(&graph[node]);
+
+        // The first state is sent in full, even though the node is at rest.
+        assert_eq!(server.node_update(node, state.clone()), Some(state.clone()));
+        assert_eq!(server.node_update(node, state), None);
+    }
+
+    #[test]
+    fn missing_prefab_is_an_error() {
+        let resource_manager = ResourceManager::new(Arc::new(TaskPool::new()));
+        let result = load_actor_prefab::<Player>("data/models/missing.rgs", &resource_manager);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn load_level_message_carries_selected_level() {
+        let mut server = test_server();
+        server.seed = Some(42);
+        let selected = Path::new("data/levels/level2.rgs");
+        for expected_match_id in [1, 2] {
+            let ServerMessage::LoadLevel {
+                path,
+                seed,
+                match_id,
+                force,
+            } = server.begin_match(selected)
+            else {
+                panic!("LoadLevel message was expected");
+            };
+            assert_eq!(path, selected);
+            assert_eq!(seed, 42);
+            assert_eq!(match_id, expected_match_id);
+            assert!(!force);
+        }
+    }
+
+    fn player_spawn(owner: usize) -> ActorSpawn {
+        ActorSpawn {
+            path: PLAYER_PREFAB_PATH.into(),
+            ids: Default::default(),
+            owner: Some(owner),
+            difficulty: Default::default(),
+            color: Server::ACTOR_COLORS[owner],
+            team: None,
+        }
+    }
+
+    #[test]
+    fn every_player_is_spawned_when_start_points_are_shared() {
+        let spawns = (0..3).map(player_spawn).collect::<Vec<_>>();
+        let start_points = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(10.0, 0.0, 0.0)];
+        for connection_num in 0..3 {
+            let descriptors = player_descriptors(&spawns, &start_points, connection_num);
+            assert_eq!(descriptors.len(), 3);
+
+            let positions = descriptors
+                .iter()
+                .map(|d| d.instance.position)
+                .collect::<Vec<_>>();
+            assert_eq!(positions[..2], start_points);
+            // The third player shares the first start point, but does not overlap the first one.
+            assert!(positions[2].metric_distance(&start_points[0]) > 0.5);
+
+            for (player_num, descriptor) in descriptors.iter().enumerate() {
+                let expected_kind = if player_num == connection_num {
+                    ActorKind::Player
+                } else {
+                    ActorKind::RemotePlayer
+                };
+                assert_eq!(descriptor.kind, expected_kind);
+                assert_eq!(
+                    descriptor.is_remote,
+                    connection_num != Server::HOST_CONNECTION
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn scene_without_start_points_spawns_at_origin() {
+        let server = test_server();
+        let mut graph = Graph::new();
+        PivotBuilder::new(BaseBuilder::new()).build(&mut graph);
+        graph.update_hierarchical_data();
+
+        let start_points = server.start_positions(&graph);
+        assert_eq!(start_points, [Vector3::default()]);
+
+        let spawns = (0..2).map(player_spawn).collect::<Vec<_>>();
+        let descriptors = player_descriptors(&spawns, &start_points, Server::HOST_CONNECTION);
+        assert_eq!(descriptors.len(), 2);
+        assert_eq!(descriptors[0].instance.position, Vector3::default());
+        // The second player is placed next to the fallback position.
+        assert!(descriptors[1].instance.position.norm() > 0.5);
     }
 }
@@ -1,3 +1,8 @@
+use crate::{
+    camera::{RagdollCameraMode, SpectatorTransition},
+    key_bindings::KeyBindings,
+    server::PLAYER_PREFAB_PATH,
+};
 use fyrox::{
     core::log::Log, core::visitor::prelude::*, engine::GraphicsContext, renderer::QualitySettings,
     scene::Scene,
@@ -7,7 +12,8 @@ use serde::{Deserialize, Serialize};
 use std::{
     fs::File,
     io::{Read, Write},
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, RangeInclusive},
+    path::PathBuf,
 };
 
 #[derive(Visit, Debug)]
@@ -60,14 +66,59 @@ impl<'a> Drop for SettingsDataRefMut<'a> {
 }
 
 #[derive(Debug, Serialize, Deserialize, Visit)]
+#[serde(default)]
 pub struct SettingsData {
     pub graphics_quality: usize,
     pub sound_volume: f32,
     pub music_volume: f32,
     #[visit(skip)] // TODO
     pub graphics_presets: Vec<(String, QualitySettings)>,
+    /// Horizontal mouse sensitivity.
     pub mouse_sensitivity: f32,
+    pub mouse_sensitivity_vertical: f32,
+    pub invert_y: bool,
     pub mouse_smoothness: f32,
+    /// Sprint key toggles sprinting instead of being held.
+    pub toggle_sprint: bool,
+    /// Pause single-player game when the game window loses focus.
+    pub pause_on_focus_loss: bool,
+    /// Vertical field of view of the camera (in degrees).
+    pub field_of_view: f32,
+    /// Smoothly interpolate objects between network updates on clients. Has no effect on the
+    /// host, which simulates every object (remote players too) locally.
+    pub render_interpolation: bool,
+    /// Max distance (in meters) at which name tags of other actors are visible.
+    pub name_tag_distance: f32,
+    /// Fraction of [`Self::name_tag_distance`] (at its end) along which name tags fade out.
+    pub name_tag_fade: f32,
+    /// Min and max pitch (in degrees) of the camera.
+    pub camera_min_pitch: f32,
+    pub camera_max_pitch: f32,
+    /// Speed (in degrees per second) at which the camera returns to the neutral pitch, when the
+    /// player does not look around. Zero disables auto-centering.
+    pub camera_auto_center_speed: f32,
+    /// Amount of input messages (per second) sent to the server.
+    pub input_send_rate: f32,
+    /// Wait for the respawn button after a fall instead of respawning automatically. The server
+    /// could disallow it or force the respawn after a while.
+    pub manual_respawn: bool,
+    /// Min amount of time (in seconds) over which corrections of replicated objects are blended.
+    pub correction_smoothing: f32,
+    /// Error (in meters) of a replicated object, beyond which it snaps to the correct position.
+    pub snap_distance: f32,
+    /// Mouse sensitivity while aiming with a cannon.
+    pub aim_sensitivity: f32,
+    /// Behavior of the camera, while the followed actor is ragdolled.
+    pub ragdoll_camera: RagdollCameraMode,
+    /// Transition of the spectator camera, when it switches to another actor.
+    pub spectator_transition: SpectatorTransition,
+    /// Speed multiplier of [`Self::spectator_transition`].
+    pub spectator_transition_speed: f32,
+    /// Model of the player's actor in multiplayer matches, see [`crate::server::player_models`].
+    pub player_model: PathBuf,
+    /// Keys of the actions of the player.
+    #[visit(skip)]
+    pub key_bindings: KeyBindings,
 }
 
 fn adjust_settings(mut settings: QualitySettings) -> QualitySettings {
@@ -76,13 +127,33 @@ fn adjust_settings(mut settings: QualitySettings) -> QualitySettings {
     settings
 }
 
+/// Name of the preset for very weak hardware, see [`potato_settings`].
+pub const POTATO_PRESET: &str = "Potato";
+
+/// Fraction of the window size, at which the level is rendered with [`POTATO_PRESET`].
+pub const POTATO_RENDER_SCALE: f32 = 0.5;
+
+/// Quality settings for very weak hardware and integrated GPUs: no shadows at all (including
+/// directional ones), no post-processing and no ambient occlusion.
+fn potato_settings() -> QualitySettings {
+    let mut settings = adjust_settings(QualitySettings::low());
+    settings.point_shadows_enabled = false;
+    settings.spot_shadows_enabled = false;
+    settings.csm_settings.enabled = false;
+    settings.light_scatter_enabled = false;
+    settings.fxaa = false;
+    settings.use_bloom = false;
+    settings
+}
+
 impl Default for SettingsData {
     fn default() -> Self {
         Self {
-            graphics_quality: 3,
+            graphics_quality: 4,
             sound_volume: 100.0,
             music_volume: 100.0,
             graphics_presets: vec![
+                (POTATO_PRESET.to_string(), potato_settings()),
                 ("Low".to_string(), adjust_settings(QualitySettings::low())),
                 (
                     "Medium".to_string(),
@@ -95,12 +166,175 @@ impl Default for SettingsData {
                 ),
             ],
             mouse_sensitivity: 0.5,
+            mouse_sensitivity_vertical: 0.5,
+            invert_y: false,
             mouse_smoothness: 0.75,
+            toggle_sprint: false,
+            pause_on_focus_loss: true,
+            field_of_view: 75.0,
+            render_interpolation: true,
+            name_tag_distance: 30.0,
+            name_tag_fade: 0.3,
+            camera_min_pitch: -70.0,
+            camera_max_pitch: 80.0,
+            camera_auto_center_speed: 0.0,
+            input_send_rate: 60.0,
+            manual_respawn: false,
+            correction_smoothing: 0.0,
+            snap_distance: 5.0,
+            aim_sensitivity: 0.25,
+            ragdoll_camera: RagdollCameraMode::FollowCenterOfMass,
+            spectator_transition: SpectatorTransition::Smooth,
+            spectator_transition_speed: 1.0,
+            player_model: PLAYER_PREFAB_PATH.into(),
+            key_bindings: Default::default(),
         }
     }
 }
 
 impl SettingsData {
+    pub const SENSITIVITY_RANGE: RangeInclusive<f32> = 0.05..=2.0;
+    pub const FIELD_OF_VIEW_RANGE: RangeInclusive<f32> = 60.0..=110.0;
+    pub const NAME_TAG_DISTANCE_RANGE: RangeInclusive<f32> = 5.0..=100.0;
+    pub const PITCH_RANGE: RangeInclusive<f32> = -89.0..=89.0;
+    pub const AUTO_CENTER_SPEED_RANGE: RangeInclusive<f32> = 0.0..=180.0;
+    pub const INPUT_SEND_RATE_RANGE: RangeInclusive<f32> = 20.0..=120.0;
+    pub const CORRECTION_SMOOTHING_RANGE: RangeInclusive<f32> = 0.0..=0.5;
+    pub const SNAP_DISTANCE_RANGE: RangeInclusive<f32> = 1.0..=20.0;
+    pub const SPECTATOR_TRANSITION_SPEED_RANGE: RangeInclusive<f32> = 0.25..=4.0;
+
+    pub fn spectator_transition_speed(&self) -> f32 {
+        self.spectator_transition_speed.clamp(
+            *Self::SPECTATOR_TRANSITION_SPEED_RANGE.start(),
+            *Self::SPECTATOR_TRANSITION_SPEED_RANGE.end(),
+        )
+    }
+
+    pub fn correction_smoothing(&self) -> f32 {
+        self.correction_smoothing.clamp(
+            *Self::CORRECTION_SMOOTHING_RANGE.start(),
+            *Self::CORRECTION_SMOOTHING_RANGE.end(),
+        )
+    }
+
+    pub fn snap_distance(&self) -> f32 {
+        self.snap_distance.clamp(
+            *Self::SNAP_DISTANCE_RANGE.start(),
+            *Self::SNAP_DISTANCE_RANGE.end(),
+        )
+    }
+
+    pub fn input_send_rate(&self) -> f32 {
+        self.input_send_rate.clamp(
+            *Self::INPUT_SEND_RATE_RANGE.start(),
+            *Self::INPUT_SEND_RATE_RANGE.end(),
+        )
+    }
+
+    /// Returns min and max pitch (in degrees) of the camera clamped to [`Self::PITCH_RANGE`]. Min
+    /// pitch is never greater than max pitch.
+    pub fn camera_pitch_limits(&self) -> (f32, f32) {
+        let min = self
+            .camera_min_pitch
+            .clamp(*Self::PITCH_RANGE.start(), *Self::PITCH_RANGE.end());
+        let max = self
+            .camera_max_pitch
+            .clamp(*Self::PITCH_RANGE.start(), *Self::PITCH_RANGE.end());
+        (min.min(max), max.max(min))
+    }
+
+    pub fn camera_auto_center_speed(&self) -> f32 {
+        self.camera_auto_center_speed.clamp(
+            *Self::AUTO_CENTER_SPEED_RANGE.start(),
+            *Self::AUTO_CENTER_SPEED_RANGE.end(),
+        )
+    }
+
+    /// Returns field of view (in degrees) clamped to [`Self::FIELD_OF_VIEW_RANGE`].
+    pub fn field_of_view(&self) -> f32 {
+        self.field_of_view.clamp(
+            *Self::FIELD_OF_VIEW_RANGE.start(),
+            *Self::FIELD_OF_VIEW_RANGE.end(),
+        )
+    }
+
+    /// Returns opacity of a name tag at the given distance from the camera. Tags are fully opaque
+    /// up to the fade zone and then linearly fade out to the max distance.
+    pub fn name_tag_opacity(&self, distance: f32) -> f32 {
+        let max_distance = self.name_tag_distance.clamp(
+            *Self::NAME_TAG_DISTANCE_RANGE.start(),
+            *Self::NAME_TAG_DISTANCE_RANGE.end(),
+        );
+        let fade_distance = max_distance * self.name_tag_fade.clamp(0.0, 1.0);
+        if distance >= max_distance {
+            0.0
+        } else if fade_distance <= f32::EPSILON {
+            1.0
+        } else {
+            ((max_distance - distance) / fade_distance).min(1.0)
+        }
+    }
+
+    pub fn horizontal_sensitivity(&self) -> f32 {
+        self.mouse_sensitivity.clamp(
+            *Self::SENSITIVITY_RANGE.start(),
+            *Self::SENSITIVITY_RANGE.end(),
+        )
+    }
+
+    /// Returns mouse sensitivity while aiming with a cannon, it is the same for both axes.
+    pub fn aim_sensitivity(&self) -> f32 {
+        self.aim_sensitivity.clamp(
+            *Self::SENSITIVITY_RANGE.start(),
+            *Self::SENSITIVITY_RANGE.end(),
+        )
+    }
+
+    /// Returns vertical sensitivity, it is negative if Y axis is inverted.
+    pub fn vertical_sensitivity(&self) -> f32 {
+        self.apply_invert_y(self.mouse_sensitivity_vertical.clamp(
+            *Self::SENSITIVITY_RANGE.start(),
+            *Self::SENSITIVITY_RANGE.end(),
+        ))
+    }
+
+    /// Returns vertical sensitivity while aiming with a cannon, it is negative if Y axis is
+    /// inverted. See [`Self::aim_sensitivity`].
+    pub fn aim_vertical_sensitivity(&self) -> f32 {
+        self.apply_invert_y(self.aim_sensitivity())
+    }
+
+    fn apply_invert_y(&self, sensitivity: f32) -> f32 {
+        if self.invert_y {
+            -sensitivity
+        } else {
+            sensitivity
+        }
+    }
+
+    /// Returns the fraction of the window size, at which the level is rendered with the selected
+    /// graphics preset.
+    pub fn render_scale(&self) -> f32 {
+        match self.graphics_presets.get(self.graphics_quality) {
+            Some((name, _)) if name == POTATO_PRESET => POTATO_RENDER_SCALE,
+            _ => 1.0,
+        }
+    }
+
+    /// Adds [`POTATO_PRESET`] to the presets of settings, that were saved before it existed. The
+    /// selected preset stays the same.
+    fn add_missing_presets(&mut self) {
+        if !self
+            .graphics_presets
+            .iter()
+            .any(|(name, _)| name == POTATO_PRESET)
+        {
+            self.graphics_presets
+                .insert(0, (POTATO_PRESET.to_string(), potato_settings()));
+            self.graphics_quality += 1;
+        }
+    }
+
     pub fn save(&self) {
         match to_string_pretty(self, Default::default()) {
             Ok(serialized) => match File::create("game_settings.ron") {
@@ -124,8 +358,14 @@ impl SettingsData {
             Ok(mut file) => {
                 let mut file_content = String::new();
                 match file.read_to_string(&mut file_content) {
-                    Ok(_) => match ron::from_str(&file_content) {
-                        Ok(settings) => {
+                    Ok(_) => match ron::from_str::<Self>(&file_content) {
+                        Ok(mut settings) => {
+                            settings.add_missing_presets();
+                            if settings.graphics_quality >= settings.graphics_presets.len() {
+                                Log::warn("Unknown graphics preset, fallback to the last one.");
+                                settings.graphics_quality =
+                                    settings.graphics_presets.len().saturating_sub(1);
+                            }
                             return settings;
                         }
                         Err(err) => Log::err(format!(
@@ -147,6 +387,13 @@ impl SettingsData {
         Default::default()
     }
 
+    /// Converts a volume (in percents) to the gain of an audio bus. Zero volume gives exactly zero
+    /// gain, so muted sounds are silent.
+    fn volume_to_gain(volume: f32) -> f32 {
+        (volume / 100.0).clamp(0.0, 1.0)
+    }
+
+    /// Sets the gain of sound effects of the level, it must be called for every loaded level.
     pub fn apply_sound_volume(&self, scene: &Scene) {
         scene
             .graph
@@ -154,15 +401,16 @@ impl SettingsData {
             .state()
             .bus_graph_mut()
             .primary_bus_mut()
-            .set_gain((self.sound_volume / 100.0).clamp(0.0, 1.0));
+            .set_gain(Self::volume_to_gain(self.sound_volume));
     }
 
+    /// Sets the gain of the music bus of the menu scene, that plays both menu and round music.
     pub fn apply_music_volume(&self, scene: &Scene) {
         let name = "Music";
         let mut sound_context = scene.graph.sound_context.state();
         for bus in sound_context.bus_graph_mut().buses_iter_mut() {
             if bus.name() == name {
-                bus.set_gain((self.music_volume / 100.0).clamp(0.0, 1.0));
+                bus.set_gain(Self::volume_to_gain(self.music_volume));
                 return;
             }
         }
@@ -2,7 +2,11 @@
 
 use crate::Game;
 use fyrox::{
-    core::{log::Log, reflect::prelude::*, type_traits::prelude::*, visitor::prelude::*},
+    core::{
+        algebra::Vector3, log::Log, reflect::prelude::*, type_traits::prelude::*,
+        visitor::prelude::*,
+    },
+    rand::{rngs::StdRng, seq::SliceRandom, SeedableRng},
     script::{ScriptContext, ScriptDeinitContext, ScriptTrait},
 };
 
@@ -11,6 +15,49 @@ use fyrox::{
 #[visit(optional)]
 pub struct StartPoint {}
 
+/// Returns the given positions of start points or the origin of the scene if there are none, so
+/// levels without start points are still playable.
+pub fn positions_or_fallback(positions: Vec<Vector3<f32>>) -> Vec<Vector3<f32>> {
+    if positions.is_empty() {
+        vec![Vector3::default()]
+    } else {
+        positions
+    }
+}
+
+/// Returns the order, in which the given start points are assigned to actors. If `shuffle` is set,
+/// the order is picked by the seed of the match, so nobody gets the best position every round, but
+/// a replay with the same seed gets the same assignment. Otherwise the order is the same as the
+/// order of the scene.
+pub fn assignment_order(
+    mut positions: Vec<Vector3<f32>>,
+    seed: u64,
+    shuffle: bool,
+) -> Vec<Vector3<f32>> {
+    if shuffle {
+        positions.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    positions
+}
+
+/// Distance (in meters) between actors, that share a start point.
+const SHARED_START_POINT_SPACING: f32 = 1.0;
+
+/// Returns the spawn position of the actor with the given index. Start points are reused if there
+/// are more actors than start points, every next actor on the same start point is moved a bit
+/// around it, so actors do not overlap. The offset depends on the index only, so it is the same on
+/// every run.
+pub fn spawn_position(positions: &[Vector3<f32>], index: usize) -> Option<Vector3<f32>> {
+    let position = *positions.get(index % positions.len().max(1))?;
+    let round = index / positions.len();
+    if round == 0 {
+        return Some(position);
+    }
+    let angle = round as f32 * std::f32::consts::FRAC_PI_2;
+    let radius = SHARED_START_POINT_SPACING * (1 + (round - 1) / 4) as f32;
+    Some(position + Vector3::new(angle.cos(), 0.0, angle.sin()).scale(radius))
+}
+
 impl ScriptTrait for StartPoint {
     fn on_init(&mut self, ctx: &mut ScriptContext) {
         ctx.plugins
@@ -30,3 +77,56 @@ impl ScriptTrait for StartPoint {
         Log::info(format!("Start point {:?} destroyed!", ctx.node_handle));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(count: usize) -> Vec<Vector3<f32>> {
+        (0..count)
+            .map(|i| Vector3::new(i as f32 * 10.0, 0.0, 0.0))
+            .collect()
+    }
+
+    #[test]
+    fn assignment_order_is_the_same_for_the_same_seed() {
+        assert_eq!(
+            assignment_order(positions(8), 42, true),
+            assignment_order(positions(8), 42, true)
+        );
+    }
+
+    #[test]
+    fn assignment_order_is_a_permutation() {
+        let mut order = assignment_order(positions(8), 42, true);
+        order.sort_by(|a, b| a.x.total_cmp(&b.x));
+        assert_eq!(order, positions(8));
+    }
+
+    #[test]
+    fn assignment_order_without_shuffle_keeps_scene_order() {
+        assert_eq!(assignment_order(positions(8), 42, false), positions(8));
+    }
+
+    #[test]
+    fn spawn_position_uses_start_points_first() {
+        let positions = positions(3);
+        for (index, position) in positions.iter().enumerate() {
+            assert_eq!(spawn_position(&positions, index), Some(*position));
+        }
+        assert_eq!(spawn_position(&[], 0), None);
+    }
+
+    #[test]
+    fn shared_spawn_positions_do_not_overlap() {
+        let positions = positions(1);
+        let spawned = (0..9)
+            .map(|index| spawn_position(&positions, index).unwrap())
+            .collect::<Vec<_>>();
+        for (i, a) in spawned.iter().enumerate() {
+            for b in &spawned[i + 1..] {
+                assert!(a.metric_distance(b) >= SHARED_START_POINT_SPACING * 0.99);
+            }
+        }
+    }
+}
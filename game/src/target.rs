@@ -1,15 +1,39 @@
-//! A target that bots will try to reach.
+//! A target that bots will try to reach. Grouped targets form objectives of the level, see
+//! [`crate::objective`].
 
 use crate::Game;
 use fyrox::{
-    core::{log::Log, reflect::prelude::*, type_traits::prelude::*, visitor::prelude::*},
+    core::{
+        log::Log, reflect::prelude::*, type_traits::prelude::*, variable::InheritableVariable,
+        visitor::prelude::*,
+    },
     script::{ScriptContext, ScriptDeinitContext, ScriptTrait},
 };
 
-#[derive(Clone, Default, Debug, Visit, Reflect, ComponentProvider, TypeUuidProvider)]
+#[derive(Clone, Debug, Visit, Reflect, ComponentProvider, TypeUuidProvider)]
 #[type_uuid(id = "dcf159d1-6bd9-4e19-8a2a-c838a1ab8f0d")]
 #[visit(optional)]
-pub struct Target {}
+pub struct Target {
+    #[reflect(
+        description = "Targets of the same group count toward a shared objective of the level \
+        (see objectives of the level config). A target without a group is on its own."
+    )]
+    pub group: InheritableVariable<Option<u32>>,
+    #[reflect(
+        description = "Distance (in meters) at which an actor reaches the target.",
+        min_value = 0.0
+    )]
+    pub reach_radius: InheritableVariable<f32>,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self {
+            group: Default::default(),
+            reach_radius: 1.5.into(),
+        }
+    }
+}
 
 impl ScriptTrait for Target {
     fn on_init(&mut self, ctx: &mut ScriptContext) {
@@ -1,4 +1,4 @@
-use crate::{actor::Actor, Game};
+use crate::{actor::Actor, event::GameEvent, Game};
 use fyrox::graph::BaseSceneGraph;
 use fyrox::{
     core::{
@@ -37,14 +37,16 @@ pub struct Trigger {
 impl ScriptTrait for Trigger {
     fn on_update(&mut self, ctx: &mut ScriptContext) {
         let game = ctx.plugins.get_mut::<Game>();
-        if game.is_client() {
+        if game.is_client() || game.is_paused() {
             return;
         }
 
         let this = &ctx.scene.graph[ctx.handle];
         let self_bounds = AxisAlignedBoundingBox::unit().transform(&this.global_transform());
 
-        for actor_handle in game.level.actors.iter() {
+        // Actors must be processed in deterministic order, so places of actors that finished
+        // at the same frame are always resolved the same way.
+        for actor_handle in game.level.ordered_actors(&ctx.scene.graph).iter() {
             if let Some(actor_script) = ctx
                 .scene
                 .graph
@@ -54,7 +56,14 @@ impl ScriptTrait for Trigger {
                 if let Some(rigid_body) = ctx.scene.graph.try_get(rigid_body) {
                     if self_bounds.is_contains_point(rigid_body.global_position()) {
                         match *self.action {
-                            Action::Finish => game.level.leaderboard.finish(*actor_handle),
+                            Action::Finish => {
+                                if let Some(place) = game.level.leaderboard.finish(*actor_handle) {
+                                    game.emit(GameEvent::ActorQualified {
+                                        actor: *actor_handle,
+                                        place,
+                                    });
+                                }
+                            }
                         }
                     }
                 }
@@ -1,14 +1,131 @@
 use fyrox::{
-    core::pool::Handle,
+    core::{
+        algebra::{Point3, Vector3},
+        color::Color,
+        pool::Handle,
+    },
     graph::{BaseSceneGraph, SceneGraph},
-    scene::{collider::Collider, graph::Graph, node::Node, sound::Sound},
+    rand::{rngs::StdRng, SeedableRng},
+    scene::{
+        camera::Camera,
+        collider::Collider,
+        debug::{Line, SceneDrawingContext},
+        graph::{physics::ContactManifold, Graph},
+        node::Node,
+        rigidbody::RigidBody,
+        sound::Sound,
+    },
 };
 
+/// Returns a random number generator for an object of a level (a bot, a hazard, etc.). It depends
+/// on the match seed, the node and the initial position of the object, so every object has its
+/// own sequence (even if a few objects are spawned at the same place), that is the same on every
+/// run and does not shift when other objects are added. Instance ids of spawned nodes are random,
+/// so the node is identified by its handle, which is the same for the same order of spawns.
+pub fn seeded_rng(seed: u64, node: Handle<Node>, position: Vector3<f32>) -> StdRng {
+    let key = position
+        .iter()
+        .map(|v| v.to_bits() as u64)
+        .chain([node.index() as u64, node.generation() as u64])
+        .fold(0u64, |key, v| key.rotate_left(21) ^ v);
+    StdRng::seed_from_u64(seed ^ key)
+}
+
+/// An active contact of a collider with some other collider.
+pub struct ActorContact<'a> {
+    /// A collider, that touches the collider.
+    pub other_collider: Handle<Node>,
+    /// A rigid body of the collider, if any.
+    pub body: Option<&'a RigidBody>,
+    pub other_body_handle: Handle<Node>,
+    /// A rigid body of the other collider, if any.
+    pub other_body: Option<&'a RigidBody>,
+    /// Max impulse among all contact points of the contact.
+    pub max_impulse: f32,
+    /// Normal (in world space) of the contact, it points from the other collider to the collider.
+    pub normal: Vector3<f32>,
+}
+
+impl<'a> ActorContact<'a> {
+    /// Returns magnitude of relative linear velocity of the bodies or zero if any of the bodies is
+    /// missing.
+    pub fn relative_velocity(&self) -> f32 {
+        match (self.body, self.other_body) {
+            (Some(body), Some(other_body)) => (body.lin_vel() - other_body.lin_vel()).norm(),
+            _ => 0.0,
+        }
+    }
+
+    /// Returns linear velocity of the other body relative to the body or zero if any of the bodies
+    /// is missing.
+    pub fn relative_velocity_vector(&self) -> Vector3<f32> {
+        match (self.body, self.other_body) {
+            (Some(body), Some(other_body)) => other_body.lin_vel() - body.lin_vel(),
+            _ => Vector3::default(),
+        }
+    }
+}
+
+/// Iterates over active contacts (i.e. contacts with at least one contact point) of the given
+/// collider. Returns an empty iterator if the handle does not point to a collider.
+pub fn actor_contacts(
+    collider: Handle<Node>,
+    graph: &Graph,
+) -> impl Iterator<Item = ActorContact<'_>> {
+    graph
+        .try_get_of_type::<Collider>(collider)
+        .into_iter()
+        .flat_map(move |collider_ref| collider_ref.contacts(&graph.physics))
+        .filter(|contact| contact.has_any_active_contact)
+        .map(move |contact| {
+            let is_first = contact.collider1 == collider;
+            let other_collider = if is_first {
+                contact.collider2
+            } else {
+                contact.collider1
+            };
+
+            // Normals of manifolds point from the first collider to the second one.
+            let (body_handle, other_body_handle, normal) = contact
+                .manifolds
+                .first()
+                .map(|manifold| {
+                    if is_first {
+                        (manifold.rigid_body1, manifold.rigid_body2, -manifold.normal)
+                    } else {
+                        (manifold.rigid_body2, manifold.rigid_body1, manifold.normal)
+                    }
+                })
+                .unwrap_or_default();
+
+            let max_impulse = contact
+                .manifolds
+                .iter()
+                .flat_map(|manifold| manifold.points.iter())
+                .map(|point| point.impulse)
+                .fold(0.0, f32::max);
+
+            ActorContact {
+                other_collider,
+                body: graph.try_get_of_type::<RigidBody>(body_handle),
+                other_body_handle,
+                other_body: graph.try_get_of_type::<RigidBody>(other_body_handle),
+                max_impulse,
+                normal,
+            }
+        })
+}
+
+/// Returns `true` if the contact manifold is steep enough to stand on.
+fn is_ground_manifold(manifold: &ContactManifold) -> bool {
+    manifold.local_n1.y.abs() > 0.7 || manifold.local_n2.y.abs() > 0.7
+}
+
 pub fn has_ground_contact(collider: Handle<Node>, graph: &Graph) -> bool {
     if let Some(collider) = graph.try_get(collider).and_then(|n| n.cast::<Collider>()) {
         for contact in collider.contacts(&graph.physics) {
             for manifold in contact.manifolds.iter() {
-                if manifold.local_n1.y.abs() > 0.7 || manifold.local_n2.y.abs() > 0.7 {
+                if is_ground_manifold(manifold) {
                     return true;
                 }
             }
@@ -17,8 +134,86 @@ pub fn has_ground_contact(collider: Handle<Node>, graph: &Graph) -> bool {
     false
 }
 
+/// Draws contact points of the collider with their normals. Contacts, that count as ground (see
+/// [`has_ground_contact`]), are green, the rest are red.
+pub fn draw_ground_contacts(
+    collider: Handle<Node>,
+    graph: &Graph,
+    drawing_context: &mut SceneDrawingContext,
+) {
+    let Some(collider_ref) = graph.try_get_of_type::<Collider>(collider) else {
+        return;
+    };
+    let transform = collider_ref.global_transform();
+    for contact in collider_ref.contacts(&graph.physics) {
+        let is_first = contact.collider1 == collider;
+        for manifold in contact.manifolds.iter() {
+            let color = if is_ground_manifold(manifold) {
+                Color::GREEN
+            } else {
+                Color::RED
+            };
+            for point in manifold.points.iter() {
+                let local = if is_first {
+                    point.local_p1
+                } else {
+                    point.local_p2
+                };
+                let position = transform.transform_point(&Point3::from(local)).coords;
+                drawing_context.draw_sphere(position, 8, 8, 0.05, color);
+                drawing_context.add_line(Line {
+                    begin: position,
+                    end: position + manifold.normal.scale(0.5),
+                    color,
+                });
+            }
+        }
+    }
+}
+
 pub fn try_play_sound(sound: Handle<Node>, graph: &mut Graph) {
     if let Some(sound) = graph.try_get_mut_of_type::<Sound>(sound) {
         sound.try_play();
     }
 }
+
+/// Returns the first enabled camera of the graph, it is the one that renders the scene.
+pub fn find_active_camera(graph: &Graph) -> Option<&Camera> {
+    graph
+        .linear_iter()
+        .filter_map(|node| node.cast::<Camera>())
+        .find(|camera| camera.is_enabled())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fyrox::rand::Rng;
+
+    fn sequence(mut rng: StdRng) -> Vec<u32> {
+        (0..8).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        let node = Handle::new(3, 1);
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!(
+            sequence(seeded_rng(7, node, position)),
+            sequence(seeded_rng(7, node, position))
+        );
+        assert_ne!(
+            sequence(seeded_rng(7, node, position)),
+            sequence(seeded_rng(8, node, position))
+        );
+    }
+
+    #[test]
+    fn seeded_rng_differs_for_objects_at_same_position() {
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        assert_ne!(
+            sequence(seeded_rng(7, Handle::new(3, 1), position)),
+            sequence(seeded_rng(7, Handle::new(4, 1), position))
+        );
+    }
+}